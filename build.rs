@@ -1,2 +0,0 @@
-extern crate git_version;
-fn main() { git_version::set_env(); }
@@ -27,17 +27,39 @@ pub struct CameraParams {
     pub position: Vector3,
     pub view_matrix: Matrix4,
     pub projection_matrix: Matrix4,
+    pub frustum: Frustum,
+}
+
+/// Camera position, target and field of view, e.g. for saving to a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraPose {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub fovy: f32, // degrees
 }
 
 // Default camera values
 const YAW: f32 = -90.0;
 const PITCH: f32 = 0.0;
-const SPEED: f32 = 2.5;
+pub const SPEED: f32 = 2.5;
+const FAST_SPEED_FACTOR: f32 = 4.0; // while Shift is held
+const SLOW_SPEED_FACTOR: f32 = 0.25; // while Alt is held
 const SENSITIVTY: f32 = 0.1;
 const ZOOM_SENSITIVITY: f32 = 0.1;
 pub const ZOOM: f32 = 45.0;
 const MIN_ZOOM: f32 = 1.0;
 const MAZ_ZOOM: f32 = 170.0;
+// range for changing the field of view by keyboard
+const MIN_FOVY: f32 = 10.0;
+const MAX_FOVY: f32 = 120.0;
+
+/// Orbit: the mouse rotates the camera around the target.
+/// Fly: the mouse rotates the view direction around the camera position (first person).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NavMode {
+    Orbit,
+    Fly,
+}
 
 #[derive(Clone)]
 pub enum NavState {
@@ -46,6 +68,13 @@ pub enum NavState {
     Panning,
 }
 
+/// Eased movement from a previous camera pose to a new one (see `OrbitControls::start_transition`)
+struct CameraTransition {
+    from: CameraPose,
+    to: CameraPose,
+    elapsed: f32, // seconds
+}
+
 /// Inspirted by `ThreeJS` `OrbitControls`
 pub struct OrbitControls {
     pub camera: Camera,
@@ -56,6 +85,7 @@ pub struct OrbitControls {
     pub target: Point3,
 
     pub state: NavState,
+    pub mode: NavMode,
 
     // current position in spherical coordinates
     spherical: Spherical,
@@ -77,13 +107,36 @@ pub struct OrbitControls {
     pub moving_right: bool,
     pub moving_forward: bool,
     pub moving_backward: bool,
+    pub move_speed: f32, // units per second
+    pub fast: bool, // Shift held
+    pub slow: bool, // Alt held
 
     pub screen_width: f32,
     pub screen_height: f32,
+
+    // multipliers for mouse input (1.0 = default speed)
+    pub rotate_sensitivity: f32,
+    pub pan_sensitivity: f32,
+    pub zoom_sensitivity: f32,
+    pub invert_scroll: bool, // zoom out when scrolling up
+    // scrolling moves the camera towards the point under the cursor (instead of narrowing the field of view)
+    pub zoom_to_cursor: bool,
+    cursor: Option<Vector2>, // window position
+
+    // limits for the distance between camera and target (e.g. derived from the scene size)
+    pub min_distance: f32,
+    pub max_distance: f32,
+
+    pub transition_duration: f32, // seconds, 0: camera switches are instant
+    transition: Option<CameraTransition>,
 }
 
 impl OrbitControls {
-    pub fn new(position: Point3, screen_width: f32, screen_height: f32) -> Self {
+    pub fn new(
+        position: Point3,
+        screen_width: f32, screen_height: f32,
+        rotate_sensitivity: f32, pan_sensitivity: f32, zoom_sensitivity: f32,
+    ) -> Self {
         OrbitControls {
             camera: Camera::default(),
 
@@ -91,6 +144,7 @@ impl OrbitControls {
             target: Point3::new(0.0, 0.0, 0.0),
 
             state: NavState::None,
+            mode: NavMode::Orbit,
 
             // current position in spherical coordinates
             spherical: Spherical::default(),
@@ -111,18 +165,36 @@ impl OrbitControls {
             moving_right: false,
             moving_forward: false,
             moving_backward: false,
+            move_speed: SPEED,
+            fast: false,
+            slow: false,
 
             screen_width,
             screen_height,
+
+            rotate_sensitivity,
+            pan_sensitivity,
+            zoom_sensitivity,
+            invert_scroll: false,
+            zoom_to_cursor: false,
+            cursor: None,
+
+            min_distance: 0.0,
+            max_distance: ::std::f32::INFINITY,
+
+            transition_duration: 0.0,
+            transition: None,
         }
     }
 
     // NOTE: could be cached
     pub fn camera_params(&self) -> CameraParams {
+        let view_matrix = self.view_matrix();
         CameraParams {
             position: self.position.to_vec(),
-            view_matrix: self.view_matrix(),
+            view_matrix,
             projection_matrix: self.camera.projection_matrix,
+            frustum: Frustum::from_matrix(&(self.camera.projection_matrix * view_matrix)),
         }
     }
 
@@ -131,6 +203,7 @@ impl OrbitControls {
     }
 
     pub fn handle_mouse_move(&mut self, x: f32, y: f32) {
+        self.cursor = Some(Vector2::new(x, y));
         match self.state {
             NavState::Rotating => self.handle_mouse_move_rotate(x, y),
             NavState::Panning => self.handle_mouse_move_pan(x, y),
@@ -146,21 +219,46 @@ impl OrbitControls {
         } else {
             Vector2::zero()
         };
+        self.rotate_start = Some(self.rotate_end);
+
+        self.rotate_by(rotate_delta);
+    }
+
+    /// Rotates as if dragging by `delta` pixels (also used for touch input)
+    pub fn rotate_by(&mut self, delta: Vector2) {
+        if self.mode == NavMode::Fly {
+            return self.look_around(delta)
+        }
 
         // rotating across whole screen goes 360 degrees around
-        let rotate_speed = 1.0; // TODO: const/param/remove?
-        let angle = 2.0 * PI * rotate_delta.x / self.screen_width * rotate_speed;
+        let rotate_speed = self.rotate_sensitivity;
+        let angle = 2.0 * PI * delta.x / self.screen_width * rotate_speed;
         self.rotate_left(angle);
 
         // rotating up and down along whole screen attempts to go 360, but limited to 180
-        let angle = 2.0 * PI * rotate_delta.y / self.screen_height * rotate_speed;
+        let angle = 2.0 * PI * delta.y / self.screen_height * rotate_speed;
         self.rotate_up(angle);
 
-        self.rotate_start = Some(self.rotate_end);
-
         self.update();
     }
 
+    /// Fly mode: turns the view direction (keeping the distance to the target)
+    fn look_around(&mut self, delta: Vector2) {
+        let yaw = -2.0 * PI * delta.x / self.screen_width * self.rotate_sensitivity;
+        let pitch = -2.0 * PI * delta.y / self.screen_height * self.rotate_sensitivity;
+
+        let offset = self.target - self.position;
+        let distance = offset.magnitude();
+        let direction = offset / distance;
+        let theta = direction.x.atan2(direction.z) + yaw;
+        // don't look straight up/down, the view matrix would flip
+        let limit = PI / 2.0 - 0.0001;
+        let phi = clamp(direction.y.asin() + pitch, -limit, limit);
+
+        let direction = vec3(phi.cos() * theta.sin(), phi.sin(), phi.cos() * theta.cos());
+        self.target = self.position + direction * distance;
+    }
+
     pub fn handle_mouse_up(&mut self) {
         self.rotate_start = None;
         self.pan_start = None;
@@ -188,10 +286,14 @@ impl OrbitControls {
             Vector2::zero()
         };
 
-        self.pan(&pan_delta);
-
         self.pan_start = Some(self.pan_end);
 
+        self.pan_by(pan_delta);
+    }
+
+    /// Pans as if dragging by `delta` pixels (also used for touch input)
+    pub fn pan_by(&mut self, delta: Vector2) {
+        self.pan(&(delta * self.pan_sensitivity));
         self.update();
     }
 
@@ -209,8 +311,12 @@ impl OrbitControls {
             let distance = 2.0 * delta.y * target_distance / self.screen_height;
             self.pan_up(distance);
         } else {
-            // TODO!: orthographic camera pan
-            unimplemented!("orthographic camera pan")
+            // the view volume is 2 * ymag high
+            let ymag = self.camera.ymag.unwrap();
+            let distance = 2.0 * delta.x * ymag / self.screen_height;
+            self.pan_left(distance);
+            let distance = 2.0 * delta.y * ymag / self.screen_height;
+            self.pan_up(distance);
         }
     }
 
@@ -222,9 +328,29 @@ impl OrbitControls {
         self.pan_offset.y -= distance
     }
 
+    /// Sign for mouse wheel/trackpad scroll offsets
+    pub fn scroll_direction(&self) -> f32 {
+        if self.invert_scroll { -1.0 } else { 1.0 }
+    }
+
     // Processes input received from a mouse scroll-wheel event. Only requires input on the vertical wheel-axis
-    pub fn process_mouse_scroll(&mut self, mut yoffset: f32) {
-        yoffset *= ZOOM_SENSITIVITY;
+    pub fn process_mouse_scroll(&mut self, yoffset: f32) {
+        match self.cursor {
+            Some(cursor) if self.zoom_to_cursor => self.zoom_towards(cursor, yoffset),
+            _ => self.zoom(yoffset),
+        }
+    }
+
+    /// Zooms towards the target
+    fn zoom(&mut self, mut yoffset: f32) {
+        if !self.camera.is_perspective() {
+            // zoom by scaling the view volume
+            let ymag = self.camera.ymag.unwrap();
+            let factor = clamp(1.0 - yoffset * ZOOM_SENSITIVITY * self.zoom_sensitivity / 10.0, 0.5, 2.0);
+            self.camera.set_orthographic(ymag * factor);
+            return;
+        }
+        yoffset *= ZOOM_SENSITIVITY * self.zoom_sensitivity;
         if self.camera.fovy >= MIN_ZOOM && self.camera.fovy <= MAZ_ZOOM {
             self.camera.fovy -= yoffset;
         }
@@ -237,6 +363,36 @@ impl OrbitControls {
         self.camera.update_projection_matrix();
     }
 
+    /// Moves the camera (and target) along the ray through the window position `cursor`, so that
+    /// the point under the cursor stays in place. The distance to the target changes by the same
+    /// factor as the view volume when zooming orthographic views.
+    fn zoom_towards(&mut self, cursor: Vector2, yoffset: f32) {
+        let factor = clamp(1.0 - yoffset * ZOOM_SENSITIVITY * self.zoom_sensitivity / 10.0, 0.5, 2.0);
+        // cursor in normalized device coordinates
+        let x = 2.0 * cursor.x / self.screen_width - 1.0;
+        let y = 1.0 - 2.0 * cursor.y / self.screen_height;
+
+        let offset = self.target - self.position;
+        let distance = offset.magnitude();
+        let forward = offset / distance;
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        let translation = if self.camera.is_perspective() {
+            let factor = clamp(distance * factor, self.min_distance, self.max_distance) / distance;
+            // ray through the cursor, scaled to reach the target plane
+            let half_height = (self.camera.fovy.to_radians() / 2.0).tan() * distance;
+            let half_width = half_height * self.camera.aspect_ratio();
+            let ray = offset + right * (x * half_width) + up * (y * half_height);
+            ray * (1.0 - factor)
+        } else {
+            let ymag = self.camera.ymag.unwrap();
+            self.camera.set_orthographic(ymag * factor);
+            (right * (x * ymag * self.camera.aspect_ratio()) + up * (y * ymag)) * (1.0 - factor)
+        };
+        self.position += translation;
+        self.target += translation;
+    }
+
     /// Update camera after processing mouse events
     fn update(&mut self) {
         let mut offset = self.position - self.target;
@@ -257,7 +413,8 @@ impl OrbitControls {
 
         self.spherical.radius *= self.scale;
 
-        // TODO?: restrict radius to be between desired limits?
+        // restrict radius to be between desired limits
+        self.spherical.radius = clamp(self.spherical.radius, self.min_distance, self.max_distance);
 
         // move target to panned location
         // NOTE: quite different from original
@@ -295,9 +452,71 @@ impl OrbitControls {
         }
     }
 
+    /// Continuous navigation with gamepad sticks/triggers (see `GamepadInput`):
+    /// a fully deflected stick rotates by half a turn / pans by a screen height per second.
+    pub fn process_gamepad(&mut self, rotate: Vector2, pan: Vector2, zoom: f32, delta_time: f64) {
+        let delta_time = delta_time as f32;
+        if rotate != Vector2::zero() {
+            let speed = PI * self.rotate_sensitivity * delta_time;
+            self.rotate_left(-rotate.x * speed);
+            self.rotate_up(rotate.y * speed);
+        }
+        if pan != Vector2::zero() {
+            // stick y points up, screen y down
+            let delta = Vector2::new(pan.x, -pan.y) * self.screen_height * delta_time;
+            self.pan(&(delta * self.pan_sensitivity));
+        }
+        if zoom != 0.0 {
+            self.zoom(zoom * 20.0 * delta_time);
+        }
+        self.update();
+    }
+
+    /// Moves the camera back to `from` and then eases it to the current pose within `transition_duration`
+    /// (see `frame_update`). Call after switching the camera with the pose from before the switch.
+    /// A transition in progress is replaced, so it continues from the pose reached so far.
+    pub fn start_transition(&mut self, from: CameraPose) {
+        if self.transition_duration <= 0.0 {
+            return
+        }
+        let to = self.pose();
+        self.transition = Some(CameraTransition { from, to, elapsed: 0.0 });
+    }
+
+    fn update_transition(&mut self, delta_time: f32) {
+        let mut transition = match self.transition.take() {
+            Some(transition) => transition,
+            None => return,
+        };
+        transition.elapsed += delta_time;
+        let t = clamp(transition.elapsed / self.transition_duration, 0.0, 1.0);
+        let t = t * t * (3.0 - 2.0 * t); // ease in and out
+        let lerp = |from: [f32; 3], to: [f32; 3]| {
+            let from = Vector3::from(from);
+            Point3::from_vec(from + (Vector3::from(to) - from) * t)
+        };
+        self.position = lerp(transition.from.position, transition.to.position);
+        self.target = lerp(transition.from.target, transition.to.target);
+        if self.camera.is_perspective() {
+            self.camera.fovy = transition.from.fovy + (transition.to.fovy - transition.from.fovy) * t;
+            self.camera.update_projection_matrix();
+        }
+        if t < 1.0 {
+            self.transition = Some(transition);
+        }
+    }
+
     /// Do frame-based updates that require delta_time
     pub fn frame_update(&mut self, delta_time: f64) {
-        let velocity = SPEED * delta_time as f32;
+        self.update_transition(delta_time as f32);
+
+        let mut velocity = self.move_speed * delta_time as f32;
+        if self.fast {
+            velocity *= FAST_SPEED_FACTOR;
+        }
+        if self.slow {
+            velocity *= SLOW_SPEED_FACTOR;
+        }
 
         let front = (self.target - self.position).normalize();
         if self.moving_forward {
@@ -320,6 +539,33 @@ impl OrbitControls {
         }
     }
 
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            position: self.position.into(),
+            target: self.target.into(),
+            fovy: self.camera.fovy,
+        }
+    }
+
+    /// NOTE: the projection matrix needs to be updated afterwards (`Camera::update_aspect_ratio`)
+    pub fn set_pose(&mut self, pose: &CameraPose) {
+        self.position = pose.position.into();
+        self.target = pose.target.into();
+        self.camera.fovy = pose.fovy;
+    }
+
+    /// Changes the vertical field of view by `delta` degrees (within a range that keeps a sane perspective)
+    pub fn change_fovy(&mut self, delta: f32) {
+        self.camera.fovy = clamp(self.camera.fovy + delta, MIN_FOVY, MAX_FOVY);
+        self.camera.update_projection_matrix();
+    }
+
+    /// Half height of an orthographic view volume that roughly matches the current perspective view at the target
+    pub fn matching_ortho_ymag(&self) -> f32 {
+        let distance = (self.position - self.target).magnitude();
+        distance * (self.camera.fovy.to_radians() / 2.0).tan()
+    }
+
     pub fn set_camera(&mut self, camera: &Camera, transform: &Matrix4) {
         // spec: If no transformation is specified, the location of the camera is at the origin.
         let pos = transform * vec4(0.0, 0.0, 0.0, 1.0);
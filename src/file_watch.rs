@@ -0,0 +1,42 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Polls the modification time of a file. A change is only reported once the file
+/// hasn't been modified for the `debounce` duration, since exporters often write in bursts.
+pub struct FileWatch {
+    path: PathBuf,
+    debounce: Duration,
+    last_modified: Option<SystemTime>,
+    changed_at: Option<Instant>, // pending change
+}
+
+impl FileWatch {
+    pub fn new<P: Into<PathBuf>>(path: P, debounce: Duration) -> FileWatch {
+        let path = path.into();
+        let last_modified = modified(&path);
+        FileWatch { path, debounce, last_modified, changed_at: None }
+    }
+
+    /// Returns true once per (settled) change. Only does a `stat`, so it's cheap enough to call every frame.
+    pub fn poll(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified != self.last_modified {
+            self.last_modified = modified;
+            self.changed_at = Some(Instant::now());
+            return false
+        }
+        match self.changed_at {
+            Some(changed_at) if changed_at.elapsed() >= self.debounce => {
+                self.changed_at = None;
+                // the file might be gone (temporarily) - the next change is picked up when it's back
+                modified.is_some()
+            },
+            _ => false,
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
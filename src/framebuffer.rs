@@ -2,49 +2,184 @@ use std::ptr;
 
 use gl;
 
+/// With `samples` > 0, rendering goes to a multisampled framebuffer, which needs
+/// to be resolved into the regular one (`id`) before reading pixels.
 #[derive(Debug)]
 pub struct Framebuffer {
-    pub id: u32
+    pub id: u32,
+    pub color_texture: u32,
+    pub depth_texture: u32, // depth + stencil
+    pub multisample_id: Option<u32>,
+    multisample_renderbuffers: [u32; 2], // color, depth + stencil
+    samples: u16,
+    color_format: u32, // internal format, e.g. RGBA8 or RGBA16F/RGBA32F for values outside of [0, 1]
+    width: u32,
+    height: u32,
 }
 
 impl Framebuffer {
-    pub fn new(width: u32, height: u32) -> Framebuffer {
+    pub fn new(width: u32, height: u32, samples: u16) -> Framebuffer {
+        Self::with_format(width, height, samples, gl::RGBA8)
+    }
+
+    /// `color_format`: gl::RGBA8, gl::RGBA16F or gl::RGBA32F.
+    /// The framebuffer binding is restored afterwards (e.g. that of a host application).
+    /// `samples` is clamped to GL_MAX_SAMPLES.
+    pub fn with_format(width: u32, height: u32, samples: u16, color_format: u32) -> Framebuffer {
         let mut framebuffer = 0;
         unsafe {
+            let samples = Self::supported_samples(samples);
+            let mut previous_framebuffer = 0;
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
             gl::GenFramebuffers(1, &mut framebuffer);
             gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
             // create a color attachment texture
             let mut texture_colorbuffer = 0;
             gl::GenTextures(1, &mut texture_colorbuffer);
             gl::BindTexture(gl::TEXTURE_2D, texture_colorbuffer);
-            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, width as i32, height as i32,
-                0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
+            gl::TexImage2D(gl::TEXTURE_2D, 0, color_format as i32, width as i32, height as i32,
+                0, gl::RGBA, pixel_type(color_format), ptr::null());
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
             gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, texture_colorbuffer, 0);
-            // create a renderbuffer object for depth and stencil attachment (we won't be sampling these)
-            let mut rbo = 0;
-            gl::GenRenderbuffers(1, &mut rbo);
-            gl::BindRenderbuffer(gl::RENDERBUFFER, rbo);
-            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, width as i32, height as i32); // use a single renderbuffer object for both a depth AND stencil buffer.
-            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, rbo); // now actually attach it
+            // create a texture for depth and stencil attachment (a texture instead of a renderbuffer, so that depth can be read back)
+            let mut texture_depth = 0;
+            gl::GenTextures(1, &mut texture_depth);
+            gl::BindTexture(gl::TEXTURE_2D, texture_depth);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::DEPTH24_STENCIL8 as i32, width as i32, height as i32,
+                0, gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8, ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::TEXTURE_2D, texture_depth, 0);
             // now that we actually created the framebuffer and added all attachments we want to check if it is actually complete now
             if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
                 panic!("ERROR::FRAMEBUFFER:: Framebuffer is not complete!");
             }
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            let (multisample_id, multisample_renderbuffers) = if samples > 0 {
+                let (id, renderbuffers) = Self::create_multisampled(width, height, samples, color_format);
+                (Some(id), renderbuffers)
+            } else {
+                (None, [0; 2])
+            };
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as u32);
+
+            Framebuffer {
+                id: framebuffer,
+                color_texture: texture_colorbuffer,
+                depth_texture: texture_depth,
+                multisample_id,
+                multisample_renderbuffers,
+                samples,
+                color_format,
+                width,
+                height,
+            }
         }
+    }
 
-        Framebuffer {
-            id: framebuffer
+    unsafe fn supported_samples(samples: u16) -> u16 {
+        if samples == 0 {
+            return 0
         }
+        let mut max_samples = 0;
+        gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+        let max_samples = max_samples.max(0).min(i32::from(u16::max_value())) as u16;
+        if samples > max_samples {
+            warn!("{} MSAA samples are not supported, using {} (GL_MAX_SAMPLES)", samples, max_samples);
+            return max_samples
+        }
+        samples
+    }
+
+    unsafe fn create_multisampled(width: u32, height: u32, samples: u16, color_format: u32) -> (u32, [u32; 2]) {
+        let mut framebuffer = 0;
+        gl::GenFramebuffers(1, &mut framebuffer);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        let mut renderbuffers = [0; 2];
+        gl::GenRenderbuffers(2, renderbuffers.as_mut_ptr());
+        let attachments = [(color_format, gl::COLOR_ATTACHMENT0), (gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL_ATTACHMENT)];
+        for (&rbo, &(format, attachment)) in renderbuffers.iter().zip(attachments.iter()) {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, rbo);
+            gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, i32::from(samples), format, width as i32, height as i32);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, rbo);
+        }
+        if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+            panic!("ERROR::FRAMEBUFFER:: Multisampled framebuffer is not complete!");
+        }
+        gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        (framebuffer, renderbuffers)
+    }
+
+    /// Reallocates the attachments with the new size (the contents are lost).
+    /// NOTE: the viewport needs to be updated separately.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let (w, h) = (width as i32, height as i32);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.color_texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, self.color_format as i32, w, h,
+                0, gl::RGBA, pixel_type(self.color_format), ptr::null());
+            gl::BindTexture(gl::TEXTURE_2D, self.depth_texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::DEPTH24_STENCIL8 as i32, w, h,
+                0, gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8, ptr::null());
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            if self.multisample_id.is_some() {
+                let formats = [self.color_format, gl::DEPTH24_STENCIL8];
+                for (&rbo, &format) in self.multisample_renderbuffers.iter().zip(formats.iter()) {
+                    gl::BindRenderbuffer(gl::RENDERBUFFER, rbo);
+                    gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, i32::from(self.samples), format, w, h);
+                }
+                gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            }
+        }
+        self.width = width;
+        self.height = height;
     }
 
     pub fn bind(&self) {
-        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.id) }
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.multisample_id.unwrap_or(self.id)) }
+    }
+
+    /// Blits the multisampled framebuffer (if any) into the regular one and binds that for reading.
+    /// Drawing continues to go to the multisampled one.
+    pub fn resolve(&self) {
+        if let Some(multisample_id) = self.multisample_id {
+            let (width, height) = (self.width as i32, self.height as i32);
+            unsafe {
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, multisample_id);
+                gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.id);
+                gl::BlitFramebuffer(0, 0, width, height, 0, 0, width, height,
+                    gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT, gl::NEAREST);
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+                gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, multisample_id);
+            }
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Deletes the GL objects (only the regular framebuffer, not the multisampled one)
+    pub fn delete(&self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            gl::DeleteTextures(1, &self.color_texture);
+            gl::DeleteTextures(1, &self.depth_texture);
+        }
     }
 
     pub fn unbind(&self) {
         unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) }
     }
 }
+
+/// Type of the (unused) initial pixel data for an internal color format
+fn pixel_type(color_format: u32) -> u32 {
+    match color_format {
+        gl::RGBA16F | gl::RGBA32F => gl::FLOAT,
+        _ => gl::UNSIGNED_BYTE,
+    }
+}
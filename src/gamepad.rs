@@ -0,0 +1,56 @@
+use gilrs::{self, Axis, Button, EventType, Gilrs};
+
+use render::math::*;
+
+/// Stick values below this are treated as 0 (sticks rarely rest exactly at the center)
+const DEAD_ZONE: f32 = 0.15;
+
+/// Input of all connected gamepads for one frame
+#[derive(Debug, Default)]
+pub struct GamepadInput {
+    pub rotate: Vector2, // left stick, [-1, 1]
+    pub pan: Vector2, // right stick, [-1, 1]
+    pub zoom: f32, // right trigger - left trigger, [-1, 1]
+    pub recenter: bool, // south button (A/Cross) was pressed
+}
+
+pub struct Gamepads {
+    gilrs: Gilrs,
+}
+
+impl Gamepads {
+    pub fn new() -> Result<Gamepads, gilrs::Error> {
+        let gilrs = Gilrs::new()?;
+        for (_, gamepad) in gilrs.gamepads() {
+            info!("Found gamepad: {}", gamepad.name());
+        }
+        Ok(Gamepads { gilrs })
+    }
+
+    /// Processes pending events and returns the current input. Call once per frame.
+    pub fn poll(&mut self) -> GamepadInput {
+        let mut input = GamepadInput::default();
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(Button::South, _) => input.recenter = true,
+                EventType::Connected => info!("Gamepad connected"),
+                EventType::Disconnected => info!("Gamepad disconnected"),
+                _ => (),
+            }
+        }
+
+        for (_, gamepad) in self.gilrs.gamepads() {
+            input.rotate += Vector2::new(dead_zone(gamepad.value(Axis::LeftStickX)),
+                dead_zone(gamepad.value(Axis::LeftStickY)));
+            input.pan += Vector2::new(dead_zone(gamepad.value(Axis::RightStickX)),
+                dead_zone(gamepad.value(Axis::RightStickY)));
+            let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+            input.zoom += trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2);
+        }
+        input
+    }
+}
+
+fn dead_zone(value: f32) -> f32 {
+    if value.abs() < DEAD_ZONE { 0.0 } else { value }
+}
@@ -0,0 +1,163 @@
+//! Encoders for linear floating point images (HDR screenshots, see `GltfViewer::screenshot_hdr`).
+//! The `image` crate can only write 8 bit formats.
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Linear RGBA pixels (4 floats each), origin at the top left
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<f32>,
+}
+
+impl HdrImage {
+    /// Saves as OpenEXR (.exr, 32 bit float RGBA with alpha) or Radiance RGBE (.hdr, RGB only)
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let extension = Path::new(filename).extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        let bytes = match extension.as_deref() {
+            Some("exr") => self.encode_exr(),
+            Some("hdr") => self.encode_rgbe(),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "HDR filename must end with .exr or .hdr")),
+        };
+        File::create(filename)?.write_all(&bytes)
+    }
+
+    /// Uncompressed scanline OpenEXR with one line per block
+    pub fn encode_exr(&self) -> Vec<u8> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut bytes = vec![0x76, 0x2f, 0x31, 0x01]; // magic number
+        push_u32(&mut bytes, 2); // version 2, single part scanline file
+
+        // header (channels sorted by name, as the channel data)
+        let mut channels = Vec::new();
+        for name in &["A", "B", "G", "R"] {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            push_u32(&mut channels, 2); // FLOAT
+            channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear, reserved
+            push_u32(&mut channels, 1); // x sampling
+            push_u32(&mut channels, 1); // y sampling
+        }
+        channels.push(0);
+        push_attribute(&mut bytes, "channels", "chlist", &channels);
+        push_attribute(&mut bytes, "compression", "compression", &[0]);
+        let mut window = Vec::new();
+        for &value in &[0, 0, width as u32 - 1, height as u32 - 1] {
+            push_u32(&mut window, value);
+        }
+        push_attribute(&mut bytes, "dataWindow", "box2i", &window);
+        push_attribute(&mut bytes, "displayWindow", "box2i", &window);
+        push_attribute(&mut bytes, "lineOrder", "lineOrder", &[0]); // increasing y (top to bottom)
+        push_attribute(&mut bytes, "pixelAspectRatio", "float", &f32_bytes(1.0));
+        push_attribute(&mut bytes, "screenWindowCenter", "v2f", &[0; 8]);
+        push_attribute(&mut bytes, "screenWindowWidth", "float", &f32_bytes(1.0));
+        bytes.push(0);
+
+        // offset table, then one block per line: y, data size and the channels one after another
+        let line_size = width * 4 * 4;
+        let first_line = bytes.len() + height * 8;
+        for y in 0..height {
+            let offset = (first_line + y * (8 + line_size)) as u64;
+            push_u32(&mut bytes, offset as u32);
+            push_u32(&mut bytes, (offset >> 32) as u32);
+        }
+        for y in 0..height {
+            push_u32(&mut bytes, y as u32);
+            push_u32(&mut bytes, line_size as u32);
+            let line = &self.pixels[y * width * 4..(y + 1) * width * 4];
+            for &channel in &[3, 2, 1, 0] {
+                for pixel in line.chunks(4) {
+                    bytes.extend_from_slice(&f32_bytes(pixel[channel]));
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Radiance RGBE without run length encoding (alpha is dropped)
+    pub fn encode_rgbe(&self) -> Vec<u8> {
+        let mut bytes = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", self.height, self.width)
+            .into_bytes();
+        for pixel in self.pixels.chunks(4) {
+            bytes.extend_from_slice(&rgbe(pixel[0], pixel[1], pixel[2]));
+        }
+        bytes
+    }
+}
+
+/// Shared exponent encoding: the mantissas are relative to the largest component
+fn rgbe(r: f32, g: f32, b: f32) -> [u8; 4] {
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0; 4]
+    }
+    // max = mantissa * 2^exponent with mantissa in [0.5, 1)
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max / 2.0f32.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let scale = 256.0 / 2.0f32.powi(exponent);
+    let channel = |value: f32| (value.max(0.0) * scale).min(255.0) as u8;
+    [channel(r), channel(g), channel(b), (exponent + 128).max(0).min(255) as u8]
+}
+
+fn push_attribute(bytes: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(attribute_type.as_bytes());
+    bytes.push(0);
+    push_u32(bytes, value.len() as u32);
+    bytes.extend_from_slice(value);
+}
+
+/// Little endian
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+}
+
+fn f32_bytes(value: f32) -> [u8; 4] {
+    let bits = value.to_bits();
+    [bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        let b = &bytes[offset..offset + 4];
+        f32::from_bits(u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24)
+    }
+
+    #[test]
+    fn exr_keeps_values_outside_of_unit_range() {
+        let image = HdrImage { width: 2, height: 1, pixels: vec![4.5, 0.25, 100.0, 1.0, -0.5, 0.0, 0.0, 0.0] };
+        let bytes = image.encode_exr();
+        let line = bytes.len() - 2 * 4 * 4; // last (and only) line, channels A, B, G, R
+        assert_eq!(read_f32(&bytes, line), 1.0); // A
+        assert_eq!(read_f32(&bytes, line + 8), 100.0); // B
+        assert_eq!(read_f32(&bytes, line + 16), 0.25); // G
+        assert_eq!(read_f32(&bytes, line + 24), 4.5); // R
+        assert_eq!(read_f32(&bytes, line + 28), -0.5);
+        // offset table points at the line block (y, size)
+        let offset = bytes.len() - 2 * 4 * 4 - 8;
+        assert_eq!(&bytes[offset - 8..offset], &[offset as u8, (offset >> 8) as u8, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rgbe_round_trip() {
+        for &(r, g, b) in &[(4.5f32, 0.25f32, 100.0f32), (0.5, 0.5, 0.5), (1.0, 2.0, 3.0), (1000.0, 0.0, 0.01)] {
+            let encoded = rgbe(r, g, b);
+            let scale = 2.0f32.powi(i32::from(encoded[3]) - 128 - 8);
+            let max = r.max(g).max(b);
+            for &(original, mantissa) in &[(r, encoded[0]), (g, encoded[1]), (b, encoded[2])] {
+                let decoded = (f32::from(mantissa) + 0.5) * scale;
+                assert!((decoded - original).abs() <= max / 128.0, "{} -> {}", original, decoded);
+            }
+        }
+        assert_eq!(rgbe(0.0, 0.0, 0.0), [0; 4]);
+    }
+}
@@ -1,20 +1,22 @@
 use std;
-use std::boxed::Box;
-use std::io::{self, Read, Write};
 use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
 
-use futures::Future;
-
-extern crate futures_cpupool;
-use self::futures_cpupool::CpuPool;
+use gltf;
 
-use gltf::import::{Source};
+use reqwest;
+use serde_json;
 
-extern crate reqwest;
+use utils::percent_decode;
 
+/// Downloads a glTF file and the buffers/images it references over HTTP(S)
+/// into a local directory, so that it can be loaded with `gltf_importer`.
+/// Relative URIs are resolved against the URL of the glTF file.
 pub struct HttpSource {
     url: reqwest::Url,
-    cpu_pool: CpuPool,
+    target_dir: PathBuf,
 }
 
 impl fmt::Debug for HttpSource {
@@ -23,75 +25,148 @@ impl fmt::Debug for HttpSource {
     }
 }
 
-impl HttpSource {
-    pub fn new(url: &str) -> HttpSource {
-        // Use 8 threads - like max parallel requests per domain in some browsers
-        // Seems to be a sweet spot (tested with VC.gltf)
-        let pool = CpuPool::new(8);
-
-        HttpSource {
-            url: reqwest::Url::parse(url)
-                .expect("Failed to parse URL"),
-            cpu_pool: pool
-        }
-    }
-}
-
 #[derive(Debug)]
 pub enum Error {
-    HttpError(String),
+    InvalidUrl(String),
+    Http(String),
+    InvalidGltf(String),
+    Io(io::Error),
 }
 
 impl HttpSource {
-    fn fetch_data(&self, url: String) -> Box<Future<Item=Box<[u8]>, Error=Error>> {
-        let future = self.cpu_pool.spawn_fn(move || {
-            let mut resp = reqwest::get(&url)
-                .expect(&format!("Network problem on GET {}", url));
-            if !resp.status().is_success() {
-                return Err(Error::HttpError(format!("{}: {}", resp.status(), url)));
+    pub fn new(url: &str) -> Result<HttpSource, Error> {
+        let url = reqwest::Url::parse(url)
+            .map_err(|err| Error::InvalidUrl(format!("{}: {}", err, url)))?;
+
+        // mirror the remote directory structure below the temp dir
+        let mut target_dir = std::env::temp_dir().join("gltf-viewer");
+        target_dir.push(url.host_str().unwrap_or("unknown-host"));
+        if let Some(segments) = url.path_segments() {
+            let segments: Vec<_> = segments.collect();
+            for segment in &segments[..segments.len() - 1] {
+                target_dir.push(segment);
             }
-            let mut data = vec![];
-            let _ = resp.read_to_end(&mut data);
-            print!(".");
-            let _ = io::stdout().flush();
-            Ok(data.into_boxed_slice())
-        });
-        Box::new(future)
+        }
+
+        Ok(HttpSource { url, target_dir })
+    }
+
+    /// Downloads the glTF and all external data. Returns the local path of the glTF file.
+    pub fn download(&self) -> Result<PathBuf, Error> {
+        let file_name = match self.url.path_segments().and_then(|segments| segments.last()) {
+            Some(name) if !name.is_empty() => name.to_owned(),
+            _ => "model.gltf".to_owned(),
+        };
+        let data = self.fetch_data(&self.url)?;
+        let gltf_path = self.save(&file_name, &data)?;
+
+        let json = serde_json::from_slice::<gltf::json::Root>(json_chunk(&data)?)
+            .map_err(|err| Error::InvalidGltf(format!("{}: {}", err, self.url)))?;
+
+        let buffer_uris = json.buffers.iter().filter_map(|buffer| buffer.uri.as_ref());
+        let image_uris = json.images.iter().filter_map(|image| image.uri.as_ref());
+        for uri in buffer_uris.chain(image_uris) {
+            if uri.starts_with("data:") {
+                continue // embedded
+            }
+            let url = self.url.join(uri)
+                .map_err(|err| Error::InvalidUrl(format!("{}: {}", err, uri)))?;
+            let data = self.fetch_data(&url)?;
+            self.save(uri, &data)?;
+        }
+
+        Ok(gltf_path)
+    }
+
+    fn fetch_data(&self, url: &reqwest::Url) -> Result<Vec<u8>, Error> {
+        info!("Downloading {}", url);
+        let mut resp = reqwest::get(url.clone())
+            .map_err(|err| Error::Http(format!("{}: {}", err, url)))?;
+        if !resp.status().is_success() {
+            return Err(Error::Http(format!("{}: {}", resp.status(), url)));
+        }
+        let mut data = vec![];
+        resp.read_to_end(&mut data).map_err(Error::Io)?;
+        Ok(data)
+    }
+
+    fn save(&self, uri: &str, data: &[u8]) -> Result<PathBuf, Error> {
+        let path = local_path(&self.target_dir, uri)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(Error::Io)?;
+        }
+        fs::File::create(&path)
+            .and_then(|mut file| file.write_all(data))
+            .map_err(Error::Io)?;
+        Ok(path)
     }
 }
 
-impl Source for HttpSource {
-    type Error = Error;
-    fn source_gltf(&self) -> Box<Future<Item=Box<[u8]>, Error=Self::Error>> {
-        println!("Downloading");
-        let _ = io::stdout().flush();
-        self.fetch_data(self.url.to_string())
+/// Path of a relative URI below `target_dir`, percent-decoded (`%20` is a space in the file name).
+/// URIs that would leave the directory (absolute or with `..` components) are rejected.
+fn local_path(target_dir: &Path, uri: &str) -> Result<PathBuf, Error> {
+    let relative_path = percent_decode(uri);
+    let outside = Path::new(&relative_path).components().any(|component| match component {
+        Component::Normal(_) | Component::CurDir => false,
+        Component::ParentDir | Component::RootDir | Component::Prefix(_) => true,
+    });
+    if outside {
+        return Err(Error::InvalidUrl(
+            format!("only URIs below the directory of the glTF file are supported: {}", uri)))
     }
+    Ok(target_dir.join(relative_path))
+}
 
-    fn source_external_data(&self, uri: &str) -> Box<Future<Item=Box<[u8]>, Error=Self::Error>> {
-        let mut new_url = self.url.clone();
-        new_url.path_segments_mut()
-            .expect("URL cannot be base")
-            .pop().push(uri);
-        self.fetch_data(new_url.to_string())
+/// Returns the JSON part of a glTF file. For binary glTF (.glb) that is the first chunk.
+pub fn json_chunk(data: &[u8]) -> Result<&[u8], Error> {
+    if !data.starts_with(b"glTF") {
+        return Ok(data)
+    }
+    if data.len() < 20 || &data[16..20] != b"JSON" {
+        return Err(Error::InvalidGltf("malformed binary glTF header".to_owned()))
+    }
+    let length = u32::from(data[12]) | u32::from(data[13]) << 8 |
+        u32::from(data[14]) << 16 | u32::from(data[15]) << 24;
+    let end = 20 + length as usize;
+    if data.len() < end {
+        return Err(Error::InvalidGltf("binary glTF JSON chunk exceeds file size".to_owned()))
     }
+    Ok(&data[20..end])
 }
 
 impl std::error::Error for Error {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
-            Error::HttpError(ref status) => status
+            Error::Io(ref err) => Some(err),
+            _ => None,
         }
     }
-
-    fn cause(&self) -> Option<&std::error::Error> {
-        None
-    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use std::error::Error;
-        write!(f, "{}", self.description())
+        match *self {
+            Error::InvalidUrl(ref msg) => write!(f, "Invalid URL: {}", msg),
+            Error::Http(ref msg) => write!(f, "HTTP request failed: {}", msg),
+            Error::InvalidGltf(ref msg) => write!(f, "Invalid glTF: {}", msg),
+            Error::Io(ref err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_paths() {
+        let dir = Path::new("/tmp/gltf-viewer/example.com");
+        assert_eq!(local_path(dir, "textures/a..b.png").unwrap(), dir.join("textures/a..b.png"));
+        assert_eq!(local_path(dir, "Box%20Textured.bin").unwrap(), dir.join("Box Textured.bin"));
+        assert_eq!(local_path(dir, "./a.bin").unwrap(), dir.join("./a.bin"));
+        assert!(local_path(dir, "../a.bin").is_err());
+        assert!(local_path(dir, "textures/../../a.bin").is_err());
+        assert!(local_path(dir, "%2E%2E/a.bin").is_err());
+        assert!(local_path(dir, "/etc/passwd").is_err());
     }
 }
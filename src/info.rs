@@ -0,0 +1,425 @@
+use std::fmt::Write;
+use std::fs::File;
+use std::io;
+use std::rc::Rc;
+
+use serde_json;
+
+use render::{Material, Root, Scene, Texture};
+use render::math::*;
+
+/// Version of the JSON report structure. Increased on incompatible changes
+/// (renamed/removed fields), but not when fields are added.
+pub const INFO_VERSION: u32 = 1;
+
+/// Summary of a loaded model, printed with `--info` or saved with `--info-json`
+#[derive(Debug, Serialize)]
+pub struct ModelInfo {
+    pub version: u32,
+    pub scenes: Vec<SceneInfo>,
+    pub meshes: Vec<MeshInfo>,
+    pub totals: Totals,
+    pub materials: Vec<MaterialInfo>, // only the ones used by meshes
+    pub textures: Vec<TextureInfo>,
+    pub animations: Vec<AnimationInfo>,
+    pub skins: Vec<SkinInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SceneInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub root_nodes: usize,
+    pub bounds_min: [f32; 3],
+    pub bounds_max: [f32; 3],
+}
+
+#[derive(Debug, Serialize)]
+pub struct MeshInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub primitives: usize,
+    pub vertices: u32,
+    pub triangles: u32,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Totals {
+    pub primitives: usize,
+    pub vertices: u32,
+    pub triangles: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaterialInfo {
+    pub index: Option<usize>, // None for the default material
+    pub name: Option<String>,
+    pub alpha_mode: String,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+    pub unlit: bool,
+    pub base_color_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: [f32; 3],
+    pub emissive_strength: f32,
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub occlusion_texture: Option<TextureRef>,
+    pub emissive_texture: Option<TextureRef>,
+    pub transmission_factor: f32,
+    pub transmission_texture: Option<TextureRef>,
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_texture: Option<TextureRef>,
+    pub clearcoat_roughness_texture: Option<TextureRef>,
+    pub clearcoat_normal_texture: Option<TextureRef>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextureRef {
+    pub index: usize,
+    pub tex_coord: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextureInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnimationInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub channels: usize,
+    pub duration: f32, // seconds
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkinInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub joints: usize,
+}
+
+impl ModelInfo {
+    pub fn from_root(root: &Root, scenes: &[Scene]) -> ModelInfo {
+        let scenes = scenes.iter().enumerate()
+            .map(|(index, scene)| SceneInfo {
+                index,
+                name: scene.name.clone(),
+                root_nodes: scene.nodes.len(),
+                bounds_min: scene.bounds.min.into(),
+                bounds_max: scene.bounds.max.into(),
+            })
+            .collect();
+
+        let mut meshes: Vec<MeshInfo> = root.meshes.iter()
+            .map(|mesh| MeshInfo {
+                index: mesh.index,
+                name: mesh.name.clone(),
+                primitives: mesh.primitives.len(),
+                vertices: mesh.primitives.iter().map(|primitive| primitive.num_vertices()).sum(),
+                triangles: mesh.primitives.iter().map(|primitive| primitive.num_triangles()).sum(),
+            })
+            .collect();
+        meshes.sort_by_key(|mesh| mesh.index);
+        let mut totals = Totals::default();
+        for mesh in &meshes {
+            totals.primitives += mesh.primitives;
+            totals.vertices += mesh.vertices;
+            totals.triangles += mesh.triangles;
+        }
+
+        let mut materials: Vec<MaterialInfo> = root.materials.iter()
+            .map(|material| MaterialInfo::from_material(material))
+            .collect();
+        materials.sort_by_key(|material| material.index);
+
+        let mut textures: Vec<TextureInfo> = root.textures.iter()
+            .map(|texture| TextureInfo {
+                index: texture.index,
+                name: texture.name.clone(),
+                width: texture.width,
+                height: texture.height,
+            })
+            .collect();
+        textures.sort_by_key(|texture| texture.index);
+        textures.dedup_by_key(|texture| texture.index); // textures are loaded per material
+
+        let animations = root.animations.iter()
+            .map(|animation| AnimationInfo {
+                index: animation.index,
+                name: animation.name.clone(),
+                channels: animation.channels.len(),
+                duration: animation.duration,
+            })
+            .collect();
+        let skins = root.skins.iter()
+            .map(|skin| SkinInfo { index: skin.index, name: skin.name.clone(), joints: skin.joints.len() })
+            .collect();
+
+        ModelInfo {
+            version: INFO_VERSION,
+            scenes,
+            meshes,
+            totals,
+            materials,
+            textures,
+            animations,
+            skins,
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Scenes: {}", self.scenes.len());
+        for scene in &self.scenes {
+            let (min, max) = (scene.bounds_min, scene.bounds_max);
+            println!("  {} ({}): {} root nodes, bounds: [{:.3}, {:.3}, {:.3}] - [{:.3}, {:.3}, {:.3}]",
+                scene.index, name_or_unnamed(&scene.name), scene.root_nodes,
+                min[0], min[1], min[2], max[0], max[1], max[2]);
+        }
+
+        println!("Meshes: {}", self.meshes.len());
+        for mesh in &self.meshes {
+            println!("  {} ({}): {} primitives, {} vertices, {} triangles",
+                mesh.index, name_or_unnamed(&mesh.name), mesh.primitives, mesh.vertices, mesh.triangles);
+        }
+        println!("  total: {} primitives, {} vertices, {} triangles",
+            self.totals.primitives, self.totals.vertices, self.totals.triangles);
+
+        println!("Materials: {}", self.materials.len());
+        for material in &self.materials {
+            material.print();
+        }
+
+        println!("Textures: {}", self.textures.len());
+        for texture in &self.textures {
+            println!("  {} ({}): {}x{}", texture.index, name_or_unnamed(&texture.name), texture.width, texture.height);
+        }
+
+        println!("Animations: {}", self.animations.len());
+        for animation in &self.animations {
+            println!("  {} ({}): {} channels, {:.3} s",
+                animation.index, name_or_unnamed(&animation.name), animation.channels, animation.duration);
+        }
+
+        println!("Skins: {}", self.skins.len());
+        for skin in &self.skins {
+            println!("  {} ({}): {} joints", skin.index, name_or_unnamed(&skin.name), skin.joints);
+        }
+    }
+
+    pub fn save_json(&self, filename: &str) -> io::Result<()> {
+        let file = File::create(filename)?;
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+}
+
+impl MaterialInfo {
+    fn from_material(material: &Material) -> MaterialInfo {
+        MaterialInfo {
+            index: material.index,
+            name: material.name.clone(),
+            alpha_mode: format!("{:?}", material.alpha_mode).to_uppercase(), // as in glTF
+            alpha_cutoff: material.alpha_cutoff,
+            double_sided: material.double_sided,
+            unlit: material.unlit,
+            base_color_factor: material.base_color_factor.into(),
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            emissive_factor: material.emissive_factor.into(),
+            emissive_strength: material.emissive_strength,
+            base_color_texture: texture_ref(&material.base_color_texture),
+            metallic_roughness_texture: texture_ref(&material.metallic_roughness_texture),
+            normal_texture: texture_ref(&material.normal_texture),
+            occlusion_texture: texture_ref(&material.occlusion_texture),
+            emissive_texture: texture_ref(&material.emissive_texture),
+            transmission_factor: material.transmission_factor,
+            transmission_texture: texture_ref(&material.transmission_texture),
+            clearcoat_factor: material.clearcoat_factor,
+            clearcoat_roughness_factor: material.clearcoat_roughness_factor,
+            clearcoat_texture: texture_ref(&material.clearcoat_texture),
+            clearcoat_roughness_texture: texture_ref(&material.clearcoat_roughness_texture),
+            clearcoat_normal_texture: texture_ref(&material.clearcoat_normal_texture),
+        }
+    }
+
+    fn print(&self) {
+        let index = self.index.map_or("-".to_owned(), |index| index.to_string());
+        let name = if self.index.is_some() { name_or_unnamed(&self.name) } else { "default" };
+        let c = self.base_color_factor;
+        println!("  {} ({}): {}{}{}{}", index, name, self.alpha_mode,
+            if self.alpha_mode == "MASK" { format!(" (cutoff: {})", self.alpha_cutoff) } else { String::new() },
+            if self.double_sided { ", double sided" } else { "" },
+            if self.unlit { ", unlit" } else { "" });
+        println!("    base color: [{:.3}, {:.3}, {:.3}, {:.3}], texture: {}",
+            c[0], c[1], c[2], c[3], texture_usage(&self.base_color_texture));
+        println!("    metallic: {:.3}, roughness: {:.3}, texture: {}",
+            self.metallic_factor, self.roughness_factor, texture_usage(&self.metallic_roughness_texture));
+        println!("    normal: {}, occlusion: {}, emissive: {}",
+            texture_usage(&self.normal_texture),
+            texture_usage(&self.occlusion_texture),
+            texture_usage(&self.emissive_texture));
+        if self.transmission_factor > 0.0 {
+            println!("    transmission: {:.3}, texture: {}",
+                self.transmission_factor, texture_usage(&self.transmission_texture));
+        }
+        if self.clearcoat_factor > 0.0 {
+            println!("    clearcoat: {:.3}, texture: {}, roughness: {:.3}, texture: {}, normal: {}",
+                self.clearcoat_factor, texture_usage(&self.clearcoat_texture),
+                self.clearcoat_roughness_factor, texture_usage(&self.clearcoat_roughness_texture),
+                texture_usage(&self.clearcoat_normal_texture));
+        }
+    }
+}
+
+/// Node hierarchy of `scene` as indented text (printed with `--dump-tree`): the mesh, camera, light
+/// and skin of each node and the translation and scale of its `final_transform` (world space).
+pub fn scene_tree(root: &Root, scene: &Scene, scene_index: usize) -> String {
+    let mut tree = format!("Scene {} ({}): {} root nodes\n",
+        scene_index, name_or_unnamed(&scene.name), scene.nodes.len());
+    for node_id in &scene.nodes {
+        write_node_tree(root, *node_id, 1, &mut tree);
+    }
+    tree
+}
+
+fn write_node_tree(root: &Root, node_id: usize, depth: usize, tree: &mut String) {
+    let node = &root.nodes[node_id];
+    let mut parts = Vec::new();
+    if let Some(ref mesh) = node.mesh {
+        parts.push(format!("mesh {} ({})", mesh.index, name_or_unnamed(&mesh.name)));
+    }
+    if let Some(ref camera) = node.camera {
+        parts.push(if camera.is_perspective() { "perspective camera" } else { "orthographic camera" }.to_owned());
+    }
+    if let Some(light) = node.light {
+        parts.push(format!("light {}", light));
+    }
+    if let Some(skin) = node.skin {
+        parts.push(format!("skin {}", skin));
+    }
+    let transform = &node.final_transform;
+    let t = transform.w.truncate();
+    let s = vec3(transform.x.truncate().magnitude(), transform.y.truncate().magnitude(),
+        transform.z.truncate().magnitude());
+    parts.push(format!("translation: [{:.3}, {:.3}, {:.3}], scale: [{:.3}, {:.3}, {:.3}]",
+        t.x, t.y, t.z, s.x, s.y, s.z));
+    let _ = writeln!(tree, "{}{} ({}): {}", "  ".repeat(depth), node.index, name_or_unnamed(&node.name), parts.join(", "));
+    for child_id in &node.children {
+        write_node_tree(root, *child_id, depth + 1, tree);
+    }
+}
+
+/// The glTF cameras (printed with `--list-cameras`), indexed like `--cam-index`
+pub fn camera_list(root: &Root) -> String {
+    let mut list = format!("{} cameras\n", root.camera_nodes.len());
+    for (index, &node_id) in root.camera_nodes.iter().enumerate() {
+        let node = &root.nodes[node_id];
+        let camera = node.camera.as_ref().unwrap();
+        let projection = if camera.is_perspective() {
+            format!("perspective, fovy: {:.1}°", camera.fovy)
+        } else {
+            format!("orthographic, ymag: {:.3}", camera.ymag.unwrap_or(0.0))
+        };
+        let _ = writeln!(list, "  {}: node {} ({}), {}", index, node.index, name_or_unnamed(&node.name), projection);
+    }
+    list
+}
+
+/// The animations (printed with `--list-animations`), indexed like `--animation`
+pub fn animation_list(root: &Root) -> String {
+    let mut list = format!("{} animations\n", root.animations.len());
+    for animation in &root.animations {
+        let _ = writeln!(list, "  {} ({}): {:.3} s, {} channels",
+            animation.index, name_or_unnamed(&animation.name), animation.duration, animation.channels.len());
+    }
+    list
+}
+
+fn texture_ref(texture: &Option<Rc<Texture>>) -> Option<TextureRef> {
+    texture.as_ref().map(|texture| TextureRef { index: texture.index, tex_coord: texture.tex_coord })
+}
+
+fn texture_usage(texture: &Option<TextureRef>) -> String {
+    match *texture {
+        Some(ref texture) => format!("{} (TEXCOORD_{})", texture.index, texture.tex_coord),
+        None => "-".to_owned(),
+    }
+}
+
+fn name_or_unnamed(name: &Option<String>) -> &str {
+    name.as_ref().map_or("unnamed", |name| name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::{Animation, Camera, Node};
+
+    #[test]
+    fn empty_model_json() {
+        let info = ModelInfo::from_root(&Root::default(), &[Scene::default()]);
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["version"], INFO_VERSION);
+        assert_eq!(json["scenes"][0]["root_nodes"], 0);
+        assert_eq!(json["totals"]["triangles"], 0);
+        for key in &["meshes", "materials", "textures", "animations", "skins"] {
+            assert!(json[*key].as_array().unwrap().is_empty(), "{}", key);
+        }
+    }
+
+    fn node(index: usize, name: Option<&str>, children: Vec<usize>, transform: Matrix4) -> Node {
+        Node {
+            index,
+            children,
+            matrix: transform,
+            mesh: None,
+            skin: None,
+            rotation: Quaternion::one(),
+            scale: vec3(1.0, 1.0, 1.0),
+            translation: Vector3::zero(),
+            weights: vec![],
+            camera: None,
+            light: None,
+            name: name.map(|name| name.to_owned()),
+            final_transform: transform,
+            bounds: Scene::default().bounds,
+        }
+    }
+
+    #[test]
+    fn dump_scene_tree() {
+        let mut root = Root::default();
+        root.nodes.push(node(0, Some("parent"), vec![1], Matrix4::from_scale(2.0)));
+        let mut child = node(1, None, vec![], Matrix4::from_translation(vec3(1.0, 2.0, 3.0)) * Matrix4::from_scale(2.0));
+        child.light = Some(0);
+        root.nodes.push(child);
+        let scene = Scene { name: Some("main".to_owned()), nodes: vec![0], ..Scene::default() };
+
+        assert_eq!(scene_tree(&root, &scene, 0), "Scene 0 (main): 1 root nodes\n\
+            \x20 0 (parent): translation: [0.000, 0.000, 0.000], scale: [2.000, 2.000, 2.000]\n\
+            \x20   1 (unnamed): light 0, translation: [1.000, 2.000, 3.000], scale: [2.000, 2.000, 2.000]\n");
+    }
+
+    #[test]
+    fn list_cameras_and_animations() {
+        let mut root = Root::default();
+        root.nodes.push(node(0, None, vec![], Matrix4::identity()));
+        let mut camera_node = node(1, Some("Closeup"), vec![], Matrix4::identity());
+        camera_node.camera = Some(Camera::default());
+        root.nodes.push(camera_node);
+        root.camera_nodes.push(1);
+        root.animations.push(Animation { index: 0, name: Some("Walk".to_owned()), channels: vec![], duration: 1.5 });
+
+        assert_eq!(camera_list(&root), "1 cameras\n  0: node 1 (Closeup), perspective, fovy: 45.0°\n");
+        assert_eq!(animation_list(&root), "1 animations\n  0 (Walk): 1.500 s, 0 channels\n");
+        assert_eq!(camera_list(&Root::default()), "0 cameras\n");
+    }
+}
@@ -0,0 +1,54 @@
+#![allow(dead_code)]
+#![allow(unknown_lints)]
+// the unsafe functions need a current OpenGL context (and the GL objects they use)
+#![allow(clippy::missing_safety_doc)]
+//! Rendering core of the `gltf-viewer` binary. Can also be embedded into other applications
+//! that provide their own OpenGL context (see `GltfViewer::from_current_context`).
+extern crate cgmath;
+
+extern crate collision;
+
+extern crate gl;
+
+extern crate glutin;
+
+extern crate gltf;
+extern crate gltf_importer;
+extern crate gltf_utils;
+
+#[cfg(feature = "basisu")]
+extern crate basis_universal;
+extern crate gif;
+#[cfg(feature = "gamepad")]
+extern crate gilrs;
+extern crate image;
+extern crate num_traits;
+extern crate reqwest;
+#[cfg(feature = "basisu")]
+extern crate ruzstd;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+#[macro_use]
+extern crate bitflags;
+
+#[macro_use]extern crate log;
+
+mod utils;
+pub mod viewer;
+pub use viewer::{GltfViewer, CameraOptions, ColorGrading, Corner, RenderOptions, ViewerError};
+
+pub mod shader;
+pub mod controls;
+mod file_watch;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod info;
+mod framebuffer;
+mod hdr_image;
+mod macros;
+mod http_source;
+mod palette;
+pub mod render;
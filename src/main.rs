@@ -3,44 +3,21 @@
 // #![allow(unused_features)]
 // #![feature(test)]
 #[macro_use] extern crate clap;
-extern crate cgmath;
-// use cgmath::prelude::*;
-
-extern crate collision;
-
-extern crate gl;
-
-extern crate glutin;
-
-extern crate gltf;
-extern crate gltf_importer;
-extern crate gltf_utils;
-
-extern crate image;
-extern crate num_traits;
+#[macro_use] extern crate log;
+extern crate simplelog;
+extern crate gltf_viewer;
 
-#[macro_use]
-extern crate bitflags;
+use std::io::{self, Read};
+use std::process;
 
 use clap::{Arg, App, AppSettings};
-
-#[macro_use]extern crate log;
-extern crate simplelog;
 use simplelog::{TermLogger, LevelFilter, Config as LogConfig};
 
-mod utils;
-mod viewer;
-use viewer::{GltfViewer, CameraOptions};
-
-mod shader;
-mod controls;
-mod framebuffer;
-mod macros;
-// TODO!: adapt Source...
-// mod http_source;
-// use http_source::HttpSource;
-mod render;
-use render::math::*;
+use gltf_viewer::{GltfViewer, CameraOptions, ColorGrading, Corner, RenderOptions};
+use gltf_viewer::viewer::{MIN_TURNTABLE_FPS, MAX_TURNTABLE_FPS};
+use gltf_viewer::render::PolygonOffset;
+use gltf_viewer::render::math::*;
+use gltf_viewer::shader::{ShaderFlags, ToneMapping};
 
 pub fn main() {
     let args = App::new("gltf-viewer")
@@ -49,25 +26,103 @@ pub fn main() {
         .setting(AppSettings::DeriveDisplayOrder)
         .before_help("glTF 2.0 viewer\n\nNavigate with the mouse (left/right click + drag, mouse wheel) \
                     or WASD/cursor keys.")
-        .arg(Arg::with_name("FILE") // TODO!: re-add URL when fixed...
+        .arg(Arg::with_name("FILE")
             .required(true)
             .takes_value(true)
-            .help("glTF file name"))
+            .help("glTF file name or URL, or - to read a self-contained binary glTF (.glb) from stdin"))
         .arg(Arg::with_name("screenshot")
             .long("screenshot")
             .short("s")
             .value_name("FILE")
-            .help("Create screenshot (PNG)"))
+            .help("Create screenshot (PNG, JPEG or BMP, depending on the file extension). \n\
+                EXR and HDR (Radiance) screenshots contain the linear colors without tone mapping."))
+        .arg(Arg::with_name("QUALITY")
+            .long("quality")
+            .default_value("90")
+            .help("Quality of JPEG screenshots (1-100)")
+            .validator(|value| match value.parse::<u8>() {
+                Ok(q) if (1..=100).contains(&q) => Ok(()),
+                Ok(_) => Err("must be between 1 and 100".to_owned()),
+                Err(err) => Err(err.to_string()),
+            }))
+        .arg(Arg::with_name("DEPTH")
+            .long("depth")
+            .value_name("FILE")
+            .requires("screenshot")
+            .conflicts_with("TILE-SIZE")
+            .help("Additionally save the linearized depth as 16-bit grayscale PNG (near: black, far: white)"))
+        .arg(Arg::with_name("PALETTE-OUT")
+            .long("palette-out")
+            .value_name("FILE")
+            .requires("screenshot")
+            .help("Additionally save the dominant colors of the screenshot as JSON ([[r, g, b], ...])"))
+        .arg(Arg::with_name("PALETTE-SIZE")
+            .long("palette-size")
+            .default_value("5")
+            .help("Number of dominant colors for --palette-out")
+            .validator(|value| value.parse::<usize>().map(|_| ()).map_err(|err| err.to_string())))
+        .arg(Arg::with_name("LOGO")
+            .long("logo")
+            .value_name("FILE")
+            .requires("screenshot")
+            .help("Image (e.g. PNG with alpha) to blend into a corner of the screenshot, like a watermark"))
+        .arg(Arg::with_name("LOGO-CORNER")
+            .long("logo-corner")
+            .default_value("bottom-right")
+            .possible_values(&["top-left", "top-right", "bottom-left", "bottom-right"])
+            .help("Corner of the screenshot for --logo"))
+        .arg(Arg::with_name("LOGO-OPACITY")
+            .long("logo-opacity")
+            .default_value("1.0")
+            .help("Opacity of the --logo (0-1)")
+            .validator(validate_fraction))
+        .arg(Arg::with_name("SHOT-EXPOSURE")
+            .long("shot-exposure")
+            .takes_value(true)
+            .value_name("EV")
+            .allow_hyphen_values(true)
+            .requires("screenshot")
+            .help("Exposure adjustment of the screenshot in stops (e.g. 0.5 or -1), the window isn't affected")
+            .validator(|value| value.parse::<f32>().map(|_| ()).map_err(|err| err.to_string())))
+        .arg(Arg::with_name("SHOT-CONTRAST")
+            .long("shot-contrast")
+            .default_value("1.0")
+            .requires("screenshot")
+            .help("Contrast of the screenshot (1: unchanged)")
+            .validator(validate_positive))
+        .arg(Arg::with_name("SHOT-SATURATION")
+            .long("shot-saturation")
+            .default_value("1.0")
+            .requires("screenshot")
+            .help("Saturation of the screenshot (0: grayscale, 1: unchanged)")
+            .validator(validate_non_negative))
+        .arg(Arg::with_name("turntable")
+            .long("turntable")
+            .value_name("FILE")
+            .help("Create a turntable of COUNT frames rotating around the object: \
+                an animated GIF (.gif) or a contact sheet (.png)"))
+        .arg(Arg::with_name("FPS")
+            .long("fps")
+            .default_value("10")
+            .help("Playback speed of turntable GIFs in frames per second (1-100)")
+            .validator(validate_fps))
         .arg(Arg::with_name("verbose")
             .long("verbose")
             .short("v")
             .multiple(true)
-            .help("Enable verbose logging (log level INFO). Can be repeated multiple times to increase log level to DEBUG/TRACE)"))
+            .help("Enable verbose logging (log level INFO, e.g. saved files and timings). \n\
+                Can be repeated multiple times to increase log level to DEBUG/TRACE)"))
+        .arg(Arg::with_name("quiet")
+            .long("quiet")
+            .short("q")
+            .conflicts_with("verbose")
+            .help("Only log errors (no warnings), e.g. for automated runs"))
         .arg(Arg::with_name("WIDTH")
             .long("width")
             .short("w")
             .default_value("800")
-            .help("Width in pixels")
+            .help("Width in pixels. Without --width/--height, screenshots have the real framebuffer size \n\
+                (e.g. twice as large on HiDPI displays).")
             .validator(|value| value.parse::<u32>().map(|_| ()).map_err(|err| err.to_string())))
         .arg(Arg::with_name("HEIGHT")
             .long("height")
@@ -75,15 +130,67 @@ pub fn main() {
             .default_value("600")
             .help("Height in pixels")
             .validator(|value| value.parse::<u32>().map(|_| ()).map_err(|err| err.to_string())))
+        .arg(Arg::with_name("MAX-FRAMES")
+            .long("max-frames")
+            .value_name("N")
+            .help("Exit after rendering N frames and log the frame time statistics (shown with -v; for \n\
+                benchmarking, e.g. with --no-vsync). Also works with --headless.")
+            .validator(validate_count))
+        .arg(Arg::with_name("DETERMINISTIC")
+            .long("deterministic")
+            .help("Reproducible rendering, e.g. for visual regression tests (also with --max-frames): \n\
+                fixed time step of 1/60 s per frame, animations start paused (step with .) and no auto-rotation."))
+        .arg(Arg::with_name("TIMER-WINDOW")
+            .long("timer-window")
+            .value_name("N")
+            .env("GLTF_VIEWER_TIMER_WINDOW")
+            .default_value("300")
+            .help("Number of frames the render timings (logged with -v) are averaged over")
+            .validator(validate_count))
         .arg(Arg::with_name("COUNT")
             .long("count")
             .short("c")
             .default_value("1")
             .help("Saves N screenshots of size WxH, rotating evenly spaced around the object")
             .validator(|value| value.parse::<u32>().map(|_| ()).map_err(|err| err.to_string())))
+        .arg(Arg::with_name("TILE-SIZE")
+            .long("tile-size")
+            .takes_value(true)
+            .help("Render screenshots/turntables larger than this (in pixels, per side) in tiles. \n\
+                Useful for resolutions exceeding the maximum framebuffer size of the GPU.")
+            .validator(|value| match value.parse::<u32>() {
+                Ok(0) => Err("must be greater than 0".to_owned()),
+                Ok(_) => Ok(()),
+                Err(err) => Err(err.to_string()),
+            }))
         .arg(Arg::with_name("headless")
             .long("headless")
             .help("Use real headless rendering for screenshots (Default is a hidden window) [EXPERIMENTAL]"))
+        .arg(Arg::with_name("INFO")
+            .long("info")
+            .help("Print a summary of the scenes, meshes, materials and textures after loading. \n\
+                Exits afterwards with --headless (unless a screenshot is requested)."))
+        .arg(Arg::with_name("INFO-JSON")
+            .long("info-json")
+            .value_name("FILE")
+            .help("Like --info, but saves the summary as JSON (including a format version)"))
+        .arg(Arg::with_name("DUMP-TREE")
+            .long("dump-tree")
+            .help("Print the node hierarchy of the scene after loading (names, mesh/camera/light references \n\
+                and world space transforms) and exit. Loads the file headless."))
+        .arg(Arg::with_name("LIST-CAMERAS")
+            .long("list-cameras")
+            .help("Print the glTF cameras (index for --cam-index, node name, projection and field of view) \n\
+                and exit. Loads the file headless."))
+        .arg(Arg::with_name("LIST-ANIMATIONS")
+            .long("list-animations")
+            .help("Print the animations (index for --animation, name, duration and channel count) \n\
+                and exit. Loads the file headless."))
+        .arg(Arg::with_name("TIMINGS-JSON")
+            .long("timings-json")
+            .value_name("FILE")
+            .help("Save the startup timings (download, import, scene build, first frame) in ms as JSON \n\
+                once the first frame is drawn. Use - for stdout."))
         .arg(Arg::with_name("CAM-INDEX")
             .long("cam-index")
             .takes_value(true)
@@ -94,6 +201,12 @@ pub fn main() {
                 Can be forced by passing -1.
                 Note: All other camera options are ignored if this one is given.")
             .validator(|value| value.parse::<i32>().map(|_| ()).map_err(|err| err.to_string())))
+        .arg(Arg::with_name("CAM-NAME")
+            .long("cam-name")
+            .takes_value(true)
+            .value_name("NAME")
+            .help("Use the glTF camera of the node with the given name (e.g. Closeup). \n\
+                Takes precedence over --cam-index. Fails if there is no such camera node."))
         .arg(Arg::with_name("CAM-POS")
             .long("cam-pos")
             .takes_value(true)
@@ -110,6 +223,249 @@ pub fn main() {
             .default_value("75")
             .help("Vertical field of view ('zoom') in degrees.")
             .validator(|value| value.parse::<u32>().map(|_| ()).map_err(|err| err.to_string())))
+        .arg(Arg::with_name("ROTATE-SENSITIVITY")
+            .long("rotate-sensitivity")
+            .default_value("1.0")
+            .help("Multiplier for mouse rotation speed")
+            .validator(validate_positive))
+        .arg(Arg::with_name("PAN-SENSITIVITY")
+            .long("pan-sensitivity")
+            .default_value("1.0")
+            .help("Multiplier for mouse panning speed")
+            .validator(validate_positive))
+        .arg(Arg::with_name("ZOOM-SENSITIVITY")
+            .long("zoom-sensitivity")
+            .default_value("1.0")
+            .help("Multiplier for mouse wheel zoom speed. Try lower values for trackpads.")
+            .validator(validate_positive))
+        .arg(Arg::with_name("INVERT-SCROLL")
+            .long("invert-scroll")
+            .help("Invert the zoom direction of the mouse wheel/trackpad. \n\
+                Default: scrolling up (or swiping up with two fingers) zooms in, with this option it zooms out."))
+        .arg(Arg::with_name("ZOOM-TO-CURSOR")
+            .long("zoom-to-cursor")
+            .help("Scrolling moves the camera towards the point under the cursor. \n\
+                Default: scrolling zooms towards the orbit target (at the center of the window)."))
+        .arg(Arg::with_name("MIN-DISTANCE")
+            .long("min-distance")
+            .takes_value(true)
+            .help("Minimum distance between camera and orbit target when zooming/rotating. \n\
+                Default: 1/100 of the scene size")
+            .validator(validate_positive))
+        .arg(Arg::with_name("MAX-DISTANCE")
+            .long("max-distance")
+            .takes_value(true)
+            .help("Maximum distance between camera and orbit target when zooming/rotating. \n\
+                Default: 10 times the scene size")
+            .validator(validate_positive))
+        .arg(Arg::with_name("MOVE-SPEED")
+            .long("move-speed")
+            .takes_value(true)
+            .help("Keyboard movement speed in units per second. \n\
+                Default: a quarter of the scene size (Shift: 4x faster, Alt: 4x slower)")
+            .validator(validate_positive))
+        .arg(Arg::with_name("CAM-TRANSITION")
+            .long("cam-transition")
+            .takes_value(true)
+            .value_name("SECONDS")
+            .help("Smoothly move the camera to its new pose when switching cameras or scenes or recentering. \n\
+                Default: instant switches")
+            .validator(validate_positive))
+        .arg(Arg::with_name("AUTO-ROTATE")
+            .long("auto-rotate")
+            .takes_value(true)
+            .value_name("DEG_PER_SEC")
+            .help("Slowly spin the model (e.g. 10 degrees per second) when there's no mouse/keyboard input \n\
+                for --idle-timeout seconds. Any input pauses it.")
+            .validator(|value| value.parse::<f32>().map(|_| ()).map_err(|err| err.to_string())))
+        .arg(Arg::with_name("IDLE-TIMEOUT")
+            .long("idle-timeout")
+            .takes_value(true)
+            .value_name("SECONDS")
+            .default_value("10")
+            .help("Seconds without input before --auto-rotate starts")
+            .validator(validate_positive))
+        .arg(Arg::with_name("LOAD-CAMERA")
+            .long("load-camera")
+            .value_name("FILE")
+            .help("Load camera pose (position, target, fovy) from a JSON file, as saved with the P key. \n\
+                Note: All other camera options are ignored if this one is given."))
+        .arg(Arg::with_name("ORTHO")
+            .long("ortho")
+            .help("Use an orthographic instead of a perspective projection. Toggle with 5."))
+        .arg(Arg::with_name("BACKGROUND")
+            .long("background")
+            .takes_value(true)
+            .help("Background (clear) color as comma-separated RGB(A) in [0, 1]. Example: 1,1,1 \n\
+                Default: transparent for screenshots, dark blue otherwise.")
+            .validator(|value| parse_color(&value).map(|_| ())))
+        .arg(Arg::with_name("BG-GRADIENT")
+            .long("bg-gradient")
+            .takes_value(true)
+            .value_name("TOP,BOTTOM")
+            .help("Vertical background gradient as comma-separated RGB colors in [0, 1]. Example: 0.6,0.6,0.6,0.2,0.2,0.2 \n\
+                Left out of screenshots with a transparent background (use --opaque to keep it).")
+            .validator(|value| parse_gradient(&value).map(|_| ())))
+        .arg(Arg::with_name("BG-COMPOSITE")
+            .long("bg-composite")
+            .takes_value(true)
+            .value_name("R,G,B")
+            .help("Color in [0, 1] that the transparent background is blended over in screenshot formats \n\
+                without alpha (JPEG). Default: the background color.")
+            .validator(|value| parse_color(&value).map(|_| ())))
+        .arg(Arg::with_name("TRANSPARENT")
+            .long("transparent")
+            .conflicts_with("OPAQUE")
+            .help("Transparent background (alpha 0) in screenshots, also when rendering in a visible window"))
+        .arg(Arg::with_name("OPAQUE")
+            .long("opaque")
+            .help("Opaque background in screenshots, also with --headless (the --background color or dark blue)"))
+        .arg(Arg::with_name("MSAA")
+            .long("msaa")
+            .value_name("N")
+            .help("Enable multisample anti-aliasing with N samples per pixel (power of two, e.g. 4; limited to what the GPU supports)")
+            .validator(validate_msaa))
+        .arg(Arg::with_name("NO-VSYNC")
+            .long("no-vsync")
+            .help("Disable vsync, i.e. don't limit the frame rate to the display refresh rate (e.g. for benchmarking)"))
+        .arg(Arg::with_name("NO-STATE-CACHING")
+            .long("no-state-caching")
+            .help("Set the program and material for every primitive, even if unchanged (e.g. for benchmarking)"))
+        .arg(Arg::with_name("NO-DRAW-SORTING")
+            .long("no-draw-sorting")
+            .help("Draw opaque primitives in scene order instead of grouped by shader and material (e.g. for benchmarking)"))
+        .arg(Arg::with_name("INSTANCING")
+            .long("instancing")
+            .help("Draw nodes sharing a mesh with instanced draw calls (experimental)"))
+        .arg(Arg::with_name("EXPOSURE")
+            .long("exposure")
+            .default_value("1.0")
+            .help("Exposure (linear multiplier) applied before tone mapping. Adjust with +/-.")
+            .validator(validate_positive))
+        .arg(Arg::with_name("TONEMAP")
+            .long("tonemap")
+            .default_value("none")
+            .possible_values(&["none", "reinhard", "aces"])
+            .help("Tone mapping operator for HDR colors"))
+        .arg(Arg::with_name("ENV")
+            .long("env")
+            .value_name("FILE")
+            .help("Equirectangular HDR environment map (.hdr) for image based lighting. Also shown as background."))
+        .arg(Arg::with_name("GRID")
+            .long("grid")
+            .help("Show a reference grid below the scene (also in screenshots). Toggle with G."))
+        .arg(Arg::with_name("SHADOWS")
+            .long("shadows")
+            .help("Cast shadows from the primary directional light (shadow map). Toggle with L."))
+        .arg(Arg::with_name("SHADOW-CATCHER")
+            .long("shadow-catcher")
+            .help("Show the shadows (see --shadows) on an otherwise invisible ground plane below the scene, \n\
+                e.g. for product shots with a transparent background."))
+        .arg(Arg::with_name("SHADOW-BIAS")
+            .long("shadow-bias")
+            .takes_value(true)
+            .default_value("0.005")
+            .help("Depth bias of the shadow map lookup. Increase against shadow acne (stripes), \n\
+                decrease if shadows detach from their casters.")
+            .validator(validate_positive))
+        .arg(Arg::with_name("SSAO")
+            .long("ssao")
+            .help("Screen space ambient occlusion (post-process)"))
+        .arg(Arg::with_name("SSAO-RADIUS")
+            .long("ssao-radius")
+            .takes_value(true)
+            .help("Sampling radius of the ambient occlusion in scene units. Default: 2% of the scene size")
+            .validator(validate_positive))
+        .arg(Arg::with_name("SSAO-INTENSITY")
+            .long("ssao-intensity")
+            .takes_value(true)
+            .default_value("1.0")
+            .help("Strength of the ambient occlusion darkening (0-1)")
+            .validator(validate_fraction))
+        .arg(Arg::with_name("OUTLINE")
+            .long("outline")
+            .help("Draw outlines at silhouettes and creases (post-process), e.g. for technical illustrations. \n\
+                Toggle with E."))
+        .arg(Arg::with_name("OUTLINE-THICKNESS")
+            .long("outline-thickness")
+            .takes_value(true)
+            .default_value("1.5")
+            .help("Outline thickness in pixels")
+            .validator(validate_positive))
+        .arg(Arg::with_name("OUTLINE-COLOR")
+            .long("outline-color")
+            .takes_value(true)
+            .default_value("0,0,0")
+            .help("Outline color as comma-separated RGB(A) in [0, 1]")
+            .validator(|value| parse_color(&value).map(|_| ())))
+        .arg(Arg::with_name("OUTLINE-FILL")
+            .long("outline-fill")
+            .takes_value(true)
+            .value_name("R,G,B")
+            .help("Flat color in [0, 1] replacing the shading of the model while outlines are shown. Example: 1,1,1")
+            .validator(|value| parse_color(&value).map(|_| ())))
+        .arg(Arg::with_name("STEREO")
+            .long("stereo")
+            .help("Red/cyan anaglyph stereo (for 3D glasses). Toggle with 3."))
+        .arg(Arg::with_name("NO-TRANSMISSION")
+            .long("no-transmission")
+            .help("Draw KHR_materials_transmission materials with simple alpha blending \n\
+                instead of refracting the scene behind them"))
+        .arg(Arg::with_name("POLYGON-OFFSET")
+            .long("polygon-offset")
+            .takes_value(true)
+            .value_name("FACTOR,UNITS")
+            .allow_hyphen_values(true)
+            .help("Depth offset of all materials (glPolygonOffset), e.g. -1,-1 to draw decals in front of \n\
+                the surface they're placed on instead of z-fighting with it")
+            .validator(|value| PolygonOffset::parse(&value).map(|_| ())))
+        .arg(Arg::with_name("MATERIAL-POLYGON-OFFSET")
+            .long("material-polygon-offset")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("MATERIAL=FACTOR,UNITS")
+            .allow_hyphen_values(true)
+            .help("Depth offset of the material with the given name, overriding --polygon-offset. \n\
+                Can be repeated.")
+            .validator(|value| PolygonOffset::parse_for_material(&value).map(|_| ())))
+        .arg(Arg::with_name("DEBUG")
+            .long("debug")
+            .takes_value(true)
+            .possible_values(&["normals", "base-color", "metallic", "roughness", "normal-map", "occlusion", "emissive"])
+            .help("Debug visualization. normals: world space normals as RGB (toggle with N). \n\
+                The others show a single material channel (cycle with C)."))
+        .arg(Arg::with_name("CLAY")
+            .long("clay")
+            .help("Clay render mode: all primitives use a uniform matte material (no textures or colors), \n\
+                to evaluate the shape. Toggle with U."))
+        .arg(Arg::with_name("LIGHT-DIR")
+            .long("light-dir")
+            .takes_value(true)
+            .allow_hyphen_values(true)
+            .validator(validate_direction)
+            .help("Direction towards the directional light as comma-separated Vector3. Example: 0,1,1 \n\
+                Default: from above and behind the camera. Rotate with Shift + cursor keys. \n\
+                Only used for scenes without KHR_lights_punctual lights."))
+        .arg(Arg::with_name("SCENE")
+            .long("scene")
+            .takes_value(true)
+            .default_value("0")
+            .help("Show the glTF scene with the given index (starting at 0). Switch scenes with [ and ].")
+            .validator(|value| value.parse::<usize>().map(|_| ()).map_err(|err| err.to_string())))
+        .arg(Arg::with_name("ANIMATION")
+            .long("animation")
+            .takes_value(true)
+            .help("Play the glTF animation with the given index (starting at 0). \n\
+                Default: the first animation, if there is any.")
+            .validator(|value| value.parse::<usize>().map(|_| ()).map_err(|err| err.to_string())))
+        .arg(Arg::with_name("WATCH")
+            .long("watch")
+            .help("Reload the glTF file when it changes, keeping the current camera pose"))
+        .arg(Arg::with_name("GAMEPAD")
+            .long("gamepad")
+            .help("Navigate with a gamepad: left stick rotates, right stick pans, triggers zoom, \n\
+                A/Cross recenters (needs a build with `--features gamepad`)"))
         .get_matches();
     let source = args.value_of("FILE").unwrap();
 
@@ -119,12 +475,70 @@ pub fn main() {
 
     let camera_options = CameraOptions {
         index: args.value_of("CAM-INDEX").map(|n| n.parse().unwrap()).unwrap(),
+        name: args.value_of("CAM-NAME").map(|name| name.to_owned()),
         position: args.value_of("CAM-POS").map(|v| parse_vec3(v).unwrap()),
         target: args.value_of("CAM-TARGET").map(|v| parse_vec3(v).unwrap()),
         fovy: args.value_of("CAM-FOVY").map(|n| n.parse().unwrap()).unwrap(),
+        orthographic: args.is_present("ORTHO"),
+        pose_file: args.value_of("LOAD-CAMERA").map(|f| f.to_owned()),
+        rotate_sensitivity: args.value_of("ROTATE-SENSITIVITY").unwrap().parse().unwrap(),
+        pan_sensitivity: args.value_of("PAN-SENSITIVITY").unwrap().parse().unwrap(),
+        zoom_sensitivity: args.value_of("ZOOM-SENSITIVITY").unwrap().parse().unwrap(),
+        invert_scroll: args.is_present("INVERT-SCROLL"),
+        zoom_to_cursor: args.is_present("ZOOM-TO-CURSOR"),
+        move_speed: args.value_of("MOVE-SPEED").map(|n| n.parse().unwrap()),
+        min_distance: args.value_of("MIN-DISTANCE").map(|n| n.parse().unwrap()),
+        max_distance: args.value_of("MAX-DISTANCE").map(|n| n.parse().unwrap()),
+        transition: args.value_of("CAM-TRANSITION").map_or(0.0, |n| n.parse().unwrap()),
+        auto_rotate: args.value_of("AUTO-ROTATE").map(|n| n.parse().unwrap()),
+        idle_timeout: args.value_of("IDLE-TIMEOUT").unwrap().parse().unwrap(),
+    };
+
+    let render_options = RenderOptions {
+        background: args.value_of("BACKGROUND").map(|v| parse_color(v).unwrap()),
+        background_gradient: args.value_of("BG-GRADIENT").map(|v| parse_gradient(v).unwrap()),
+        transparent: if args.is_present("TRANSPARENT") {
+            Some(true)
+        } else if args.is_present("OPAQUE") {
+            Some(false)
+        } else {
+            None
+        },
+        light_direction: args.value_of("LIGHT-DIR").map(|v| parse_vec3(v).unwrap()),
+        msaa_samples: args.value_of("MSAA").map_or(0, |n| n.parse().unwrap()),
+        vsync: !args.is_present("NO-VSYNC"),
+        state_caching: !args.is_present("NO-STATE-CACHING"),
+        draw_sorting: !args.is_present("NO-DRAW-SORTING"),
+        instancing: args.is_present("INSTANCING"),
+        exposure: args.value_of("EXPOSURE").unwrap().parse().unwrap(),
+        tone_mapping: ToneMapping::from_name(args.value_of("TONEMAP").unwrap()).unwrap(),
+        environment: args.value_of("ENV").map(|f| f.to_owned()),
+        grid: args.is_present("GRID"),
+        debug_flags: args.value_of("DEBUG")
+            .and_then(ShaderFlags::debug_flag_from_name)
+            .unwrap_or_else(ShaderFlags::empty) |
+            if args.is_present("CLAY") { ShaderFlags::CLAY } else { ShaderFlags::empty() },
+        timer_window: args.value_of("TIMER-WINDOW").unwrap().parse().unwrap(),
+        shadows: args.is_present("SHADOWS"),
+        shadow_bias: args.value_of("SHADOW-BIAS").unwrap().parse().unwrap(),
+        shadow_catcher: args.is_present("SHADOW-CATCHER"),
+        ssao: args.is_present("SSAO"),
+        ssao_radius: args.value_of("SSAO-RADIUS").map(|n| n.parse().unwrap()),
+        ssao_intensity: args.value_of("SSAO-INTENSITY").unwrap().parse().unwrap(),
+        outline: args.is_present("OUTLINE"),
+        outline_thickness: args.value_of("OUTLINE-THICKNESS").unwrap().parse().unwrap(),
+        outline_color: parse_color(args.value_of("OUTLINE-COLOR").unwrap()).unwrap(),
+        outline_fill: args.value_of("OUTLINE-FILL").map(|v| parse_color(v).unwrap()),
+        stereo: args.is_present("STEREO"),
+        transmission: !args.is_present("NO-TRANSMISSION"),
+        polygon_offset: args.value_of("POLYGON-OFFSET").map(|v| PolygonOffset::parse(v).unwrap()),
+        material_polygon_offsets: args.values_of("MATERIAL-POLYGON-OFFSET")
+            .map(|values| values.map(|v| PolygonOffset::parse_for_material(v).unwrap()).collect())
+            .unwrap_or_default(),
     };
 
     let log_level = match args.occurrences_of("verbose") {
+        _ if args.is_present("quiet") => LevelFilter::Error,
         0 => LevelFilter::Warn,
         1 => LevelFilter::Info,
         2 => LevelFilter::Debug,
@@ -133,31 +547,208 @@ pub fn main() {
 
     let _ = TermLogger::init(log_level, LogConfig { time: None, target: None, ..LogConfig::default() });
 
-    let mut viewer = GltfViewer::new(source, width, height,
-        args.is_present("headless"),
-        !args.is_present("screenshot"),
-        camera_options);
+    // the window/framebuffer only needs to hold one tile
+    let (window_width, window_height) = match args.value_of("TILE-SIZE").map(|n| n.parse::<u32>().unwrap()) {
+        Some(tile_size) => (width.min(tile_size), height.min(tile_size)),
+        None => (width, height),
+    };
+
+    // only printing the node hierarchy, cameras or animations doesn't need a window
+    let print_only = args.is_present("DUMP-TREE") || args.is_present("LIST-CAMERAS") ||
+        args.is_present("LIST-ANIMATIONS");
+    let headless = args.is_present("headless") || print_only;
+    let visible = !print_only && !args.is_present("screenshot") && !args.is_present("turntable");
+    let scene_index = args.value_of("SCENE").map(|n| n.parse().unwrap()).unwrap();
+    let animation_index = args.value_of("ANIMATION").map(|n| n.parse().unwrap());
+    let viewer = if source == "-" {
+        let mut data = Vec::new();
+        if let Err(err) = io::stdin().read_to_end(&mut data) {
+            error!("Failed to read glTF from stdin: {}", err);
+            process::exit(1)
+        }
+        GltfViewer::from_bytes(&data, window_width, window_height, headless, visible,
+            camera_options, render_options, scene_index, animation_index)
+    } else {
+        GltfViewer::new(source, window_width, window_height, headless, visible,
+            camera_options, render_options, scene_index, animation_index)
+    };
+    let mut viewer = match viewer {
+        Ok(viewer) => viewer,
+        Err(err) => {
+            error!("{}", err);
+            process::exit(err.exit_code())
+        }
+    };
+
+    if args.is_present("INFO") {
+        viewer.print_info();
+    }
+    if let Some(filename) = args.value_of("INFO-JSON") {
+        viewer.save_info_json(filename);
+    }
+    if args.is_present("DUMP-TREE") || args.is_present("LIST-CAMERAS") || args.is_present("LIST-ANIMATIONS") {
+        if args.is_present("DUMP-TREE") {
+            viewer.print_tree();
+        }
+        if args.is_present("LIST-CAMERAS") {
+            viewer.print_cameras();
+        }
+        if args.is_present("LIST-ANIMATIONS") {
+            viewer.print_animations();
+        }
+        return;
+    }
+    if let Some(filename) = args.value_of("TIMINGS-JSON") {
+        viewer.set_timings_output(filename);
+    }
+    if (args.is_present("INFO") || args.is_present("INFO-JSON")) &&
+            args.is_present("headless") &&
+            !args.is_present("screenshot") && !args.is_present("turntable") {
+        return;
+    }
+
+    // On HiDPI displays, the framebuffer has more pixels than the requested window size.
+    // Screenshots use the real framebuffer size unless the size is given explicitly.
+    let framebuffer_size = viewer.framebuffer_size();
+    let explicit_size = args.occurrences_of("WIDTH") > 0 || args.occurrences_of("HEIGHT") > 0;
+    let (width, height) = if explicit_size || args.is_present("TILE-SIZE") {
+        if !args.is_present("TILE-SIZE") && (width > framebuffer_size.0 || height > framebuffer_size.1) {
+            warn!("Screenshot size {}x{} exceeds the framebuffer size {}x{}, rendering in tiles",
+                width, height, framebuffer_size.0, framebuffer_size.1);
+        }
+        (width, height)
+    } else {
+        framebuffer_size
+    };
+
+    if args.is_present("DETERMINISTIC") {
+        viewer.set_deterministic();
+    }
+
+    if let Some(filename) = args.value_of("turntable") {
+        let fps: u32 = args.value_of("FPS").unwrap().parse().unwrap();
+        if let Err(err) = viewer.turntable(filename, width, height, count, fps) {
+            error!("Failed to save turntable: {}", err);
+            process::exit(1)
+        }
+        return;
+    }
 
     if args.is_present("screenshot") {
         let filename = args.value_of("screenshot").unwrap();
 
-        if !filename.to_lowercase().ends_with(".png") {
-            warn!("filename should end with .png");
+        viewer.set_jpeg_quality(args.value_of("QUALITY").unwrap().parse().unwrap());
+        if let Some(color) = args.value_of("BG-COMPOSITE") {
+            let color = parse_color(color).unwrap();
+            viewer.set_composite_background([color[0], color[1], color[2]]);
+        }
+        if let Some(palette_filename) = args.value_of("PALETTE-OUT") {
+            let size = args.value_of("PALETTE-SIZE").unwrap().parse().unwrap();
+            viewer.set_palette_output(palette_filename, size);
+        }
+        viewer.set_screenshot_grading(ColorGrading {
+            exposure: args.value_of("SHOT-EXPOSURE").map_or(0.0, |n| n.parse().unwrap()),
+            contrast: args.value_of("SHOT-CONTRAST").unwrap().parse().unwrap(),
+            saturation: args.value_of("SHOT-SATURATION").unwrap().parse().unwrap(),
+        });
+        if let Some(logo_filename) = args.value_of("LOGO") {
+            let corner = Corner::from_name(args.value_of("LOGO-CORNER").unwrap()).unwrap();
+            let opacity = args.value_of("LOGO-OPACITY").unwrap().parse().unwrap();
+            if let Err(err) = viewer.set_logo(logo_filename, corner, opacity) {
+                error!("Failed to load logo {}: {}", logo_filename, err);
+                process::exit(1)
+            }
         }
         if count > 1 {
             viewer.multiscreenshot(filename, width, height, count)
         } else {
             viewer.screenshot(filename, width, height)
         }
+        if let Some(depth_filename) = args.value_of("DEPTH") {
+            viewer.screenshot_depth(depth_filename, width, height)
+        }
         return;
     }
 
+    if let Some(max_frames) = args.value_of("MAX-FRAMES") {
+        viewer.set_max_frames(max_frames.parse().unwrap());
+    }
+    if args.is_present("WATCH") {
+        if source == "-" {
+            warn!("Watching is not supported for models read from stdin");
+        } else {
+            viewer.watch();
+        }
+    }
+    if args.is_present("GAMEPAD") {
+        viewer.enable_gamepad();
+    }
     viewer.start_render_loop();
 }
 
+fn validate_msaa(value: String) -> Result<(), String> {
+    match value.parse::<u16>() {
+        Ok(n) if n.is_power_of_two() => Ok(()),
+        Ok(_) => Err("must be a power of two".to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn validate_count(value: String) -> Result<(), String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(()),
+        Ok(_) => Err("must be at least 1".to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn validate_fps(value: String) -> Result<(), String> {
+    match value.parse::<u32>() {
+        Ok(n) if (MIN_TURNTABLE_FPS..=MAX_TURNTABLE_FPS).contains(&n) => Ok(()),
+        Ok(_) => Err(format!("must be between {} and {}", MIN_TURNTABLE_FPS, MAX_TURNTABLE_FPS)),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn validate_positive(value: String) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(v) if v > 0.0 => Ok(()),
+        Ok(_) => Err("must be greater than 0".to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn validate_non_negative(value: String) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(v) if v >= 0.0 => Ok(()),
+        Ok(_) => Err("must not be negative".to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn validate_fraction(value: String) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(()),
+        Ok(_) => Err("must be between 0 and 1".to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn validate_direction(value: String) -> Result<(), String> {
+    if value.split(',').count() != 3 {
+        return Err("must have 3 comma-separated components".to_owned())
+    }
+    match parse_vec3(&value) {
+        Ok(v) if v.magnitude2() > 0.0 && v.magnitude2().is_finite() => Ok(()),
+        Ok(_) => Err("must be a finite, non-zero vector".to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use gltf_viewer::render;
 
     #[test]
     fn print_struct_sizes() {
@@ -175,6 +766,17 @@ mod tests {
         println!("Vec<Node>:      {:>3}", std::mem::size_of::<Vec<render::Node>>());
     }
 
+    #[test]
+    fn validate_light_direction() {
+        assert!(validate_direction("0,1,1".to_owned()).is_ok());
+        assert!(validate_direction("-1,-0.5,2".to_owned()).is_ok());
+        assert!(validate_direction("0,0,0".to_owned()).is_err());
+        assert!(validate_direction("0,1".to_owned()).is_err());
+        assert!(validate_direction("0,1,1,1".to_owned()).is_err());
+        assert!(validate_direction("x,1,1".to_owned()).is_err());
+        assert!(validate_direction("inf,0,0".to_owned()).is_err());
+    }
+
 //     extern crate test;
 //     use self::test::Bencher;
 //     #[bench]
@@ -0,0 +1,70 @@
+use std::fs::File;
+use std::io;
+
+use image::RgbaImage;
+use serde_json;
+
+/// Bits per channel kept when grouping similar colors
+const QUANTIZATION_BITS: u32 = 4;
+
+/// Returns up to `count` dominant colors of the image, most frequent first.
+/// Similar colors are grouped (by quantizing them), each group is represented by its average color.
+/// (Mostly) transparent pixels, e.g. the background of screenshots, are ignored.
+pub fn dominant_colors(image: &RgbaImage, count: usize) -> Vec<[u8; 3]> {
+    let shift = 8 - QUANTIZATION_BITS;
+    let num_buckets = 1 << (3 * QUANTIZATION_BITS);
+    // sum of r, g, b and number of pixels per bucket
+    let mut buckets = vec![[0u64; 4]; num_buckets];
+    for pixel in image.pixels() {
+        let (r, g, b, a) = (pixel.data[0], pixel.data[1], pixel.data[2], pixel.data[3]);
+        if a < 128 {
+            continue
+        }
+        let index = ((r as usize >> shift) << (2 * QUANTIZATION_BITS)) |
+            ((g as usize >> shift) << QUANTIZATION_BITS) |
+            (b as usize >> shift);
+        let bucket = &mut buckets[index];
+        bucket[0] += u64::from(r);
+        bucket[1] += u64::from(g);
+        bucket[2] += u64::from(b);
+        bucket[3] += 1;
+    }
+
+    let mut used: Vec<&[u64; 4]> = buckets.iter().filter(|bucket| bucket[3] > 0).collect();
+    // stable sort: ties keep the bucket order, so the result is deterministic
+    used.sort_by(|a, b| b[3].cmp(&a[3]));
+    used.iter()
+        .take(count)
+        .map(|bucket| [
+            (bucket[0] / bucket[3]) as u8,
+            (bucket[1] / bucket[3]) as u8,
+            (bucket[2] / bucket[3]) as u8,
+        ])
+        .collect()
+}
+
+/// Saves the colors as JSON array of `[r, g, b]` arrays
+pub fn save_palette(filename: &str, colors: &[[u8; 3]]) -> io::Result<()> {
+    let file = File::create(filename)?;
+    serde_json::to_writer(file, colors)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn find_dominant_colors() {
+        let image = RgbaImage::from_fn(10, 10, |x, y| match (x, y) {
+            (0, 0) => Rgba { data: [0, 255, 0, 255] },
+            (_, 0) | (_, 1) => Rgba { data: [0, 0, 0, 0] }, // transparent
+            (0..=5, _) => Rgba { data: [250, 10, 10, 255] },
+            _ => Rgba { data: [10, 10, 250, 255] },
+        });
+        assert_eq!(dominant_colors(&image, 2), vec![[250, 10, 10], [10, 10, 250]]);
+        assert_eq!(dominant_colors(&image, 5).len(), 3);
+        assert!(dominant_colors(&RgbaImage::new(4, 4), 5).is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+use cgmath::Vector1;
+use gltf;
+use gltf::accessor::DataType;
+use gltf::json::animation::{InterpolationAlgorithm as Interpolation, TrsProperty};
+use gltf_importer;
+use gltf_utils::AccessorIter;
+
+use render::math::*;
+use render::{Node, Root};
+
+/// Keyframe values of a channel. Rotations are stored as (x, y, z, w) quaternions.
+/// Morph target weights are stored per target (i.e. `weights[target][keyframe]`).
+pub enum ChannelOutputs {
+    Translations(Vec<Vector3>),
+    Rotations(Vec<Vector4>),
+    Scales(Vec<Vector3>),
+    Weights(Vec<Vec<Vector1<f32>>>),
+}
+
+pub struct Channel {
+    pub node: usize, // glTF index
+    pub interpolation: Interpolation,
+    pub inputs: Vec<f32>, // keyframe times in seconds
+    pub outputs: ChannelOutputs,
+
+    // value of the animated property before the animation was applied (for resetting)
+    rest_translation: Vector3,
+    rest_rotation: Quaternion,
+    rest_scale: Vector3,
+    rest_weights: Vec<f32>,
+}
+
+pub struct Animation {
+    pub index: usize, // glTF index
+    pub name: Option<String>,
+    pub channels: Vec<Channel>,
+    pub duration: f32, // seconds
+}
+
+impl Animation {
+    pub fn from_gltf(g_animation: &gltf::Animation, root: &Root, buffers: &gltf_importer::Buffers) -> Animation {
+        let mut channels = Vec::new();
+        for g_channel in g_animation.channels() {
+            let g_sampler = g_channel.sampler();
+            let target = g_channel.target();
+            let node = &root.nodes[target.node().index()];
+
+            let inputs: Vec<f32> = AccessorIter::<f32>::new(g_sampler.input(), buffers).collect();
+            if inputs.is_empty() {
+                continue
+            }
+            let outputs = match target.path() {
+                TrsProperty::Translation => ChannelOutputs::Translations(
+                    AccessorIter::<[f32; 3]>::new(g_sampler.output(), buffers).map(Vector3::from).collect()),
+                TrsProperty::Rotation => ChannelOutputs::Rotations(rotations(g_sampler.output(), buffers)),
+                TrsProperty::Scale => ChannelOutputs::Scales(
+                    AccessorIter::<[f32; 3]>::new(g_sampler.output(), buffers).map(Vector3::from).collect()),
+                TrsProperty::Weights => {
+                    let values: Vec<f32> = AccessorIter::<f32>::new(g_sampler.output(), buffers).collect();
+                    let values_per_keyframe = match g_sampler.interpolation() {
+                        Interpolation::CubicSpline => 3,
+                        _ => 1,
+                    };
+                    let num_targets = values.len() / (inputs.len() * values_per_keyframe);
+                    if num_targets == 0 {
+                        continue
+                    }
+                    ChannelOutputs::Weights((0..num_targets)
+                        .map(|target| values.iter()
+                            .enumerate()
+                            .filter(|&(i, _)| i % num_targets == target)
+                            .map(|(_, &weight)| Vector1::new(weight))
+                            .collect())
+                        .collect())
+                }
+            };
+
+            let interpolation = match g_sampler.interpolation() {
+                Interpolation::Linear => Interpolation::Linear,
+                Interpolation::Step => Interpolation::Step,
+                Interpolation::CubicSpline => Interpolation::CubicSpline,
+                other => {
+                    warn!("Unsupported interpolation {:?}, falling back to LINEAR (animation: {})",
+                        other, g_animation.index());
+                    Interpolation::Linear
+                }
+            };
+
+            channels.push(Channel {
+                node: node.index,
+                interpolation,
+                inputs,
+                outputs,
+                rest_translation: node.translation,
+                rest_rotation: node.rotation,
+                rest_scale: node.scale,
+                rest_weights: node.weights.clone(),
+            });
+        }
+
+        let duration = channels.iter()
+            .filter_map(|channel| channel.inputs.last())
+            .fold(0.0, |max: f32, &time| max.max(time));
+
+        Animation {
+            index: g_animation.index(),
+            name: g_animation.name().map(|s| s.into()),
+            channels,
+            duration,
+        }
+    }
+
+    /// Sets the animated node properties to their values at `time` (looping).
+    /// Node transforms need to be updated afterwards.
+    pub fn animate(&self, nodes: &mut [Node], time: f32) {
+        let time = if self.duration > 0.0 { time % self.duration } else { 0.0 };
+        for channel in &self.channels {
+            let node = &mut nodes[channel.node];
+            channel.apply(node, time);
+        }
+    }
+
+    /// Restores the properties of all animated nodes to their original values.
+    pub fn reset(&self, nodes: &mut [Node]) {
+        for channel in &self.channels {
+            let node = &mut nodes[channel.node];
+            match channel.outputs {
+                ChannelOutputs::Translations(_) => node.translation = channel.rest_translation,
+                ChannelOutputs::Rotations(_) => node.rotation = channel.rest_rotation,
+                ChannelOutputs::Scales(_) => node.scale = channel.rest_scale,
+                ChannelOutputs::Weights(_) => node.weights = channel.rest_weights.clone(),
+            }
+        }
+    }
+}
+
+impl Channel {
+    fn apply(&self, node: &mut Node, time: f32) {
+        match self.outputs {
+            ChannelOutputs::Translations(ref values) =>
+                node.translation = sample(&self.interpolation, &self.inputs, values, time, lerp),
+            ChannelOutputs::Rotations(ref values) => {
+                let r = sample(&self.interpolation, &self.inputs, values, time, nlerp).normalize();
+                node.rotation = Quaternion::new(r.w, r.x, r.y, r.z); // NOTE: different element order!
+            },
+            ChannelOutputs::Scales(ref values) =>
+                node.scale = sample(&self.interpolation, &self.inputs, values, time, lerp),
+            ChannelOutputs::Weights(ref targets) => {
+                if node.weights.len() < targets.len() {
+                    node.weights.resize(targets.len(), 0.0);
+                }
+                for (weight, values) in node.weights.iter_mut().zip(targets) {
+                    *weight = sample(&self.interpolation, &self.inputs, values, time, lerp).x;
+                }
+            },
+        }
+    }
+}
+
+fn lerp<T: VectorSpace<Scalar=f32>>(a: T, b: T, amount: f32) -> T {
+    a + (b - a) * amount
+}
+
+/// Normalized linear interpolation of quaternions (as Vector4), taking the shortest path.
+fn nlerp(a: Vector4, b: Vector4, amount: f32) -> Vector4 {
+    let b = if a.dot(b) < 0.0 { -b } else { b };
+    lerp(a, b, amount).normalize()
+}
+
+/// Samples the keyframes at `time`. For CUBICSPLINE, `values` contains
+/// (in-tangent, value, out-tangent) triples for each keyframe.
+fn sample<T, F>(interpolation: &Interpolation, inputs: &[f32], values: &[T], time: f32, linear: F) -> T
+    where T: VectorSpace<Scalar=f32>, F: Fn(T, T, f32) -> T
+{
+    let value = |i: usize| match *interpolation {
+        Interpolation::CubicSpline => values[i * 3 + 1],
+        _ => values[i],
+    };
+
+    let last = inputs.len() - 1;
+    if time <= inputs[0] {
+        return value(0)
+    }
+    if time >= inputs[last] {
+        return value(last)
+    }
+    let next = inputs.iter().position(|&t| t > time).unwrap();
+    let prev = next - 1;
+    let delta = inputs[next] - inputs[prev];
+    let t = (time - inputs[prev]) / delta;
+
+    match *interpolation {
+        Interpolation::Step => value(prev),
+        Interpolation::CubicSpline => {
+            // spec: Appendix C - Spline Interpolation
+            let out_tangent = values[prev * 3 + 2] * delta;
+            let in_tangent = values[next * 3] * delta;
+            let t2 = t * t;
+            let t3 = t2 * t;
+            value(prev) * (2.0 * t3 - 3.0 * t2 + 1.0) +
+                out_tangent * (t3 - 2.0 * t2 + t) +
+                value(next) * (-2.0 * t3 + 3.0 * t2) +
+                in_tangent * (t3 - t2)
+        },
+        _ => linear(value(prev), value(next), t),
+    }
+}
+
+/// Reads rotation keyframes, which may also be stored as normalized (unsigned) bytes or shorts.
+fn rotations(accessor: gltf::Accessor, buffers: &gltf_importer::Buffers) -> Vec<Vector4> {
+    match accessor.data_type() {
+        DataType::I8 => AccessorIter::<[i8; 4]>::new(accessor, buffers)
+            .map(|r| denormalize(r, |c| (f32::from(c) / 127.0).max(-1.0))).collect(),
+        DataType::U8 => AccessorIter::<[u8; 4]>::new(accessor, buffers)
+            .map(|r| denormalize(r, |c| f32::from(c) / 255.0)).collect(),
+        DataType::I16 => AccessorIter::<[i16; 4]>::new(accessor, buffers)
+            .map(|r| denormalize(r, |c| (f32::from(c) / 32767.0).max(-1.0))).collect(),
+        DataType::U16 => AccessorIter::<[u16; 4]>::new(accessor, buffers)
+            .map(|r| denormalize(r, |c| f32::from(c) / 65535.0)).collect(),
+        _ => AccessorIter::<[f32; 4]>::new(accessor, buffers).map(Vector4::from).collect(),
+    }
+}
+
+fn denormalize<T: Copy, F: Fn(T) -> f32>(components: [T; 4], to_f32: F) -> Vector4 {
+    Vector4::new(to_f32(components[0]), to_f32(components[1]), to_f32(components[2]), to_f32(components[3]))
+}
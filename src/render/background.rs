@@ -0,0 +1,56 @@
+use gl;
+
+use render::math::*;
+use shader::Shader;
+
+/// Vertical color gradient drawn behind the scene instead of the plain clear color
+pub struct GradientBackground {
+    shader: Shader,
+    u_top_color: i32,
+    u_bottom_color: i32,
+    u_inverse_tile_projection: i32,
+    vao: u32, // empty (full screen triangle without vertex attributes)
+}
+
+impl GradientBackground {
+    /// NOTE: Needs a current GL context
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> GradientBackground {
+        let mut shader = Shader::from_source(
+            include_str!("../shaders/fullscreen-vert.glsl"),
+            include_str!("../shaders/gradient-frag.glsl"),
+            &[]);
+        unsafe {
+            let u_top_color = shader.uniform_location("u_TopColor");
+            let u_bottom_color = shader.uniform_location("u_BottomColor");
+            let u_inverse_tile_projection = shader.uniform_location("u_InverseTileProjection");
+            let mut vao = 0;
+            gl::GenVertexArrays(1, &mut vao);
+            GradientBackground { shader, u_top_color, u_bottom_color, u_inverse_tile_projection, vao }
+        }
+    }
+
+    /// Fills the viewport without testing or writing depth, so everything drawn afterwards is in front.
+    /// `tile_projection`: see `GltfViewer::render_tiled` (the gradient spans the whole image).
+    pub unsafe fn draw(&self, top: &[f32; 3], bottom: &[f32; 3], tile_projection: Option<&Matrix4>) {
+        let inverse_tile_projection = tile_projection
+            .and_then(|projection| projection.invert())
+            .unwrap_or_else(Matrix4::identity);
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        gl::Disable(gl::BLEND);
+        gl::Disable(gl::CULL_FACE);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::DepthMask(gl::FALSE);
+
+        self.shader.use_program();
+        self.shader.set_vec3(self.u_top_color, top[0], top[1], top[2]);
+        self.shader.set_vec3(self.u_bottom_color, bottom[0], bottom[1], bottom[2]);
+        self.shader.set_mat4(self.u_inverse_tile_projection, &inverse_tile_projection);
+        gl::BindVertexArray(self.vao);
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        gl::BindVertexArray(0);
+
+        gl::DepthMask(gl::TRUE);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+}
@@ -0,0 +1,77 @@
+use std::mem::size_of;
+use std::os::raw::c_void;
+use std::ptr;
+
+use gl;
+
+use render::math::*;
+use shader::Shader;
+
+pub const SCENE_BOUNDS_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0]; // yellow
+pub const MESH_BOUNDS_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 1.0]; // cyan
+
+/// Draws axis-aligned bounding boxes as wireframes (for debugging)
+pub struct BoundingBoxRenderer {
+    shader: Shader,
+    u_mvp_matrix: i32,
+    u_color: i32,
+    vao: u32,
+}
+
+impl BoundingBoxRenderer {
+    /// NOTE: Needs a current GL context
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> BoundingBoxRenderer {
+        let mut shader = Shader::from_source(
+            include_str!("../shaders/line-vert.glsl"),
+            include_str!("../shaders/line-frag.glsl"),
+            &[]);
+
+        // the 12 edges of the unit cube [0, 1]^3: corner i has x/y/z = 1 if bit 0/1/2 is set
+        let mut vertices: Vec<f32> = Vec::with_capacity(24 * 3);
+        for i in 0..8usize {
+            for &axis_bit in &[1usize, 2, 4] {
+                if i & axis_bit == 0 {
+                    for &corner in &[i, i | axis_bit] {
+                        vertices.extend_from_slice(&[
+                            (corner & 1) as f32, ((corner >> 1) & 1) as f32, ((corner >> 2) & 1) as f32]);
+                    }
+                }
+            }
+        }
+
+        unsafe {
+            let u_mvp_matrix = shader.uniform_location("u_MVPMatrix");
+            let u_color = shader.uniform_location("u_Color");
+
+            let (mut vao, mut vbo) = (0, 0);
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(gl::ARRAY_BUFFER, (vertices.len() * size_of::<f32>()) as isize,
+                vertices.as_ptr() as *const c_void, gl::STATIC_DRAW);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 3 * size_of::<f32>() as i32, ptr::null());
+            gl::BindVertexArray(0);
+
+            BoundingBoxRenderer { shader, u_mvp_matrix, u_color, vao }
+        }
+    }
+
+    /// Draws the box on top of everything (without depth testing)
+    pub unsafe fn draw(&self, bounds: &Aabb3, view_projection: &Matrix4, color: [f32; 4]) {
+        let size = bounds.max - bounds.min;
+        let model_matrix = Matrix4::from_translation(bounds.min.to_vec()) *
+            Matrix4::from_nonuniform_scale(size.x, size.y, size.z);
+
+        gl::Disable(gl::DEPTH_TEST);
+        self.shader.use_program();
+        self.shader.set_mat4(self.u_mvp_matrix, &(view_projection * model_matrix));
+        gl::Uniform4f(self.u_color, color[0], color[1], color[2], color[3]);
+        gl::BindVertexArray(self.vao);
+        gl::DrawArrays(gl::LINES, 0, 24);
+        gl::BindVertexArray(0);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+}
@@ -1,4 +1,4 @@
-use cgmath::{Deg, Rad, perspective};
+use cgmath::{Deg, Rad, ortho, perspective};
 
 use gltf;
 use gltf::camera::Projection;
@@ -72,6 +72,27 @@ impl Camera {
 
     pub fn update_aspect_ratio(&mut self, aspect_ratio: f32) {
         self.aspect_ratio = aspect_ratio;
+        if let Some(ymag) = self.ymag {
+            // retain window aspect ratio (like for perspective cameras)
+            self.xmag = Some(ymag * aspect_ratio);
+        }
+        self.update_projection_matrix();
+    }
+
+    /// Switch to an orthographic projection. `ymag` is half the height of the view volume.
+    pub fn set_orthographic(&mut self, ymag: f32) {
+        self.ymag = Some(ymag);
+        self.xmag = Some(ymag * self.aspect_ratio);
+        if self.zfar.is_none() {
+            self.zfar = Some(1000.0);
+        }
+        self.update_projection_matrix();
+    }
+
+    /// Switch to a perspective projection (using the current `fovy`)
+    pub fn set_perspective(&mut self) {
+        self.xmag = None;
+        self.ymag = None;
         self.update_projection_matrix();
     }
 
@@ -80,8 +101,9 @@ impl Camera {
     }
 
     pub fn update_projection_matrix(&mut self) {
-        if let Some(_xmag) = self.xmag {
-            unimplemented!("orthographic camera") // TODO!!!: ortho camera
+        if let (Some(xmag), Some(ymag)) = (self.xmag, self.ymag) {
+            let zfar = self.zfar.expect("orthographic camera requires zfar");
+            self.projection_matrix = ortho(-xmag, xmag, -ymag, ymag, self.znear, zfar)
         } else if let Some(zfar) = self.zfar {
             self.projection_matrix = perspective(
                 Deg(self.fovy),
@@ -0,0 +1,301 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::mem::size_of;
+use std::os::raw::c_void;
+use std::path::Path;
+use std::ptr;
+use std::time::Instant;
+
+use cgmath::{Deg, perspective};
+use gl;
+use image::hdr::HDRDecoder;
+
+use render::math::*;
+use shader::{Shader, ToneMapping};
+use utils::print_elapsed;
+
+const ENVIRONMENT_SIZE: u32 = 512;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+/// NOTE: `mipCount` in pbr-frag.glsl needs to match (max. level)
+const PREFILTERED_MIP_LEVELS: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 512;
+
+// texture units used by the PBR shader (0-4 are used for material textures)
+pub const DIFFUSE_ENV_UNIT: u32 = 5;
+pub const SPECULAR_ENV_UNIT: u32 = 6;
+pub const BRDF_LUT_UNIT: u32 = 7;
+
+/// Image based lighting: cubemaps precomputed from an equirectangular HDR environment map
+/// and the BRDF lookup table for the split-sum approximation.
+pub struct Environment {
+    pub cubemap: u32, // for the skybox
+    pub irradiance_map: u32, // diffuse
+    pub prefiltered_map: u32, // specular, increasing roughness per mip level
+    pub brdf_lut: u32,
+
+    cube_vao: u32,
+    skybox_shader: Shader,
+    u_view_projection: i32,
+    u_exposure: i32,
+    u_tone_mapping: i32,
+}
+
+impl Environment {
+    /// Loads an equirectangular .hdr file. NOTE: Needs a current GL context
+    pub fn from_hdr(path: &Path) -> Result<Environment, String> {
+        let start_time = Instant::now();
+        let file = File::open(path).map_err(|err| err.to_string())?;
+        let decoder = HDRDecoder::new(BufReader::new(file)).map_err(|err| err.to_string())?;
+        let metadata = decoder.metadata();
+        let (width, height) = (metadata.width as usize, metadata.height as usize);
+        let pixels = decoder.read_image_hdr().map_err(|err| err.to_string())?;
+        // flip, since OpenGL expects the bottom row first
+        let mut data = Vec::with_capacity(width * height * 3);
+        for row in pixels.chunks(width).rev() {
+            for pixel in row {
+                data.extend_from_slice(&pixel.data);
+            }
+        }
+        print_elapsed("Loaded environment map in ", &start_time);
+
+        let start_time = Instant::now();
+        let environment = unsafe { Self::precompute(&data, width as i32, height as i32) };
+        print_elapsed("Precomputed IBL maps in ", &start_time);
+        Ok(environment)
+    }
+
+    unsafe fn precompute(data: &[f32], width: i32, height: i32) -> Environment {
+        // remember state to restore afterwards (e.g. the headless framebuffer)
+        let mut previous_framebuffer = 0;
+        gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+        let mut previous_viewport = [0; 4];
+        gl::GetIntegerv(gl::VIEWPORT, previous_viewport.as_mut_ptr());
+
+        gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+        gl::Disable(gl::CULL_FACE);
+        gl::Disable(gl::DEPTH_TEST);
+
+        let mut equirect_texture = 0;
+        gl::GenTextures(1, &mut equirect_texture);
+        gl::BindTexture(gl::TEXTURE_2D, equirect_texture);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGB16F as i32, width, height, 0,
+            gl::RGB, gl::FLOAT, data.as_ptr() as *const c_void);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+
+        let mut capture_fbo = 0;
+        gl::GenFramebuffers(1, &mut capture_fbo);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, capture_fbo);
+
+        let cube_vao = create_cube_vao();
+        gl::BindVertexArray(cube_vao);
+
+        // 1. equirectangular -> cubemap (mipmapped, for sampling in the prefilter step)
+        let cubemap = create_cubemap(ENVIRONMENT_SIZE, true);
+        let mut shader = Shader::from_source(
+            include_str!("../shaders/ibl-cubemap-vert.glsl"),
+            include_str!("../shaders/ibl-equirect-frag.glsl"), &[]);
+        shader.use_program();
+        let sampler = shader.uniform_location("u_EquirectSampler");
+        shader.set_int(sampler, 0);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, equirect_texture);
+        render_cube_faces(&mut shader, cubemap, ENVIRONMENT_SIZE, 0);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, cubemap);
+        gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+
+        // 2. diffuse irradiance
+        let irradiance_map = create_cubemap(IRRADIANCE_SIZE, false);
+        let mut shader = Shader::from_source(
+            include_str!("../shaders/ibl-cubemap-vert.glsl"),
+            include_str!("../shaders/ibl-irradiance-frag.glsl"), &[]);
+        shader.use_program();
+        let sampler = shader.uniform_location("u_EnvironmentSampler");
+        shader.set_int(sampler, 0);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, cubemap);
+        render_cube_faces(&mut shader, irradiance_map, IRRADIANCE_SIZE, 0);
+
+        // 3. specular prefiltering, one roughness level per mip
+        let prefiltered_map = create_cubemap(PREFILTERED_SIZE, true);
+        let mut shader = Shader::from_source(
+            include_str!("../shaders/ibl-cubemap-vert.glsl"),
+            include_str!("../shaders/ibl-prefilter-frag.glsl"), &[]);
+        shader.use_program();
+        let sampler = shader.uniform_location("u_EnvironmentSampler");
+        shader.set_int(sampler, 0);
+        let resolution = shader.uniform_location("u_Resolution");
+        shader.set_float(resolution, ENVIRONMENT_SIZE as f32);
+        let roughness = shader.uniform_location("u_Roughness");
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, cubemap);
+        for level in 0..PREFILTERED_MIP_LEVELS {
+            shader.use_program();
+            shader.set_float(roughness, level as f32 / (PREFILTERED_MIP_LEVELS - 1) as f32);
+            render_cube_faces(&mut shader, prefiltered_map, PREFILTERED_SIZE >> level, level as i32);
+        }
+
+        // 4. BRDF integration lookup table
+        let mut brdf_lut = 0;
+        gl::GenTextures(1, &mut brdf_lut);
+        gl::BindTexture(gl::TEXTURE_2D, brdf_lut);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RG16F as i32, BRDF_LUT_SIZE as i32, BRDF_LUT_SIZE as i32, 0,
+            gl::RG, gl::FLOAT, ptr::null());
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        let shader = Shader::from_source(
+            include_str!("../shaders/fullscreen-vert.glsl"),
+            include_str!("../shaders/ibl-brdf-frag.glsl"), &[]);
+        shader.use_program();
+        gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, brdf_lut, 0);
+        gl::Viewport(0, 0, BRDF_LUT_SIZE as i32, BRDF_LUT_SIZE as i32);
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+
+        // clean up
+        gl::BindVertexArray(0);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as u32);
+        gl::Viewport(previous_viewport[0], previous_viewport[1], previous_viewport[2], previous_viewport[3]);
+        gl::Enable(gl::DEPTH_TEST);
+        gl::DeleteFramebuffers(1, &capture_fbo);
+        gl::DeleteTextures(1, &equirect_texture);
+
+        let mut skybox_shader = Shader::from_source(
+            include_str!("../shaders/skybox-vert.glsl"),
+            include_str!("../shaders/skybox-frag.glsl"), &[]);
+        let u_view_projection = skybox_shader.uniform_location("u_ViewProjection");
+        let u_exposure = skybox_shader.uniform_location("u_Exposure");
+        let u_tone_mapping = skybox_shader.uniform_location("u_ToneMapping");
+        let sampler = skybox_shader.uniform_location("u_EnvironmentSampler");
+        skybox_shader.use_program();
+        skybox_shader.set_int(sampler, 0);
+
+        Environment {
+            cubemap,
+            irradiance_map,
+            prefiltered_map,
+            brdf_lut,
+            cube_vao,
+            skybox_shader,
+            u_view_projection,
+            u_exposure,
+            u_tone_mapping,
+        }
+    }
+
+    /// Binds the maps to the texture units expected by the PBR shader
+    pub unsafe fn bind(&self) {
+        gl::ActiveTexture(gl::TEXTURE0 + DIFFUSE_ENV_UNIT);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.irradiance_map);
+        gl::ActiveTexture(gl::TEXTURE0 + SPECULAR_ENV_UNIT);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.prefiltered_map);
+        gl::ActiveTexture(gl::TEXTURE0 + BRDF_LUT_UNIT);
+        gl::BindTexture(gl::TEXTURE_2D, self.brdf_lut);
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+
+    /// Draws the environment as background. Should be done after drawing the scene,
+    /// so that only uncovered pixels need to be shaded.
+    /// `tile_projection`: optional transform of the clip space (see `GltfViewer::render_tiled`)
+    pub unsafe fn draw_skybox(&self, view_matrix: &Matrix4, fovy: f32, aspect_ratio: f32,
+            tile_projection: Option<&Matrix4>, tone_mapping: ToneMapping, exposure: f32) {
+        // NOTE: always perspective, an orthographic skybox would be a single color
+        let mut projection = perspective(Deg(fovy), aspect_ratio, 0.1, 10.0);
+        if let Some(tile_projection) = tile_projection {
+            projection = tile_projection * projection;
+        }
+        let mut rotation = *view_matrix;
+        rotation.w = vec4(0.0, 0.0, 0.0, 1.0); // remove translation
+
+        gl::DepthFunc(gl::LEQUAL);
+        gl::Disable(gl::CULL_FACE);
+        self.skybox_shader.use_program();
+        self.skybox_shader.set_mat4(self.u_view_projection, &(projection * rotation));
+        self.skybox_shader.set_float(self.u_exposure, exposure);
+        self.skybox_shader.set_int(self.u_tone_mapping, tone_mapping as i32);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.cubemap);
+        gl::BindVertexArray(self.cube_vao);
+        gl::DrawArrays(gl::TRIANGLES, 0, 36);
+        gl::BindVertexArray(0);
+        gl::DepthFunc(gl::LESS);
+    }
+}
+
+unsafe fn create_cubemap(size: u32, mipmapped: bool) -> u32 {
+    let mut cubemap = 0;
+    gl::GenTextures(1, &mut cubemap);
+    gl::BindTexture(gl::TEXTURE_CUBE_MAP, cubemap);
+    for face in 0..6 {
+        gl::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + face, 0, gl::RGB16F as i32,
+            size as i32, size as i32, 0, gl::RGB, gl::FLOAT, ptr::null());
+    }
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+    let min_filter = if mipmapped { gl::LINEAR_MIPMAP_LINEAR } else { gl::LINEAR };
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+    if mipmapped {
+        // allocate the mip chain
+        gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+    }
+    cubemap
+}
+
+/// Renders the unit cube into each face of the given mip level of `cubemap`.
+/// Expects the shader to be active and the capture framebuffer and cube VAO to be bound.
+unsafe fn render_cube_faces(shader: &mut Shader, cubemap: u32, size: u32, level: i32) {
+    let projection = perspective(Deg(90.0), 1.0, 0.1, 10.0);
+    let origin = Point3::new(0.0, 0.0, 0.0);
+    // (direction, up) per face, following the cubemap conventions
+    let faces = [
+        (vec3(1.0, 0.0, 0.0), vec3(0.0, -1.0, 0.0)),
+        (vec3(-1.0, 0.0, 0.0), vec3(0.0, -1.0, 0.0)),
+        (vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0)),
+        (vec3(0.0, -1.0, 0.0), vec3(0.0, 0.0, -1.0)),
+        (vec3(0.0, 0.0, 1.0), vec3(0.0, -1.0, 0.0)),
+        (vec3(0.0, 0.0, -1.0), vec3(0.0, -1.0, 0.0)),
+    ];
+    let u_view_projection = shader.uniform_location("u_ViewProjection");
+    gl::Viewport(0, 0, size as i32, size as i32);
+    for (i, &(direction, up)) in faces.iter().enumerate() {
+        let view = Matrix4::look_at(origin, origin + direction, up);
+        shader.set_mat4(u_view_projection, &(projection * view));
+        gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32, cubemap, level);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+        gl::DrawArrays(gl::TRIANGLES, 0, 36);
+    }
+}
+
+/// Unit cube (positions only), 36 vertices
+unsafe fn create_cube_vao() -> u32 {
+    // corner i: x/y/z = +1 if bit 0/1/2 is set, else -1
+    let corner = |i: usize| [
+        if i & 1 != 0 { 1.0 } else { -1.0 },
+        if i & 2 != 0 { 1.0 } else { -1.0 },
+        if i & 4 != 0 { 1.0 } else { -1.0f32 },
+    ];
+    let quads = [[0, 2, 6, 4], [1, 5, 7, 3], [0, 4, 5, 1], [2, 3, 7, 6], [0, 1, 3, 2], [4, 6, 7, 5]];
+    let mut vertices: Vec<f32> = Vec::with_capacity(36 * 3);
+    for quad in &quads {
+        for &i in &[quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]] {
+            vertices.extend_from_slice(&corner(i));
+        }
+    }
+
+    let (mut vao, mut vbo) = (0, 0);
+    gl::GenVertexArrays(1, &mut vao);
+    gl::GenBuffers(1, &mut vbo);
+    gl::BindVertexArray(vao);
+    gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+    gl::BufferData(gl::ARRAY_BUFFER, (vertices.len() * size_of::<f32>()) as isize,
+        vertices.as_ptr() as *const c_void, gl::STATIC_DRAW);
+    gl::EnableVertexAttribArray(0);
+    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 3 * size_of::<f32>() as i32, ptr::null());
+    gl::BindVertexArray(0);
+    vao
+}
@@ -0,0 +1,102 @@
+use std::mem::size_of;
+use std::os::raw::c_void;
+use std::ptr;
+
+use gl;
+
+use render::math::*;
+use shader::Shader;
+
+/// Reference grid on a horizontal plane, fading out towards the edges
+pub struct Grid {
+    shader: Shader,
+    u_view_projection: i32,
+    u_center: i32,
+    u_extent: i32,
+    u_cell_size: i32,
+    vao: u32,
+
+    pub center: Point3,
+    pub extent: f32, // half the side length
+    pub cell_size: f32, // every 10th line is emphasized
+}
+
+impl Grid {
+    /// NOTE: Needs a current GL context
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Grid {
+        let mut shader = Shader::from_source(
+            include_str!("../shaders/grid-vert.glsl"),
+            include_str!("../shaders/grid-frag.glsl"),
+            &[]);
+
+        unsafe {
+            let u_view_projection = shader.uniform_location("u_ViewProjection");
+            let u_center = shader.uniform_location("u_Center");
+            let u_extent = shader.uniform_location("u_Extent");
+            let u_cell_size = shader.uniform_location("u_CellSize");
+            let u_color = shader.uniform_location("u_Color");
+            shader.use_program();
+            gl::Uniform4f(u_color, 0.5, 0.5, 0.5, 1.0);
+
+            let vertices: [f32; 12] = [
+                -1.0, -1.0,   1.0, -1.0,   1.0, 1.0,
+                -1.0, -1.0,   1.0, 1.0,   -1.0, 1.0,
+            ];
+            let (mut vao, mut vbo) = (0, 0);
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(gl::ARRAY_BUFFER, (vertices.len() * size_of::<f32>()) as isize,
+                vertices.as_ptr() as *const c_void, gl::STATIC_DRAW);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 2 * size_of::<f32>() as i32, ptr::null());
+            gl::BindVertexArray(0);
+
+            Grid {
+                shader,
+                u_view_projection,
+                u_center,
+                u_extent,
+                u_cell_size,
+                vao,
+                center: Point3::new(0.0, 0.0, 0.0),
+                extent: 10.0,
+                cell_size: 1.0,
+            }
+        }
+    }
+
+    /// Places the grid below the given bounds, with a power of 10 as cell size
+    pub fn fit_to_bounds(&mut self, bounds: &Aabb3) {
+        let size = (bounds.max - bounds.min).magnitude().max(1e-6);
+        self.center = Point3::new(
+            (bounds.min.x + bounds.max.x) / 2.0,
+            bounds.min.y,
+            (bounds.min.z + bounds.max.z) / 2.0);
+        self.extent = size * 5.0;
+        self.cell_size = 10.0f32.powf(size.log10().floor() - 1.0);
+    }
+
+    /// Draws with depth testing (but without writing depth), blended over the current frame
+    pub unsafe fn draw(&self, view_projection: &Matrix4) {
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        gl::Disable(gl::CULL_FACE);
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        gl::DepthMask(gl::FALSE);
+
+        self.shader.use_program();
+        self.shader.set_mat4(self.u_view_projection, view_projection);
+        self.shader.set_vec3(self.u_center, self.center.x, self.center.y, self.center.z);
+        self.shader.set_float(self.u_extent, self.extent);
+        self.shader.set_float(self.u_cell_size, self.cell_size);
+        gl::BindVertexArray(self.vao);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+        gl::BindVertexArray(0);
+
+        gl::DepthMask(gl::TRUE);
+        gl::Disable(gl::BLEND);
+    }
+}
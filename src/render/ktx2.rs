@@ -0,0 +1,444 @@
+use std::borrow::Cow;
+use std::fmt;
+#[cfg(feature = "basisu")]
+use std::io::Read;
+#[cfg(feature = "basisu")]
+use std::os::raw::c_void;
+#[cfg(feature = "basisu")]
+use std::ptr;
+
+use gl;
+#[cfg(feature = "basisu")]
+use basis_universal::{sys, transcoder_init, DecodeFlags, TranscoderBlockFormat};
+#[cfg(feature = "basisu")]
+use ruzstd::StreamingDecoder;
+
+/// KTX2 file identifier («KTX 20»\r\n\x1A\n)
+const IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const HEADER_SIZE: usize = 80; // identifier, header and index (without the level index)
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_ZSTD: u32 = 2; // BasisLZ (1) is only used for ETC1S
+
+// Data format descriptor: color models of Basis Universal and the UASTC channel ids with alpha
+const KHR_DF_MODEL_UASTC: u8 = 166;
+const KHR_DF_CHANNEL_UASTC_RGBA: u8 = 3;
+const KHR_DF_CHANNEL_UASTC_RRRG: u8 = 5;
+const UASTC_BLOCK_SIZE: u64 = 16; // bytes of a 4x4 block
+
+// Vulkan formats (VkFormat) that can be uploaded directly
+const VK_FORMAT_UNDEFINED: u32 = 0; // Basis Universal (BasisLZ/ETC1S or UASTC)
+const VK_FORMAT_R8G8B8_UNORM: u32 = 23;
+const VK_FORMAT_R8G8B8_SRGB: u32 = 29;
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+const VK_FORMAT_BC1_RGB_UNORM_BLOCK: u32 = 131;
+const VK_FORMAT_BC1_RGB_SRGB_BLOCK: u32 = 132;
+const VK_FORMAT_BC1_RGBA_UNORM_BLOCK: u32 = 133;
+const VK_FORMAT_BC1_RGBA_SRGB_BLOCK: u32 = 134;
+const VK_FORMAT_BC3_UNORM_BLOCK: u32 = 137;
+const VK_FORMAT_BC3_SRGB_BLOCK: u32 = 138;
+const VK_FORMAT_BC7_UNORM_BLOCK: u32 = 145;
+const VK_FORMAT_BC7_SRGB_BLOCK: u32 = 146;
+
+// Compressed GL formats from extensions (not part of the generated core bindings)
+const COMPRESSED_RGB_S3TC_DXT1_EXT: u32 = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1_EXT: u32 = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1_EXT: u32 = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: u32 = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: u32 = 0x8C4F;
+const COMPRESSED_RGBA_BPTC_UNORM: u32 = 0x8E8C;
+const COMPRESSED_SRGB_ALPHA_BPTC_UNORM: u32 = 0x8E8D;
+
+#[derive(Debug, PartialEq)]
+pub enum Ktx2Error {
+    InvalidIdentifier,
+    Truncated,
+    /// Basis Universal ETC1S (BasisLZ) or supercompressed non-UASTC data, which there is no transcoder for
+    NeedsTranscoding,
+    UnsupportedFormat(u32), // VkFormat
+    Unsupported(&'static str), // e.g. cube maps
+    /// The data of a mip level is smaller than its size and format require
+    LevelTooShort { level: usize, length: usize, expected: u64 },
+    /// Zstandard decompression or UASTC transcoding of a mip level failed
+    TranscodingFailed { level: usize },
+}
+
+impl fmt::Display for Ktx2Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Ktx2Error::InvalidIdentifier => write!(f, "not a KTX2 file"),
+            Ktx2Error::Truncated => write!(f, "file is truncated"),
+            Ktx2Error::NeedsTranscoding =>
+                write!(f, "Basis Universal ETC1S and supercompressed textures aren't supported (only UASTC)"),
+            Ktx2Error::UnsupportedFormat(vk_format) => write!(f, "unsupported format (VkFormat {})", vk_format),
+            Ktx2Error::Unsupported(what) => write!(f, "{} aren't supported", what),
+            Ktx2Error::LevelTooShort { level, length, expected } =>
+                write!(f, "mip level {} has {} bytes, but needs {}", level, length, expected),
+            Ktx2Error::TranscodingFailed { level } => write!(f, "failed to transcode mip level {}", level),
+        }
+    }
+}
+
+/// GL formats for uploading the levels of a KTX2 texture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ktx2Format {
+    Uncompressed { internal_format: u32, format: u32 },
+    /// Only if listed in `GL_COMPRESSED_TEXTURE_FORMATS` (see `compressed_format_supported`)
+    Compressed { internal_format: u32 },
+}
+
+/// GL format and data of each mip level (see `Ktx2::gl_levels`)
+pub type Ktx2Levels<'a> = (Ktx2Format, Vec<Cow<'a, [u8]>>);
+
+/// The parts of a KTX2 file needed for uploading a 2D texture
+#[derive(Debug)]
+pub struct Ktx2 {
+    pub vk_format: u32,
+    pub width: u32,
+    pub height: u32,
+    /// (offset, length) of each mip level in the file, starting with the largest
+    pub levels: Vec<(usize, usize)>,
+    /// Basis Universal UASTC (`KHR_texture_basisu`), which has to be transcoded (see `transcode_uastc`)
+    pub uastc: bool,
+    pub has_alpha: bool, // UASTC only, other formats tell by `vk_format`
+    pub zstd: bool, // Zstandard supercompressed levels (UASTC only)
+}
+
+impl Ktx2 {
+    pub fn is_ktx2(data: &[u8]) -> bool {
+        data.starts_with(&IDENTIFIER)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Ktx2, Ktx2Error> {
+        if !Self::is_ktx2(data) {
+            return Err(Ktx2Error::InvalidIdentifier)
+        }
+        if data.len() < HEADER_SIZE {
+            return Err(Ktx2Error::Truncated)
+        }
+        let vk_format = read_u32(data, 12);
+        let width = read_u32(data, 20);
+        let height = read_u32(data, 24);
+        let depth = read_u32(data, 28);
+        let layer_count = read_u32(data, 32);
+        let face_count = read_u32(data, 36);
+        let level_count = read_u32(data, 40).max(1) as usize;
+        let supercompression_scheme = read_u32(data, 44);
+
+        let (uastc, has_alpha) = if vk_format == VK_FORMAT_UNDEFINED {
+            // basic descriptor block after the total size: color model at byte 8, first sample at 24
+            let dfd_offset = read_u32(data, 48) as usize;
+            if data.len() < dfd_offset.saturating_add(4 + 28) {
+                return Err(Ktx2Error::Truncated)
+            }
+            if data[dfd_offset + 4 + 8] != KHR_DF_MODEL_UASTC {
+                return Err(Ktx2Error::NeedsTranscoding) // ETC1S
+            }
+            let channel = data[dfd_offset + 4 + 24 + 3] & 0x0F;
+            (true, channel == KHR_DF_CHANNEL_UASTC_RGBA || channel == KHR_DF_CHANNEL_UASTC_RRRG)
+        } else {
+            (false, false)
+        };
+        let zstd = match supercompression_scheme {
+            SUPERCOMPRESSION_NONE => false,
+            SUPERCOMPRESSION_ZSTD if uastc => true,
+            _ => return Err(Ktx2Error::NeedsTranscoding),
+        };
+        if depth > 1 || layer_count > 1 || face_count > 1 {
+            return Err(Ktx2Error::Unsupported("3D, array and cube map textures"))
+        }
+
+        let index_end = HEADER_SIZE + level_count * LEVEL_INDEX_ENTRY_SIZE;
+        if data.len() < index_end {
+            return Err(Ktx2Error::Truncated)
+        }
+        let mut levels = Vec::with_capacity(level_count);
+        for level in 0..level_count {
+            let entry = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+            let offset = read_u64(data, entry) as usize;
+            let length = read_u64(data, entry + 8) as usize;
+            if offset.checked_add(length).map_or(true, |end| end > data.len()) {
+                return Err(Ktx2Error::Truncated)
+            }
+            // the upload reads as many bytes as size and format require, whatever the index says
+            let (level_width, level_height) = mip_size(width, height, level);
+            if let Some(expected) = level_data_size(vk_format, level_width, level_height) {
+                if (length as u64) < expected {
+                    return Err(Ktx2Error::LevelTooShort { level, length, expected })
+                }
+            }
+            levels.push((offset, length));
+        }
+        Ok(Ktx2 { vk_format, width, height, levels, uastc, has_alpha, zstd })
+    }
+
+    /// The data of each level (starting with the largest) and the GL format to upload it with.
+    /// UASTC is transcoded to BC7 if the context supports it, otherwise to RGBA8.
+    pub unsafe fn gl_levels<'a>(&self, data: &'a [u8], srgb: bool) -> Result<Ktx2Levels<'a>, Ktx2Error> {
+        if !self.uastc {
+            let levels = self.levels.iter()
+                .map(|&(offset, length)| Cow::Borrowed(&data[offset..offset + length]))
+                .collect();
+            return Ok((self.gl_format(srgb)?, levels))
+        }
+        let bc7 = uastc_gl_format(true, srgb);
+        let bc7_supported = match bc7 {
+            Ktx2Format::Compressed { internal_format } => compressed_format_supported(internal_format),
+            Ktx2Format::Uncompressed { .. } => false,
+        };
+        let levels = self.transcode_uastc(data, bc7_supported)?;
+        Ok((uastc_gl_format(bc7_supported, srgb), levels.into_iter().map(Cow::Owned).collect()))
+    }
+
+    /// Transcodes the UASTC levels to BC7 or RGBA8 (after Zstandard decompression if needed)
+    #[cfg(feature = "basisu")]
+    pub fn transcode_uastc(&self, data: &[u8], bc7: bool) -> Result<Vec<Vec<u8>>, Ktx2Error> {
+        self.levels.iter().enumerate()
+            .map(|(level, &(offset, length))| {
+                let mut level_data = Cow::Borrowed(&data[offset..offset + length]);
+                if self.zstd {
+                    let mut source = &data[offset..offset + length];
+                    let mut decompressed = Vec::new();
+                    StreamingDecoder::new(&mut source)
+                        .map_err(|_| Ktx2Error::TranscodingFailed { level })?
+                        .read_to_end(&mut decompressed)
+                        .map_err(|_| Ktx2Error::TranscodingFailed { level })?;
+                    level_data = Cow::Owned(decompressed);
+                }
+                let (width, height) = self.level_size(level);
+                let expected = u64::from((width + 3) / 4) * u64::from((height + 3) / 4) * UASTC_BLOCK_SIZE;
+                if (level_data.len() as u64) < expected {
+                    return Err(Ktx2Error::LevelTooShort { level, length: level_data.len(), expected })
+                }
+                transcode_uastc_level(&level_data, width, height, self.has_alpha, bc7)
+                    .ok_or(Ktx2Error::TranscodingFailed { level })
+            })
+            .collect()
+    }
+
+    #[cfg(not(feature = "basisu"))]
+    pub fn transcode_uastc(&self, _data: &[u8], _bc7: bool) -> Result<Vec<Vec<u8>>, Ktx2Error> {
+        Err(Ktx2Error::Unsupported("UASTC textures without the `basisu` feature"))
+    }
+
+    /// With `srgb`, the sRGB variant of the format is used (independent of the VkFormat, like for PNG/JPEG)
+    pub fn gl_format(&self, srgb: bool) -> Result<Ktx2Format, Ktx2Error> {
+        let uncompressed = |internal_format, format| Ktx2Format::Uncompressed { internal_format, format };
+        let compressed = |internal_format| Ktx2Format::Compressed { internal_format };
+        Ok(match self.vk_format {
+            VK_FORMAT_R8G8B8_UNORM | VK_FORMAT_R8G8B8_SRGB =>
+                uncompressed(if srgb { gl::SRGB8 } else { gl::RGB8 }, gl::RGB),
+            VK_FORMAT_R8G8B8A8_UNORM | VK_FORMAT_R8G8B8A8_SRGB =>
+                uncompressed(if srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 }, gl::RGBA),
+            VK_FORMAT_BC1_RGB_UNORM_BLOCK | VK_FORMAT_BC1_RGB_SRGB_BLOCK if srgb =>
+                compressed(COMPRESSED_SRGB_S3TC_DXT1_EXT),
+            VK_FORMAT_BC1_RGB_UNORM_BLOCK | VK_FORMAT_BC1_RGB_SRGB_BLOCK =>
+                compressed(COMPRESSED_RGB_S3TC_DXT1_EXT),
+            VK_FORMAT_BC1_RGBA_UNORM_BLOCK | VK_FORMAT_BC1_RGBA_SRGB_BLOCK if srgb =>
+                compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT),
+            VK_FORMAT_BC1_RGBA_UNORM_BLOCK | VK_FORMAT_BC1_RGBA_SRGB_BLOCK =>
+                compressed(COMPRESSED_RGBA_S3TC_DXT1_EXT),
+            VK_FORMAT_BC3_UNORM_BLOCK | VK_FORMAT_BC3_SRGB_BLOCK if srgb =>
+                compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT),
+            VK_FORMAT_BC3_UNORM_BLOCK | VK_FORMAT_BC3_SRGB_BLOCK =>
+                compressed(COMPRESSED_RGBA_S3TC_DXT5_EXT),
+            VK_FORMAT_BC7_UNORM_BLOCK | VK_FORMAT_BC7_SRGB_BLOCK if srgb =>
+                compressed(COMPRESSED_SRGB_ALPHA_BPTC_UNORM),
+            VK_FORMAT_BC7_UNORM_BLOCK | VK_FORMAT_BC7_SRGB_BLOCK =>
+                compressed(COMPRESSED_RGBA_BPTC_UNORM),
+            vk_format => return Err(Ktx2Error::UnsupportedFormat(vk_format)),
+        })
+    }
+
+    /// Size of mip `level` (at least 1x1)
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        mip_size(self.width, self.height, level)
+    }
+}
+
+/// Transcodes a `width` x `height` UASTC level to BC7 blocks or RGBA8 pixels.
+/// NOTE: `LowLevelUastcTranscoder::transcode_slice` of basis-universal 0.3 passes the row pitch in blocks
+/// for uncompressed formats too (which expect pixels) and overflows its output, so the C++ transcoder is
+/// called directly.
+#[cfg(feature = "basisu")]
+fn transcode_uastc_level(data: &[u8], width: u32, height: u32, has_alpha: bool, bc7: bool) -> Option<Vec<u8>> {
+    let (blocks_x, blocks_y) = ((width + 3) / 4, (height + 3) / 4);
+    let (format, stride, row_pitch, size) = if bc7 {
+        (TranscoderBlockFormat::BC7, 16, blocks_x, blocks_x * blocks_y * 16)
+    } else {
+        (TranscoderBlockFormat::RGBA32, 4, width, width * height * 4)
+    };
+    let mut output = vec![0; size as usize];
+    transcoder_init();
+    unsafe {
+        let transcoder = sys::low_level_uastc_transcoder_new();
+        let success = sys::low_level_uastc_transcoder_transcode_slice(transcoder,
+            output.as_mut_ptr() as *mut c_void, blocks_x, blocks_y, data.as_ptr(), data.len() as u32,
+            format.into(), stride, false, has_alpha, width, height, row_pitch, ptr::null_mut(), height,
+            0, 3, DecodeFlags::HIGH_QUALITY.bits()); // channels: RGB, A
+        sys::low_level_uastc_transcoder_delete(transcoder);
+        if success { Some(output) } else { None }
+    }
+}
+
+/// Format of transcoded UASTC levels (see `Ktx2::transcode_uastc`)
+fn uastc_gl_format(bc7: bool, srgb: bool) -> Ktx2Format {
+    match (bc7, srgb) {
+        (true, true) => Ktx2Format::Compressed { internal_format: COMPRESSED_SRGB_ALPHA_BPTC_UNORM },
+        (true, false) => Ktx2Format::Compressed { internal_format: COMPRESSED_RGBA_BPTC_UNORM },
+        (false, true) => Ktx2Format::Uncompressed { internal_format: gl::SRGB8_ALPHA8, format: gl::RGBA },
+        (false, false) => Ktx2Format::Uncompressed { internal_format: gl::RGBA8, format: gl::RGBA },
+    }
+}
+
+fn mip_size(width: u32, height: u32, level: usize) -> (u32, u32) {
+    let shift = |size: u32| size.checked_shr(level as u32).unwrap_or(0).max(1);
+    (shift(width), shift(height))
+}
+
+/// Bytes of a `width` x `height` level in `vk_format` (None for formats that can't be uploaded)
+fn level_data_size(vk_format: u32, width: u32, height: u32) -> Option<u64> {
+    let (width, height) = (u64::from(width), u64::from(height));
+    let blocks = ((width + 3) / 4).saturating_mul((height + 3) / 4); // 4x4 pixels each
+    match vk_format {
+        VK_FORMAT_R8G8B8_UNORM | VK_FORMAT_R8G8B8_SRGB => Some(width.saturating_mul(height).saturating_mul(3)),
+        VK_FORMAT_R8G8B8A8_UNORM | VK_FORMAT_R8G8B8A8_SRGB => Some(width.saturating_mul(height).saturating_mul(4)),
+        VK_FORMAT_BC1_RGB_UNORM_BLOCK | VK_FORMAT_BC1_RGB_SRGB_BLOCK |
+        VK_FORMAT_BC1_RGBA_UNORM_BLOCK | VK_FORMAT_BC1_RGBA_SRGB_BLOCK => Some(blocks.saturating_mul(8)),
+        VK_FORMAT_BC3_UNORM_BLOCK | VK_FORMAT_BC3_SRGB_BLOCK |
+        VK_FORMAT_BC7_UNORM_BLOCK | VK_FORMAT_BC7_SRGB_BLOCK => Some(blocks.saturating_mul(16)),
+        _ => None,
+    }
+}
+
+/// Whether the context can upload textures in the compressed `internal_format`
+pub unsafe fn compressed_format_supported(internal_format: u32) -> bool {
+    let mut count = 0;
+    gl::GetIntegerv(gl::NUM_COMPRESSED_TEXTURE_FORMATS, &mut count);
+    let mut formats = vec![0; count as usize];
+    if count > 0 {
+        gl::GetIntegerv(gl::COMPRESSED_TEXTURE_FORMATS, formats.as_mut_ptr());
+    }
+    formats.contains(&(internal_format as i32))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data[offset..offset + 4].iter().rev().fold(0, |acc, &b| (acc << 8) | u32::from(b))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    data[offset..offset + 8].iter().rev().fold(0, |acc, &b| (acc << 8) | u64::from(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+
+    use super::*;
+
+    fn push_u32(data: &mut Vec<u8>, value: u32) {
+        data.extend((0..4).map(|i| (value >> (8 * i)) as u8));
+    }
+
+    fn push_u64(data: &mut Vec<u8>, value: u64) {
+        data.extend((0..8).map(|i| (value >> (8 * i)) as u8));
+    }
+
+    /// 2x2 RGBA8 texture with a single level
+    fn rgba_file(vk_format: u32, supercompression_scheme: u32) -> Vec<u8> {
+        let mut data = IDENTIFIER.to_vec();
+        for &value in &[vk_format, 1, 2, 2, 0, 0, 1, 1, supercompression_scheme] {
+            push_u32(&mut data, value);
+        }
+        for _ in 0..4 { push_u32(&mut data, 0) } // DFD and KVD
+        for _ in 0..2 { push_u64(&mut data, 0) } // SGD
+        let level_offset = (data.len() + LEVEL_INDEX_ENTRY_SIZE) as u64;
+        push_u64(&mut data, level_offset);
+        push_u64(&mut data, 16);
+        push_u64(&mut data, 16);
+        data.extend(vec![255; 16]);
+        data
+    }
+
+    #[test]
+    fn parse_uncompressed() {
+        let data = rgba_file(VK_FORMAT_R8G8B8A8_SRGB, 0);
+        let ktx2 = Ktx2::parse(&data).unwrap();
+        assert_eq!((ktx2.width, ktx2.height), (2, 2));
+        assert_eq!(ktx2.levels, vec![(HEADER_SIZE + LEVEL_INDEX_ENTRY_SIZE, 16)]);
+        assert_eq!(ktx2.gl_format(true).unwrap(),
+            Ktx2Format::Uncompressed { internal_format: gl::SRGB8_ALPHA8, format: gl::RGBA });
+        assert_eq!(ktx2.level_size(3), (1, 1));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Ktx2::parse(b"\x89PNG").unwrap_err(), Ktx2Error::InvalidIdentifier);
+        let data = rgba_file(VK_FORMAT_R8G8B8A8_UNORM, 0);
+        assert_eq!(Ktx2::parse(&data[..data.len() - 1]).unwrap_err(), Ktx2Error::Truncated);
+        let basis = rgba_file(VK_FORMAT_UNDEFINED, 1);
+        assert_eq!(Ktx2::parse(&basis).unwrap_err(), Ktx2Error::NeedsTranscoding);
+        let zstd = rgba_file(VK_FORMAT_R8G8B8A8_UNORM, SUPERCOMPRESSION_ZSTD);
+        assert_eq!(Ktx2::parse(&zstd).unwrap_err(), Ktx2Error::NeedsTranscoding);
+    }
+
+    fn read_file(path: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        File::open(path).and_then(|mut file| file.read_to_end(&mut data)).unwrap();
+        data
+    }
+
+    #[test]
+    fn parse_uastc() {
+        // 8x4 with 4 levels: a red (opaque) and a blue (alpha 128) block
+        for &(path, zstd) in &[("src/data/uastc.ktx2", false), ("src/data/uastc-zstd.ktx2", true)] {
+            let ktx2 = Ktx2::parse(&read_file(path)).unwrap();
+            assert!(ktx2.uastc && ktx2.has_alpha);
+            assert_eq!(ktx2.zstd, zstd);
+            assert_eq!((ktx2.width, ktx2.height, ktx2.levels.len()), (8, 4, 4));
+        }
+        assert_eq!(uastc_gl_format(false, true),
+            Ktx2Format::Uncompressed { internal_format: gl::SRGB8_ALPHA8, format: gl::RGBA });
+    }
+
+    #[cfg(feature = "basisu")]
+    #[test]
+    fn transcode_uastc() {
+        for path in &["src/data/uastc.ktx2", "src/data/uastc-zstd.ktx2"] {
+            let data = read_file(path);
+            let ktx2 = Ktx2::parse(&data).unwrap();
+            let levels = ktx2.transcode_uastc(&data, false).unwrap();
+            let sizes: Vec<_> = levels.iter().map(|level| level.len()).collect();
+            assert_eq!(sizes, vec![8 * 4 * 4, 4 * 2 * 4, 2 * 4, 4]);
+            let pixel = |x: usize, y: usize| &levels[0][(y * 8 + x) * 4..][..4];
+            assert_eq!(pixel(0, 0), &[255, 0, 0, 255]);
+            assert_eq!(pixel(7, 3), &[0, 0, 255, 128]);
+
+            let bc7 = ktx2.transcode_uastc(&data, true).unwrap();
+            assert_eq!(bc7[0].len(), 2 * 16); // two 4x4 blocks
+        }
+        // truncated level
+        let mut data = read_file("src/data/uastc.ktx2");
+        data[HEADER_SIZE + 8] = 16;
+        let ktx2 = Ktx2::parse(&data).unwrap();
+        assert_eq!(ktx2.transcode_uastc(&data, false).unwrap_err(),
+            Ktx2Error::LevelTooShort { level: 0, length: 16, expected: 32 });
+    }
+
+    #[test]
+    fn reject_short_levels() {
+        // the level index claims 12 bytes for 2x2 RGBA8 pixels (16 bytes)
+        let mut data = rgba_file(VK_FORMAT_R8G8B8A8_UNORM, 0);
+        data[HEADER_SIZE + 8] = 12;
+        assert_eq!(Ktx2::parse(&data).unwrap_err(),
+            Ktx2Error::LevelTooShort { level: 0, length: 12, expected: 16 });
+        // a single 4x4 block covers 2x2 BC1 pixels
+        assert_eq!(level_data_size(VK_FORMAT_BC1_RGB_UNORM_BLOCK, 2, 2), Some(8));
+        assert_eq!(level_data_size(VK_FORMAT_BC7_UNORM_BLOCK, 5, 4), Some(32));
+        assert_eq!(mip_size(2, 2, 40), (1, 1));
+    }
+}
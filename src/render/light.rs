@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use serde_json;
+
+use render::math::*;
+
+/// Maximum number of lights passed to the shaders (see pbr-frag.glsl)
+pub const MAX_LIGHTS: usize = 8;
+
+/// Color (times intensity) of the default directional light used without `KHR_lights_punctual`
+pub const DEFAULT_LIGHT_COLOR: [f32; 3] = [5.0, 5.0, 5.0];
+
+/// NOTE: the values are passed to the shaders (see `LIGHT_*` in pbr-frag.glsl)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightType {
+    Directional = 0,
+    Point = 1,
+    Spot = 2,
+}
+
+/// A light defined by `KHR_lights_punctual`
+/// NOTE: The gltf crate doesn't support the extension, so it's read from the JSON
+/// (see `read_gltf_json`).
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub index: usize, // glTF index
+    pub name: Option<String>,
+    pub light_type: LightType,
+    pub color: Vector3,
+    pub intensity: f32, // candela (point/spot) or lux (directional)
+    pub range: Option<f32>, // None: infinite
+    pub inner_cone_angle: f32, // radians (spot only)
+    pub outer_cone_angle: f32,
+}
+
+impl Light {
+    pub fn from_json(index: usize, json: &serde_json::Value) -> Option<Light> {
+        let light_type = match json["type"].as_str() {
+            Some("directional") => LightType::Directional,
+            Some("point") => LightType::Point,
+            Some("spot") => LightType::Spot,
+            other => {
+                warn!("Ignoring light {} with invalid type {:?}", index, other);
+                return None
+            }
+        };
+        let color = json["color"].as_array()
+            .and_then(|color| if color.len() == 3 { Some(color) } else { None })
+            .map(|color| vec3(
+                color[0].as_f64().unwrap_or(1.0) as f32,
+                color[1].as_f64().unwrap_or(1.0) as f32,
+                color[2].as_f64().unwrap_or(1.0) as f32))
+            .unwrap_or_else(|| vec3(1.0, 1.0, 1.0));
+        let spot = &json["spot"];
+        Some(Light {
+            index,
+            name: json["name"].as_str().map(|s| s.to_owned()),
+            light_type,
+            color,
+            intensity: json["intensity"].as_f64().unwrap_or(1.0) as f32,
+            range: json["range"].as_f64().map(|range| range as f32),
+            inner_cone_angle: spot["innerConeAngle"].as_f64().unwrap_or(0.0) as f32,
+            outer_cone_angle: spot["outerConeAngle"].as_f64().unwrap_or(::std::f64::consts::FRAC_PI_4) as f32,
+        })
+    }
+
+    /// Scale and offset for the spot cone falloff: `clamp(cos(angle) * scale + offset, 0, 1)`
+    pub fn cone_scale_offset(&self) -> (f32, f32) {
+        let cos_outer = self.outer_cone_angle.cos();
+        let scale = 1.0 / (self.inner_cone_angle.cos() - cos_outer).max(0.001);
+        (scale, -cos_outer * scale)
+    }
+}
+
+/// Returns the lights of the `KHR_lights_punctual` extension (in glTF order; invalid ones are skipped).
+pub fn lights_from_json(json: &serde_json::Value) -> Vec<Light> {
+    json["extensions"]["KHR_lights_punctual"]["lights"].as_array()
+        .map(|lights| lights.iter()
+            .enumerate()
+            .filter_map(|(index, light)| Light::from_json(index, light))
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Returns the light index of each node with a `KHR_lights_punctual` light (by node index).
+pub fn light_nodes_from_json(json: &serde_json::Value) -> HashMap<usize, usize> {
+    json["nodes"].as_array()
+        .map(|nodes| nodes.iter()
+            .enumerate()
+            .filter_map(|(index, node)| node["extensions"]["KHR_lights_punctual"]["light"].as_u64()
+                .map(|light| (index, light as usize)))
+            .collect())
+        .unwrap_or_default()
+}
+
+/// A light placed in the scene (world space), as passed to the shaders
+#[derive(Debug, Clone, Copy)]
+pub struct LightInstance {
+    pub light_type: LightType,
+    pub position: Vector3,
+    pub direction: Vector3, // from the surface towards the light (directional/spot)
+    pub color: Vector3, // times intensity
+    pub range: f32, // 0: infinite
+    pub cone_scale: f32,
+    pub cone_offset: f32,
+}
+
+impl LightInstance {
+    /// Places `light` using the (final) transform of its node. Lights point along the node's -Z axis.
+    pub fn new(light: &Light, transform: &Matrix4) -> Self {
+        let (cone_scale, cone_offset) = light.cone_scale_offset();
+        LightInstance {
+            light_type: light.light_type,
+            position: transform.w.truncate(),
+            direction: transform.z.truncate().normalize(),
+            color: light.color * light.intensity,
+            range: light.range.unwrap_or(0.0),
+            cone_scale,
+            cone_offset,
+        }
+    }
+
+    /// The default light (see `DEFAULT_LIGHT_COLOR`)
+    pub fn default_directional(direction: Vector3) -> Self {
+        LightInstance {
+            light_type: LightType::Directional,
+            position: Vector3::zero(),
+            direction: direction.normalize(),
+            color: DEFAULT_LIGHT_COLOR.into(),
+            range: 0.0,
+            cone_scale: 0.0,
+            cone_offset: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lights() {
+        let json = serde_json::from_str(r#"{
+            "extensionsUsed": ["KHR_lights_punctual"],
+            "extensions": { "KHR_lights_punctual": { "lights": [
+                { "type": "directional", "color": [1, 0.5, 0], "intensity": 3 },
+                { "type": "point", "range": 10 },
+                { "type": "spot", "name": "spot", "spot": { "innerConeAngle": 0.5, "outerConeAngle": 1 } },
+                { "type": "area" }
+            ] } },
+            "nodes": [
+                { "name": "no light" },
+                { "extensions": { "KHR_lights_punctual": { "light": 2 } } }
+            ]
+        }"#).unwrap();
+        let lights = lights_from_json(&json);
+        assert_eq!(lights.len(), 3);
+        assert_eq!(lights[0].light_type, LightType::Directional);
+        assert_eq!(lights[0].color * lights[0].intensity, vec3(3.0, 1.5, 0.0));
+        assert_eq!(lights[1].range, Some(10.0));
+        assert_eq!(lights[1].intensity, 1.0);
+        assert_eq!(lights[2].name, Some("spot".to_owned()));
+        assert_eq!(lights[2].inner_cone_angle, 0.5);
+
+        let light_nodes = light_nodes_from_json(&json);
+        assert_eq!(light_nodes.len(), 1);
+        assert_eq!(light_nodes[&1], 2);
+
+        assert!(lights_from_json(&serde_json::Value::Null).is_empty());
+    }
+
+    #[test]
+    fn spot_cone_falloff() {
+        let json = serde_json::from_str(r#"{ "type": "spot", "spot": { "innerConeAngle": 0.2, "outerConeAngle": 0.6 } }"#)
+            .unwrap();
+        let light = Light::from_json(0, &json).unwrap();
+        let (scale, offset) = light.cone_scale_offset();
+        let falloff = |angle: f32| (angle.cos() * scale + offset).max(0.0).min(1.0);
+        assert!((falloff(0.2) - 1.0).abs() < 1e-5);
+        assert!(falloff(0.6).abs() < 1e-5);
+        assert!(falloff(0.4) > 0.0 && falloff(0.4) < 1.0);
+    }
+}
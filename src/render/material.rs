@@ -1,13 +1,23 @@
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
+use std::rc::Rc;
 
 use gltf;
+use gltf::material::AlphaMode;
 use gltf_importer;
+use serde_json;
 
 use render::math::*;
 use render::{ Root, Texture };
 use shader::*;
 
+/// Texture units of the `KHR_materials_clearcoat` textures (see also PbrShader constructor)
+pub const CLEARCOAT_UNIT: u32 = 13;
+pub const CLEARCOAT_ROUGHNESS_UNIT: u32 = 14;
+pub const CLEARCOAT_NORMAL_UNIT: u32 = 15;
+
 pub struct Material {
     pub index: Option<usize>, /// glTF index
     pub name: Option<String>,
@@ -26,12 +36,30 @@ pub struct Material {
     pub occlusion_strength: f32,
     pub emissive_factor: Vector3,
     pub emissive_texture: Option<Rc<Texture>>,
+    pub emissive_strength: f32, // KHR_materials_emissive_strength
 
     pub alpha_cutoff: f32,
     pub alpha_mode: gltf::material::AlphaMode,
 
     pub double_sided: bool,
 
+    pub unlit: bool, // KHR_materials_unlit
+
+    // KHR_materials_transmission (0: opaque as usual)
+    pub transmission_factor: f32,
+    pub transmission_texture: Option<Rc<Texture>>,
+
+    // KHR_materials_clearcoat (0: no clearcoat layer)
+    pub clearcoat_factor: f32,
+    pub clearcoat_texture: Option<Rc<Texture>>,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_roughness_texture: Option<Rc<Texture>>,
+    pub clearcoat_normal_texture: Option<Rc<Texture>>,
+    pub clearcoat_normal_scale: f32,
+
+    pub texture_transforms: HashMap<TextureSlot, TextureTransform>, // KHR_texture_transform
+
+    pub polygon_offset: Option<PolygonOffset>, // overrides `Root::polygon_offset`
 }
 
 impl Material {
@@ -60,34 +88,73 @@ impl Material {
 
             emissive_factor: g_material.emissive_factor().into(),
             emissive_texture: None,
+            emissive_strength: g_material.index()
+                .and_then(|index| root.emissive_strengths.get(&index).cloned())
+                .unwrap_or(1.0),
 
             alpha_cutoff: g_material.alpha_cutoff(),
             alpha_mode: g_material.alpha_mode(),
 
             double_sided: g_material.double_sided(),
+
+            unlit: g_material.index().map_or(false, |index| root.unlit_materials.contains(&index)),
+
+            transmission_factor: 0.0,
+            transmission_texture: None,
+
+            clearcoat_factor: 0.0,
+            clearcoat_texture: None,
+            clearcoat_roughness_factor: 0.0,
+            clearcoat_roughness_texture: None,
+            clearcoat_normal_texture: None,
+            clearcoat_normal_scale: 1.0,
+
+            texture_transforms: g_material.index()
+                .and_then(|index| root.texture_transforms.get(&index).cloned())
+                .unwrap_or_default(),
+
+            polygon_offset: g_material.name().and_then(|name| root.material_polygon_offsets.get(name).cloned()),
         };
 
+        // NOTE: the textures of these extensions are loaded in `Root::from_gltf`,
+        // since the gltf crate doesn't know them
+        if let Some(transmission) = g_material.index().and_then(|index| root.transmissions.get(&index).cloned()) {
+            material.transmission_factor = transmission.factor;
+            material.transmission_texture = find_texture(root, transmission.texture);
+        }
+        if let Some(clearcoat) = g_material.index().and_then(|index| root.clearcoats.get(&index).cloned()) {
+            material.clearcoat_factor = clearcoat.factor;
+            material.clearcoat_texture = find_texture(root, clearcoat.texture);
+            material.clearcoat_roughness_factor = clearcoat.roughness_factor;
+            material.clearcoat_roughness_texture = find_texture(root, clearcoat.roughness_texture);
+            material.clearcoat_normal_texture = find_texture(root, clearcoat.normal_texture);
+            material.clearcoat_normal_scale = clearcoat.normal_scale;
+        }
+
         if let Some(color_info) = pbr.base_color_texture() {
             material.base_color_texture = Some(
-                load_texture(&color_info.texture(), color_info.tex_coord(), root, buffers, base_path));
+                load_texture(&color_info.texture(), material.tex_coord(TextureSlot::BaseColor, color_info.tex_coord()), true, root, buffers, base_path));
         }
         if let Some(mr_info) = pbr.metallic_roughness_texture() {
             material.metallic_roughness_texture = Some(
-                load_texture(&mr_info.texture(), mr_info.tex_coord(), root, buffers, base_path));
+                load_texture(&mr_info.texture(), material.tex_coord(TextureSlot::MetallicRoughness, mr_info.tex_coord()), false, root, buffers, base_path));
         }
         if let Some(normal_texture) = g_material.normal_texture() {
             material.normal_texture = Some(
-                load_texture(&normal_texture.texture(), normal_texture.tex_coord(), root, buffers, base_path));
+                load_texture(&normal_texture.texture(), material.tex_coord(TextureSlot::Normal, normal_texture.tex_coord()),
+                    false, root, buffers, base_path));
             material.normal_scale = Some(normal_texture.scale());
         }
         if let Some(occ_texture) = g_material.occlusion_texture() {
             material.occlusion_texture = Some(
-                load_texture(&occ_texture.texture(), occ_texture.tex_coord(), root, buffers, base_path));
+                load_texture(&occ_texture.texture(), material.tex_coord(TextureSlot::Occlusion, occ_texture.tex_coord()),
+                    false, root, buffers, base_path));
             material.occlusion_strength = occ_texture.strength();
         }
         if let Some(em_info) = g_material.emissive_texture() {
             material.emissive_texture = Some(
-                load_texture(&em_info.texture(), em_info.tex_coord(), root, buffers, base_path));
+                load_texture(&em_info.texture(), material.tex_coord(TextureSlot::Emissive, em_info.tex_coord()),
+                    true, root, buffers, base_path));
         }
 
         material
@@ -110,26 +177,480 @@ impl Material {
         if self.occlusion_texture.is_some() {
             flags |= ShaderFlags::HAS_OCCLUSIONMAP;
         }
+        if self.unlit {
+            flags |= ShaderFlags::UNLIT;
+        }
+        if self.transmission_factor > 0.0 {
+            flags |= ShaderFlags::TRANSMISSION;
+            if self.transmission_texture.is_some() {
+                flags |= ShaderFlags::HAS_TRANSMISSIONMAP;
+            }
+        }
+        if !self.texture_transforms.is_empty() {
+            flags |= ShaderFlags::TEXTURE_TRANSFORM;
+        }
+        if self.clearcoat_factor > 0.0 {
+            flags |= ShaderFlags::CLEARCOAT;
+            if self.clearcoat_texture.is_some() {
+                flags |= ShaderFlags::HAS_CLEARCOATMAP;
+            }
+            if self.clearcoat_roughness_texture.is_some() {
+                flags |= ShaderFlags::HAS_CLEARCOATROUGHNESSMAP;
+            }
+            if self.clearcoat_normal_texture.is_some() {
+                flags |= ShaderFlags::HAS_CLEARCOATNORMALMAP;
+            }
+        }
+        match self.alpha_mode {
+            AlphaMode::Mask => flags |= ShaderFlags::ALPHA_MASK,
+            AlphaMode::Blend => flags |= ShaderFlags::ALPHA_BLEND,
+            AlphaMode::Opaque => (),
+        }
         flags
     }
 
+    /// The tex coord set of the texture in `slot` (`KHR_texture_transform` may override it)
+    fn tex_coord(&self, slot: TextureSlot, tex_coord: u32) -> u32 {
+        self.texture_transforms.get(&slot)
+            .and_then(|transform| transform.tex_coord)
+            .unwrap_or(tex_coord)
+    }
+
+    /// The UV matrix of the texture in `slot` (identity without `KHR_texture_transform`)
+    pub fn uv_transform(&self, slot: TextureSlot) -> Matrix3 {
+        self.texture_transforms.get(&slot).map_or(Matrix3::identity(), |transform| transform.matrix())
+    }
+
+    /// The emissive factor scaled by `KHR_materials_emissive_strength` (may exceed 1)
+    pub fn emissive(&self) -> Vector3 {
+        self.emissive_factor * self.emissive_strength
+    }
+}
+
+/// Reads the glTF JSON from `path` (.gltf or .glb).
+/// NOTE: The gltf crate drops unknown extensions (e.g. `KHR_materials_unlit`, `KHR_lights_punctual`),
+/// so the JSON is read again to find them.
+pub fn read_gltf_json(path: &Path) -> Option<serde_json::Value> {
+    let mut data = Vec::new();
+    if let Err(err) = File::open(path).and_then(|mut file| file.read_to_end(&mut data)) {
+        warn!("Failed to read {} for extensions: {}", path.display(), err);
+        return None
+    }
+    // binary glTF: 12 byte header, then the JSON chunk (length, type, data)
+    let json = if data.starts_with(b"glTF") && data.len() >= 20 {
+        let length = data[12..16].iter().rev().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        &data[20..(20 + length).min(data.len())]
+    } else {
+        &data[..]
+    };
+    match serde_json::from_slice(json) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("Failed to parse {} for extensions: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Returns the indices of the materials using `KHR_materials_unlit`.
+pub fn unlit_material_indices(json: &serde_json::Value) -> HashSet<usize> {
+    json["materials"].as_array()
+        .map(|materials| materials.iter()
+            .enumerate()
+            .filter(|&(_, material)| !material["extensions"]["KHR_materials_unlit"].is_null())
+            .map(|(index, _)| index)
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Returns the `KHR_materials_emissive_strength` of the materials using it (by index).
+pub fn emissive_strengths(json: &serde_json::Value) -> HashMap<usize, f32> {
+    json["materials"].as_array()
+        .map(|materials| materials.iter()
+            .enumerate()
+            .filter_map(|(index, material)| {
+                let ext = &material["extensions"]["KHR_materials_emissive_strength"];
+                if ext.is_null() {
+                    return None
+                }
+                // spec default: 1.0
+                Some((index, ext["emissiveStrength"].as_f64().unwrap_or(1.0) as f32))
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+/// `KHR_materials_transmission` properties of a material
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transmission {
+    pub factor: f32,
+    pub texture: Option<(usize, u32)>, // glTF texture index and tex coord set (red channel)
+}
+
+/// Returns the `KHR_materials_transmission` of the materials using it (by index).
+pub fn transmissions(json: &serde_json::Value) -> HashMap<usize, Transmission> {
+    json["materials"].as_array()
+        .map(|materials| materials.iter()
+            .enumerate()
+            .filter_map(|(index, material)| {
+                let ext = &material["extensions"]["KHR_materials_transmission"];
+                if ext.is_null() {
+                    return None
+                }
+                // spec default: 0.0
+                Some((index, Transmission {
+                    factor: ext["transmissionFactor"].as_f64().unwrap_or(0.0) as f32,
+                    texture: texture_info(&ext["transmissionTexture"]),
+                }))
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Depth offset of a material's primitives (see `glPolygonOffset`), e.g. to draw decals placed
+/// exactly on a surface in front of it (negative values move towards the camera).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolygonOffset {
+    pub factor: f32, // scaled by the depth slope of the polygon
+    pub units: f32, // scaled by the smallest resolvable depth difference
+}
+
+impl PolygonOffset {
+    /// Parses `FACTOR,UNITS`. Example: -1,-1
+    pub fn parse(s: &str) -> Result<PolygonOffset, String> {
+        let values = s.split(',')
+            .map(|v| v.trim().parse::<f32>().map_err(|err| format!("{} ({})", err, s)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.len() != 2 {
+            return Err(format!("expected FACTOR,UNITS ({})", s))
+        }
+        Ok(PolygonOffset { factor: values[0], units: values[1] })
+    }
+
+    /// Parses `MATERIAL=FACTOR,UNITS` (by material name). Example: Decal=-1,-1
+    pub fn parse_for_material(s: &str) -> Result<(String, PolygonOffset), String> {
+        match s.rfind('=') {
+            Some(i) if i > 0 => Ok((s[..i].to_owned(), PolygonOffset::parse(&s[i + 1..])?)),
+            _ => Err(format!("expected MATERIAL=FACTOR,UNITS ({})", s)),
+        }
+    }
+}
+
+/// The texture references of a material (core spec only)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureSlot {
+    BaseColor,
+    MetallicRoughness,
+    Normal,
+    Occlusion,
+    Emissive,
+}
+
+/// `KHR_texture_transform` of a texture reference
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureTransform {
+    pub offset: [f32; 2],
+    pub rotation: f32, // radians, counter-clockwise
+    pub scale: [f32; 2],
+    pub tex_coord: Option<u32>, // overrides the tex coord set of the texture reference
+}
+
+impl TextureTransform {
+    /// Reads the extension of a glTF `textureInfo` object (None if it isn't used)
+    pub fn from_json(texture_info: &serde_json::Value) -> Option<TextureTransform> {
+        let ext = &texture_info["extensions"]["KHR_texture_transform"];
+        if ext.is_null() {
+            return None
+        }
+        let vec2 = |value: &serde_json::Value, default: f32| [
+            value[0].as_f64().map_or(default, |v| v as f32),
+            value[1].as_f64().map_or(default, |v| v as f32),
+        ];
+        Some(TextureTransform {
+            offset: vec2(&ext["offset"], 0.0),
+            rotation: ext["rotation"].as_f64().unwrap_or(0.0) as f32,
+            scale: vec2(&ext["scale"], 1.0),
+            tex_coord: ext["texCoord"].as_u64().map(|tex_coord| tex_coord as u32),
+        })
+    }
+
+    /// UV matrix as defined by the spec: translation * rotation * scale
+    pub fn matrix(&self) -> Matrix3 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let translation = Matrix3::new(
+            1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            self.offset[0], self.offset[1], 1.0);
+        let rotation = Matrix3::new(
+            cos, -sin, 0.0,
+            sin, cos, 0.0,
+            0.0, 0.0, 1.0);
+        let scale = Matrix3::new(
+            self.scale[0], 0.0, 0.0,
+            0.0, self.scale[1], 0.0,
+            0.0, 0.0, 1.0);
+        translation * rotation * scale
+    }
+}
+
+/// Returns the `KHR_texture_transform`s of the materials' texture references (by material index).
+pub fn texture_transforms(json: &serde_json::Value) -> HashMap<usize, HashMap<TextureSlot, TextureTransform>> {
+    json["materials"].as_array()
+        .map(|materials| materials.iter()
+            .enumerate()
+            .filter_map(|(index, material)| {
+                let pbr = &material["pbrMetallicRoughness"];
+                let slots = [
+                    (TextureSlot::BaseColor, &pbr["baseColorTexture"]),
+                    (TextureSlot::MetallicRoughness, &pbr["metallicRoughnessTexture"]),
+                    (TextureSlot::Normal, &material["normalTexture"]),
+                    (TextureSlot::Occlusion, &material["occlusionTexture"]),
+                    (TextureSlot::Emissive, &material["emissiveTexture"]),
+                ];
+                let transforms: HashMap<_, _> = slots.iter()
+                    .filter_map(|&(slot, texture_info)| TextureTransform::from_json(texture_info)
+                        .map(|transform| (slot, transform)))
+                    .collect();
+                if transforms.is_empty() { None } else { Some((index, transforms)) }
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Returns the `KHR_texture_basisu` source image of the textures using it (by texture index).
+pub fn basisu_sources(json: &serde_json::Value) -> HashMap<usize, usize> {
+    json["textures"].as_array()
+        .map(|textures| textures.iter()
+            .enumerate()
+            .filter_map(|(index, texture)| texture["extensions"]["KHR_texture_basisu"]["source"].as_u64()
+                .map(|source| (index, source as usize)))
+            .collect())
+        .unwrap_or_default()
+}
+
+/// `KHR_materials_clearcoat` properties of a material
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clearcoat {
+    pub factor: f32,
+    pub texture: Option<(usize, u32)>, // glTF texture index and tex coord set (red channel)
+    pub roughness_factor: f32,
+    pub roughness_texture: Option<(usize, u32)>, // green channel
+    pub normal_texture: Option<(usize, u32)>,
+    pub normal_scale: f32,
+}
+
+/// Returns the `KHR_materials_clearcoat` of the materials using it (by index).
+pub fn clearcoats(json: &serde_json::Value) -> HashMap<usize, Clearcoat> {
+    json["materials"].as_array()
+        .map(|materials| materials.iter()
+            .enumerate()
+            .filter_map(|(index, material)| {
+                let ext = &material["extensions"]["KHR_materials_clearcoat"];
+                if ext.is_null() {
+                    return None
+                }
+                // spec defaults: 0.0 (factors), 1.0 (normal scale)
+                Some((index, Clearcoat {
+                    factor: ext["clearcoatFactor"].as_f64().unwrap_or(0.0) as f32,
+                    texture: texture_info(&ext["clearcoatTexture"]),
+                    roughness_factor: ext["clearcoatRoughnessFactor"].as_f64().unwrap_or(0.0) as f32,
+                    roughness_texture: texture_info(&ext["clearcoatRoughnessTexture"]),
+                    normal_texture: texture_info(&ext["clearcoatNormalTexture"]),
+                    normal_scale: ext["clearcoatNormalTexture"]["scale"].as_f64().unwrap_or(1.0) as f32,
+                }))
+            })
+            .collect())
+        .unwrap_or_default()
 }
 
-fn load_texture(
+/// Texture index and tex coord set of a glTF `textureInfo` object (None if missing or invalid)
+fn texture_info(json: &serde_json::Value) -> Option<(usize, u32)> {
+    json["index"].as_u64()
+        .map(|index| (index as usize, json["texCoord"].as_u64().unwrap_or(0) as u32))
+}
+
+/// Finds an already loaded data (non-sRGB) texture, e.g. of an extension (see `Root::from_gltf`)
+fn find_texture(root: &Root, texture: Option<(usize, u32)>) -> Option<Rc<Texture>> {
+    texture.and_then(|(index, tex_coord)| root.textures.iter()
+        .find(|tex| tex.index == index && !tex.srgb && tex.tex_coord == tex_coord)
+        .cloned())
+}
+
+pub fn load_texture(
     g_texture: &gltf::texture::Texture,
     tex_coord: u32,
+    srgb: bool,
     root: &mut Root,
     buffers: &gltf_importer::Buffers,
     base_path: &Path) -> Rc<Texture>
 {
-    // TODO!: handle tex coord set in shaders
-    assert_eq!(tex_coord, 0, "not yet implemented: tex coord set must be 0 (Material::from_gltf)");
+    if tex_coord > 1 {
+        warn!("Texture {} uses TEXCOORD_{}, only TEXCOORD_0 and TEXCOORD_1 are supported",
+            g_texture.index(), tex_coord);
+    }
 
-    if let Some(tex) = root.textures.iter().find(|tex| (***tex).index == g_texture.index()) {
+    // NOTE: a texture used both for colors and data is loaded twice (with different internal formats),
+    // as is one used with different tex coord sets (rare)
+    let existing = root.textures.iter()
+        .find(|tex| (***tex).index == g_texture.index() && tex.srgb == srgb && tex.tex_coord == tex_coord);
+    if let Some(tex) = existing {
         return Rc::clone(tex)
     }
 
-    let texture = Rc::new(Texture::from_gltf(g_texture, tex_coord, buffers, base_path));
+    let basisu_image = root.basisu_images.get(&g_texture.index());
+    let texture = Rc::new(Texture::from_gltf(g_texture, tex_coord, srgb, &root.gl_extensions, basisu_image,
+        buffers, base_path));
     root.textures.push(Rc::clone(&texture));
     texture
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_unlit_materials() {
+        let json = serde_json::from_str(r#"{
+            "extensionsUsed": ["KHR_materials_unlit"],
+            "materials": [
+                { "name": "lit" },
+                { "name": "unlit", "extensions": { "KHR_materials_unlit": {} } }
+            ]
+        }"#).unwrap();
+        let unlit = unlit_material_indices(&json);
+        assert_eq!(unlit.len(), 1);
+        assert!(unlit.contains(&1));
+
+        assert!(unlit_material_indices(&serde_json::Value::Null).is_empty());
+    }
+
+    #[test]
+    fn find_emissive_strengths() {
+        let json = serde_json::from_str(r#"{
+            "extensionsUsed": ["KHR_materials_emissive_strength"],
+            "materials": [
+                { "name": "plain", "emissiveFactor": [1, 1, 1] },
+                { "name": "bright", "extensions": { "KHR_materials_emissive_strength": { "emissiveStrength": 5.5 } } },
+                { "name": "default", "extensions": { "KHR_materials_emissive_strength": {} } }
+            ]
+        }"#).unwrap();
+        let strengths = emissive_strengths(&json);
+        assert_eq!(strengths.len(), 2);
+        assert_eq!(strengths[&1], 5.5);
+        assert_eq!(strengths[&2], 1.0);
+
+        assert!(emissive_strengths(&serde_json::Value::Null).is_empty());
+    }
+
+    #[test]
+    fn find_transmissions() {
+        let json = serde_json::from_str(r#"{
+            "extensionsUsed": ["KHR_materials_transmission"],
+            "materials": [
+                { "name": "opaque" },
+                { "name": "glass", "extensions": { "KHR_materials_transmission": { "transmissionFactor": 0.9 } } },
+                { "name": "textured", "extensions": { "KHR_materials_transmission": {
+                    "transmissionFactor": 1, "transmissionTexture": { "index": 3, "texCoord": 1 } } } },
+                { "name": "default", "extensions": { "KHR_materials_transmission": {} } }
+            ]
+        }"#).unwrap();
+        let found = transmissions(&json);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[&1], Transmission { factor: 0.9, texture: None });
+        assert_eq!(found[&2], Transmission { factor: 1.0, texture: Some((3, 1)) });
+        assert_eq!(found[&3].factor, 0.0);
+
+        assert!(transmissions(&serde_json::Value::Null).is_empty());
+    }
+
+    #[test]
+    fn find_clearcoats() {
+        let json = serde_json::from_str(r#"{
+            "extensionsUsed": ["KHR_materials_clearcoat"],
+            "materials": [
+                { "name": "plain" },
+                { "name": "lacquer", "extensions": { "KHR_materials_clearcoat": {
+                    "clearcoatFactor": 1, "clearcoatRoughnessFactor": 0.1 } } },
+                { "name": "car paint", "extensions": { "KHR_materials_clearcoat": {
+                    "clearcoatFactor": 0.8,
+                    "clearcoatTexture": { "index": 0 },
+                    "clearcoatRoughnessTexture": { "index": 1, "texCoord": 1 },
+                    "clearcoatNormalTexture": { "index": 2, "scale": 0.5 } } } }
+            ]
+        }"#).unwrap();
+        let found = clearcoats(&json);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[&1], Clearcoat {
+            factor: 1.0, texture: None,
+            roughness_factor: 0.1, roughness_texture: None,
+            normal_texture: None, normal_scale: 1.0,
+        });
+        let paint = &found[&2];
+        assert_eq!(paint.roughness_factor, 0.0);
+        assert_eq!(paint.texture, Some((0, 0)));
+        assert_eq!(paint.roughness_texture, Some((1, 1)));
+        assert_eq!(paint.normal_texture, Some((2, 0)));
+        assert_eq!(paint.normal_scale, 0.5);
+
+        assert!(clearcoats(&serde_json::Value::Null).is_empty());
+    }
+
+    #[test]
+    fn find_basisu_sources() {
+        let json = serde_json::from_str(r#"{
+            "extensionsUsed": ["KHR_texture_basisu"],
+            "textures": [
+                { "source": 0 },
+                { "source": 1, "extensions": { "KHR_texture_basisu": { "source": 2 } } }
+            ]
+        }"#).unwrap();
+        let sources = basisu_sources(&json);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[&1], 2);
+
+        assert!(basisu_sources(&serde_json::Value::Null).is_empty());
+    }
+
+    #[test]
+    fn tile_with_texture_transform() {
+        let json = serde_json::from_str(r#"{
+            "extensionsUsed": ["KHR_texture_transform"],
+            "materials": [
+                { "name": "plain", "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } },
+                { "name": "tiled", "pbrMetallicRoughness": { "baseColorTexture": { "index": 0, "extensions": {
+                    "KHR_texture_transform": { "offset": [0.5, 0], "scale": [2, 3] } } } } },
+                { "name": "rotated", "emissiveTexture": { "index": 1, "extensions": {
+                    "KHR_texture_transform": { "rotation": 1.5707964, "texCoord": 1 } } } }
+            ]
+        }"#).unwrap();
+        let transforms = texture_transforms(&json);
+        assert_eq!(transforms.len(), 2);
+
+        // the texture repeats twice horizontally and three times vertically, shifted by half a tile
+        let tiled = transforms[&1][&TextureSlot::BaseColor].matrix();
+        let uv = |m: &Matrix3, u: f32, v: f32| { let p = *m * vec3(u, v, 1.0); (p.x, p.y) };
+        assert_eq!(uv(&tiled, 0.0, 0.0), (0.5, 0.0));
+        assert_eq!(uv(&tiled, 1.0, 1.0), (2.5, 3.0));
+        assert_eq!(uv(&tiled, 0.5, 0.5), (1.5, 1.5));
+
+        // rotated counter-clockwise in UV space (v points down)
+        let rotated = transforms[&2][&TextureSlot::Emissive];
+        assert_eq!(rotated.tex_coord, Some(1));
+        let (u, v) = uv(&rotated.matrix(), 1.0, 0.0);
+        assert!(u.abs() < 1e-6 && (v + 1.0).abs() < 1e-6, "{:?}", (u, v));
+
+        assert!(texture_transforms(&serde_json::Value::Null).is_empty());
+    }
+
+    #[test]
+    fn parse_polygon_offsets() {
+        assert_eq!(PolygonOffset::parse("-1, -2"), Ok(PolygonOffset { factor: -1.0, units: -2.0 }));
+        assert!(PolygonOffset::parse("-1").is_err());
+        assert!(PolygonOffset::parse("a,b").is_err());
+
+        assert_eq!(PolygonOffset::parse_for_material("Road=Markings=0,-4"),
+            Ok(("Road=Markings".to_owned(), PolygonOffset { factor: 0.0, units: -4.0 })));
+        assert!(PolygonOffset::parse_for_material("=0,-4").is_err());
+        assert!(PolygonOffset::parse_for_material("0,-4").is_err());
+    }
+}
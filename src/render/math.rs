@@ -14,6 +14,7 @@ pub type Vector4 = cgmath::Vector4<f32>;
 
 pub type Point3 = cgmath::Point3<f32>;
 
+pub type Matrix3 = cgmath::Matrix3<f32>;
 pub type Matrix4 = cgmath::Matrix4<f32>;
 pub type Quaternion = cgmath::Quaternion<f32>;
 
@@ -59,13 +60,116 @@ impl Spherical {
     }
 }
 
+/// View frustum, represented by six planes (a, b, c, d) with normals pointing inwards.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    planes: [Vector4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a (projection * view) matrix (Gribb/Hartmann method)
+    pub fn from_matrix(m: &Matrix4) -> Self {
+        let (r0, r1, r2, r3) = (m.row(0), m.row(1), m.row(2), m.row(3));
+        let planes = [
+            r3 + r0, // left
+            r3 - r0, // right
+            r3 + r1, // bottom
+            r3 - r1, // top
+            r3 + r2, // near
+            r3 - r2, // far
+        ];
+        Frustum { planes }
+    }
+
+    /// Returns false if the box is completely outside of the frustum.
+    /// Conservative: may return true for some boxes that are outside, but near a corner.
+    pub fn intersects(&self, aabb: &Aabb3) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner that is furthest along the plane normal
+            let p = vec3(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            plane.truncate().dot(p) + plane.w >= 0.0
+        })
+    }
+}
+
 use std::num::ParseFloatError;
 pub fn parse_vec3(s: &str) -> Result<Vector3, ParseFloatError> {
     let coords: Vec<&str> = s.split(',').collect();
-    coords.len() != 3 && panic!("Failed to parse Vector3 ({})", s);
+    if coords.len() != 3 {
+        panic!("Failed to parse Vector3 ({})", s);
+    }
     let x = coords[0].parse::<f32>()?;
     let y = coords[1].parse::<f32>()?;
     let z = coords[2].parse::<f32>()?;
 
     Ok(vec3(x, y, z))
 }
+
+/// Parses a comma-separated RGB or RGBA color with components in [0, 1].
+/// Alpha defaults to 1.0. Example: 1.0,0.5,0.0
+pub fn parse_color(s: &str) -> Result<[f32; 4], String> {
+    let components = s.split(',')
+        .map(|c| c.trim().parse::<f32>().map_err(|err| format!("{} ({})", err, s)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if components.len() != 3 && components.len() != 4 {
+        return Err(format!("expected 3 or 4 color components ({})", s))
+    }
+    if components.iter().any(|c| *c < 0.0 || *c > 1.0) {
+        return Err(format!("color components must be between 0 and 1 ({})", s))
+    }
+    let alpha = if components.len() == 4 { components[3] } else { 1.0 };
+    Ok([components[0], components[1], components[2], alpha])
+}
+
+/// Parses the top and bottom color of a vertical gradient, e.g. "1,1,1,0.2,0.2,0.2" (RGB each, in [0, 1])
+pub fn parse_gradient(s: &str) -> Result<([f32; 3], [f32; 3]), String> {
+    let components: Vec<&str> = s.split(',').collect();
+    if components.len() != 6 {
+        return Err(format!("expected 6 components: top r,g,b and bottom r,g,b ({})", s))
+    }
+    let top = parse_color(&components[..3].join(","))?;
+    let bottom = parse_color(&components[3..].join(","))?;
+    Ok(([top[0], top[1], top[2]], [bottom[0], bottom[1], bottom[2]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(parse_color("1,1,1"), Ok([1.0, 1.0, 1.0, 1.0]));
+        assert_eq!(parse_color("0.1, 0.2, 0.3, 0.5"), Ok([0.1, 0.2, 0.3, 0.5]));
+        assert!(parse_color("1,1").is_err());
+        assert!(parse_color("1,1,2").is_err());
+        assert!(parse_color("a,b,c").is_err());
+    }
+
+    #[test]
+    fn parse_gradients() {
+        assert_eq!(parse_gradient("1,1,1, 0.2,0.3,0.4"), Ok(([1.0, 1.0, 1.0], [0.2, 0.3, 0.4])));
+        assert!(parse_gradient("1,1,1").is_err());
+        assert!(parse_gradient("1,1,1,1,0,0,0,1").is_err());
+        assert!(parse_gradient("1,1,1,2,0,0").is_err());
+    }
+
+    #[test]
+    fn frustum_culling() {
+        let projection = cgmath::perspective(cgmath::Deg(75.0), 4.0 / 3.0, 0.01, 100.0);
+        let view = Matrix4::look_at(Point3::new(0.0, 0.0, 5.0), Point3::new(0.0, 0.0, 0.0), Vector3::unit_y());
+        let frustum = Frustum::from_matrix(&(projection * view));
+
+        let in_front = Aabb3 { min: Point3::new(-1.0, -1.0, -1.0), max: Point3::new(1.0, 1.0, 1.0) };
+        assert!(frustum.intersects(&in_front));
+
+        let behind = Aabb3 { min: Point3::new(-1.0, -1.0, 10.0), max: Point3::new(1.0, 1.0, 12.0) };
+        assert!(!frustum.intersects(&behind));
+
+        let beyond_far_plane = Aabb3 { min: Point3::new(-1.0, -1.0, -200.0), max: Point3::new(1.0, 1.0, -150.0) };
+        assert!(!frustum.intersects(&beyond_far_plane));
+    }
+}
@@ -7,13 +7,11 @@ use gltf;
 use gltf_importer;
 
 use render::math::*;
-use render::{Primitive, Root};
+use render::{DrawStats, Primitive, Root};
 
 pub struct Mesh {
     pub index: usize, // glTF index
     pub primitives: Vec<Primitive>,
-    // TODO: weights
-    // pub weights: Vec<Rc<?>>
     pub name: Option<String>,
 
     pub bounds: Aabb3<f32>,
@@ -38,15 +36,33 @@ impl Mesh {
 
         Mesh {
             index: g_mesh.index(),
-            primitives: primitives,
+            primitives,
             name: g_mesh.name().map(|s| s.into()),
             bounds,
         }
     }
 
-    pub fn draw(&self, model_matrix: &Matrix4, mvp_matrix: &Matrix4, camera_position: &Vector3) {
-        for primitive in &self.primitives {
-            unsafe { primitive.draw(model_matrix, mvp_matrix, camera_position) }
+    pub fn num_morph_targets(&self) -> usize {
+        self.primitives.iter().map(|prim| prim.num_morph_targets()).max().unwrap_or(0)
+    }
+
+    pub fn has_blended_primitives(&self) -> bool {
+        self.primitives.iter().any(|primitive| primitive.is_blended())
+    }
+
+    /// Draws either the opaque/masked or the blended primitives, depending on `root.blend_pass`
+    /// `skinned`: joint matrices are bound (see `Skin::bind_joint_matrices`)
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(&self, root: &mut Root, model_matrix: &Matrix4, mvp_matrix: &Matrix4, camera_position: &Vector3,
+            skinned: bool, morph_weights: &[f32], stats: &mut DrawStats) {
+        // the picking ID pass draws everything at once
+        let (picking, blend_pass) = (root.picking, root.blend_pass);
+        let primitives = self.primitives.iter()
+            .filter(|primitive| picking || primitive.is_blended() == blend_pass);
+        for primitive in primitives {
+            unsafe { primitive.draw(root, model_matrix, mvp_matrix, camera_position, skinned, morph_weights) }
+            stats.draw_calls += 1;
+            stats.triangles += primitive.num_triangles();
         }
     }
 }
@@ -16,6 +16,40 @@ mod material;
 pub use self::material::*;
 mod texture;
 pub use self::texture::*;
+mod ktx2;
+pub use self::ktx2::*;
 
 mod camera;
 pub use self::camera::*;
+mod light;
+pub use self::light::*;
+
+mod animation;
+pub use self::animation::*;
+mod skin;
+pub use self::skin::*;
+
+mod environment;
+pub use self::environment::*;
+mod background;
+pub use self::background::*;
+
+mod bounding_box;
+pub use self::bounding_box::*;
+mod grid;
+pub use self::grid::*;
+mod outline;
+pub use self::outline::*;
+mod picking;
+pub use self::picking::*;
+mod shadow;
+pub use self::shadow::*;
+mod shadow_catcher;
+pub use self::shadow_catcher::*;
+mod ssao;
+pub use self::ssao::*;
+mod transmission;
+pub use self::transmission::*;
+
+mod text;
+pub use self::text::*;
@@ -9,20 +9,50 @@ use collision::{Aabb, Union};
 use controls::CameraParams;
 use render::math::*;
 use render::mesh::Mesh;
-use render::Root;
+use render::{BoundingBoxRenderer, Root, MESH_BOUNDS_COLOR};
 use render::camera::Camera;
 
+/// Number of meshes drawn/skipped by frustum culling in a frame
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DrawStats {
+    pub drawn_meshes: u32,
+    pub culled_meshes: u32,
+    pub draw_calls: u32,
+    pub triangles: u32,
+}
+
+impl DrawStats {
+    /// Drawn and culled meshes, e.g. "12 drawn, 3 culled" (for the overlay and the render timer log)
+    pub fn meshes_text(&self) -> String {
+        format!("{} drawn, {} culled", self.drawn_meshes, self.culled_meshes)
+    }
+}
+
+/// A primitive to draw in the opaque pass (see `Scene::draw`), sorted by shader flags and material
+#[derive(Debug, Clone, Copy)]
+pub struct DrawItem {
+    pub shader_flags: u64,
+    pub material: Option<usize>, // glTF index
+    pub mesh: usize, // glTF index
+    pub primitive: usize, // index in the node's mesh
+    pub node: usize,
+    pub skinned: bool,
+    /// Can be drawn together with other nodes using the same primitive (no per-node uniforms)
+    pub instanceable: bool,
+}
+
 pub struct Node {
     pub index: usize, // glTF index
     pub children: Vec<usize>,
     pub matrix: Matrix4,
     pub mesh: Option<Rc<Mesh>>,
+    pub skin: Option<usize>, // glTF index
     pub rotation: Quaternion,
     pub scale: Vector3,
     pub translation: Vector3,
-    // TODO: weights
-    // weights_id: usize,
+    pub weights: Vec<f32>, // morph target weights (one per target of the mesh)
     pub camera: Option<Camera>,
+    pub light: Option<usize>, // glTF index (KHR_lights_punctual)
     pub name: Option<String>,
 
     pub final_transform: Matrix4, // including parent transforms
@@ -58,6 +88,15 @@ impl Node {
                 root.meshes.push(mesh.clone().unwrap());
             }
         }
+        // spec: node weights override the default weights of the mesh
+        let mut weights = match g_node.weights() {
+            Some(weights) => weights.to_vec(),
+            None => g_node.mesh()
+                .and_then(|g_mesh| g_mesh.weights().map(|weights| weights.to_vec()))
+                .unwrap_or_default(),
+        };
+        weights.resize(mesh.as_ref().map_or(0, |mesh| mesh.num_morph_targets()), 0.0);
+
         let children: Vec<_> = g_node.children()
                 .map(|g_node| g_node.index())
                 .collect();
@@ -67,10 +106,13 @@ impl Node {
             children,
             matrix,
             mesh,
+            skin: g_node.skin().map(|g_skin| g_skin.index()),
             rotation,
             scale: g_node.scale().into(),
             translation: g_node.translation().into(),
+            weights,
             camera: g_node.camera().as_ref().map(Camera::from_gltf),
+            light: None, // set by Root::from_gltf
             name: g_node.name().map(|s| s.into()),
 
             final_transform: Matrix4::identity(),
@@ -121,15 +163,98 @@ impl Node {
         }
     }
 
-    pub fn draw(&mut self, root: &mut Root, cam_params: &CameraParams) {
+    /// Appends the visible non-blended primitives of this node and its descendants
+    /// (all primitives when picking) and counts drawn/culled meshes.
+    pub fn collect_draws(&self, root: &Root, cam_params: &CameraParams, draws: &mut Vec<DrawItem>,
+            stats: &mut DrawStats) {
         if let Some(ref mesh) = self.mesh {
-            let mvp_matrix = cam_params.projection_matrix * cam_params.view_matrix * self.final_transform;
+            // NOTE: not using self.bounds, since it doesn't reflect animated transforms
+            let world_bounds = mesh.bounds.transform(&self.final_transform);
+            // skinned meshes aren't culled since their bounds only cover the bind pose
+            let skinned = self.skin.map_or(false, |index| index < root.skins.len());
+            if skinned || cam_params.frustum.intersects(&world_bounds) {
+                let instanceable = !skinned && mesh.num_morph_targets() == 0 &&
+                    root.selected_node != Some(self.index);
+                let primitives = mesh.primitives.iter().enumerate()
+                    .filter(|&(_, primitive)| root.picking || !primitive.is_blended());
+                for (i, primitive) in primitives {
+                    draws.push(DrawItem {
+                        shader_flags: primitive.shader_flags().bits(),
+                        material: primitive.material.index,
+                        mesh: mesh.index,
+                        primitive: i,
+                        node: self.index,
+                        skinned,
+                        instanceable,
+                    });
+                }
+                stats.drawn_meshes += 1;
+            }
+            else {
+                stats.culled_meshes += 1;
+            }
+        }
+        for node_id in &self.children {
+            root.nodes[*node_id].collect_draws(root, cam_params, draws, stats);
+        }
+    }
+
+    /// Draws a primitive collected with `collect_draws`.
+    /// The joint matrices of skinned nodes must already be bound.
+    pub unsafe fn draw_primitive(&self, root: &mut Root, cam_params: &CameraParams, draw: &DrawItem,
+            stats: &mut DrawStats) {
+        let mesh = self.mesh.as_ref().unwrap();
+        let primitive = &mesh.primitives[draw.primitive];
+        let mvp_matrix = cam_params.projection_matrix * cam_params.view_matrix * self.final_transform;
+        root.current_node = self.index;
+        primitive.draw(root, &self.final_transform, &mvp_matrix, &cam_params.position,
+            draw.skinned, &self.weights);
+        stats.draw_calls += 1;
+        stats.triangles += primitive.num_triangles();
+    }
 
-            (*mesh).draw(&self.final_transform, &mvp_matrix, &cam_params.position);
+    /// Draws the mesh of this node only (not the children). Used for the blend pass,
+    /// the meshes are already counted in `DrawStats` by `collect_draws`.
+    pub fn draw_mesh(&mut self, root: &mut Root, cam_params: &CameraParams, stats: &mut DrawStats) {
+        if let Some(ref mesh) = self.mesh {
+            // NOTE: not using self.bounds, since it doesn't reflect animated transforms
+            let world_bounds = mesh.bounds.transform(&self.final_transform);
+            // skinned meshes aren't culled since their bounds only cover the bind pose
+            let skinned = self.skin.map_or(false, |index| index < root.skins.len());
+            if skinned || cam_params.frustum.intersects(&world_bounds) {
+                let mvp_matrix = cam_params.projection_matrix * cam_params.view_matrix * self.final_transform;
+
+                if skinned {
+                    let skin = &root.skins[self.skin.unwrap()];
+                    unsafe { skin.bind_joint_matrices(&root.nodes, &self.final_transform) }
+                }
+                root.current_node = self.index;
+                (*mesh).draw(root, &self.final_transform, &mvp_matrix, &cam_params.position,
+                    skinned, &self.weights, stats);
+            }
+        }
+    }
+
+    /// Appends this node and its descendants that have blended primitives,
+    /// with the squared distance of their (world space) mesh bounds center to `camera_position`
+    pub fn collect_blended(&self, root: &Root, camera_position: &Vector3, nodes: &mut Vec<(f32, usize)>) {
+        if let Some(ref mesh) = self.mesh {
+            if mesh.has_blended_primitives() {
+                let center = mesh.bounds.transform(&self.final_transform).center();
+                nodes.push(((center.to_vec() - camera_position).magnitude2(), self.index));
+            }
         }
         for node_id in &self.children {
-            let node = root.unsafe_get_node_mut(*node_id);
-            node.draw(root, cam_params);
+            root.nodes[*node_id].collect_blended(root, camera_position, nodes);
+        }
+    }
+
+    pub unsafe fn draw_mesh_bounds(&self, root: &Root, renderer: &BoundingBoxRenderer, view_projection: &Matrix4) {
+        if let Some(ref mesh) = self.mesh {
+            renderer.draw(&mesh.bounds.transform(&self.final_transform), view_projection, MESH_BOUNDS_COLOR);
+        }
+        for node_id in &self.children {
+            root.nodes[*node_id].draw_mesh_bounds(root, renderer, view_projection);
         }
     }
 }
@@ -0,0 +1,96 @@
+use gl;
+
+use framebuffer::Framebuffer;
+use shader::Shader;
+
+/// Outline (silhouette and crease) rendering as a post-process, e.g. for technical illustrations:
+/// the scene is rendered into an offscreen framebuffer (between `begin` and `apply`), then edges
+/// are detected in its depth and composited into the previously bound framebuffer.
+pub struct Outline {
+    scene_framebuffer: Framebuffer, // color + depth of the scene
+    target_framebuffer: u32, // bound before `begin`
+
+    shader: Shader,
+    u_thickness: i32,
+    u_outline_color: i32,
+    u_fill_color: i32,
+    vao: u32, // empty (full screen triangle without vertex attributes)
+}
+
+impl Outline {
+    /// NOTE: Needs a current GL context
+    pub fn new(width: u32, height: u32) -> Outline {
+        let scene_framebuffer = Framebuffer::new(width, height, 0);
+        let mut shader = Shader::from_source(
+            include_str!("../shaders/fullscreen-vert.glsl"),
+            include_str!("../shaders/outline-frag.glsl"),
+            &[]);
+
+        unsafe {
+            let u_thickness = shader.uniform_location("u_Thickness");
+            let u_outline_color = shader.uniform_location("u_OutlineColor");
+            let u_fill_color = shader.uniform_location("u_FillColor");
+            let u_color_sampler = shader.uniform_location("u_ColorSampler");
+            let u_depth_sampler = shader.uniform_location("u_DepthSampler");
+            shader.use_program();
+            shader.set_int(u_color_sampler, 0);
+            shader.set_int(u_depth_sampler, 1);
+
+            let mut vao = 0;
+            gl::GenVertexArrays(1, &mut vao);
+
+            Outline {
+                scene_framebuffer,
+                target_framebuffer: 0,
+                shader,
+                u_thickness,
+                u_outline_color,
+                u_fill_color,
+                vao,
+            }
+        }
+    }
+
+    /// Redirects rendering into the offscreen scene framebuffer, resizing it to the current viewport.
+    pub unsafe fn begin(&mut self) {
+        let mut target_framebuffer = 0;
+        gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut target_framebuffer);
+        self.target_framebuffer = target_framebuffer as u32;
+
+        let mut viewport = [0; 4];
+        gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        let size = (viewport[2] as u32, viewport[3] as u32);
+        if self.scene_framebuffer.size() != size {
+            self.scene_framebuffer.resize(size.0, size.1);
+        }
+        self.scene_framebuffer.bind();
+    }
+
+    /// Draws the scene rendered since `begin` with outlines (including its depth) into the framebuffer
+    /// bound before. `thickness` is in pixels, `fill` replaces the shaded colors of the scene if given.
+    pub unsafe fn apply(&self, thickness: f32, color: &[f32; 4], fill: Option<&[f32; 4]>) {
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        gl::Disable(gl::BLEND);
+        gl::Disable(gl::CULL_FACE);
+        gl::BindVertexArray(self.vao);
+
+        // writes depth as well, so that e.g. the grid is still occluded by the scene
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.target_framebuffer);
+        gl::Enable(gl::DEPTH_TEST);
+        gl::DepthFunc(gl::ALWAYS);
+        self.shader.use_program();
+        self.shader.set_float(self.u_thickness, thickness);
+        gl::Uniform4f(self.u_outline_color, color[0], color[1], color[2], color[3]);
+        let fill = fill.cloned().unwrap_or([0.0; 4]);
+        gl::Uniform4f(self.u_fill_color, fill[0], fill[1], fill[2], fill[3]);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, self.scene_framebuffer.color_texture);
+        gl::ActiveTexture(gl::TEXTURE1);
+        gl::BindTexture(gl::TEXTURE_2D, self.scene_framebuffer.depth_texture);
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+
+        gl::DepthFunc(gl::LESS);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindVertexArray(0);
+    }
+}
@@ -0,0 +1,35 @@
+use render::math::*;
+
+/// Color that encodes a node index in the picking ID pass: index + 1 as 24 bit RGB,
+/// so that black (the clear color) means "nothing".
+pub fn pick_color(node_index: usize) -> Vector4 {
+    let id = node_index + 1;
+    let channel = |shift: usize| ((id >> shift) & 0xff) as f32 / 255.0;
+    Vector4::new(channel(16), channel(8), channel(0), 1.0)
+}
+
+/// Inverse of `pick_color` for a pixel read back from the ID pass
+pub fn node_from_pick_color(pixel: [u8; 4]) -> Option<usize> {
+    let id = (usize::from(pixel[0]) << 16) | (usize::from(pixel[1]) << 8) | usize::from(pixel[2]);
+    if id == 0 { None } else { Some(id - 1) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_color_round_trip() {
+        for &index in &[0, 1, 254, 255, 256, 70_000, 0xff_fffe] {
+            let color = pick_color(index);
+            let pixel = [
+                (color.x * 255.0).round() as u8,
+                (color.y * 255.0).round() as u8,
+                (color.z * 255.0).round() as u8,
+                255,
+            ];
+            assert_eq!(node_from_pick_color(pixel), Some(index));
+        }
+        assert_eq!(node_from_pick_color([0, 0, 0, 0]), None);
+    }
+}
@@ -7,12 +7,14 @@ use std::rc::Rc;
 use gl;
 use gltf;
 use gltf::json::mesh::Mode;
+use gltf::material::AlphaMode;
 use gltf_importer;
-use gltf_utils::PrimitiveIterators;
+use gltf_utils::{AccessorIter, PrimitiveIterators};
 
 // use camera::Camera;
 use render::math::*;
-use render::{Material, Root};
+use render::{pick_color, Material, Root, TextureSlot, TRANSMISSION_UNIT};
+use render::{CLEARCOAT_UNIT, CLEARCOAT_ROUGHNESS_UNIT, CLEARCOAT_NORMAL_UNIT};
 use shader::*;
 
 #[derive(Debug)]
@@ -42,13 +44,21 @@ impl Default for Vertex {
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct Texture {
-    pub id: u32,
-    pub type_: String,
-    pub path: String,
+/// Texture unit of the morph target buffer (see also the other units in environment.rs and skin.rs)
+pub const MORPH_TARGETS_UNIT: u32 = 9;
+/// NOTE: must match the define in pbr-vert.glsl
+pub const MAX_MORPH_TARGETS: usize = 8;
+/// First attribute location of the per-instance model matrix (one per column, see pbr-vert.glsl)
+pub const INSTANCE_MATRIX_LOCATION: u32 = 8;
+
+/// Position and normal displacements of all morph targets in a buffer texture,
+/// indexed by `(2 * target + attribute) * num_vertices + vertex` in the vertex shader
+struct MorphTargets {
+    texture: u32, // NOTE: the texture references the buffer, so its id isn't needed
+    count: usize,
 }
 
+
 pub struct Primitive {
     pub bounds: Aabb3,
 
@@ -59,11 +69,14 @@ pub struct Primitive {
     ebo: Option<u32>,
     num_indices: u32,
 
-    material: Rc<Material>,
+    pub material: Rc<Material>,
 
     pbr_shader: Rc<PbrShader>,
 
-    // TODO!: mode, targets
+    morph_targets: Option<MorphTargets>,
+
+    /// GL primitive type (`gl::TRIANGLES` etc.)
+    mode: u32,
 }
 
 impl Primitive {
@@ -73,6 +86,7 @@ impl Primitive {
         indices: Option<Vec<u32>>,
         material: Rc<Material>,
         shader: Rc<PbrShader>,
+        mode: u32,
     ) -> Primitive {
         let num_indices = indices.as_ref().map(|i| i.len()).unwrap_or(0);
         let mut prim = Primitive {
@@ -82,6 +96,8 @@ impl Primitive {
             vao: 0, vbo: 0, ebo: None,
             material,
             pbr_shader: shader,
+            morph_targets: None,
+            mode,
         };
 
         // now that we have all the required data, set the vertex buffers and its attribute pointers.
@@ -99,7 +115,7 @@ impl Primitive {
     {
         // positions
         let positions = g_primitive.positions(buffers)
-            .expect(&format!("primitives must have the POSITION attribute (mesh: {}, primitive: {})",
+            .unwrap_or_else(|| panic!("primitives must have the POSITION attribute (mesh: {}, primitive: {})",
                 mesh_index, primitive_index));
 
         let bounds = g_primitive.position_bounds()
@@ -160,11 +176,11 @@ impl Primitive {
                     _ => unreachable!()
                 }
             }
-            shader_flags |= ShaderFlags::HAS_UV;
+            shader_flags |= if tex_coord_set == 0 { ShaderFlags::HAS_UV } else { ShaderFlags::HAS_UV_1 };
             tex_coord_set += 1;
         }
 
-        // colors
+        // colors (VEC3 or VEC4; float or normalized unsigned byte/short - converted by gltf_utils)
         if let Some(colors) = g_primitive.colors_rgba_f32(0, 1.0, buffers) {
             for (i, c) in colors.enumerate() {
                 vertices[i].color_0 = c.into();
@@ -176,10 +192,12 @@ impl Primitive {
                 mesh_index, primitive_index);
         }
 
+        let mut has_joints = false;
         if let Some(joints) = g_primitive.joints_u16(0, buffers) {
             for (i, joint) in joints.enumerate() {
                 vertices[i].joints_0 = joint;
             }
+            has_joints = true;
         }
         if g_primitive.joints_u16(1, buffers).is_some() {
             warn!("Ignoring further joint attributes, only supporting JOINTS_0. (mesh: {}, primitive: {})",
@@ -190,15 +208,41 @@ impl Primitive {
             for (i, weights) in weights.enumerate() {
                 vertices[i].weights_0 = weights.into();
             }
+            if has_joints {
+                shader_flags |= ShaderFlags::HAS_SKIN;
+            }
         }
         if g_primitive.weights_f32(1, buffers).is_some() {
             warn!("Ignoring further weight attributes, only supporting WEIGHTS_0. (mesh: {}, primitive: {})",
                 mesh_index, primitive_index);
         }
 
+        // morph targets: positions and normals for all vertices, target after target (TODO: tangents)
+        let mut morph_data = Vec::new();
+        let mut num_morph_targets = 0;
+        for (target_index, g_target) in g_primitive.morph_targets().enumerate() {
+            if target_index >= MAX_MORPH_TARGETS {
+                warn!("Ignoring morph targets beyond the first {} (mesh: {}, primitive: {})",
+                    MAX_MORPH_TARGETS, mesh_index, primitive_index);
+                break
+            }
+            for accessor in vec![g_target.positions(), g_target.normals()] {
+                let start = morph_data.len();
+                if let Some(accessor) = accessor {
+                    morph_data.extend(AccessorIter::<[f32; 3]>::new(accessor, buffers)
+                        .map(|v| Vector4::new(v[0], v[1], v[2], 0.0)));
+                }
+                morph_data.resize(start + vertices.len(), Vector4::zero());
+            }
+            num_morph_targets += 1;
+        }
+        if num_morph_targets > 0 {
+            shader_flags |= ShaderFlags::HAS_MORPH_TARGETS;
+        }
+
         let indices: Option<Vec<u32>> = g_primitive.indices_u32(buffers).map(|indices| indices.collect());
 
-        assert_eq!(g_primitive.mode(), Mode::Triangles, "not yet implemented: primitive mode must be Triangles.");
+        let mode = gl_mode(g_primitive.mode());
 
         let g_material = g_primitive.material();
 
@@ -214,83 +258,222 @@ impl Primitive {
         };
         let material = material.unwrap();
         shader_flags |= material.shader_flags();
+        if root.environment.is_some() {
+            shader_flags |= ShaderFlags::USE_IBL | ShaderFlags::USE_TEX_LOD;
+        }
 
-        let mut new_shader = false; // borrow checker workaround
-        let shader =
-            if let Some(shader) = root.shaders.get(&shader_flags) {
-                Rc::clone(shader)
-            }
-            else {
-                new_shader = true;
-                PbrShader::new(shader_flags).into()
+        let shader = root.get_shader(shader_flags);
 
-            };
-        if new_shader {
-            root.shaders.insert(shader_flags, Rc::clone(&shader));
+        let mut primitive = Primitive::new(bounds, &vertices, indices, material, shader, mode);
+        if num_morph_targets > 0 {
+            unsafe { primitive.setup_morph_targets(&morph_data, num_morph_targets) }
         }
+        primitive
+    }
+
+    pub fn num_morph_targets(&self) -> usize {
+        self.morph_targets.as_ref().map_or(0, |targets| targets.count)
+    }
+
+    /// Whether the primitive is drawn in the blend pass (after all opaque/masked ones).
+    /// Transmissive primitives are as well, since they show what's behind them.
+    pub fn is_blended(&self) -> bool {
+        match self.material.alpha_mode {
+            AlphaMode::Blend => true,
+            _ => self.material.transmission_factor > 0.0,
+        }
+    }
+
+    /// Flags of the primitive's own shader variant (without per-draw flags, see `draw`)
+    pub fn shader_flags(&self) -> ShaderFlags {
+        self.pbr_shader.flags
+    }
+
+    pub fn num_vertices(&self) -> u32 {
+        self.num_vertices
+    }
 
-        Primitive::new(bounds, &vertices, indices, material, shader)
+    pub fn num_triangles(&self) -> u32 {
+        let count = if self.ebo.is_some() { self.num_indices } else { self.num_vertices };
+        triangle_count(self.mode, count)
     }
 
     /// render the mesh
-    pub unsafe fn draw(&self, model_matrix: &Matrix4, mvp_matrix: &Matrix4, camera_position: &Vector3) {
-        // TODO!: determine if shader+material already active to reduce work...
+    /// Without `skinned`, the joint attributes are ignored (i.e. the mesh is drawn in bind pose)
+    pub unsafe fn draw(&self, root: &mut Root, model_matrix: &Matrix4, mvp_matrix: &Matrix4, camera_position: &Vector3,
+            skinned: bool, morph_weights: &[f32]) {
+        let pbr_shader = self.prepare_draw(root, camera_position, skinned, false);
+        pbr_shader.shader.set_mat4(pbr_shader.uniforms.u_ModelMatrix, model_matrix);
+        pbr_shader.shader.set_mat4(pbr_shader.uniforms.u_MVPMatrix, mvp_matrix);
+        if let Some(ref targets) = self.morph_targets {
+            self.configure_morph_targets(&pbr_shader, targets, morph_weights);
+        }
+        if root.picking {
+            pbr_shader.shader.set_vector4(pbr_shader.uniforms.u_PickColor, &pick_color(root.current_node));
+        }
+
+        self.draw_elements(1);
+    }
+
+    /// Draws the primitive once per model matrix with a single draw call.
+    /// Not for skinned or morphed primitives or picking (all instances use the same uniforms).
+    pub unsafe fn draw_instanced(&self, root: &mut Root, model_matrices: &[Matrix4], view_projection: &Matrix4,
+            camera_position: &Vector3) {
+        let pbr_shader = self.prepare_draw(root, camera_position, false, true);
+        pbr_shader.shader.set_mat4(pbr_shader.uniforms.u_ViewProjectionMatrix, view_projection);
+
+        if root.instance_buffer == 0 {
+            gl::GenBuffers(1, &mut root.instance_buffer);
+        }
+        gl::BindVertexArray(self.vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, root.instance_buffer);
+        let size = (model_matrices.len() * size_of::<Matrix4>()) as isize;
+        gl::BufferData(gl::ARRAY_BUFFER, size, model_matrices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+        // one attribute per matrix column
+        let stride = size_of::<Matrix4>() as i32;
+        for column in 0..4 {
+            let location = INSTANCE_MATRIX_LOCATION + column;
+            let offset = column as usize * size_of::<Vector4>();
+            gl::EnableVertexAttribArray(location);
+            gl::VertexAttribPointer(location, 4, gl::FLOAT, gl::FALSE, stride, offset as *const c_void);
+            gl::VertexAttribDivisor(location, 1);
+        }
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+        self.draw_elements(model_matrices.len() as i32);
+    }
 
-        if self.material.double_sided {
+    /// Selects the shader variant and sets the render state and all uniforms except the matrices
+    unsafe fn prepare_draw(&self, root: &mut Root, camera_position: &Vector3, skinned: bool, instanced: bool)
+            -> Rc<PbrShader> {
+        let mut flags = self.pbr_shader.flags | root.debug_flags;
+        if flags.contains(ShaderFlags::CLAY) {
+            // only the geometry matters
+            flags.remove(ShaderFlags::HAS_BASECOLORMAP | ShaderFlags::HAS_NORMALMAP | ShaderFlags::HAS_EMISSIVEMAP |
+                ShaderFlags::HAS_METALROUGHNESSMAP | ShaderFlags::HAS_OCCLUSIONMAP | ShaderFlags::HAS_COLORS |
+                ShaderFlags::UNLIT | ShaderFlags::ALPHA_MASK | ShaderFlags::ALPHA_BLEND |
+                ShaderFlags::TRANSMISSION | ShaderFlags::HAS_TRANSMISSIONMAP |
+                ShaderFlags::CLEARCOAT | ShaderFlags::HAS_CLEARCOATMAP | ShaderFlags::HAS_CLEARCOATROUGHNESSMAP |
+                ShaderFlags::HAS_CLEARCOATNORMALMAP | ShaderFlags::TEXTURE_TRANSFORM);
+        }
+        if flags.contains(ShaderFlags::TRANSMISSION) && !root.transmission {
+            // fall back to simple alpha blending (see `configure_material`)
+            flags.remove(ShaderFlags::TRANSMISSION | ShaderFlags::HAS_TRANSMISSIONMAP);
+            flags.insert(ShaderFlags::ALPHA_BLEND);
+        }
+        if root.hide_vertex_colors {
+            flags.remove(ShaderFlags::HAS_COLORS);
+        }
+        if !skinned {
+            flags.remove(ShaderFlags::HAS_SKIN);
+        }
+        if instanced {
+            flags.insert(ShaderFlags::INSTANCED);
+        }
+        if root.selected_node == Some(root.current_node) {
+            flags.insert(ShaderFlags::HIGHLIGHT);
+        }
+        if root.shadows.is_some() {
+            flags.insert(ShaderFlags::SHADOWS);
+        }
+        if root.picking || root.shadow_pass {
+            // only the vertex shader inputs matter
+            flags &= ShaderFlags::HAS_NORMALS | ShaderFlags::HAS_TANGENTS | ShaderFlags::HAS_UV |
+                ShaderFlags::HAS_COLORS | ShaderFlags::HAS_SKIN | ShaderFlags::HAS_MORPH_TARGETS |
+                ShaderFlags::INSTANCED;
+            flags.insert(if root.picking { ShaderFlags::PICKING } else { ShaderFlags::SHADOW_PASS });
+        }
+        let pbr_shader = if flags == self.pbr_shader.flags {
+            Rc::clone(&self.pbr_shader)
+        } else {
+            root.get_shader(flags)
+        };
+
+        // set for every primitive, so the state of the previous one doesn't leak
+        if self.material.double_sided || root.disable_culling {
             gl::Disable(gl::CULL_FACE);
         } else {
             gl::Enable(gl::CULL_FACE);
         }
+        match self.material.polygon_offset.or(root.polygon_offset) {
+            Some(offset) => {
+                gl::Enable(gl::POLYGON_OFFSET_FILL);
+                gl::PolygonOffset(offset.factor, offset.units);
+            },
+            None => gl::Disable(gl::POLYGON_OFFSET_FILL),
+        }
+
+        // skip redundant program switches and material setup (see `DrawState`)
+        let program_changed = !root.state_caching || root.draw_state.program != pbr_shader.shader.id;
+        if program_changed {
+            pbr_shader.shader.use_program();
+            root.draw_state.program = pbr_shader.shader.id;
+        }
+        let material_changed = !root.state_caching || root.draw_state.material.as_ref()
+            .map_or(true, |material| !Rc::ptr_eq(material, &self.material));
+        if material_changed {
+            root.draw_state.material = Some(Rc::clone(&self.material));
+        }
 
-        self.configure_shader(model_matrix, mvp_matrix, camera_position);
+        pbr_shader.shader.set_vector3(pbr_shader.uniforms.u_Camera, camera_position);
+        if program_changed || material_changed {
+            self.configure_material(&pbr_shader);
+        }
+        pbr_shader
+    }
 
-        // draw mesh
+    unsafe fn draw_elements(&self, instance_count: i32) {
         gl::BindVertexArray(self.vao);
-        if self.ebo.is_some() {
-            gl::DrawElements(gl::TRIANGLES, self.num_indices as i32, gl::UNSIGNED_INT, ptr::null());
-        }
-        else {
-            gl::DrawArrays(gl::TRIANGLES, 0, self.num_vertices as i32)
+        match (self.ebo.is_some(), instance_count > 1) {
+            (true, false) =>
+                gl::DrawElements(self.mode, self.num_indices as i32, gl::UNSIGNED_INT, ptr::null()),
+            (true, true) =>
+                gl::DrawElementsInstanced(self.mode, self.num_indices as i32, gl::UNSIGNED_INT,
+                    ptr::null(), instance_count),
+            (false, false) =>
+                gl::DrawArrays(self.mode, 0, self.num_vertices as i32),
+            (false, true) =>
+                gl::DrawArraysInstanced(self.mode, 0, self.num_vertices as i32, instance_count),
         }
 
         gl::BindVertexArray(0);
         gl::ActiveTexture(gl::TEXTURE0);
     }
 
-    unsafe fn configure_shader(&self, model_matrix: &Matrix4,
-        mvp_matrix: &Matrix4, camera_position: &Vector3)
-    {
-        // let pbr_shader = &Rc::get_mut(&mut self.pbr_shader).unwrap();
+    /// Sets the material uniforms and binds its textures. Expects the program to be in use.
+    unsafe fn configure_material(&self, pbr_shader: &PbrShader) {
         let mat = &self.material;
-        let shader = &self.pbr_shader.shader;
-        let uniforms = &self.pbr_shader.uniforms;
-        self.pbr_shader.shader.use_program();
-
-        // camera params
-        shader.set_mat4(uniforms.u_ModelMatrix, model_matrix);
-        shader.set_mat4(uniforms.u_MVPMatrix, mvp_matrix);
-        shader.set_vector3(uniforms.u_Camera, camera_position);
+        let shader = &pbr_shader.shader;
+        let uniforms = &pbr_shader.uniforms;
 
         // NOTE: for sampler numbers, see also PbrShader constructor
-        shader.set_vector4(uniforms.u_BaseColorFactor, &mat.base_color_factor);
+        let mut base_color_factor = mat.base_color_factor;
+        if mat.transmission_factor > 0.0 && !pbr_shader.flags.contains(ShaderFlags::TRANSMISSION) {
+            // without the captured scene, the transmitted light is approximated by transparency
+            base_color_factor.w *= 1.0 - mat.transmission_factor;
+        }
+        shader.set_vector4(uniforms.u_BaseColorFactor, &base_color_factor);
         if let Some(ref base_color_texture) = mat.base_color_texture {
             gl::ActiveTexture(gl::TEXTURE0);
             gl::BindTexture(gl::TEXTURE_2D, base_color_texture.id);
+            shader.set_int(uniforms.u_BaseColorTexCoord, base_color_texture.tex_coord as i32);
         }
         if let Some(ref normal_texture) = mat.normal_texture {
             gl::ActiveTexture(gl::TEXTURE1);
             gl::BindTexture(gl::TEXTURE_2D, normal_texture.id);
+            shader.set_int(uniforms.u_NormalTexCoord, normal_texture.tex_coord as i32);
         }
         if let Some(ref emissive_texture) = mat.emissive_texture {
             gl::ActiveTexture(gl::TEXTURE2);
             gl::BindTexture(gl::TEXTURE_2D, emissive_texture.id);
-
-            shader.set_vector3(uniforms.u_EmissiveFactor, &mat.emissive_factor);
+            shader.set_int(uniforms.u_EmissiveTexCoord, emissive_texture.tex_coord as i32);
         }
+        shader.set_vector3(uniforms.u_EmissiveFactor, &mat.emissive());
 
         if let Some(ref mr_texture) = mat.metallic_roughness_texture {
             gl::ActiveTexture(gl::TEXTURE3);
             gl::BindTexture(gl::TEXTURE_2D, mr_texture.id);
+            shader.set_int(uniforms.u_MetallicRoughnessTexCoord, mr_texture.tex_coord as i32);
         }
         shader.set_vec2(uniforms.u_MetallicRoughnessValues,
             mat.metallic_factor, mat.roughness_factor);
@@ -298,9 +481,79 @@ impl Primitive {
         if let Some(ref occlusion_texture) = mat.occlusion_texture {
             gl::ActiveTexture(gl::TEXTURE4);
             gl::BindTexture(gl::TEXTURE_2D, occlusion_texture.id);
+            shader.set_int(uniforms.u_OcclusionTexCoord, occlusion_texture.tex_coord as i32);
 
             shader.set_float(uniforms.u_OcclusionStrength, mat.occlusion_strength);
         }
+
+        if let AlphaMode::Mask = mat.alpha_mode {
+            shader.set_float(uniforms.u_AlphaCutoff, mat.alpha_cutoff);
+        }
+
+        if pbr_shader.flags.contains(ShaderFlags::TEXTURE_TRANSFORM) {
+            shader.set_mat3(uniforms.u_BaseColorUVTransform, &mat.uv_transform(TextureSlot::BaseColor));
+            shader.set_mat3(uniforms.u_NormalUVTransform, &mat.uv_transform(TextureSlot::Normal));
+            shader.set_mat3(uniforms.u_EmissiveUVTransform, &mat.uv_transform(TextureSlot::Emissive));
+            shader.set_mat3(uniforms.u_MetallicRoughnessUVTransform, &mat.uv_transform(TextureSlot::MetallicRoughness));
+            shader.set_mat3(uniforms.u_OcclusionUVTransform, &mat.uv_transform(TextureSlot::Occlusion));
+        }
+
+        if pbr_shader.flags.contains(ShaderFlags::TRANSMISSION) {
+            shader.set_float(uniforms.u_TransmissionFactor, mat.transmission_factor);
+            if let Some(ref transmission_texture) = mat.transmission_texture {
+                gl::ActiveTexture(gl::TEXTURE0 + TRANSMISSION_UNIT);
+                gl::BindTexture(gl::TEXTURE_2D, transmission_texture.id);
+                shader.set_int(uniforms.u_TransmissionTexCoord, transmission_texture.tex_coord as i32);
+            }
+        }
+
+        if pbr_shader.flags.contains(ShaderFlags::CLEARCOAT) {
+            shader.set_float(uniforms.u_ClearcoatFactor, mat.clearcoat_factor);
+            shader.set_float(uniforms.u_ClearcoatRoughnessFactor, mat.clearcoat_roughness_factor);
+            if let Some(ref clearcoat_texture) = mat.clearcoat_texture {
+                gl::ActiveTexture(gl::TEXTURE0 + CLEARCOAT_UNIT);
+                gl::BindTexture(gl::TEXTURE_2D, clearcoat_texture.id);
+                shader.set_int(uniforms.u_ClearcoatTexCoord, clearcoat_texture.tex_coord as i32);
+            }
+            if let Some(ref roughness_texture) = mat.clearcoat_roughness_texture {
+                gl::ActiveTexture(gl::TEXTURE0 + CLEARCOAT_ROUGHNESS_UNIT);
+                gl::BindTexture(gl::TEXTURE_2D, roughness_texture.id);
+                shader.set_int(uniforms.u_ClearcoatRoughnessTexCoord, roughness_texture.tex_coord as i32);
+            }
+            if let Some(ref normal_texture) = mat.clearcoat_normal_texture {
+                gl::ActiveTexture(gl::TEXTURE0 + CLEARCOAT_NORMAL_UNIT);
+                gl::BindTexture(gl::TEXTURE_2D, normal_texture.id);
+                shader.set_int(uniforms.u_ClearcoatNormalTexCoord, normal_texture.tex_coord as i32);
+                shader.set_float(uniforms.u_ClearcoatNormalScale, mat.clearcoat_normal_scale);
+            }
+        }
+    }
+
+    unsafe fn configure_morph_targets(&self, pbr_shader: &PbrShader, targets: &MorphTargets, weights: &[f32]) {
+        let uniforms = &pbr_shader.uniforms;
+        let mut padded_weights = [0.0; MAX_MORPH_TARGETS];
+        for (padded, weight) in padded_weights.iter_mut().zip(weights) {
+            *padded = *weight;
+        }
+        gl::ActiveTexture(gl::TEXTURE0 + MORPH_TARGETS_UNIT);
+        gl::BindTexture(gl::TEXTURE_BUFFER, targets.texture);
+        pbr_shader.shader.set_int(uniforms.u_NumMorphTargets, targets.count as i32);
+        pbr_shader.shader.set_int(uniforms.u_NumVertices, self.num_vertices as i32);
+        gl::Uniform1fv(uniforms.u_MorphWeights, MAX_MORPH_TARGETS as i32, padded_weights.as_ptr());
+    }
+
+    unsafe fn setup_morph_targets(&mut self, data: &[Vector4], count: usize) {
+        let (mut buffer, mut texture) = (0, 0);
+        gl::GenBuffers(1, &mut buffer);
+        gl::BindBuffer(gl::TEXTURE_BUFFER, buffer);
+        gl::BufferData(gl::TEXTURE_BUFFER, (data.len() * size_of::<Vector4>()) as isize,
+            data.as_ptr() as *const c_void, gl::STATIC_DRAW);
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_BUFFER, texture);
+        gl::TexBuffer(gl::TEXTURE_BUFFER, gl::RGBA32F, buffer);
+        gl::BindTexture(gl::TEXTURE_BUFFER, 0);
+        gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+        self.morph_targets = Some(MorphTargets { texture, count });
     }
 
     unsafe fn setup_primitive(&mut self, vertices: &[Vertex], indices: Option<Vec<u32>>) {
@@ -348,9 +601,8 @@ impl Primitive {
         // COLOR_0
         gl::EnableVertexAttribArray(5);
         gl::VertexAttribPointer(5, 4, gl::FLOAT, gl::FALSE, size, offset_of!(Vertex, color_0) as *const c_void);
-        // JOINTS_0
+        // JOINTS_0 (converted to float, but not normalized)
         gl::EnableVertexAttribArray(6);
-        // TODO: normalization?
         gl::VertexAttribPointer(6, 4, gl::UNSIGNED_SHORT, gl::FALSE, size, offset_of!(Vertex, joints_0) as *const c_void);
         // WEIGHTS_0
         gl::EnableVertexAttribArray(7);
@@ -359,3 +611,41 @@ impl Primitive {
         gl::BindVertexArray(0);
     }
 }
+
+/// GL primitive type for a glTF primitive mode
+fn gl_mode(mode: Mode) -> u32 {
+    match mode {
+        Mode::Points => gl::POINTS,
+        Mode::Lines => gl::LINES,
+        Mode::LineLoop => gl::LINE_LOOP,
+        Mode::LineStrip => gl::LINE_STRIP,
+        Mode::Triangles => gl::TRIANGLES,
+        Mode::TriangleStrip => gl::TRIANGLE_STRIP,
+        Mode::TriangleFan => gl::TRIANGLE_FAN,
+    }
+}
+
+/// Number of triangles drawn from `count` vertices/indices (0 for points and lines)
+fn triangle_count(mode: u32, count: u32) -> u32 {
+    match mode {
+        gl::TRIANGLES => count / 3,
+        gl::TRIANGLE_STRIP | gl::TRIANGLE_FAN => count.saturating_sub(2),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triangle_count_by_mode() {
+        assert_eq!(triangle_count(gl::TRIANGLES, 9), 3);
+        assert_eq!(triangle_count(gl::TRIANGLE_STRIP, 5), 3);
+        assert_eq!(triangle_count(gl::TRIANGLE_FAN, 5), 3);
+        assert_eq!(triangle_count(gl::TRIANGLE_FAN, 1), 0);
+        assert_eq!(triangle_count(gl::POINTS, 9), 0);
+        assert_eq!(triangle_count(gl::LINES, 8), 0);
+        assert_eq!(triangle_count(gl::LINE_STRIP, 8), 0);
+    }
+}
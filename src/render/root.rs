@@ -1,14 +1,30 @@
 #![macro_use]
 
 use std::rc::Rc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
+use gl;
 use gltf;
 use gltf_importer;
 
 use shader::*;
-use render::{Mesh, Node, Texture, Material};
+use render::{Animation, Environment, Mesh, Node, Skin, Texture, Material};
+use render::{read_gltf_json, unlit_material_indices, emissive_strengths, transmissions, clearcoats, load_texture};
+use render::basisu_sources;
+use render::{texture_transforms, Clearcoat, PolygonOffset, TextureSlot, TextureTransform, Transmission, TransmissionBuffer};
+use render::{Light, LightInstance, MAX_LIGHTS, lights_from_json, light_nodes_from_json};
+use render::ShadowSettings;
+use render::math::*;
+use utils::extensions;
+
+/// GL state set by the last drawn primitive, to skip redundant state changes.
+/// Reset at the start of `Scene::draw`, since other renderers (grid, text...) change it as well.
+#[derive(Default)]
+pub struct DrawState {
+    pub program: u32, // 0: unknown
+    pub material: Option<Rc<Material>>, // textures bound and uniforms set for `program`
+}
 
 #[derive(Default)]
 pub struct Root {
@@ -19,20 +35,112 @@ pub struct Root {
     pub shaders: HashMap<ShaderFlags, Rc<PbrShader>>,
 
     pub camera_nodes: Vec<usize>, // indices of camera nodes
+    pub lights: Vec<Light>, // KHR_lights_punctual
+    pub light_instances: Vec<LightInstance>, // current lights of all shaders (see `set_lights`)
+    pub shadows: Option<ShadowSettings>, // current shadow map settings (see `set_shadows`)
+    pub animations: Vec<Animation>,
+    pub skins: Vec<Skin>,
+    pub environment: Option<Rc<Environment>>, // for image based lighting
+    pub debug_flags: ShaderFlags, // added to the flags of all primitives when drawing
+    pub hide_vertex_colors: bool, // removes HAS_COLORS from the flags of all primitives when drawing
+    pub disable_culling: bool, // draw all primitives as if double sided (e.g. to find inverted normals)
+    pub unlit_materials: HashSet<usize>, // glTF indices of materials using KHR_materials_unlit
+    pub emissive_strengths: HashMap<usize, f32>, // KHR_materials_emissive_strength by glTF material index
+    pub transmissions: HashMap<usize, Transmission>, // KHR_materials_transmission by glTF material index
+    pub transmission: bool, // refraction through transmissive primitives (otherwise simple alpha blending)
+    pub transmission_buffer: Option<TransmissionBuffer>, // created when first needed (see `Scene::draw`)
+    pub clearcoats: HashMap<usize, Clearcoat>, // KHR_materials_clearcoat by glTF material index
+    pub texture_transforms: HashMap<usize, HashMap<TextureSlot, TextureTransform>>, // KHR_texture_transform by glTF material index
+    pub basisu_images: HashMap<usize, (usize, Vec<u8>)>, // KHR_texture_basisu (image index, KTX2 data) by glTF texture index
+    pub polygon_offset: Option<PolygonOffset>, // for all materials without their own (see `Material::polygon_offset`)
+    pub material_polygon_offsets: HashMap<String, PolygonOffset>, // by material name, applied when loading
+    pub picking: bool, // ID pass: primitives are drawn in the pick color of their node
+    pub shadow_pass: bool, // depth only pass from the shadow casting light (non-blended primitives only)
+    pub blend_pass: bool, // only primitives with alpha mode BLEND are drawn (otherwise only the others)
+    pub current_node: usize, // node being drawn
+    pub selected_node: Option<usize>, // highlighted when drawing
+    pub draw_state: DrawState,
+    pub state_caching: bool, // skip redundant state changes between primitives (see `DrawState`)
+    pub draw_sorting: bool, // group opaque primitives by shader variant and material (see `Scene::draw`)
+    pub instancing: bool, // draw nodes sharing a mesh with instanced draw calls (see `Scene::draw`)
+    pub instance_buffer: u32, // per-instance model matrices (0: not created yet)
+    pub gl_extensions: Vec<String>, // queried once in `from_gltf`, not for every texture
     // TODO!: joint_nodes, mesh_nodes?
 }
 
 impl Root {
-    pub fn from_gltf(gltf: &gltf::Gltf, buffers: &gltf_importer::Buffers, base_path: &Path) -> Self {
-        let mut root = Root::default();
+    /// With an `environment`, all shaders use image based lighting.
+    /// Materials named in `material_polygon_offsets` get that depth offset.
+    pub fn from_gltf(gltf: &gltf::Gltf, buffers: &gltf_importer::Buffers, base_path: &Path,
+            environment: Option<Rc<Environment>>, material_polygon_offsets: &HashMap<String, PolygonOffset>) -> Self {
+        let mut root = Root {
+            environment,
+            material_polygon_offsets: material_polygon_offsets.clone(),
+            gl_extensions: unsafe { extensions() },
+            ..Root::default()
+        };
+        let uses_extension = |name: &str| gltf.as_json().extensions_used.iter().any(|ext| ext == name);
+        let mut light_nodes = HashMap::new();
+        let mut basisu_textures = HashMap::new();
+        if uses_extension("KHR_materials_unlit") || uses_extension("KHR_materials_emissive_strength") ||
+                uses_extension("KHR_lights_punctual") || uses_extension("KHR_materials_transmission") ||
+                uses_extension("KHR_materials_clearcoat") || uses_extension("KHR_texture_transform") ||
+                uses_extension("KHR_texture_basisu") {
+            if let Some(json) = read_gltf_json(base_path) {
+                root.unlit_materials = unlit_material_indices(&json);
+                root.emissive_strengths = emissive_strengths(&json);
+                root.transmissions = transmissions(&json);
+                root.clearcoats = clearcoats(&json);
+                root.texture_transforms = texture_transforms(&json);
+                root.lights = lights_from_json(&json);
+                light_nodes = light_nodes_from_json(&json);
+                basisu_textures = basisu_sources(&json);
+            }
+        }
+        // read before loading any texture, `load_texture` prefers them over the fallback `source`
+        for (texture_index, image_index) in basisu_textures {
+            let data = gltf.images().nth(image_index)
+                .and_then(|g_img| Texture::ktx2_data(&g_img, buffers, base_path));
+            match data {
+                Some(data) => { root.basisu_images.insert(texture_index, (image_index, data)); },
+                None => warn!("Ignoring KHR_texture_basisu source {} of texture {} (not a KTX2 image)",
+                    image_index, texture_index),
+            }
+        }
+        // the materials find them in `textures` (the gltf crate doesn't know the extension)
+        let mut extension_textures: Vec<(usize, u32)> = root.transmissions.values()
+            .filter_map(|transmission| transmission.texture)
+            .collect();
+        for clearcoat in root.clearcoats.values() {
+            extension_textures.extend(
+                [clearcoat.texture, clearcoat.roughness_texture, clearcoat.normal_texture].iter().filter_map(|t| *t));
+        }
+        for (index, tex_coord) in extension_textures {
+            match gltf.textures().nth(index) {
+                Some(g_texture) => { load_texture(&g_texture, tex_coord, false, &mut root, buffers, base_path); },
+                None => warn!("Ignoring invalid texture {} of a material extension", index),
+            }
+        }
         let nodes = gltf.nodes()
             .map(|g_node| Node::from_gltf(&g_node, &mut root, buffers, base_path))
             .collect();
         root.nodes = nodes;
+        for (node_index, light_index) in light_nodes {
+            if let Some(node) = root.nodes.get_mut(node_index) {
+                node.light = Some(light_index);
+            }
+        }
         root.camera_nodes = root.nodes.iter()
             .filter(|node| node.camera.is_some())
             .map(|node| node.index)
             .collect();
+        root.skins = gltf.skins()
+            .map(|g_skin| Skin::from_gltf(&g_skin, buffers))
+            .collect();
+        let animations = gltf.animations()
+            .map(|g_animation| Animation::from_gltf(&g_animation, &root, buffers))
+            .collect();
+        root.animations = animations;
         root
     }
 
@@ -45,6 +153,95 @@ impl Root {
         }
     }
 
+    /// Returns the shader for the given flags, compiling it on first use.
+    /// New shaders get the current lights and shadow settings.
+    pub fn get_shader(&mut self, flags: ShaderFlags) -> Rc<PbrShader> {
+        if let Some(pbr_shader) = self.shaders.get(&flags) {
+            return Rc::clone(pbr_shader)
+        }
+        let pbr_shader = Rc::new(PbrShader::new(flags));
+        unsafe {
+            self.apply_lights(&pbr_shader);
+            self.apply_shadows(&pbr_shader);
+        }
+        self.shaders.insert(flags, Rc::clone(&pbr_shader));
+        pbr_shader
+    }
+
+    /// Sets the lights (at most `MAX_LIGHTS`) for all shaders
+    pub unsafe fn set_lights(&mut self, lights: &[LightInstance]) {
+        self.light_instances = lights[..lights.len().min(MAX_LIGHTS)].to_vec();
+        for pbr_shader in self.shaders.values() {
+            self.apply_lights(pbr_shader);
+        }
+    }
+
+    /// Sets the shadow map settings for all shaders (`None`: no shadows)
+    pub unsafe fn set_shadows(&mut self, shadows: Option<ShadowSettings>) {
+        self.shadows = shadows;
+        for pbr_shader in self.shaders.values() {
+            self.apply_shadows(pbr_shader);
+        }
+    }
+
+    /// Passes `light_instances` to `pbr_shader` (unless empty, i.e. `set_lights` wasn't called yet)
+    unsafe fn apply_lights(&self, pbr_shader: &PbrShader) {
+        let lights = &self.light_instances;
+        if lights.is_empty() {
+            return
+        }
+        let types: Vec<i32> = lights.iter().map(|light| light.light_type as i32).collect();
+        let positions: Vec<Vector3> = lights.iter().map(|light| light.position).collect();
+        let directions: Vec<Vector3> = lights.iter().map(|light| light.direction).collect();
+        let colors: Vec<Vector3> = lights.iter().map(|light| light.color).collect();
+        let ranges: Vec<f32> = lights.iter().map(|light| light.range).collect();
+        let cone_scales: Vec<f32> = lights.iter().map(|light| light.cone_scale).collect();
+        let cone_offsets: Vec<f32> = lights.iter().map(|light| light.cone_offset).collect();
+        let count = lights.len() as i32;
+
+        let uniforms = &pbr_shader.uniforms;
+        pbr_shader.shader.use_program();
+        pbr_shader.shader.set_int(uniforms.u_LightCount, count);
+        gl::Uniform1iv(uniforms.u_LightTypes, count, types.as_ptr());
+        gl::Uniform3fv(uniforms.u_LightPositions, count, positions.as_ptr() as *const f32);
+        gl::Uniform3fv(uniforms.u_LightDirections, count, directions.as_ptr() as *const f32);
+        gl::Uniform3fv(uniforms.u_LightColors, count, colors.as_ptr() as *const f32);
+        gl::Uniform1fv(uniforms.u_LightRanges, count, ranges.as_ptr());
+        gl::Uniform1fv(uniforms.u_LightConeScales, count, cone_scales.as_ptr());
+        gl::Uniform1fv(uniforms.u_LightConeOffsets, count, cone_offsets.as_ptr());
+    }
+
+    /// Passes `shadows` to `pbr_shader` (only matters with `ShaderFlags::SHADOWS`)
+    unsafe fn apply_shadows(&self, pbr_shader: &PbrShader) {
+        if !pbr_shader.flags.contains(ShaderFlags::SHADOWS) {
+            return
+        }
+        let uniforms = &pbr_shader.uniforms;
+        pbr_shader.shader.use_program();
+        match self.shadows {
+            Some(ref shadows) => {
+                pbr_shader.shader.set_mat4(uniforms.u_LightSpaceMatrix, &shadows.light_space_matrix);
+                pbr_shader.shader.set_float(uniforms.u_ShadowBias, shadows.bias);
+                pbr_shader.shader.set_int(uniforms.u_ShadowLight, shadows.light_index as i32);
+            },
+            None => pbr_shader.shader.set_int(uniforms.u_ShadowLight, -1),
+        }
+    }
+
+    /// Sets the exposure (linear multiplier) and tone mapping operator for all shaders
+    pub unsafe fn set_tone_mapping(&self, tone_mapping: ToneMapping, exposure: f32) {
+        for pbr_shader in self.shaders.values() {
+            pbr_shader.shader.use_program();
+            pbr_shader.shader.set_float(pbr_shader.uniforms.u_Exposure, exposure);
+            pbr_shader.shader.set_int(pbr_shader.uniforms.u_ToneMapping, tone_mapping as i32);
+        }
+    }
+
+    /// Whether any material uses `KHR_materials_transmission`
+    pub fn has_transmission(&self) -> bool {
+        self.materials.iter().any(|material| material.transmission_factor > 0.0)
+    }
+
     /// Note: index refers to the vec of camera node indices!
     pub fn get_camera_node(&self, index: usize) -> &Node {
         &self.nodes[self.camera_nodes[index]]
@@ -1,15 +1,25 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use gl;
 use gltf;
 
 use collision::{Aabb, Union};
 
 use controls::CameraParams;
-use render::{Root};
+use render::{BoundingBoxRenderer, DrawItem, DrawState, Root, DrawStats, SCENE_BOUNDS_COLOR};
+use render::{LightInstance, MAX_LIGHTS, TransmissionBuffer};
 use render::math::*;
 
 pub struct Scene {
     pub name: Option<String>,
     pub nodes: Vec<usize>,
     pub bounds: Aabb3,
+    /// glTF indices of meshes used by more than one node (candidates for instanced drawing)
+    pub shared_meshes: HashSet<usize>,
+    /// Nodes with a `KHR_lights_punctual` light (at most `MAX_LIGHTS`)
+    pub light_nodes: Vec<usize>,
 }
 
 impl Default for Scene {
@@ -17,7 +27,9 @@ impl Default for Scene {
         Self {
             name: None,
             nodes: vec![],
-            bounds: Aabb3::zero()
+            bounds: Aabb3::zero(),
+            shared_meshes: HashSet::new(),
+            light_nodes: vec![],
         }
     }
 }
@@ -33,23 +45,247 @@ impl Scene {
             .collect();
 
         // propagate transforms
-        let root_transform = Matrix4::identity();
+        scene.update_transforms(root);
         for node_id in &scene.nodes {
             let node = root.unsafe_get_node_mut(*node_id);
-            node.update_transform(root, &root_transform);
             node.update_bounds(root);
-            // TODO!: visualize final bounds
             scene.bounds = scene.bounds.union(&node.bounds);
         }
 
+        let mut mesh_uses = HashMap::new();
+        for node_id in &scene.nodes {
+            Self::count_mesh_uses(root, *node_id, &mut mesh_uses);
+        }
+        scene.shared_meshes = mesh_uses.into_iter()
+            .filter(|&(_, count)| count > 1)
+            .map(|(mesh, _)| mesh)
+            .collect();
+
+        for node_id in &scene.nodes {
+            Self::collect_light_nodes(root, *node_id, &mut scene.light_nodes);
+        }
+        if scene.light_nodes.len() > MAX_LIGHTS {
+            warn!("Scene has {} lights, only the first {} are used", scene.light_nodes.len(), MAX_LIGHTS);
+            scene.light_nodes.truncate(MAX_LIGHTS);
+        }
+
         scene
     }
 
-    // TODO: flatten draw call hierarchy (global Vec<Primitive>?)
-    pub fn draw(&mut self, root: &mut Root, cam_params: &CameraParams) {
+    fn collect_light_nodes(root: &Root, node_id: usize, light_nodes: &mut Vec<usize>) {
+        let node = &root.nodes[node_id];
+        if let Some(light_index) = node.light {
+            if root.lights.iter().any(|light| light.index == light_index) {
+                light_nodes.push(node_id);
+            }
+        }
+        for child_id in &node.children {
+            Self::collect_light_nodes(root, *child_id, light_nodes);
+        }
+    }
+
+    /// The lights of the scene in world space (using the current node transforms)
+    pub fn lights(&self, root: &Root) -> Vec<LightInstance> {
+        self.light_nodes.iter()
+            .filter_map(|&node_id| {
+                let node = &root.nodes[node_id];
+                node.light
+                    .and_then(|light_index| root.lights.iter().find(|light| light.index == light_index))
+                    .map(|light| LightInstance::new(light, &node.final_transform))
+            })
+            .collect()
+    }
+
+    fn count_mesh_uses(root: &Root, node_id: usize, mesh_uses: &mut HashMap<usize, usize>) {
+        let node = &root.nodes[node_id];
+        if let Some(ref mesh) = node.mesh {
+            *mesh_uses.entry(mesh.index).or_insert(0) += 1;
+        }
+        for child_id in &node.children {
+            Self::count_mesh_uses(root, *child_id, mesh_uses);
+        }
+    }
+
+    /// Recalculates the final transforms of all nodes (e.g. after animating them)
+    pub fn update_transforms(&self, root: &mut Root) {
+        let root_transform = Matrix4::identity();
         for node_id in &self.nodes {
             let node = root.unsafe_get_node_mut(*node_id);
-            node.draw(root, cam_params);
+            node.update_transform(root, &root_transform);
+        }
+    }
+
+    pub fn draw(&mut self, root: &mut Root, cam_params: &CameraParams) -> DrawStats {
+        let mut stats = DrawStats::default();
+        root.draw_state = DrawState::default();
+        if let Some(ref environment) = root.environment {
+            unsafe { environment.bind() }
+        }
+        unsafe { self.draw_opaque(root, cam_params, &mut stats) }
+        if !root.picking && !root.shadow_pass {
+            if root.transmission && root.has_transmission() {
+                // what transmissive primitives show through them
+                unsafe { root.transmission_buffer.get_or_insert_with(|| TransmissionBuffer::new()).capture() }
+            }
+            unsafe { self.draw_blended(root, cam_params, &mut stats) }
+        }
+        // set per primitive, but other renderers (grid, bounds...) don't expect it
+        unsafe { gl::Disable(gl::POLYGON_OFFSET_FILL) }
+        stats
+    }
+
+    /// Draws all non-blended primitives, grouped by shader variant and material
+    /// so that consecutive draws can share the program and material setup (see `DrawState`).
+    /// Runs of the same primitive of a shared mesh are drawn with one instanced draw call.
+    unsafe fn draw_opaque(&self, root: &mut Root, cam_params: &CameraParams, stats: &mut DrawStats) {
+        let draws = self.collect_draws(root, cam_params, stats);
+        let view_projection = cam_params.projection_matrix * cam_params.view_matrix;
+        let mut joints_node = None; // node whose joint matrices are currently bound
+        let mut i = 0;
+        while i < draws.len() {
+            let draw = draws[i];
+            let instances = if self.can_instance(root, &draw) {
+                draws[i..].iter()
+                    .take_while(|other| other.mesh == draw.mesh && other.primitive == draw.primitive &&
+                        self.can_instance(root, other))
+                    .count()
+            } else { 1 };
+
+            let node = root.unsafe_get_node_mut(draw.node);
+            if instances > 1 {
+                let model_matrices: Vec<Matrix4> = draws[i..i + instances].iter()
+                    .map(|other| root.nodes[other.node].final_transform)
+                    .collect();
+                let mesh = Rc::clone(node.mesh.as_ref().unwrap());
+                let primitive = &mesh.primitives[draw.primitive];
+                root.current_node = draw.node;
+                primitive.draw_instanced(root, &model_matrices, &view_projection, &cam_params.position);
+                stats.draw_calls += 1;
+                stats.triangles += primitive.num_triangles() * instances as u32;
+            }
+            else {
+                if draw.skinned && joints_node != Some(draw.node) {
+                    root.skins[node.skin.unwrap()].bind_joint_matrices(&root.nodes, &node.final_transform);
+                    joints_node = Some(draw.node);
+                }
+                node.draw_primitive(root, cam_params, &draw, stats);
+            }
+            i += instances;
         }
     }
+
+    /// The non-blended primitives of all meshes inside the view frustum (see `Node::collect_draws`),
+    /// sorted if `root.draw_sorting` is set. Drawn and culled meshes are counted in `stats`.
+    fn collect_draws(&self, root: &Root, cam_params: &CameraParams, stats: &mut DrawStats) -> Vec<DrawItem> {
+        let mut draws = Vec::new();
+        for node_id in &self.nodes {
+            root.nodes[*node_id].collect_draws(root, cam_params, &mut draws, stats);
+        }
+        // stable, so primitives with the same state are still drawn in scene order
+        if root.draw_sorting {
+            draws.sort_by_key(|draw| (draw.shader_flags, draw.material, draw.mesh, draw.primitive));
+        }
+        draws
+    }
+
+    fn can_instance(&self, root: &Root, draw: &DrawItem) -> bool {
+        root.instancing && !root.picking && draw.instanceable && self.shared_meshes.contains(&draw.mesh)
+    }
+
+    /// Draws the primitives with alpha mode BLEND (or transmission) after all others, sorted back to front
+    /// (per node, by the center of the mesh bounds) and without writing depth.
+    unsafe fn draw_blended(&self, root: &mut Root, cam_params: &CameraParams, stats: &mut DrawStats) {
+        let mut nodes = Vec::new();
+        for node_id in &self.nodes {
+            root.nodes[*node_id].collect_blended(root, &cam_params.position, &mut nodes);
+        }
+        if nodes.is_empty() {
+            return
+        }
+        nodes.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+        gl::Enable(gl::BLEND);
+        // separate alpha factors, so the alpha of screenshots with a transparent background stays correct
+        gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+        gl::DepthMask(gl::FALSE);
+        root.blend_pass = true;
+        for &(_, node_id) in &nodes {
+            let node = root.unsafe_get_node_mut(node_id);
+            node.draw_mesh(root, cam_params, stats);
+        }
+        root.blend_pass = false;
+        gl::DepthMask(gl::TRUE);
+        gl::Disable(gl::BLEND);
+    }
+
+    /// Draws the scene bounds and optionally the (world space) bounds of each mesh
+    pub unsafe fn draw_bounds(&self, root: &Root, renderer: &BoundingBoxRenderer,
+            view_projection: &Matrix4, meshes: bool) {
+        renderer.draw(&self.bounds, view_projection, SCENE_BOUNDS_COLOR);
+        if meshes {
+            for node_id in &self.nodes {
+                root.nodes[*node_id].draw_mesh_bounds(root, renderer, view_projection);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use controls::OrbitControls;
+    use render::{Mesh, Node};
+
+    /// Node with an (empty) unit cube mesh at `translation`
+    fn mesh_node(index: usize, translation: Vector3) -> Node {
+        let mesh = Mesh {
+            index,
+            primitives: vec![],
+            name: None,
+            bounds: Aabb3::new(Point3::new(-0.5, -0.5, -0.5), Point3::new(0.5, 0.5, 0.5)),
+        };
+        Node {
+            index,
+            children: vec![],
+            matrix: Matrix4::from_translation(translation),
+            mesh: Some(Rc::new(mesh)),
+            skin: None,
+            rotation: Quaternion::one(),
+            scale: vec3(1.0, 1.0, 1.0),
+            translation,
+            weights: vec![],
+            camera: None,
+            light: None,
+            name: None,
+            final_transform: Matrix4::from_translation(translation),
+            bounds: Aabb3::zero(),
+        }
+    }
+
+    #[test]
+    fn cull_meshes_behind_camera() {
+        let root = Root {
+            nodes: vec![mesh_node(0, vec3(0.0, 0.0, 0.0)), mesh_node(1, vec3(0.0, 0.0, 20.0))],
+            ..Root::default()
+        };
+        // looking from +z at the origin, so the second node is behind the camera
+        let mut controls = OrbitControls::new(Point3::new(0.0, 0.0, 5.0), 800.0, 600.0, 1.0, 1.0, 1.0);
+        controls.camera.update_projection_matrix();
+        let cam_params = controls.camera_params();
+
+        let visible = Scene { nodes: vec![0], ..Scene::default() };
+        let mut stats = DrawStats::default();
+        visible.collect_draws(&root, &cam_params, &mut stats);
+        assert_eq!((stats.drawn_meshes, stats.culled_meshes), (1, 0));
+
+        let behind = Scene { nodes: vec![1], ..Scene::default() };
+        let mut stats = DrawStats::default();
+        behind.collect_draws(&root, &cam_params, &mut stats);
+        assert_eq!((stats.drawn_meshes, stats.culled_meshes), (0, 1));
+
+        let both = Scene { nodes: vec![0, 1], ..Scene::default() };
+        let mut stats = DrawStats::default();
+        both.collect_draws(&root, &cam_params, &mut stats);
+        assert_eq!(stats.meshes_text(), "1 drawn, 1 culled");
+    }
 }
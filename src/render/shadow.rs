@@ -0,0 +1,130 @@
+use std::ptr;
+
+use cgmath::ortho;
+use collision::Aabb;
+
+use gl;
+
+use controls::CameraParams;
+use render::{Root, Scene};
+use render::math::*;
+
+/// Texture unit of the shadow map (see also PbrShader constructor)
+pub const SHADOW_MAP_UNIT: u32 = 10;
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+
+/// Shadow map parameters for the shaders (see `Root::set_shadows`)
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    pub light_space_matrix: Matrix4, // world space -> light clip space
+    pub light_index: usize, // index of the shadow casting light in the lights passed to `Root::set_lights`
+    pub bias: f32, // depth bias against shadow acne (scaled by the slope)
+}
+
+/// Depth texture rendered from a directional light
+pub struct ShadowMap {
+    framebuffer: u32,
+    depth_texture: u32,
+    size: u32,
+}
+
+impl ShadowMap {
+    pub unsafe fn new(size: u32) -> ShadowMap {
+        let mut depth_texture = 0;
+        gl::GenTextures(1, &mut depth_texture);
+        gl::BindTexture(gl::TEXTURE_2D, depth_texture);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::DEPTH_COMPONENT24 as i32, size as i32, size as i32,
+            0, gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null());
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        // everything outside of the light's frustum is lit
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+        let border = [1.0f32; 4];
+        gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, border.as_ptr());
+
+        let mut previous_framebuffer = 0;
+        gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+        let mut framebuffer = 0;
+        gl::GenFramebuffers(1, &mut framebuffer);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, depth_texture, 0);
+        gl::DrawBuffer(gl::NONE);
+        gl::ReadBuffer(gl::NONE);
+        if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+            panic!("ERROR::FRAMEBUFFER:: Shadow map framebuffer is not complete!");
+        }
+        gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as u32);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+
+        ShadowMap { framebuffer, depth_texture, size }
+    }
+
+    /// Renders the depth of the non-blended primitives of `scene` as seen from `light_params`
+    /// (see `light_camera_params`). Restores the framebuffer binding and viewport afterwards.
+    pub unsafe fn render(&self, scene: &mut Scene, root: &mut Root, light_params: &CameraParams) {
+        let mut previous_framebuffer = 0;
+        gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+        let mut viewport = [0; 4];
+        gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        gl::Viewport(0, 0, self.size as i32, self.size as i32);
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        gl::Clear(gl::DEPTH_BUFFER_BIT);
+
+        root.shadow_pass = true;
+        scene.draw(root, light_params);
+        root.shadow_pass = false;
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as u32);
+        gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+    }
+
+    /// Binds the depth texture to `SHADOW_MAP_UNIT`
+    pub unsafe fn bind(&self) {
+        gl::ActiveTexture(gl::TEXTURE0 + SHADOW_MAP_UNIT);
+        gl::BindTexture(gl::TEXTURE_2D, self.depth_texture);
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+/// View and orthographic projection of a directional light (`direction`: towards the light)
+/// that tightly enclose the bounding sphere of `bounds`.
+pub fn light_camera_params(direction: &Vector3, bounds: &Aabb3) -> CameraParams {
+    let direction = direction.normalize();
+    let center = bounds.center();
+    let radius = ((bounds.max - bounds.min).magnitude() / 2.0).max(0.001);
+    let position = center + direction * radius * 2.0;
+    let up = if direction.y.abs() > 0.99 { Vector3::unit_z() } else { Vector3::unit_y() };
+    let view_matrix = Matrix4::look_at(position, center, up);
+    let projection_matrix = ortho(-radius, radius, -radius, radius, radius, radius * 3.0);
+    CameraParams {
+        position: position.to_vec(),
+        view_matrix,
+        projection_matrix,
+        frustum: Frustum::from_matrix(&(projection_matrix * view_matrix)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_frustum_encloses_bounds() {
+        let bounds = Aabb3::new(Point3::new(-1.0, 0.0, -2.0), Point3::new(3.0, 2.0, 1.0));
+        for direction in &[vec3(0.0, 1.0, 0.0), vec3(1.0, 1.0, 1.0), vec3(-0.3, 0.2, -1.0)] {
+            let params = light_camera_params(direction, &bounds);
+            let light_space = params.projection_matrix * params.view_matrix;
+            let center = light_space * bounds.center().to_homogeneous();
+            assert!(center.x.abs() < 1e-5 && center.y.abs() < 1e-5 && center.z.abs() < 1e-5);
+            // the corners are on the bounding sphere, so they can touch the frustum
+            let inside = |v: f32| v.abs() <= 1.0 + 1e-5;
+            for corner in &bounds.to_corners() {
+                let p = light_space * corner.to_homogeneous();
+                assert!(inside(p.x) && inside(p.y) && inside(p.z), "{:?}", p);
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::mem::size_of;
+use std::os::raw::c_void;
+use std::ptr;
+
+use gl;
+
+use render::math::*;
+use render::{ShadowSettings, SHADOW_MAP_UNIT};
+use shader::Shader;
+
+/// Invisible ground plane below the scene that only shows the shadows it receives, e.g. for
+/// product shots composited over any background. Needs the shadow map (see `ShadowMap`).
+pub struct ShadowCatcher {
+    shader: Shader,
+    u_view_projection: i32,
+    u_center: i32,
+    u_extent: i32,
+    u_light_space_matrix: i32,
+    u_shadow_bias: i32,
+    u_opacity: i32,
+    vao: u32,
+
+    pub center: Point3,
+    pub extent: f32, // half the side length
+    pub opacity: f32, // of full shadow
+}
+
+impl ShadowCatcher {
+    /// NOTE: Needs a current GL context
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> ShadowCatcher {
+        // the plane is placed like the grid
+        let mut shader = Shader::from_source(
+            include_str!("../shaders/grid-vert.glsl"),
+            include_str!("../shaders/shadow-catcher-frag.glsl"),
+            &[]);
+
+        unsafe {
+            let u_view_projection = shader.uniform_location("u_ViewProjection");
+            let u_center = shader.uniform_location("u_Center");
+            let u_extent = shader.uniform_location("u_Extent");
+            let u_light_space_matrix = shader.uniform_location("u_LightSpaceMatrix");
+            let u_shadow_bias = shader.uniform_location("u_ShadowBias");
+            let u_opacity = shader.uniform_location("u_Opacity");
+            let u_shadow_map = shader.uniform_location("u_ShadowMap");
+            shader.use_program();
+            shader.set_int(u_shadow_map, SHADOW_MAP_UNIT as i32);
+
+            let vertices: [f32; 12] = [
+                -1.0, -1.0,   1.0, -1.0,   1.0, 1.0,
+                -1.0, -1.0,   1.0, 1.0,   -1.0, 1.0,
+            ];
+            let (mut vao, mut vbo) = (0, 0);
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(gl::ARRAY_BUFFER, (vertices.len() * size_of::<f32>()) as isize,
+                vertices.as_ptr() as *const c_void, gl::STATIC_DRAW);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 2 * size_of::<f32>() as i32, ptr::null());
+            gl::BindVertexArray(0);
+
+            ShadowCatcher {
+                shader,
+                u_view_projection,
+                u_center,
+                u_extent,
+                u_light_space_matrix,
+                u_shadow_bias,
+                u_opacity,
+                vao,
+                center: Point3::new(0.0, 0.0, 0.0),
+                extent: 1.0,
+                opacity: 0.6,
+            }
+        }
+    }
+
+    /// Places the plane at the bottom of the given bounds, large enough for long shadows
+    pub fn fit_to_bounds(&mut self, bounds: &Aabb3) {
+        self.center = Point3::new(
+            (bounds.min.x + bounds.max.x) / 2.0,
+            bounds.min.y,
+            (bounds.min.z + bounds.max.z) / 2.0);
+        self.extent = (bounds.max - bounds.min).magnitude().max(1e-6) * 1.5;
+    }
+
+    /// Blends the shadow over the current frame, with depth testing (but without writing depth).
+    /// The alpha of the frame accumulates, so the shadow stays visible on a transparent background.
+    pub unsafe fn draw(&self, view_projection: &Matrix4, shadows: &ShadowSettings) {
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        gl::Disable(gl::CULL_FACE);
+        gl::Enable(gl::BLEND);
+        gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+        gl::DepthMask(gl::FALSE);
+
+        self.shader.use_program();
+        self.shader.set_mat4(self.u_view_projection, view_projection);
+        self.shader.set_vec3(self.u_center, self.center.x, self.center.y, self.center.z);
+        self.shader.set_float(self.u_extent, self.extent);
+        self.shader.set_mat4(self.u_light_space_matrix, &shadows.light_space_matrix);
+        self.shader.set_float(self.u_shadow_bias, shadows.bias);
+        self.shader.set_float(self.u_opacity, self.opacity);
+        gl::BindVertexArray(self.vao);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+        gl::BindVertexArray(0);
+
+        gl::DepthMask(gl::TRUE);
+        gl::Disable(gl::BLEND);
+    }
+}
@@ -0,0 +1,81 @@
+use std::os::raw::c_void;
+
+use gl;
+use gltf;
+use gltf_importer;
+use gltf_utils::AccessorIter;
+
+use render::math::*;
+use render::Node;
+
+/// Texture unit of the joint matrices (see also the other units in environment.rs)
+pub const JOINT_MATRICES_UNIT: u32 = 8;
+
+/// Joint matrices are stored in a float texture instead of a uniform array, so the number
+/// of joints isn't limited by the number of uniform components: one row per joint,
+/// 4 RGBA texels (= columns) per matrix.
+pub struct Skin {
+    pub index: usize, // glTF index
+    pub name: Option<String>,
+    pub joints: Vec<usize>, // node indices
+    pub inverse_bind_matrices: Vec<Matrix4>,
+
+    texture: u32,
+}
+
+impl Skin {
+    /// NOTE: Needs a current GL context
+    pub fn from_gltf(g_skin: &gltf::Skin, buffers: &gltf_importer::Buffers) -> Skin {
+        let joints: Vec<usize> = g_skin.joints().map(|g_node| g_node.index()).collect();
+        // spec: "When undefined, each matrix is a 4x4 identity matrix."
+        let mut inverse_bind_matrices: Vec<Matrix4> = match g_skin.inverse_bind_matrices() {
+            Some(accessor) => AccessorIter::<[[f32; 4]; 4]>::new(accessor, buffers)
+                .map(Matrix4::from)
+                .collect(),
+            None => vec![Matrix4::identity(); joints.len()],
+        };
+        if inverse_bind_matrices.len() != joints.len() {
+            warn!("Number of inverse bind matrices ({}) doesn't match the number of joints ({}) (skin: {}), \
+                using identity matrices for the missing ones",
+                inverse_bind_matrices.len(), joints.len(), g_skin.index());
+            // the shader indexes every joint, so there must be one matrix for each
+            inverse_bind_matrices.resize(joints.len(), Matrix4::identity());
+        }
+
+        let mut texture = 0;
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        }
+
+        Skin {
+            index: g_skin.index(),
+            name: g_skin.name().map(|s| s.into()),
+            joints,
+            inverse_bind_matrices,
+            texture,
+        }
+    }
+
+    /// Computes the joint matrices relative to the skinned mesh node (so the usual model matrix
+    /// can still be applied in the shader), uploads and binds them.
+    pub unsafe fn bind_joint_matrices(&self, nodes: &[Node], mesh_transform: &Matrix4) {
+        let inverse_mesh_transform = mesh_transform.invert().unwrap_or_else(Matrix4::identity);
+        let joint_matrices: Vec<Matrix4> = self.joints.iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(&joint, inverse_bind_matrix)|
+                inverse_mesh_transform * nodes[joint].final_transform * *inverse_bind_matrix)
+            .collect();
+        if joint_matrices.is_empty() {
+            return
+        }
+
+        gl::ActiveTexture(gl::TEXTURE0 + JOINT_MATRICES_UNIT);
+        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA32F as i32, 4, joint_matrices.len() as i32,
+            0, gl::RGBA, gl::FLOAT, joint_matrices.as_ptr() as *const c_void);
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
@@ -0,0 +1,152 @@
+use std::ptr;
+
+use gl;
+
+use framebuffer::Framebuffer;
+use render::math::*;
+use shader::Shader;
+
+/// Screen space ambient occlusion as a post-process: the scene is rendered into an offscreen
+/// framebuffer (between `begin` and `apply`), then the occlusion is computed from its depth
+/// and composited into the previously bound framebuffer.
+pub struct Ssao {
+    scene_framebuffer: Framebuffer, // color + depth of the scene
+    occlusion_framebuffer: u32,
+    occlusion_texture: u32, // single channel, 1: not occluded
+    target_framebuffer: u32, // bound before `begin`
+
+    ssao_shader: Shader,
+    u_projection: i32,
+    u_inverse_projection: i32,
+    u_radius: i32,
+    composite_shader: Shader,
+    u_intensity: i32,
+    vao: u32, // empty (full screen triangle without vertex attributes)
+}
+
+impl Ssao {
+    /// NOTE: Needs a current GL context. Keeps the framebuffer binding (`begin` captures it as target).
+    pub fn new(width: u32, height: u32) -> Ssao {
+        let mut previous_framebuffer = 0;
+        unsafe { gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer); }
+        let scene_framebuffer = Framebuffer::new(width, height, 0);
+        let mut ssao_shader = Shader::from_source(
+            include_str!("../shaders/fullscreen-vert.glsl"),
+            include_str!("../shaders/ssao-frag.glsl"),
+            &[]);
+        let mut composite_shader = Shader::from_source(
+            include_str!("../shaders/fullscreen-vert.glsl"),
+            include_str!("../shaders/ssao-composite-frag.glsl"),
+            &[]);
+
+        unsafe {
+            let mut occlusion_texture = 0;
+            gl::GenTextures(1, &mut occlusion_texture);
+            gl::BindTexture(gl::TEXTURE_2D, occlusion_texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::R8 as i32, width as i32, height as i32,
+                0, gl::RED, gl::UNSIGNED_BYTE, ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            let mut occlusion_framebuffer = 0;
+            gl::GenFramebuffers(1, &mut occlusion_framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, occlusion_framebuffer);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, occlusion_texture, 0);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                panic!("ERROR::FRAMEBUFFER:: SSAO framebuffer is not complete!");
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as u32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            let u_projection = ssao_shader.uniform_location("u_Projection");
+            let u_inverse_projection = ssao_shader.uniform_location("u_InverseProjection");
+            let u_radius = ssao_shader.uniform_location("u_Radius");
+            let u_depth_sampler = ssao_shader.uniform_location("u_DepthSampler");
+            ssao_shader.use_program();
+            ssao_shader.set_int(u_depth_sampler, 1);
+
+            let u_intensity = composite_shader.uniform_location("u_Intensity");
+            let u_color_sampler = composite_shader.uniform_location("u_ColorSampler");
+            let u_depth_sampler = composite_shader.uniform_location("u_DepthSampler");
+            let u_occlusion_sampler = composite_shader.uniform_location("u_OcclusionSampler");
+            composite_shader.use_program();
+            composite_shader.set_int(u_color_sampler, 0);
+            composite_shader.set_int(u_depth_sampler, 1);
+            composite_shader.set_int(u_occlusion_sampler, 2);
+
+            let mut vao = 0;
+            gl::GenVertexArrays(1, &mut vao);
+
+            Ssao {
+                scene_framebuffer,
+                occlusion_framebuffer,
+                occlusion_texture,
+                target_framebuffer: 0,
+                ssao_shader,
+                u_projection,
+                u_inverse_projection,
+                u_radius,
+                composite_shader,
+                u_intensity,
+                vao,
+            }
+        }
+    }
+
+    /// Redirects rendering into the offscreen scene framebuffer, resizing it to the current viewport.
+    pub unsafe fn begin(&mut self) {
+        let mut target_framebuffer = 0;
+        gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut target_framebuffer);
+        self.target_framebuffer = target_framebuffer as u32;
+
+        let mut viewport = [0; 4];
+        gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        let size = (viewport[2] as u32, viewport[3] as u32);
+        if self.scene_framebuffer.size() != size {
+            self.scene_framebuffer.resize(size.0, size.1);
+            gl::BindTexture(gl::TEXTURE_2D, self.occlusion_texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::R8 as i32, size.0 as i32, size.1 as i32,
+                0, gl::RED, gl::UNSIGNED_BYTE, ptr::null());
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        self.scene_framebuffer.bind();
+    }
+
+    /// Computes the occlusion of the scene rendered since `begin` and draws the darkened scene
+    /// (including its depth) into the framebuffer bound before. `radius` is in view space units,
+    /// `intensity` in [0, 1].
+    pub unsafe fn apply(&self, projection: &Matrix4, radius: f32, intensity: f32) {
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        gl::Disable(gl::BLEND);
+        gl::Disable(gl::CULL_FACE);
+        gl::BindVertexArray(self.vao);
+        gl::ActiveTexture(gl::TEXTURE1);
+        gl::BindTexture(gl::TEXTURE_2D, self.scene_framebuffer.depth_texture);
+
+        // occlusion pass
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.occlusion_framebuffer);
+        gl::Disable(gl::DEPTH_TEST);
+        self.ssao_shader.use_program();
+        self.ssao_shader.set_mat4(self.u_projection, projection);
+        self.ssao_shader.set_mat4(self.u_inverse_projection, &projection.invert().unwrap_or_else(Matrix4::identity));
+        self.ssao_shader.set_float(self.u_radius, radius);
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+
+        // composite (writes depth as well, so that e.g. the grid is still occluded by the scene)
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.target_framebuffer);
+        gl::Enable(gl::DEPTH_TEST);
+        gl::DepthFunc(gl::ALWAYS);
+        self.composite_shader.use_program();
+        self.composite_shader.set_float(self.u_intensity, intensity);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, self.scene_framebuffer.color_texture);
+        gl::ActiveTexture(gl::TEXTURE2);
+        gl::BindTexture(gl::TEXTURE_2D, self.occlusion_texture);
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+
+        gl::DepthFunc(gl::LESS);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindVertexArray(0);
+    }
+}
@@ -0,0 +1,207 @@
+use std::mem::size_of;
+use std::os::raw::c_void;
+use std::ptr;
+
+use gl;
+
+use shader::Shader;
+
+/// Characters contained in `GLYPHS` (in that order). Lowercase letters are drawn as uppercase,
+/// other unknown characters as space.
+const CHARACTERS: &str = " %-./0123456789:ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+/// 5x7 pixel bitmap font: one byte per row (top to bottom), bit 4 is the leftmost pixel.
+const GLYPHS: [[u8; GLYPH_HEIGHT]; 42] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+];
+
+/// Minimal screen-space text rendering with a built-in bitmap font (for debug overlays).
+pub struct TextRenderer {
+    shader: Shader,
+    u_screen_size: i32,
+    u_color: i32,
+    texture: u32,
+    vao: u32,
+    vbo: u32,
+    /// Size of a font pixel in screen pixels
+    pub scale: f32,
+}
+
+impl TextRenderer {
+    /// NOTE: Needs a current GL context
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> TextRenderer {
+        let mut shader = Shader::from_source(
+            include_str!("../shaders/text-vert.glsl"),
+            include_str!("../shaders/text-frag.glsl"),
+            &[]);
+
+        let mut renderer = unsafe {
+            let u_screen_size = shader.uniform_location("u_ScreenSize");
+            let u_color = shader.uniform_location("u_Color");
+            let u_font_sampler = shader.uniform_location("u_FontSampler");
+            shader.use_program();
+            shader.set_int(u_font_sampler, 0);
+
+            TextRenderer {
+                shader,
+                u_screen_size,
+                u_color,
+                texture: 0,
+                vao: 0,
+                vbo: 0,
+                scale: 2.0,
+            }
+        };
+        unsafe { renderer.setup() };
+        renderer
+    }
+
+    unsafe fn setup(&mut self) {
+        // font texture: all glyphs in a row
+        let width = GLYPHS.len() * GLYPH_WIDTH;
+        let mut pixels = vec![0u8; width * GLYPH_HEIGHT];
+        for (i, glyph) in GLYPHS.iter().enumerate() {
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (0x10 >> x) != 0 {
+                        pixels[y * width + i * GLYPH_WIDTH + x] = 255;
+                    }
+                }
+            }
+        }
+        gl::GenTextures(1, &mut self.texture);
+        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::R8 as i32, width as i32, GLYPH_HEIGHT as i32,
+            0, gl::RED, gl::UNSIGNED_BYTE, pixels.as_ptr() as *const c_void);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+
+        // vertices: (x, y, u, v), re-uploaded for every draw
+        gl::GenVertexArrays(1, &mut self.vao);
+        gl::GenBuffers(1, &mut self.vbo);
+        gl::BindVertexArray(self.vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        let stride = 4 * size_of::<f32>() as i32;
+        gl::EnableVertexAttribArray(0);
+        gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, ptr::null());
+        gl::EnableVertexAttribArray(1);
+        gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, stride, (2 * size_of::<f32>()) as *const c_void);
+        gl::BindVertexArray(0);
+    }
+
+    /// Draws (multi-line) white text with a drop shadow. `x`/`y` are the pixel coordinates
+    /// of the top left corner, with the origin at the top left of the screen.
+    pub unsafe fn draw(&mut self, text: &str, x: f32, y: f32, screen_width: u32, screen_height: u32) {
+        let vertices = self.vertices(text, x, y);
+        if vertices.is_empty() {
+            return
+        }
+
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::CULL_FACE);
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+
+        self.shader.use_program();
+        self.shader.set_vec2(self.u_screen_size, screen_width as f32, screen_height as f32);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        gl::BindVertexArray(self.vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+
+        let count = (vertices.len() / 4) as i32;
+        let shadow: Vec<f32> = vertices.chunks(4)
+            .flat_map(|v| vec![v[0] + self.scale, v[1] + self.scale, v[2], v[3]])
+            .collect();
+        for &(data, color) in &[(&shadow, [0.0, 0.0, 0.0, 1.0]), (&vertices, [1.0, 1.0, 1.0, 1.0])] {
+            gl::BufferData(gl::ARRAY_BUFFER, (data.len() * size_of::<f32>()) as isize,
+                data.as_ptr() as *const c_void, gl::STREAM_DRAW);
+            gl::Uniform4f(self.u_color, color[0], color[1], color[2], color[3]);
+            gl::DrawArrays(gl::TRIANGLES, 0, count);
+        }
+
+        gl::BindVertexArray(0);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+
+    /// Two triangles per character
+    fn vertices(&self, text: &str, x: f32, y: f32) -> Vec<f32> {
+        let glyph_w = GLYPH_WIDTH as f32 * self.scale;
+        let glyph_h = GLYPH_HEIGHT as f32 * self.scale;
+        let advance = glyph_w + self.scale;
+        let line_height = glyph_h + 2.0 * self.scale;
+        let u_step = 1.0 / GLYPHS.len() as f32;
+
+        let mut vertices = Vec::with_capacity(text.len() * 6 * 4);
+        for (line_index, line) in text.lines().enumerate() {
+            let top = y + line_index as f32 * line_height;
+            for (i, c) in line.chars().enumerate() {
+                let glyph = CHARACTERS.find(c.to_ascii_uppercase()).unwrap_or(0);
+                if glyph == 0 {
+                    continue // space
+                }
+                let left = x + i as f32 * advance;
+                let (right, bottom) = (left + glyph_w, top + glyph_h);
+                let u0 = glyph as f32 * u_step;
+                let u1 = u0 + u_step;
+                vertices.extend_from_slice(&[
+                    left, top, u0, 0.0,
+                    left, bottom, u0, 1.0,
+                    right, bottom, u1, 1.0,
+                    left, top, u0, 0.0,
+                    right, bottom, u1, 1.0,
+                    right, top, u1, 0.0,
+                ]);
+            }
+        }
+        vertices
+    }
+}
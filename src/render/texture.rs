@@ -1,10 +1,11 @@
+use std::io::Read;
 use std::os::raw::c_void;
 use std::path::Path;
 use std::{fs, io};
 
 use gl;
 use gltf;
-use gltf::json::texture::MinFilter;
+use gltf::json::texture::{MagFilter, MinFilter, WrappingMode};
 use gltf::image::Data;
 use gltf_importer;
 
@@ -14,51 +15,93 @@ use image::DynamicImage::*;
 use image::GenericImage;
 use image::FilterType;
 
+use render::{compressed_format_supported, Ktx2, Ktx2Format};
+use utils::percent_decode;
+
+// EXT_texture_filter_anisotropic (core since OpenGL 4.6, not in the generated bindings)
+const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+/// Upper limit for anisotropic filtering of mip mapped textures
+const MAX_ANISOTROPY: f32 = 16.0;
+
 pub struct Texture {
     pub index: usize, // glTF index
     pub name: Option<String>,
 
     pub id: u32, // OpenGL id
     pub tex_coord: u32, // the tex coord set to use
+    pub srgb: bool, // color (base color, emissive) instead of data texture
+
+    // of the source image (the texture may be resized to a power of two)
+    pub width: u32,
+    pub height: u32,
 }
 
 impl Texture {
-    pub fn from_gltf(g_texture: &gltf::Texture, tex_coord: u32, buffers: &gltf_importer::Buffers, base_path: &Path) -> Texture {
+    /// With `srgb`, the texture is decoded to linear when sampling (for color textures).
+    /// `gl_extensions`: those of the current context (see `Root::gl_extensions`)
+    /// `basisu_image`: index and KTX2 data of the `KHR_texture_basisu` source, preferred over `source`
+    /// (which is used if it can't be transcoded)
+    pub fn from_gltf(g_texture: &gltf::Texture, tex_coord: u32, srgb: bool, gl_extensions: &[String],
+            basisu_image: Option<&(usize, Vec<u8>)>, buffers: &gltf_importer::Buffers, base_path: &Path) -> Texture {
         let mut texture_id = 0;
         unsafe {
             gl::GenTextures(1, &mut texture_id);
             gl::BindTexture(gl::TEXTURE_2D, texture_id);
         }
         let (needs_power_of_two, generate_mip_maps) =
-            unsafe { Self::set_sampler_params(&g_texture.sampler()) };
+            unsafe { Self::set_sampler_params(&g_texture.sampler(), gl_extensions) };
 
         // TODO!: share images via Rc? detect if occurs?
         // TODO!!: better I/O abstraction...
         let g_img = g_texture.source();
+        let ktx2_texture = |(width, height)| Texture {
+            index: g_texture.index(),
+            name: g_texture.name().map(|s| s.into()),
+            id: texture_id,
+            tex_coord,
+            srgb,
+            width,
+            height,
+        };
+        if let Some(&(image_index, ref data)) = basisu_image {
+            if let Some(size) = unsafe { Self::upload_ktx2(data, srgb, generate_mip_maps, image_index) } {
+                return ktx2_texture(size)
+            }
+            warn!("Using the fallback source (image {}) of texture {}", g_img.index(), g_texture.index());
+        }
+        if let Some(data) = Self::ktx2_data(&g_img, buffers, base_path) {
+            let size = unsafe { Self::upload_ktx2(&data, srgb, generate_mip_maps, g_img.index()) }
+                .unwrap_or_else(|| {
+                    warn!("Using a white placeholder for image {}", g_img.index());
+                    unsafe { Self::upload_placeholder() }
+                });
+            return ktx2_texture(size)
+        }
         let img = match g_img.data() {
             Data::View { view, mime_type } => {
                 let data = buffers.view(&view).expect("Failed to get buffer view for image");
                 match mime_type {
                     "image/jpeg" => image::load_from_memory_with_format(data, JPEG),
                     "image/png" => image::load_from_memory_with_format(data, PNG),
-                    _ => panic!(format!("unsupported image type (image: {}, mime_type: {})",
-                        g_img.index(), mime_type)),
+                    _ => panic!("unsupported image type (image: {}, mime_type: {})",
+                        g_img.index(), mime_type),
                 }
             },
             Data::Uri { uri, mime_type } => {
                 if let Some(mime_type) = mime_type {
-                    let path = base_path.parent().unwrap_or_else(|| Path::new("./")).join(uri);
+                    let path = base_path.parent().unwrap_or_else(|| Path::new("./")).join(percent_decode(uri));
                     let file = fs::File::open(path).unwrap();
                     let reader = io::BufReader::new(file);
                     match mime_type {
                         "image/jpeg" => image::load(reader, JPEG),
                         "image/png" => image::load(reader, PNG),
-                        _ => panic!(format!("unsupported image type (image: {}, mime_type: {})",
-                            g_img.index(), mime_type)),
+                        _ => panic!("unsupported image type (image: {}, mime_type: {})",
+                            g_img.index(), mime_type),
                     }
                 }
                 else {
-                    let path = base_path.parent().unwrap_or_else(||Path::new("./")).join(uri);
+                    let path = base_path.parent().unwrap_or_else(||Path::new("./")).join(percent_decode(uri));
                     image::open(path)
                 }
             }
@@ -73,6 +116,12 @@ impl Texture {
             ImageRgb8(_) => gl::RGB,
             ImageRgba8(_) => gl::RGBA,
         };
+        // NOTE: there are no sRGB formats with less than 3 channels
+        let internal_format = match format {
+            gl::RGB if srgb => gl::SRGB8,
+            gl::RGBA if srgb => gl::SRGB8_ALPHA8,
+            _ => format,
+        };
 
         // **Non-Power-Of-Two Texture Implementation Note**: glTF does not guarantee that a texture's
         // dimensions are a power-of-two.  At runtime, if a texture's width or height is not a
@@ -81,7 +130,8 @@ impl Texture {
         // * Has a wrapping mode (either `wrapS` or `wrapT`) equal to `REPEAT` or `MIRRORED_REPEAT`, or
         // * Has a minification filter (`minFilter`) that uses mipmapping (`NEAREST_MIPMAP_NEAREST`, \\
         //   `NEAREST_MIPMAP_LINEAR`, `LINEAR_MIPMAP_NEAREST`, or `LINEAR_MIPMAP_LINEAR`).
-        let (width, height) = dyn_img.dimensions();
+        let (image_width, image_height) = dyn_img.dimensions();
+        let (width, height) = (image_width, image_height);
         let (data, width, height) =
             if needs_power_of_two && (!width.is_power_of_two() || !height.is_power_of_two()) {
                 let nwidth = width.next_power_of_two();
@@ -94,7 +144,7 @@ impl Texture {
             };
 
         unsafe {
-            gl::TexImage2D(gl::TEXTURE_2D, 0, format as i32, width as i32, height as i32,
+            gl::TexImage2D(gl::TEXTURE_2D, 0, internal_format as i32, width as i32, height as i32,
                 0, format, gl::UNSIGNED_BYTE, &data[0] as *const u8 as *const c_void);
 
             if generate_mip_maps {
@@ -105,50 +155,226 @@ impl Texture {
             index: g_texture.index(),
             name: g_texture.name().map(|s| s.into()),
             id: texture_id,
-            tex_coord: tex_coord,
+            tex_coord,
+            srgb,
+            width: image_width,
+            height: image_height,
+        }
+    }
+
+    /// The file content of KTX2 images (by MIME type or file extension)
+    pub fn ktx2_data(g_img: &gltf::image::Image, buffers: &gltf_importer::Buffers, base_path: &Path) -> Option<Vec<u8>> {
+        match g_img.data() {
+            Data::View { ref view, mime_type } if mime_type == "image/ktx2" =>
+                Some(buffers.view(view).expect("Failed to get buffer view for image").to_vec()),
+            Data::Uri { uri, mime_type } if mime_type == Some("image/ktx2") || uri.ends_with(".ktx2") => {
+                let path = base_path.parent().unwrap_or_else(|| Path::new("./")).join(percent_decode(uri));
+                let mut data = Vec::new();
+                fs::File::open(path).and_then(|mut file| file.read_to_end(&mut data)).unwrap();
+                Some(data)
+            },
+            _ => None,
         }
     }
 
+    /// Uploads all levels of a KTX2 image (Basis Universal UASTC is transcoded to BC7 or RGBA8).
+    /// Returns the image size, or None if the format isn't supported or the file is invalid
+    /// (the caller uploads a fallback).
+    unsafe fn upload_ktx2(data: &[u8], srgb: bool, generate_mip_maps: bool, image_index: usize) -> Option<(u32, u32)> {
+        let parsed = Ktx2::parse(data)
+            .and_then(|ktx2| ktx2.gl_levels(data, srgb).map(|(format, levels)| (ktx2, format, levels)));
+        let (ktx2, format, levels) = match parsed {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                error!("Failed to load KTX2 image {}: {}", image_index, err);
+                return None
+            }
+        };
+        if let Ktx2Format::Compressed { internal_format } = format {
+            if !compressed_format_supported(internal_format) {
+                warn!("Compressed format 0x{:X} of KTX2 image {} not supported by the OpenGL context",
+                    internal_format, image_index);
+                return None
+            }
+        }
+
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1); // rows aren't padded
+        for (level, level_data) in levels.iter().enumerate() {
+            let (width, height) = ktx2.level_size(level);
+            let (pixels, length) = (level_data.as_ptr() as *const c_void, level_data.len());
+            match format {
+                Ktx2Format::Uncompressed { internal_format, format } =>
+                    gl::TexImage2D(gl::TEXTURE_2D, level as i32, internal_format as i32, width as i32,
+                        height as i32, 0, format, gl::UNSIGNED_BYTE, pixels),
+                Ktx2Format::Compressed { internal_format } =>
+                    gl::CompressedTexImage2D(gl::TEXTURE_2D, level as i32, internal_format, width as i32,
+                        height as i32, 0, length as i32, pixels),
+            }
+        }
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+        // mip maps can only be generated for uncompressed textures
+        let is_compressed = matches!(format, Ktx2Format::Compressed { .. });
+        if generate_mip_maps && ktx2.levels.len() == 1 && !is_compressed {
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        } else {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, ktx2.levels.len() as i32 - 1);
+        }
+        Some((ktx2.width, ktx2.height))
+    }
+
+    unsafe fn upload_placeholder() -> (u32, u32) {
+        let white = [255u8; 4];
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as i32, 1, 1, 0, gl::RGBA, gl::UNSIGNED_BYTE,
+            white.as_ptr() as *const c_void);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+        (1, 1)
+    }
+
     // Returns whether image needs to be Power-Of-Two-sized and whether mip maps should be generated
-    // TODO: refactor return type into enum?
-    unsafe fn set_sampler_params(sampler: &gltf::texture::Sampler) -> (bool, bool) {
+    unsafe fn set_sampler_params(sampler: &gltf::texture::Sampler, gl_extensions: &[String]) -> (bool, bool) {
+        let params = SamplerParams::from_gltf(sampler);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, params.min_filter);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, params.mag_filter);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, params.wrap_s);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, params.wrap_t);
+
+        // reduces blurring of surfaces at grazing angles (e.g. floors receding into the distance)
+        if params.mip_maps && gl_extensions.iter().any(|ext| ext == "GL_EXT_texture_filter_anisotropic") {
+            let mut max_anisotropy = 0.0;
+            gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+            gl::TexParameterf(gl::TEXTURE_2D, TEXTURE_MAX_ANISOTROPY, max_anisotropy.min(MAX_ANISOTROPY));
+        }
+
+        (params.needs_power_of_two(), params.mip_maps)
+    }
+}
+
+/// GL texture parameters for a glTF sampler (`TEXTURE_MIN_FILTER` etc.)
+#[derive(Debug, PartialEq)]
+struct SamplerParams {
+    min_filter: i32,
+    mag_filter: i32,
+    wrap_s: i32,
+    wrap_t: i32,
+    mip_maps: bool,
+}
+
+impl SamplerParams {
+    fn from_gltf(sampler: &gltf::texture::Sampler) -> SamplerParams {
+        Self::new(sampler.min_filter(), sampler.mag_filter(), sampler.wrap_s(), sampler.wrap_t())
+    }
+
+    fn new(min_filter: Option<MinFilter>, mag_filter: Option<MagFilter>, wrap_s: WrappingMode,
+            wrap_t: WrappingMode) -> SamplerParams {
         // **Mipmapping Implementation Note**: When a sampler's minification filter (`minFilter`)
         // uses mipmapping (`NEAREST_MIPMAP_NEAREST`, `NEAREST_MIPMAP_LINEAR`, `LINEAR_MIPMAP_NEAREST`,
         // or `LINEAR_MIPMAP_LINEAR`), any texture referencing the sampler needs to have mipmaps,
         // e.g., by calling GL's `generateMipmap()` function.
-        let mip_maps = match sampler.min_filter() {
+        let mip_maps = matches!(min_filter,
             Some(MinFilter::NearestMipmapNearest) |
             Some(MinFilter::LinearMipmapNearest) |
             Some(MinFilter::NearestMipmapLinear) |
             Some(MinFilter::LinearMipmapLinear) |
-            None => true, // see below
-            _ => false
-        };
+            None); // see below
 
         // **Default Filtering Implementation Note:** When filtering options are defined,
         // runtime must use them. Otherwise, it is free to adapt filtering to performance or quality goals.
-        if let Some(min_filter) = sampler.min_filter() {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter.as_gl_enum());
-        }
-        else {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
-        }
-        if let Some(mag_filter) = sampler.mag_filter() {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter.as_gl_enum());
-        }
-        else {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        // (the wrap modes default to REPEAT in the gltf crate, as in the spec)
+        SamplerParams {
+            min_filter: min_filter.map_or(gl::LINEAR_MIPMAP_LINEAR as i32, |filter| filter.as_gl_enum()),
+            mag_filter: mag_filter.map_or(gl::LINEAR as i32, |filter| filter.as_gl_enum()),
+            wrap_s: wrap_s.as_gl_enum(),
+            wrap_t: wrap_t.as_gl_enum(),
+            mip_maps,
         }
+    }
 
-        let wrap_s = sampler.wrap_s().as_gl_enum();
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap_s);
-        let wrap_t = sampler.wrap_t().as_gl_enum();
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap_t);
+    fn needs_power_of_two(&self) -> bool {
+        self.wrap_s != gl::CLAMP_TO_EDGE as i32 ||
+            self.wrap_t != gl::CLAMP_TO_EDGE as i32 ||
+            self.mip_maps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unspecified_filters() {
+        let params = SamplerParams::new(None, None, WrappingMode::Repeat, WrappingMode::Repeat);
+        assert_eq!(params, SamplerParams {
+            min_filter: gl::LINEAR_MIPMAP_LINEAR as i32,
+            mag_filter: gl::LINEAR as i32,
+            wrap_s: gl::REPEAT as i32,
+            wrap_t: gl::REPEAT as i32,
+            mip_maps: true,
+        });
+    }
+
+    #[test]
+    fn explicit_sampler_params() {
+        let params = SamplerParams::new(Some(MinFilter::Nearest), Some(MagFilter::Nearest),
+            WrappingMode::MirroredRepeat, WrappingMode::ClampToEdge);
+        assert_eq!(params, SamplerParams {
+            min_filter: gl::NEAREST as i32,
+            mag_filter: gl::NEAREST as i32,
+            wrap_s: gl::MIRRORED_REPEAT as i32,
+            wrap_t: gl::CLAMP_TO_EDGE as i32,
+            mip_maps: false,
+        });
+        assert!(params.needs_power_of_two());
+
+        let clamped = SamplerParams::new(Some(MinFilter::Linear), None,
+            WrappingMode::ClampToEdge, WrappingMode::ClampToEdge);
+        assert!(!clamped.needs_power_of_two());
+    }
 
-        let needs_power_of_two =
-            wrap_s != gl::CLAMP_TO_EDGE as i32 ||
-            wrap_t != gl::CLAMP_TO_EDGE as i32 ||
-            mip_maps;
-        (needs_power_of_two, mip_maps)
+    #[test]
+    fn repeat_sampler_of_tiled_quad() {
+        use gltf_utils::PrimitiveIterators;
+
+        let (gltf, buffers) = gltf_importer::import("src/data/TiledQuad.gltf")
+            .expect("failed to import TiledQuad.gltf");
+        let mesh = gltf.meshes().next().unwrap();
+        let primitive = mesh.primitives().next().unwrap();
+        // the texture only tiles with REPEAT
+        let max_tex_coord = primitive.tex_coords_f32(0, &buffers).unwrap()
+            .fold(0.0f32, |max, uv| max.max(uv[0]).max(uv[1]));
+        assert!(max_tex_coord > 1.0);
+
+        let texture = gltf.textures().next().unwrap();
+        assert_eq!(SamplerParams::from_gltf(&texture.sampler()), SamplerParams {
+            min_filter: gl::LINEAR_MIPMAP_LINEAR as i32,
+            mag_filter: gl::LINEAR as i32,
+            wrap_s: gl::REPEAT as i32,
+            wrap_t: gl::REPEAT as i32,
+            mip_maps: true,
+        });
+    }
+
+    #[test]
+    fn repeat_sampler_state_of_tiled_quad() {
+        use std::collections::HashMap;
+        use viewer::{test_gl_context, GltfViewer};
+
+        let _context = match test_gl_context() {
+            Some(context) => context,
+            None => return,
+        };
+        let (root, _, _) = GltfViewer::load("src/data/TiledQuad.gltf", None, &HashMap::new())
+            .expect("failed to load TiledQuad.gltf");
+        let texture = &root.textures[0];
+        let parameter = |name| unsafe {
+            let mut value = 0;
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            gl::GetTexParameteriv(gl::TEXTURE_2D, name, &mut value);
+            value as u32
+        };
+        assert_eq!(parameter(gl::TEXTURE_WRAP_S), gl::REPEAT);
+        assert_eq!(parameter(gl::TEXTURE_WRAP_T), gl::REPEAT);
+        assert_eq!(parameter(gl::TEXTURE_MIN_FILTER), gl::LINEAR_MIPMAP_LINEAR);
+        assert_eq!(parameter(gl::TEXTURE_MAG_FILTER), gl::LINEAR);
     }
 }
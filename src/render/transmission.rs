@@ -0,0 +1,72 @@
+use std::ptr;
+
+use gl;
+
+/// Texture unit of the `KHR_materials_transmission` texture (see also PbrShader constructor)
+pub const TRANSMISSION_UNIT: u32 = 11;
+/// Texture unit of the captured scene colors seen through transmissive primitives
+pub const TRANSMISSION_SCENE_UNIT: u32 = 12;
+
+/// Colors of the opaque scene, captured before the blend pass and sampled by transmissive
+/// primitives (`ShaderFlags::TRANSMISSION`). Rougher surfaces sample coarser mip levels,
+/// which approximates the blur of refraction through them.
+pub struct TransmissionBuffer {
+    framebuffer: u32,
+    color_texture: u32, // mipmapped
+    size: (u32, u32),
+}
+
+impl TransmissionBuffer {
+    /// NOTE: Needs a current GL context. The texture is allocated on the first `capture`.
+    pub unsafe fn new() -> TransmissionBuffer {
+        let mut color_texture = 0;
+        gl::GenTextures(1, &mut color_texture);
+        gl::BindTexture(gl::TEXTURE_2D, color_texture);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+
+        let mut framebuffer = 0;
+        gl::GenFramebuffers(1, &mut framebuffer);
+
+        TransmissionBuffer { framebuffer, color_texture, size: (0, 0) }
+    }
+
+    /// Copies the colors of the bound framebuffer (current viewport) into the texture and binds it to
+    /// `TRANSMISSION_SCENE_UNIT`. Multisampled framebuffers are resolved by the copy.
+    pub unsafe fn capture(&mut self) {
+        let mut target_framebuffer = 0;
+        gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut target_framebuffer);
+        let mut viewport = [0; 4];
+        gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        let size = (viewport[2] as u32, viewport[3] as u32);
+
+        if self.size != size {
+            self.size = size;
+            gl::BindTexture(gl::TEXTURE_2D, self.color_texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as i32, size.0 as i32, size.1 as i32,
+                0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.color_texture, 0);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                panic!("ERROR::FRAMEBUFFER:: Transmission framebuffer is not complete!");
+            }
+        }
+
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, target_framebuffer as u32);
+        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.framebuffer);
+        gl::BlitFramebuffer(
+            viewport[0], viewport[1], viewport[0] + viewport[2], viewport[1] + viewport[3],
+            0, 0, viewport[2], viewport[3],
+            gl::COLOR_BUFFER_BIT, gl::NEAREST);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, target_framebuffer as u32);
+
+        gl::ActiveTexture(gl::TEXTURE0 + TRANSMISSION_SCENE_UNIT);
+        gl::BindTexture(gl::TEXTURE_2D, self.color_texture);
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
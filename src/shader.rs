@@ -8,9 +8,15 @@ use std::str;
 use gl;
 use gl::types::*;
 
-use cgmath::{Matrix, Matrix4, Vector3, Vector4};
+use cgmath::{Matrix, Matrix3, Matrix4, Vector3, Vector4, vec3};
 use cgmath::prelude::*;
 
+use render::{DIFFUSE_ENV_UNIT, SPECULAR_ENV_UNIT, BRDF_LUT_UNIT, JOINT_MATRICES_UNIT, MORPH_TARGETS_UNIT};
+use render::SHADOW_MAP_UNIT;
+use render::{TRANSMISSION_UNIT, TRANSMISSION_SCENE_UNIT};
+use render::{CLEARCOAT_UNIT, CLEARCOAT_ROUGHNESS_UNIT, CLEARCOAT_NORMAL_UNIT};
+use render::{LightType, DEFAULT_LIGHT_COLOR};
+
 pub struct Shader {
     pub id: u32,
     uniform_location_cache: HashMap<&'static str, i32>
@@ -19,8 +25,10 @@ pub struct Shader {
 impl Shader {
     pub fn new(vertex_path: &str, fragment_path: &str, defines: &[String]) -> Shader {
         // 1. retrieve the vertex/fragment source code from filesystem
-        let mut v_shader_file = File::open(vertex_path).expect(&format!("Failed to open {}", vertex_path));
-        let mut f_shader_file = File::open(fragment_path).expect(&format!("Failed to open {}", fragment_path));
+        let mut v_shader_file = File::open(vertex_path)
+            .unwrap_or_else(|err| panic!("Failed to open {}: {}", vertex_path, err));
+        let mut f_shader_file = File::open(fragment_path)
+            .unwrap_or_else(|err| panic!("Failed to open {}: {}", fragment_path, err));
         let mut vertex_code = String::new();
         let mut fragment_code = String::new();
         v_shader_file
@@ -124,6 +132,10 @@ impl Shader {
         gl::Uniform3f(location, x, y, z);
     }
     /// ------------------------------------------------------------------------
+    pub unsafe fn set_mat3(&self, location: i32, mat: &Matrix3<f32>) {
+        gl::UniformMatrix3fv(location, 1, gl::FALSE, mat.as_ptr());
+    }
+    /// ------------------------------------------------------------------------
     pub unsafe fn set_mat4(&self, location: i32, mat: &Matrix4<f32>) {
         gl::UniformMatrix4fv(location, 1, gl::FALSE, mat.as_ptr());
     }
@@ -147,8 +159,7 @@ impl Shader {
     /// ------------------------------------------------------------------------
     unsafe fn check_compile_errors(&self, shader: u32, type_: &str) {
         let mut success = i32::from(gl::FALSE);
-        let mut info_log = Vec::with_capacity(1024);
-        info_log.set_len(1024 - 1); // subtract 1 to skip the trailing null character
+        let mut info_log = vec![0u8; 1024 - 1]; // subtract 1 to skip the trailing null character
         if type_ != "PROGRAM" {
             gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
             let log_type = if success == i32::from(gl::TRUE) { "WARNING" } else { "ERROR" };
@@ -177,7 +188,8 @@ impl Shader {
 
 bitflags! {
     /// Flags matching the defines in the PBR shader
-    pub struct ShaderFlags: u16 {
+    #[derive(Default)]
+    pub struct ShaderFlags: u64 {
         // vertex shader + fragment shader
         const HAS_NORMALS           = 1;
         const HAS_TANGENTS          = 1 << 1;
@@ -193,38 +205,160 @@ bitflags! {
         const HAS_METALROUGHNESSMAP = 1 << 8;
         const HAS_OCCLUSIONMAP      = 1 << 9;
         const USE_TEX_LOD           = 1 << 10;
+
+        // debug visualizations (fragment shader only)
+        const DEBUG_NORMALS         = 1 << 11;
+        // isolated material channels
+        const DEBUG_BASE_COLOR      = 1 << 12;
+        const DEBUG_METALLIC        = 1 << 13;
+        const DEBUG_ROUGHNESS       = 1 << 14;
+        const DEBUG_NORMAL_MAP      = 1 << 15;
+        const DEBUG_OCCLUSION       = 1 << 16;
+        const DEBUG_EMISSIVE        = 1 << 17;
+
+        // KHR_materials_unlit (fragment shader only)
+        const UNLIT                 = 1 << 18;
+
+        // JOINTS_0 + WEIGHTS_0 (vertex shader only)
+        const HAS_SKIN              = 1 << 19;
+        const HAS_MORPH_TARGETS     = 1 << 20;
+
+        // ID pass for picking (fragment shader only)
+        const PICKING               = 1 << 21;
+        // tint of the selected node (fragment shader only)
+        const HIGHLIGHT             = 1 << 22;
+
+        // material alpha mode (fragment shader only, OPAQUE if neither)
+        const ALPHA_MASK            = 1 << 23;
+        const ALPHA_BLEND           = 1 << 24;
+
+        // per-instance model matrix attribute (vertex shader only)
+        const INSTANCED             = 1 << 25;
+
+        // TEXCOORD_1 (vertex shader + fragment shader)
+        const HAS_UV_1              = 1 << 26;
+
+        // depth only pass for the shadow map (fragment shader only)
+        const SHADOW_PASS           = 1 << 27;
+        // shadow map lookup for the primary directional light (fragment shader only)
+        const SHADOWS               = 1 << 28;
+
+        // uniform matte material for all primitives, lit as usual (fragment shader only)
+        const CLAY                  = 1 << 29;
+
+        // KHR_materials_transmission, samples the captured scene (fragment shader only)
+        const TRANSMISSION          = 1 << 30;
+        const HAS_TRANSMISSIONMAP   = 1 << 31;
+
+        // KHR_materials_clearcoat (fragment shader only)
+        const CLEARCOAT                 = 1 << 32;
+        const HAS_CLEARCOATMAP          = 1 << 33;
+        const HAS_CLEARCOATROUGHNESSMAP = 1 << 34;
+        const HAS_CLEARCOATNORMALMAP    = 1 << 35;
+
+        // KHR_texture_transform of the core textures (fragment shader only)
+        const TEXTURE_TRANSFORM         = 1 << 36;
     }
 }
 
+/// Material channels that can be isolated for debugging, in cycling order
+pub const DEBUG_CHANNELS: [ShaderFlags; 6] = [
+    ShaderFlags::DEBUG_BASE_COLOR,
+    ShaderFlags::DEBUG_METALLIC,
+    ShaderFlags::DEBUG_ROUGHNESS,
+    ShaderFlags::DEBUG_NORMAL_MAP,
+    ShaderFlags::DEBUG_OCCLUSION,
+    ShaderFlags::DEBUG_EMISSIVE,
+];
+
 impl ShaderFlags {
+    /// Debug visualization by (CLI) name
+    pub fn debug_flag_from_name(name: &str) -> Option<ShaderFlags> {
+        match name {
+            "normals" => Some(ShaderFlags::DEBUG_NORMALS),
+            "base-color" => Some(ShaderFlags::DEBUG_BASE_COLOR),
+            "metallic" => Some(ShaderFlags::DEBUG_METALLIC),
+            "roughness" => Some(ShaderFlags::DEBUG_ROUGHNESS),
+            "normal-map" => Some(ShaderFlags::DEBUG_NORMAL_MAP),
+            "occlusion" => Some(ShaderFlags::DEBUG_OCCLUSION),
+            "emissive" => Some(ShaderFlags::DEBUG_EMISSIVE),
+            _ => None,
+        }
+    }
+
     pub fn as_strings(&self) -> Vec<String> {
-        (0..15)
-            .map(|i| 1u16 << i)
+        (0..64)
+            .map(|i| 1u64 << i)
             .filter(|i| self.bits & i != 0)
             .map(|i| format!("{:?}", ShaderFlags::from_bits_truncate(i)))
             .collect()
     }
 }
 
+/// Tone mapping operator of the PBR shader (the values match `u_ToneMapping`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapping {
+    None = 0,
+    Reinhard = 1,
+    Aces = 2,
+    /// Exposure only, without sRGB encoding (for HDR screenshots, see `GltfViewer::render_linear`)
+    Linear = 3,
+}
+
+impl ToneMapping {
+    pub fn from_name(name: &str) -> Option<ToneMapping> {
+        match name.to_lowercase().as_str() {
+            "none" => Some(ToneMapping::None),
+            "reinhard" => Some(ToneMapping::Reinhard),
+            "aces" => Some(ToneMapping::Aces),
+            _ => None,
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 pub struct PbrUniformLocations {
     // uniform locations
     // TODO!: UBO for matrices, camera, light(s)?
     pub u_MVPMatrix: i32,
     pub u_ModelMatrix: i32,
+    pub u_ViewProjectionMatrix: i32, // instanced only
     pub u_Camera: i32,
 
-    pub u_LightDirection: i32,
-    pub u_LightColor: i32,
+    pub u_Exposure: i32,
+    pub u_ToneMapping: i32,
+
+    // lights (arrays, see `Root::set_lights`)
+    pub u_LightCount: i32,
+    pub u_LightTypes: i32,
+    pub u_LightPositions: i32,
+    pub u_LightDirections: i32,
+    pub u_LightColors: i32,
+    pub u_LightRanges: i32,
+    pub u_LightConeScales: i32,
+    pub u_LightConeOffsets: i32,
 
     pub u_AmbientLightColor: i32,
     pub u_AmbientLightIntensity: i32,
 
-    // TODO!: set when integrating IBL (unused now)
     pub u_DiffuseEnvSampler: i32,
     pub u_SpecularEnvSampler: i32,
     pub u_brdfLUT: i32,
 
+    pub u_JointMatrixSampler: i32,
+
+    pub u_MorphTargetSampler: i32,
+    pub u_MorphWeights: i32,
+    pub u_NumMorphTargets: i32,
+    pub u_NumVertices: i32,
+
+    pub u_PickColor: i32,
+
+    pub u_ShadowMap: i32,
+    pub u_LightSpaceMatrix: i32,
+    pub u_ShadowBias: i32,
+    pub u_ShadowLight: i32,
+
     ///
 
     pub u_BaseColorSampler: i32,
@@ -242,6 +376,37 @@ pub struct PbrUniformLocations {
     pub u_OcclusionSampler: i32,
     pub u_OcclusionStrength: i32,
 
+    pub u_AlphaCutoff: i32,
+
+    pub u_TransmissionFactor: i32,
+    pub u_TransmissionSampler: i32,
+    pub u_TransmissionSceneSampler: i32,
+
+    pub u_ClearcoatFactor: i32,
+    pub u_ClearcoatSampler: i32,
+    pub u_ClearcoatRoughnessFactor: i32,
+    pub u_ClearcoatRoughnessSampler: i32,
+    pub u_ClearcoatNormalSampler: i32,
+    pub u_ClearcoatNormalScale: i32,
+
+    // texture coordinate sets (with HAS_UV_1 only)
+    pub u_BaseColorTexCoord: i32,
+    pub u_NormalTexCoord: i32,
+    pub u_EmissiveTexCoord: i32,
+    pub u_MetallicRoughnessTexCoord: i32,
+    pub u_OcclusionTexCoord: i32,
+    pub u_TransmissionTexCoord: i32,
+    pub u_ClearcoatTexCoord: i32,
+    pub u_ClearcoatRoughnessTexCoord: i32,
+    pub u_ClearcoatNormalTexCoord: i32,
+
+    // KHR_texture_transform (with TEXTURE_TRANSFORM only)
+    pub u_BaseColorUVTransform: i32,
+    pub u_NormalUVTransform: i32,
+    pub u_EmissiveUVTransform: i32,
+    pub u_MetallicRoughnessUVTransform: i32,
+    pub u_OcclusionUVTransform: i32,
+
     // TODO!: use/remove debugging uniforms
     // debugging flags used for shader output of intermediate PBR variables
     pub u_ScaleDiffBaseMR: i32,
@@ -272,10 +437,20 @@ impl PbrShader {
             let uniforms = PbrUniformLocations {
                 u_MVPMatrix: shader.uniform_location("u_MVPMatrix"),
                 u_ModelMatrix: shader.uniform_location("u_ModelMatrix"),
+                u_ViewProjectionMatrix: shader.uniform_location("u_ViewProjectionMatrix"),
                 u_Camera: shader.uniform_location("u_Camera"),
 
-                u_LightDirection: shader.uniform_location("u_LightDirection"),
-                u_LightColor: shader.uniform_location("u_LightColor"),
+                u_Exposure: shader.uniform_location("u_Exposure"),
+                u_ToneMapping: shader.uniform_location("u_ToneMapping"),
+
+                u_LightCount: shader.uniform_location("u_LightCount"),
+                u_LightTypes: shader.uniform_location("u_LightTypes"),
+                u_LightPositions: shader.uniform_location("u_LightPositions"),
+                u_LightDirections: shader.uniform_location("u_LightDirections"),
+                u_LightColors: shader.uniform_location("u_LightColors"),
+                u_LightRanges: shader.uniform_location("u_LightRanges"),
+                u_LightConeScales: shader.uniform_location("u_LightConeScales"),
+                u_LightConeOffsets: shader.uniform_location("u_LightConeOffsets"),
 
                 u_AmbientLightColor: shader.uniform_location("u_AmbientLightColor"),
                 u_AmbientLightIntensity: shader.uniform_location("u_AmbientLightIntensity"),
@@ -284,6 +459,20 @@ impl PbrShader {
                 u_SpecularEnvSampler: shader.uniform_location("u_SpecularEnvSampler"),
                 u_brdfLUT: shader.uniform_location("u_brdfLUT"),
 
+                u_JointMatrixSampler: shader.uniform_location("u_JointMatrixSampler"),
+
+                u_MorphTargetSampler: shader.uniform_location("u_MorphTargetSampler"),
+                u_MorphWeights: shader.uniform_location("u_MorphWeights"),
+                u_NumMorphTargets: shader.uniform_location("u_NumMorphTargets"),
+                u_NumVertices: shader.uniform_location("u_NumVertices"),
+
+                u_PickColor: shader.uniform_location("u_PickColor"),
+
+                u_ShadowMap: shader.uniform_location("u_ShadowMap"),
+                u_LightSpaceMatrix: shader.uniform_location("u_LightSpaceMatrix"),
+                u_ShadowBias: shader.uniform_location("u_ShadowBias"),
+                u_ShadowLight: shader.uniform_location("u_ShadowLight"),
+
                 u_BaseColorSampler: shader.uniform_location("u_BaseColorSampler"),
                 u_BaseColorFactor: shader.uniform_location("u_BaseColorFactor"),
 
@@ -299,6 +488,35 @@ impl PbrShader {
                 u_OcclusionSampler: shader.uniform_location("u_OcclusionSampler"),
                 u_OcclusionStrength: shader.uniform_location("u_OcclusionStrength"),
 
+                u_AlphaCutoff: shader.uniform_location("u_AlphaCutoff"),
+
+                u_TransmissionFactor: shader.uniform_location("u_TransmissionFactor"),
+                u_TransmissionSampler: shader.uniform_location("u_TransmissionSampler"),
+                u_TransmissionSceneSampler: shader.uniform_location("u_TransmissionSceneSampler"),
+
+                u_ClearcoatFactor: shader.uniform_location("u_ClearcoatFactor"),
+                u_ClearcoatSampler: shader.uniform_location("u_ClearcoatSampler"),
+                u_ClearcoatRoughnessFactor: shader.uniform_location("u_ClearcoatRoughnessFactor"),
+                u_ClearcoatRoughnessSampler: shader.uniform_location("u_ClearcoatRoughnessSampler"),
+                u_ClearcoatNormalSampler: shader.uniform_location("u_ClearcoatNormalSampler"),
+                u_ClearcoatNormalScale: shader.uniform_location("u_ClearcoatNormalScale"),
+
+                u_BaseColorTexCoord: shader.uniform_location("u_BaseColorTexCoord"),
+                u_NormalTexCoord: shader.uniform_location("u_NormalTexCoord"),
+                u_EmissiveTexCoord: shader.uniform_location("u_EmissiveTexCoord"),
+                u_MetallicRoughnessTexCoord: shader.uniform_location("u_MetallicRoughnessTexCoord"),
+                u_OcclusionTexCoord: shader.uniform_location("u_OcclusionTexCoord"),
+                u_TransmissionTexCoord: shader.uniform_location("u_TransmissionTexCoord"),
+                u_ClearcoatTexCoord: shader.uniform_location("u_ClearcoatTexCoord"),
+                u_ClearcoatRoughnessTexCoord: shader.uniform_location("u_ClearcoatRoughnessTexCoord"),
+                u_ClearcoatNormalTexCoord: shader.uniform_location("u_ClearcoatNormalTexCoord"),
+
+                u_BaseColorUVTransform: shader.uniform_location("u_BaseColorUVTransform"),
+                u_NormalUVTransform: shader.uniform_location("u_NormalUVTransform"),
+                u_EmissiveUVTransform: shader.uniform_location("u_EmissiveUVTransform"),
+                u_MetallicRoughnessUVTransform: shader.uniform_location("u_MetallicRoughnessUVTransform"),
+                u_OcclusionUVTransform: shader.uniform_location("u_OcclusionUVTransform"),
+
                 u_ScaleDiffBaseMR: shader.uniform_location("u_ScaleDiffBaseMR"),
                 u_ScaleFGDSpec: shader.uniform_location("u_ScaleFGDSpec"),
                 u_ScaleIBLAmbient: shader.uniform_location("u_ScaleIBLAmbient"),
@@ -310,14 +528,34 @@ impl PbrShader {
             shader.set_int(uniforms.u_EmissiveSampler, 2);
             shader.set_int(uniforms.u_MetallicRoughnessSampler, 3);
             shader.set_int(uniforms.u_OcclusionSampler, 4);
-
-            shader.set_vec3(uniforms.u_LightColor, 5.0, 5.0, 5.0);
-            // TODO!: optional minus on z
-            shader.set_vec3(uniforms.u_LightDirection, 0.0, 0.5, 0.5);
+            shader.set_int(uniforms.u_DiffuseEnvSampler, DIFFUSE_ENV_UNIT as i32);
+            shader.set_int(uniforms.u_SpecularEnvSampler, SPECULAR_ENV_UNIT as i32);
+            shader.set_int(uniforms.u_brdfLUT, BRDF_LUT_UNIT as i32);
+            shader.set_int(uniforms.u_JointMatrixSampler, JOINT_MATRICES_UNIT as i32);
+            shader.set_int(uniforms.u_MorphTargetSampler, MORPH_TARGETS_UNIT as i32);
+            shader.set_int(uniforms.u_ShadowMap, SHADOW_MAP_UNIT as i32);
+            shader.set_int(uniforms.u_TransmissionSampler, TRANSMISSION_UNIT as i32);
+            shader.set_int(uniforms.u_TransmissionSceneSampler, TRANSMISSION_SCENE_UNIT as i32);
+            shader.set_int(uniforms.u_ClearcoatSampler, CLEARCOAT_UNIT as i32);
+            shader.set_int(uniforms.u_ClearcoatRoughnessSampler, CLEARCOAT_ROUGHNESS_UNIT as i32);
+            shader.set_int(uniforms.u_ClearcoatNormalSampler, CLEARCOAT_NORMAL_UNIT as i32);
+            // no shadows until the first `Root::set_shadows` call
+            shader.set_int(uniforms.u_ShadowLight, -1);
+            // full diffuse and specular IBL contribution
+            gl::Uniform4f(uniforms.u_ScaleIBLAmbient, 1.0, 1.0, 0.0, 0.0);
+
+            // a single directional light until the first `Root::set_lights` call
+            shader.set_int(uniforms.u_LightCount, 1);
+            gl::Uniform1i(uniforms.u_LightTypes, LightType::Directional as i32);
+            gl::Uniform3f(uniforms.u_LightColors, DEFAULT_LIGHT_COLOR[0], DEFAULT_LIGHT_COLOR[1], DEFAULT_LIGHT_COLOR[2]);
+            shader.set_vector3(uniforms.u_LightDirections, &vec3(0.0, 0.5, 0.5).normalize());
 
             shader.set_vec3(uniforms.u_AmbientLightColor, 1.0, 1.0, 1.0);
             shader.set_float(uniforms.u_AmbientLightIntensity, 0.2);
 
+            shader.set_float(uniforms.u_Exposure, 1.0);
+            shader.set_int(uniforms.u_ToneMapping, ToneMapping::None as i32);
+
             uniforms
         };
 
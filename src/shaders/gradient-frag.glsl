@@ -0,0 +1,15 @@
+#version 330 core
+in vec2 v_UV;
+
+uniform vec3 u_TopColor;
+uniform vec3 u_BottomColor;
+uniform mat4 u_InverseTileProjection; // clip space of the current tile -> of the whole image
+
+out vec4 FragColor;
+
+void main()
+{
+    vec4 position = u_InverseTileProjection * vec4(v_UV * 2.0 - 1.0, 0.0, 1.0);
+    float t = clamp(position.y / position.w * 0.5 + 0.5, 0.0, 1.0);
+    FragColor = vec4(mix(u_BottomColor, u_TopColor, t), 1.0);
+}
@@ -0,0 +1,32 @@
+#version 330 core
+in vec3 v_WorldPosition;
+
+uniform vec3 u_Center;
+uniform float u_Extent;
+uniform float u_CellSize;
+uniform vec4 u_Color;
+
+out vec4 FragColor;
+
+// 1.0 on grid lines (one pixel wide, anti-aliased), 0.0 elsewhere
+float gridLines(vec2 coord)
+{
+    vec2 grid = abs(fract(coord - 0.5) - 0.5) / fwidth(coord);
+    return 1.0 - min(min(grid.x, grid.y), 1.0);
+}
+
+void main()
+{
+    vec2 position = v_WorldPosition.xz;
+    float minor = gridLines(position / u_CellSize);
+    float major = gridLines(position / (u_CellSize * 10.0));
+    float alpha = max(0.4 * minor, major);
+
+    // fade out towards the edges, so that the plane looks infinite
+    float distance = length(position - u_Center.xz) / u_Extent;
+    alpha *= 1.0 - smoothstep(0.3, 1.0, distance);
+    if (alpha <= 0.0)
+        discard;
+
+    FragColor = vec4(u_Color.rgb, u_Color.a * alpha);
+}
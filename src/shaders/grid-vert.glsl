@@ -0,0 +1,14 @@
+#version 330 core
+layout (location = 0) in vec2 a_Position; // [-1, 1]
+
+uniform mat4 u_ViewProjection;
+uniform vec3 u_Center;
+uniform float u_Extent; // half the side length
+
+out vec3 v_WorldPosition;
+
+void main()
+{
+    v_WorldPosition = u_Center + vec3(a_Position.x, 0.0, a_Position.y) * u_Extent;
+    gl_Position = u_ViewProjection * vec4(v_WorldPosition, 1.0);
+}
@@ -0,0 +1,63 @@
+// Precomputes the split-sum BRDF integration lookup table (scale and bias to F0, see [1] in pbr-frag.glsl)
+// Based on https://learnopengl.com/PBR/IBL/Specular-IBL
+#version 330 core
+in vec2 v_UV;
+
+out vec2 FragColor;
+
+const float M_PI = 3.141592653589793;
+const uint SAMPLE_COUNT = 1024u;
+
+float radicalInverse(uint bits)
+{
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10; // / 0x100000000
+}
+
+vec3 importanceSampleGGX(vec2 xi, float roughness)
+{
+    // tangent space with n = (0, 0, 1)
+    float a = roughness * roughness;
+    float phi = 2.0 * M_PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    return vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+}
+
+float geometrySchlickGGX(float NdotV, float roughness)
+{
+    float k = (roughness * roughness) / 2.0;
+    return NdotV / (NdotV * (1.0 - k) + k);
+}
+
+void main()
+{
+    float NdotV = max(v_UV.x, 0.001);
+    // NOTE: flipped to match the lookup in pbr-frag.glsl
+    float roughness = 1.0 - v_UV.y;
+
+    vec3 v = vec3(sqrt(1.0 - NdotV * NdotV), 0.0, NdotV);
+    float scale = 0.0;
+    float bias = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+        vec2 xi = vec2(float(i) / float(SAMPLE_COUNT), radicalInverse(i));
+        vec3 h = importanceSampleGGX(xi, roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+
+        float NdotL = max(l.z, 0.0);
+        float NdotH = max(h.z, 0.0);
+        float VdotH = max(dot(v, h), 0.0);
+        if (NdotL > 0.0) {
+            float g = geometrySchlickGGX(NdotV, roughness) * geometrySchlickGGX(NdotL, roughness);
+            float gVis = (g * VdotH) / (NdotH * NdotV);
+            float fc = pow(1.0 - VdotH, 5.0);
+            scale += (1.0 - fc) * gVis;
+            bias += fc * gVis;
+        }
+    }
+    FragColor = vec2(scale, bias) / float(SAMPLE_COUNT);
+}
@@ -0,0 +1,13 @@
+// Renders a unit cube from the inside, e.g. to draw into a cubemap face
+#version 330 core
+layout (location = 0) in vec3 a_Position;
+
+uniform mat4 u_ViewProjection;
+
+out vec3 v_Direction;
+
+void main()
+{
+    v_Direction = a_Position;
+    gl_Position = u_ViewProjection * vec4(a_Position, 1.0);
+}
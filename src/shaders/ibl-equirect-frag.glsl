@@ -0,0 +1,16 @@
+// Converts an equirectangular (latitude/longitude) map to a cubemap face
+#version 330 core
+in vec3 v_Direction;
+
+uniform sampler2D u_EquirectSampler;
+
+out vec4 FragColor;
+
+const float M_PI = 3.141592653589793;
+
+void main()
+{
+    vec3 d = normalize(v_Direction);
+    vec2 uv = vec2(atan(d.z, d.x) / (2.0 * M_PI), asin(d.y) / M_PI) + 0.5;
+    FragColor = vec4(texture(u_EquirectSampler, uv).rgb, 1.0);
+}
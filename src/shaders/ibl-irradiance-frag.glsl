@@ -0,0 +1,32 @@
+// Convolves the environment cubemap for diffuse lighting (cosine weighted hemisphere)
+// Based on https://learnopengl.com/PBR/IBL/Diffuse-irradiance
+#version 330 core
+in vec3 v_Direction;
+
+uniform samplerCube u_EnvironmentSampler;
+
+out vec4 FragColor;
+
+const float M_PI = 3.141592653589793;
+
+void main()
+{
+    vec3 n = normalize(v_Direction);
+    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, n));
+    up = cross(n, right);
+
+    const float sampleDelta = 0.025;
+    vec3 irradiance = vec3(0.0);
+    float sampleCount = 0.0;
+    for (float phi = 0.0; phi < 2.0 * M_PI; phi += sampleDelta) {
+        for (float theta = 0.0; theta < 0.5 * M_PI; theta += sampleDelta) {
+            // spherical to cartesian (in tangent space), then to world space
+            vec3 t = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 sampleDirection = t.x * right + t.y * up + t.z * n;
+            irradiance += texture(u_EnvironmentSampler, sampleDirection).rgb * cos(theta) * sin(theta);
+            sampleCount++;
+        }
+    }
+    FragColor = vec4(M_PI * irradiance / sampleCount, 1.0);
+}
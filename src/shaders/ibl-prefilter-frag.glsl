@@ -0,0 +1,81 @@
+// Prefilters the environment cubemap for specular lighting with the GGX distribution
+// (one roughness value per mip level).
+// Based on https://learnopengl.com/PBR/IBL/Specular-IBL
+#version 330 core
+in vec3 v_Direction;
+
+uniform samplerCube u_EnvironmentSampler;
+uniform float u_Roughness;
+uniform float u_Resolution; // of a face of the environment cubemap (mip level 0)
+
+out vec4 FragColor;
+
+const float M_PI = 3.141592653589793;
+const uint SAMPLE_COUNT = 1024u;
+
+float distributionGGX(float NdotH, float roughness)
+{
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float denom = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (M_PI * denom * denom);
+}
+
+// Van der Corput sequence
+float radicalInverse(uint bits)
+{
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10; // / 0x100000000
+}
+
+vec2 hammersley(uint i, uint n)
+{
+    return vec2(float(i) / float(n), radicalInverse(i));
+}
+
+vec3 importanceSampleGGX(vec2 xi, vec3 n, float roughness)
+{
+    float a = roughness * roughness;
+    float phi = 2.0 * M_PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    vec3 h = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, n));
+    vec3 bitangent = cross(n, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+}
+
+void main()
+{
+    // assumption: view direction = normal = reflection direction
+    vec3 n = normalize(v_Direction);
+    vec3 v = n;
+
+    vec3 color = vec3(0.0);
+    float totalWeight = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+        vec2 xi = hammersley(i, SAMPLE_COUNT);
+        vec3 h = importanceSampleGGX(xi, n, u_Roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+        float NdotL = dot(n, l);
+        if (NdotL > 0.0) {
+            // sample from a mip level of the environment according to the pdf to reduce artifacts
+            float NdotH = max(dot(n, h), 0.0);
+            float HdotV = max(dot(h, v), 0.0);
+            float pdf = distributionGGX(NdotH, u_Roughness) * NdotH / (4.0 * HdotV) + 0.0001;
+            float saTexel = 4.0 * M_PI / (6.0 * u_Resolution * u_Resolution);
+            float saSample = 1.0 / (float(SAMPLE_COUNT) * pdf + 0.0001);
+            float mipLevel = u_Roughness == 0.0 ? 0.0 : 0.5 * log2(saSample / saTexel);
+
+            color += textureLod(u_EnvironmentSampler, l, mipLevel).rgb * NdotL;
+            totalWeight += NdotL;
+        }
+    }
+    FragColor = vec4(color / totalWeight, 1.0);
+}
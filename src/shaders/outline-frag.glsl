@@ -0,0 +1,42 @@
+// Outlines (silhouettes and creases) from discontinuities of the scene depth, optionally over a
+// flat fill instead of the shaded colors. Restores the scene depth.
+#version 330 core
+in vec2 v_UV;
+
+out vec4 FragColor;
+
+uniform sampler2D u_ColorSampler;
+uniform sampler2D u_DepthSampler;
+uniform float u_Thickness; // pixels
+uniform vec4 u_OutlineColor;
+uniform vec4 u_FillColor; // alpha 0: keep the shaded colors
+
+// Depth is affine in screen space across planar surfaces (for both projections), so its second
+// derivative only stands out (relative to the slope) at silhouettes and creases.
+float edge(vec2 uv, vec2 offset)
+{
+    float center = texture(u_DepthSampler, uv).r;
+    float left = texture(u_DepthSampler, uv - vec2(offset.x, 0.0)).r;
+    float right = texture(u_DepthSampler, uv + vec2(offset.x, 0.0)).r;
+    float down = texture(u_DepthSampler, uv - vec2(0.0, offset.y)).r;
+    float up = texture(u_DepthSampler, uv + vec2(0.0, offset.y)).r;
+    float curvature = abs(left + right - 2.0 * center) + abs(down + up - 2.0 * center);
+    float slope = abs(right - left) + abs(up - down);
+    return smoothstep(0.25, 0.75, curvature / (slope + 1e-5));
+}
+
+void main()
+{
+    vec2 texelSize = 1.0 / vec2(textureSize(u_DepthSampler, 0));
+    // the half offset softens the inner border of the line (antialiasing)
+    float outline = 0.5 * (edge(v_UV, u_Thickness * texelSize) + edge(v_UV, 0.5 * u_Thickness * texelSize));
+    outline *= u_OutlineColor.a;
+
+    float depth = texture(u_DepthSampler, v_UV).r;
+    vec4 color = texture(u_ColorSampler, v_UV);
+    if (depth < 1.0) {
+        color.rgb = mix(color.rgb, u_FillColor.rgb, u_FillColor.a);
+    }
+    FragColor = vec4(mix(color.rgb, u_OutlineColor.rgb, outline), max(color.a, outline));
+    gl_FragDepth = depth;
+}
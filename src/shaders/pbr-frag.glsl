@@ -20,8 +20,20 @@
 
 precision highp float;
 
-uniform vec3 u_LightDirection;
-uniform vec3 u_LightColor;
+// Punctual lights (KHR_lights_punctual or the default directional light), in world space.
+// NOTE: MAX_LIGHTS and the light types must match light.rs
+#define MAX_LIGHTS 8
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+uniform int u_LightCount;
+uniform int u_LightTypes[MAX_LIGHTS];
+uniform vec3 u_LightPositions[MAX_LIGHTS];
+uniform vec3 u_LightDirections[MAX_LIGHTS]; // from the surface towards the light (directional and spot)
+uniform vec3 u_LightColors[MAX_LIGHTS]; // color * intensity
+uniform float u_LightRanges[MAX_LIGHTS]; // 0: infinite
+uniform float u_LightConeScales[MAX_LIGHTS]; // spot cone falloff: cos(angle) * scale + offset
+uniform float u_LightConeOffsets[MAX_LIGHTS];
 
 uniform vec3 u_AmbientLightColor;
 uniform float u_AmbientLightIntensity;
@@ -41,7 +53,6 @@ uniform float u_NormalScale;
 #endif
 #ifdef HAS_EMISSIVEMAP
 uniform sampler2D u_EmissiveSampler;
-uniform vec3 u_EmissiveFactor;
 #endif
 #ifdef HAS_METALROUGHNESSMAP
 uniform sampler2D u_MetallicRoughnessSampler;
@@ -53,9 +64,37 @@ uniform float u_OcclusionStrength;
 
 uniform vec2 u_MetallicRoughnessValues;
 uniform vec4 u_BaseColorFactor;
+uniform vec3 u_EmissiveFactor; // scaled by KHR_materials_emissive_strength
+#ifdef ALPHA_MASK
+uniform float u_AlphaCutoff;
+#endif
+#ifdef TRANSMISSION
+uniform float u_TransmissionFactor;
+#ifdef HAS_TRANSMISSIONMAP
+uniform sampler2D u_TransmissionSampler;
+#endif
+uniform sampler2D u_TransmissionSceneSampler; // the opaque scene (mipmapped, see transmission.rs)
+#endif
+#ifdef CLEARCOAT
+uniform float u_ClearcoatFactor;
+uniform float u_ClearcoatRoughnessFactor;
+#ifdef HAS_CLEARCOATMAP
+uniform sampler2D u_ClearcoatSampler;
+#endif
+#ifdef HAS_CLEARCOATROUGHNESSMAP
+uniform sampler2D u_ClearcoatRoughnessSampler;
+#endif
+#ifdef HAS_CLEARCOATNORMALMAP
+uniform sampler2D u_ClearcoatNormalSampler;
+uniform float u_ClearcoatNormalScale;
+#endif
+#endif
 
 uniform vec3 u_Camera;
 
+uniform float u_Exposure;
+uniform int u_ToneMapping; // 0: none, 1: Reinhard, 2: ACES filmic, 3: linear output (no sRGB encoding)
+
 // TODO!: remove or ifdef?
 // debugging flags used for shader output of intermediate PBR variables
 uniform vec4 u_ScaleDiffBaseMR;
@@ -65,6 +104,34 @@ uniform vec4 u_ScaleIBLAmbient;
 in vec3 v_Position;
 
 in vec2 v_UV;
+#ifdef HAS_UV_1
+in vec2 v_UV1;
+// texture coordinate set of each texture (0: TEXCOORD_0, 1: TEXCOORD_1)
+uniform int u_BaseColorTexCoord;
+uniform int u_NormalTexCoord;
+uniform int u_EmissiveTexCoord;
+uniform int u_MetallicRoughnessTexCoord;
+uniform int u_OcclusionTexCoord;
+uniform int u_TransmissionTexCoord;
+uniform int u_ClearcoatTexCoord;
+uniform int u_ClearcoatRoughnessTexCoord;
+uniform int u_ClearcoatNormalTexCoord;
+#define UV(texCoord) ((texCoord) == 1 ? v_UV1 : v_UV)
+#else
+#define UV(texCoord) v_UV
+#endif
+
+#ifdef TEXTURE_TRANSFORM
+// KHR_texture_transform of each texture (identity if it has none)
+uniform mat3 u_BaseColorUVTransform;
+uniform mat3 u_NormalUVTransform;
+uniform mat3 u_EmissiveUVTransform;
+uniform mat3 u_MetallicRoughnessUVTransform;
+uniform mat3 u_OcclusionUVTransform;
+#define UV_TRANSFORM(uv, transform) ((transform) * vec3(uv, 1.0)).xy
+#else
+#define UV_TRANSFORM(uv, transform) (uv)
+#endif
 
 in vec4 v_Color;
 
@@ -76,6 +143,28 @@ in vec3 v_Normal;
 #endif
 #endif
 
+#ifdef PICKING
+uniform vec4 u_PickColor; // encodes the node index
+#endif
+
+#ifdef SHADOWS
+uniform sampler2D u_ShadowMap;
+uniform mat4 u_LightSpaceMatrix; // world space -> light clip space
+uniform float u_ShadowBias;
+uniform int u_ShadowLight; // index of the shadow casting light (-1: none)
+#endif
+
+#ifdef HIGHLIGHT
+const vec3 HIGHLIGHT_COLOR = vec3(1.0, 0.6, 0.0);
+const float HIGHLIGHT_STRENGTH = 0.35;
+#endif
+
+#ifdef CLAY
+// matte light gray, used instead of the material
+const vec3 CLAY_COLOR = vec3(0.7);
+const float CLAY_ROUGHNESS = 0.8;
+#endif
+
 out vec4 FragColor;
 
 // Encapsulate the various inputs used by the various functions in the shading equation
@@ -100,16 +189,15 @@ struct PBRInfo
 const float M_PI = 3.141592653589793;
 const float c_MinRoughness = 0.04;
 
-// Find the normal for this fragment, pulling either from a predefined normal map
-// or from the interpolated mesh normal and tangent attributes.
-vec3 getNormal()
+// Retrieve the tangent space matrix, from the tangent attribute or derived from the
+// texture coordinates of the normal map
+mat3 getTBN()
 {
-    // Retrieve the tangent space matrix
 #ifndef HAS_TANGENTS
     vec3 pos_dx = dFdx(v_Position);
     vec3 pos_dy = dFdy(v_Position);
-    vec3 tex_dx = dFdx(vec3(v_UV, 0.0));
-    vec3 tex_dy = dFdy(vec3(v_UV, 0.0));
+    vec3 tex_dx = dFdx(vec3(UV_TRANSFORM(UV(u_NormalTexCoord), u_NormalUVTransform), 0.0));
+    vec3 tex_dy = dFdy(vec3(UV_TRANSFORM(UV(u_NormalTexCoord), u_NormalUVTransform), 0.0));
     vec3 t = (tex_dy.t * pos_dx - tex_dx.t * pos_dy) / (tex_dx.s * tex_dy.t - tex_dy.s * tex_dx.t);
 
 #ifdef HAS_NORMALS
@@ -124,9 +212,17 @@ vec3 getNormal()
 #else // HAS_TANGENTS
     mat3 tbn = v_TBN;
 #endif
+    return tbn;
+}
+
+// Find the normal for this fragment, pulling either from a predefined normal map
+// or from the interpolated mesh normal and tangent attributes.
+vec3 getNormal()
+{
+    mat3 tbn = getTBN();
 
 #ifdef HAS_NORMALMAP
-    vec3 n = texture(u_NormalSampler, v_UV).rgb;
+    vec3 n = texture(u_NormalSampler, UV_TRANSFORM(UV(u_NormalTexCoord), u_NormalUVTransform)).rgb;
     n = normalize(tbn * ((2.0 * n - 1.0) * vec3(u_NormalScale, u_NormalScale, 1.0)));
 #else
     vec3 n = tbn[2].xyz;
@@ -139,22 +235,38 @@ vec3 getNormal()
     return n;
 }
 
+#ifdef CLEARCOAT
+// Normal of the clearcoat layer: its own normal map or the geometry normal (not the base normal map)
+vec3 getClearcoatNormal()
+{
+    mat3 tbn = getTBN();
+#ifdef HAS_CLEARCOATNORMALMAP
+    vec3 n = texture(u_ClearcoatNormalSampler, UV(u_ClearcoatNormalTexCoord)).rgb;
+    n = normalize(tbn * ((2.0 * n - 1.0) * vec3(u_ClearcoatNormalScale, u_ClearcoatNormalScale, 1.0)));
+#else
+    vec3 n = tbn[2].xyz;
+#endif
+    n *= (2.0 * float(gl_FrontFacing) - 1.0);
+    return n;
+}
+#endif
+
 #ifdef USE_IBL
 // Calculation of the lighting contribution from an optional Image Based Light source.
 // Precomputed Environment Maps are required uniform inputs and are computed as outlined in [1].
 // See our README.md on Environment Maps [3] for additional discussion.
 vec3 getIBLContribution(PBRInfo pbrInputs, vec3 n, vec3 reflection)
 {
-    float mipCount = 9.0; // resolution of 512x512
+    float mipCount = 4.0; // max. mip level of the prefiltered map (PREFILTERED_MIP_LEVELS - 1 in environment.rs)
     float lod = (pbrInputs.perceptualRoughness * mipCount);
     // retrieve a scale and bias to F0. See [1], Figure 3
     vec3 brdf = texture(u_brdfLUT, vec2(pbrInputs.NdotV, 1.0 - pbrInputs.perceptualRoughness)).rgb;
-    vec3 diffuseLight = textureCube(u_DiffuseEnvSampler, n).rgb;
+    vec3 diffuseLight = texture(u_DiffuseEnvSampler, n).rgb;
 
 #ifdef USE_TEX_LOD
-    vec3 specularLight = textureCubeLodEXT(u_SpecularEnvSampler, reflection, lod).rgb;
+    vec3 specularLight = textureLod(u_SpecularEnvSampler, reflection, lod).rgb;
 #else
-    vec3 specularLight = textureCube(u_SpecularEnvSampler, reflection).rgb;
+    vec3 specularLight = texture(u_SpecularEnvSampler, reflection).rgb;
 #endif
 
     vec3 diffuse = diffuseLight * pbrInputs.diffuseColor;
@@ -166,6 +278,21 @@ vec3 getIBLContribution(PBRInfo pbrInputs, vec3 n, vec3 reflection)
 
     return diffuse + specular;
 }
+
+#ifdef CLEARCOAT
+// Specular image based lighting of the clearcoat layer (dielectric, reflectance 0.04)
+vec3 getIBLClearcoat(float perceptualRoughness, float NdotV, vec3 reflection)
+{
+    float mipCount = 4.0; // see getIBLContribution
+    vec3 brdf = texture(u_brdfLUT, vec2(NdotV, 1.0 - perceptualRoughness)).rgb;
+#ifdef USE_TEX_LOD
+    vec3 specularLight = textureLod(u_SpecularEnvSampler, reflection, perceptualRoughness * mipCount).rgb;
+#else
+    vec3 specularLight = texture(u_SpecularEnvSampler, reflection).rgb;
+#endif
+    return specularLight * (0.04 * brdf.x + brdf.y) * u_ScaleIBLAmbient.y;
+}
+#endif
 #endif
 
 // Basic Lambertian diffuse
@@ -208,8 +335,128 @@ float microfacetDistribution(PBRInfo pbrInputs)
     return roughnessSq / (M_PI * f * f);
 }
 
+#ifdef CLEARCOAT
+// Specular lobe of the clearcoat layer (KHR_materials_clearcoat) for light direction l,
+// using the layer's own normal n and roughness (in coat)
+vec3 clearcoatSpecular(PBRInfo coat, vec3 n, vec3 v, vec3 l)
+{
+    vec3 h = normalize(l + v);
+    coat.NdotL = clamp(dot(n, l), 0.001, 1.0);
+    coat.NdotV = abs(dot(n, v)) + 0.001;
+    coat.NdotH = clamp(dot(n, h), 0.0, 1.0);
+    coat.LdotH = clamp(dot(l, h), 0.0, 1.0);
+    coat.VdotH = clamp(dot(v, h), 0.0, 1.0);
+    vec3 F = specularReflection(coat);
+    float G = geometricOcclusion(coat);
+    float D = microfacetDistribution(coat);
+    return coat.NdotL * F * G * D / (4.0 * coat.NdotL * coat.NdotV);
+}
+#endif
+
+// Distance (inverse square, windowed by the range) and spot cone attenuation of light i,
+// as recommended by the KHR_lights_punctual spec
+float lightAttenuation(int i, vec3 pointToLight)
+{
+    if (u_LightTypes[i] == LIGHT_DIRECTIONAL) {
+        return 1.0;
+    }
+    float distanceSq = dot(pointToLight, pointToLight);
+    float attenuation = 1.0 / max(distanceSq, 0.0001);
+    if (u_LightRanges[i] > 0.0) {
+        float ratio = sqrt(distanceSq) / u_LightRanges[i];
+        float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+        attenuation *= window * window;
+    }
+    if (u_LightTypes[i] == LIGHT_SPOT) {
+        float cd = dot(u_LightDirections[i], normalize(pointToLight));
+        float cone = clamp(cd * u_LightConeScales[i] + u_LightConeOffsets[i], 0.0, 1.0);
+        attenuation *= cone * cone;
+    }
+    return attenuation;
+}
+
+#ifdef SHADOWS
+// Fraction of the shadow casting light reaching the fragment (0: in shadow, 1: lit),
+// averaged over 3x3 shadow map texels (PCF)
+float shadowFactor(vec3 n, vec3 l)
+{
+    vec4 lightSpacePosition = u_LightSpaceMatrix * vec4(v_Position, 1.0);
+    vec3 coords = lightSpacePosition.xyz / lightSpacePosition.w * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 1.0;
+    }
+    // larger bias for surfaces at a steep angle to the light, against shadow acne
+    float bias = max(u_ShadowBias * (1.0 - dot(n, l)), u_ShadowBias * 0.1);
+    vec2 texelSize = 1.0 / vec2(textureSize(u_ShadowMap, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; ++x) {
+        for (int y = -1; y <= 1; ++y) {
+            float depth = texture(u_ShadowMap, coords.xy + vec2(x, y) * texelSize).r;
+            lit += coords.z - bias > depth ? 0.0 : 1.0;
+        }
+    }
+    return lit / 9.0;
+}
+#endif
+
+#ifdef TRANSMISSION
+// Light from the scene behind the fragment (linear), blurred more for rougher surfaces.
+// NOTE: an approximation - the refracted direction is ignored and the captured colors
+// are only converted back from sRGB (tone mapping isn't undone).
+vec3 getTransmittedLight(float perceptualRoughness)
+{
+    vec2 size = vec2(textureSize(u_TransmissionSceneSampler, 0));
+    float lod = log2(max(size.x, size.y)) * clamp(perceptualRoughness * 1.5 - 0.25, 0.0, 1.0);
+    vec3 sceneColor = textureLod(u_TransmissionSceneSampler, gl_FragCoord.xy / size, lod).rgb;
+    if (u_ToneMapping != 3) {
+        sceneColor = pow(sceneColor, vec3(2.2));
+    }
+    return sceneColor / max(u_Exposure, 0.0001);
+}
+#endif
+
+// Applies exposure and the selected tone mapping operator to a linear HDR color
+vec3 toneMap(vec3 color)
+{
+    color *= u_Exposure;
+    if (u_ToneMapping == 1) {
+        return color / (color + vec3(1.0));
+    }
+    else if (u_ToneMapping == 2) {
+        // ACES filmic curve fit by Krzysztof Narkowicz
+        // https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+        const float a = 2.51;
+        const float b = 0.03;
+        const float c = 2.43;
+        const float d = 0.59;
+        const float e = 0.14;
+        return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+    }
+    return color;
+}
+
+// Colors are computed in linear space, but the framebuffer expects sRGB (it isn't an sRGB framebuffer,
+// so there's no automatic conversion). Color textures are decoded when sampling (sRGB internal format).
+vec3 linearToSrgb(vec3 color)
+{
+    if (u_ToneMapping == 3) {
+        return color; // HDR output
+    }
+    return pow(color, vec3(1.0 / 2.2));
+}
+
 void main()
 {
+#ifdef PICKING
+    FragColor = u_PickColor;
+    return;
+#endif
+
+#ifdef SHADOW_PASS
+    // depth only
+    return;
+#endif
+
     // Metallic and Roughness material properties are packed together
     // In glTF, these factors can be specified by fixed scalar values
     // or from a metallic-roughness map
@@ -218,7 +465,7 @@ void main()
 #ifdef HAS_METALROUGHNESSMAP
     // Roughness is stored in the 'g' channel, metallic is stored in the 'b' channel.
     // This layout intentionally reserves the 'r' channel for (optional) occlusion map data
-    vec4 mrSample = texture(u_MetallicRoughnessSampler, v_UV);
+    vec4 mrSample = texture(u_MetallicRoughnessSampler, UV_TRANSFORM(UV(u_MetallicRoughnessTexCoord), u_MetallicRoughnessUVTransform));
     perceptualRoughness = mrSample.g * perceptualRoughness;
     metallic = mrSample.b * metallic;
 #endif
@@ -230,16 +477,43 @@ void main()
 
     // The albedo may be defined from a base texture or a flat color
 #ifdef HAS_BASECOLORMAP
-    vec4 baseColor = texture(u_BaseColorSampler, v_UV) * u_BaseColorFactor;
+    vec4 baseColor = texture(u_BaseColorSampler, UV_TRANSFORM(UV(u_BaseColorTexCoord), u_BaseColorUVTransform)) * u_BaseColorFactor;
 #else
     vec4 baseColor = u_BaseColorFactor;
 #endif
     // spec: COLOR_0 ... acts as an additional linear multiplier to baseColor
     baseColor *= v_Color;
 
+#ifdef CLAY
+    // NOTE: the textures are already disabled via the shader flags (see Primitive::prepare_draw)
+    baseColor = vec4(CLAY_COLOR, 1.0);
+    perceptualRoughness = CLAY_ROUGHNESS;
+    alphaRoughness = perceptualRoughness * perceptualRoughness;
+    metallic = 0.0;
+#endif
+
+#ifdef ALPHA_MASK
+    if (baseColor.a < u_AlphaCutoff) {
+        discard;
+    }
+    baseColor.a = 1.0;
+#elif !defined(ALPHA_BLEND)
+    // spec: OPAQUE - the alpha value is ignored
+    baseColor.a = 1.0;
+#endif
+
     vec3 f0 = vec3(0.04);
     vec3 diffuseColor = baseColor.rgb * (vec3(1.0) - f0);
     diffuseColor *= 1.0 - metallic;
+#ifdef TRANSMISSION
+    float transmission = u_TransmissionFactor;
+#ifdef HAS_TRANSMISSIONMAP
+    transmission *= texture(u_TransmissionSampler, UV(u_TransmissionTexCoord)).r;
+#endif
+    // the transmitted light (tinted by the base color) replaces part of the diffuse reflection
+    vec3 transmissionColor = diffuseColor * transmission;
+    diffuseColor *= 1.0 - transmission;
+#endif
     vec3 specularColor = mix(f0, baseColor.rgb, metallic);
 
     // Compute reflectance.
@@ -252,23 +526,66 @@ void main()
     vec3 specularEnvironmentR90 = vec3(1.0, 1.0, 1.0) * reflectance90;
 
     vec3 n = getNormal();                             // normal at surface point
+
+#ifdef DEBUG_NORMALS
+    // world space normal as color. NOTE: undoes the backface flip, so that inverted normals stand out
+    FragColor = vec4(n * (2.0 * float(gl_FrontFacing) - 1.0) * 0.5 + 0.5, 1.0);
+    return;
+#endif
+
+    // isolated material channels (with the defaults if a texture is missing)
+#if defined(DEBUG_BASE_COLOR)
+    FragColor = vec4(linearToSrgb(baseColor.rgb), 1.0);
+    return;
+#elif defined(DEBUG_METALLIC)
+    FragColor = vec4(vec3(metallic), 1.0);
+    return;
+#elif defined(DEBUG_ROUGHNESS)
+    FragColor = vec4(vec3(perceptualRoughness), 1.0);
+    return;
+#elif defined(DEBUG_NORMAL_MAP)
+#ifdef HAS_NORMALMAP
+    FragColor = vec4(texture(u_NormalSampler, UV_TRANSFORM(UV(u_NormalTexCoord), u_NormalUVTransform)).rgb, 1.0);
+#else
+    FragColor = vec4(0.5, 0.5, 1.0, 1.0); // flat
+#endif
+    return;
+#elif defined(DEBUG_OCCLUSION)
+#ifdef HAS_OCCLUSIONMAP
+    FragColor = vec4(vec3(texture(u_OcclusionSampler, UV_TRANSFORM(UV(u_OcclusionTexCoord), u_OcclusionUVTransform)).r), 1.0);
+#else
+    FragColor = vec4(1.0); // not occluded
+#endif
+    return;
+#elif defined(DEBUG_EMISSIVE)
+#ifdef HAS_EMISSIVEMAP
+    FragColor = vec4(linearToSrgb(texture(u_EmissiveSampler, UV_TRANSFORM(UV(u_EmissiveTexCoord), u_EmissiveUVTransform)).rgb * u_EmissiveFactor), 1.0);
+#else
+    FragColor = vec4(linearToSrgb(u_EmissiveFactor), 1.0);
+#endif
+    return;
+#endif
+
+#ifdef UNLIT
+    // KHR_materials_unlit: no lighting, tone mapping or exposure
+    FragColor = vec4(linearToSrgb(baseColor.rgb), baseColor.a);
+#ifdef HIGHLIGHT
+    FragColor.rgb = mix(FragColor.rgb, HIGHLIGHT_COLOR, HIGHLIGHT_STRENGTH);
+#endif
+    return;
+#endif
     vec3 v = normalize(u_Camera - v_Position);        // Vector from surface point to camera
-    vec3 l = normalize(u_LightDirection);             // Vector from surface point to light
-    vec3 h = normalize(l+v);                          // Half vector between both l and v
     vec3 reflection = -normalize(reflect(v, n));
 
-    float NdotL = clamp(dot(n, l), 0.001, 1.0);
     float NdotV = abs(dot(n, v)) + 0.001;
-    float NdotH = clamp(dot(n, h), 0.0, 1.0);
-    float LdotH = clamp(dot(l, h), 0.0, 1.0);
-    float VdotH = clamp(dot(v, h), 0.0, 1.0);
 
+    // the light dependent terms are set per light below
     PBRInfo pbrInputs = PBRInfo(
-        NdotL,
+        0.0,
         NdotV,
-        NdotH,
-        LdotH,
-        VdotH,
+        0.0,
+        0.0,
+        0.0,
         perceptualRoughness,
         metallic,
         specularEnvironmentR0,
@@ -278,15 +595,69 @@ void main()
         specularColor
     );
 
-    // Calculate the shading terms for the microfacet specular shading model
-    vec3 F = specularReflection(pbrInputs);
-    float G = geometricOcclusion(pbrInputs);
-    float D = microfacetDistribution(pbrInputs);
+#ifdef CLEARCOAT
+    float clearcoat = u_ClearcoatFactor;
+#ifdef HAS_CLEARCOATMAP
+    clearcoat *= texture(u_ClearcoatSampler, UV(u_ClearcoatTexCoord)).r;
+#endif
+    float clearcoatRoughness = u_ClearcoatRoughnessFactor;
+#ifdef HAS_CLEARCOATROUGHNESSMAP
+    clearcoatRoughness *= texture(u_ClearcoatRoughnessSampler, UV(u_ClearcoatRoughnessTexCoord)).g;
+#endif
+    clearcoatRoughness = clamp(clearcoatRoughness, c_MinRoughness, 1.0);
+    vec3 clearcoatNormal = getClearcoatNormal();
+    PBRInfo clearcoatInputs = PBRInfo(
+        0.0, 0.0, 0.0, 0.0, 0.0,
+        clearcoatRoughness,
+        0.0,
+        vec3(0.04),
+        vec3(1.0),
+        clearcoatRoughness * clearcoatRoughness,
+        vec3(0.0),
+        vec3(0.04)
+    );
+    vec3 clearcoatColor = vec3(0.0);
+#endif
 
     // Calculation of analytical lighting contribution
-    vec3 diffuseContrib = (1.0 - F) * diffuse(pbrInputs);
-    vec3 specContrib = F * G * D / (4.0 * NdotL * NdotV);
-    vec3 color = NdotL * u_LightColor * (diffuseContrib + specContrib);
+    // (the shading terms of the last light are kept for the debug visualization below)
+    vec3 color = vec3(0.0);
+    vec3 F = vec3(0.0);
+    float G = 0.0;
+    float D = 0.0;
+    vec3 diffuseContrib = vec3(0.0);
+    vec3 specContrib = vec3(0.0);
+    for (int i = 0; i < MAX_LIGHTS; ++i) {
+        if (i >= u_LightCount) {
+            break;
+        }
+        vec3 pointToLight = u_LightTypes[i] == LIGHT_DIRECTIONAL ? u_LightDirections[i] : u_LightPositions[i] - v_Position;
+        vec3 l = normalize(pointToLight);             // Vector from surface point to light
+        vec3 h = normalize(l+v);                      // Half vector between both l and v
+
+        pbrInputs.NdotL = clamp(dot(n, l), 0.001, 1.0);
+        pbrInputs.NdotH = clamp(dot(n, h), 0.0, 1.0);
+        pbrInputs.LdotH = clamp(dot(l, h), 0.0, 1.0);
+        pbrInputs.VdotH = clamp(dot(v, h), 0.0, 1.0);
+
+        // Calculate the shading terms for the microfacet specular shading model
+        F = specularReflection(pbrInputs);
+        G = geometricOcclusion(pbrInputs);
+        D = microfacetDistribution(pbrInputs);
+
+        diffuseContrib = (1.0 - F) * diffuse(pbrInputs);
+        specContrib = F * G * D / (4.0 * pbrInputs.NdotL * NdotV);
+        float attenuation = lightAttenuation(i, pointToLight);
+#ifdef SHADOWS
+        if (i == u_ShadowLight) {
+            attenuation *= shadowFactor(n, l);
+        }
+#endif
+        color += pbrInputs.NdotL * u_LightColors[i] * attenuation * (diffuseContrib + specContrib);
+#ifdef CLEARCOAT
+        clearcoatColor += u_LightColors[i] * attenuation * clearcoatSpecular(clearcoatInputs, clearcoatNormal, v, l);
+#endif
+    }
 
     // Calculate lighting contribution from image based lighting source (IBL)
 #ifdef USE_IBL
@@ -298,15 +669,35 @@ void main()
 
     // Apply optional PBR terms for additional (optional) shading
 #ifdef HAS_OCCLUSIONMAP
-    float ao = texture(u_OcclusionSampler, v_UV).r;
+    float ao = texture(u_OcclusionSampler, UV_TRANSFORM(UV(u_OcclusionTexCoord), u_OcclusionUVTransform)).r;
     color = mix(color, color * ao, u_OcclusionStrength);
 #endif
 
+#ifdef TRANSMISSION
+    color += getTransmittedLight(perceptualRoughness) * transmissionColor;
+#endif
+
+#ifdef CLEARCOAT
+    float clearcoatNdotV = abs(dot(clearcoatNormal, v)) + 0.001;
+#ifdef USE_IBL
+    clearcoatColor += getIBLClearcoat(clearcoatRoughness, clearcoatNdotV,
+        -normalize(reflect(v, clearcoatNormal)));
+#endif
+    // the base layer only receives the light that isn't reflected by the coat
+    float clearcoatFresnel = 0.04 + 0.96 * pow(clamp(1.0 - clearcoatNdotV, 0.0, 1.0), 5.0);
+    color = color * (1.0 - clearcoat * clearcoatFresnel) + clearcoat * clearcoatColor;
+#endif
+
+#ifndef CLAY
+    vec3 emissive = u_EmissiveFactor;
 #ifdef HAS_EMISSIVEMAP
-    vec3 emissive = texture(u_EmissiveSampler, v_UV).rgb * u_EmissiveFactor;
+    emissive *= texture(u_EmissiveSampler, UV_TRANSFORM(UV(u_EmissiveTexCoord), u_EmissiveUVTransform)).rgb;
+#endif
     color += emissive;
 #endif
 
+    color = linearToSrgb(toneMap(color));
+
     // This section uses mix to override final color for reference app visualization
     // of various parameters in the lighting equation.
     color = mix(color, F, u_ScaleFGDSpec.x);
@@ -319,5 +710,9 @@ void main()
     color = mix(color, vec3(metallic), u_ScaleDiffBaseMR.z);
     color = mix(color, vec3(perceptualRoughness), u_ScaleDiffBaseMR.w);
 
+#ifdef HIGHLIGHT
+    color = mix(color, HIGHLIGHT_COLOR, HIGHLIGHT_STRENGTH);
+#endif
+
     FragColor = vec4(color, baseColor.a);
 }
@@ -12,16 +12,53 @@ layout (location = 2) in vec4 a_Tangent;
 #ifdef HAS_UV
 layout (location = 3) in vec2 a_UV; // TEXCOORD_0
 #endif
-// TODO!: tex_coord_1, joints_0, weights_0
+#ifdef HAS_UV_1
+layout (location = 4) in vec2 a_UV1; // TEXCOORD_1
+#endif
 #ifdef HAS_COLORS
 layout (location = 5) in vec4 a_Color; // COLOR_0
 #endif
+#ifdef HAS_SKIN
+layout (location = 6) in vec4 a_Joint; // JOINTS_0
+layout (location = 7) in vec4 a_Weight; // WEIGHTS_0
+
+// one row per joint, one texel per matrix column
+uniform sampler2D u_JointMatrixSampler;
+
+mat4 getJointMatrix(float joint)
+{
+  int row = int(joint);
+  return mat4(
+    texelFetch(u_JointMatrixSampler, ivec2(0, row), 0),
+    texelFetch(u_JointMatrixSampler, ivec2(1, row), 0),
+    texelFetch(u_JointMatrixSampler, ivec2(2, row), 0),
+    texelFetch(u_JointMatrixSampler, ivec2(3, row), 0));
+}
+#endif
+#ifdef HAS_MORPH_TARGETS
+#define MAX_MORPH_TARGETS 8
+// displacements: (2 * target + attribute) * u_NumVertices + vertex (attribute 0: position, 1: normal)
+uniform samplerBuffer u_MorphTargetSampler;
+uniform int u_NumMorphTargets;
+uniform int u_NumVertices;
+uniform float u_MorphWeights[MAX_MORPH_TARGETS];
+#endif
 
+#ifdef INSTANCED
+layout (location = 8) in mat4 a_ModelMatrix; // per instance, locations 8-11
+uniform mat4 u_ViewProjectionMatrix;
+#define MODEL_MATRIX a_ModelMatrix
+#else
 uniform mat4 u_MVPMatrix;
 uniform mat4 u_ModelMatrix;
+#define MODEL_MATRIX u_ModelMatrix
+#endif
 
 out vec3 v_Position;
 out vec2 v_UV;
+#ifdef HAS_UV_1
+out vec2 v_UV1;
+#endif
 out vec4 v_Color;
 
 #ifdef HAS_NORMALS
@@ -35,17 +72,42 @@ out vec3 v_Normal;
 
 void main()
 {
-  vec4 pos = u_ModelMatrix * a_Position;
+  // linear blend skinning (joint matrices are relative to the mesh node)
+  #ifdef HAS_SKIN
+  mat4 skinMatrix =
+    a_Weight.x * getJointMatrix(a_Joint.x) +
+    a_Weight.y * getJointMatrix(a_Joint.y) +
+    a_Weight.z * getJointMatrix(a_Joint.z) +
+    a_Weight.w * getJointMatrix(a_Joint.w);
+  #else
+  mat4 skinMatrix = mat4(1.0);
+  #endif
+  vec4 morphedPosition = a_Position;
+  #ifdef HAS_NORMALS
+  vec4 morphedNormal = vec4(a_Normal.xyz, 0.0);
+  #endif
+  #ifdef HAS_MORPH_TARGETS
+  for (int i = 0; i < u_NumMorphTargets; i++) {
+    int offset = 2 * i * u_NumVertices + gl_VertexID;
+    morphedPosition.xyz += u_MorphWeights[i] * texelFetch(u_MorphTargetSampler, offset).xyz;
+    #ifdef HAS_NORMALS
+    morphedNormal.xyz += u_MorphWeights[i] * texelFetch(u_MorphTargetSampler, offset + u_NumVertices).xyz;
+    #endif
+  }
+  #endif
+  vec4 position = skinMatrix * morphedPosition;
+
+  vec4 pos = MODEL_MATRIX * position;
   v_Position = vec3(pos.xyz) / pos.w;
 
   #ifdef HAS_NORMALS
   #ifdef HAS_TANGENTS
-  vec3 normalW = normalize(vec3(u_ModelMatrix * vec4(a_Normal.xyz, 0.0)));
-  vec3 tangentW = normalize(vec3(u_ModelMatrix * vec4(a_Tangent.xyz, 0.0)));
+  vec3 normalW = normalize(vec3(MODEL_MATRIX * skinMatrix * morphedNormal));
+  vec3 tangentW = normalize(vec3(MODEL_MATRIX * skinMatrix * vec4(a_Tangent.xyz, 0.0)));
   vec3 bitangentW = cross(normalW, tangentW) * a_Tangent.w;
   v_TBN = mat3(tangentW, bitangentW, normalW);
   #else // HAS_TANGENTS != 1
-  v_Normal = normalize(vec3(u_ModelMatrix * vec4(a_Normal.xyz, 0.0)));
+  v_Normal = normalize(vec3(MODEL_MATRIX * skinMatrix * morphedNormal));
   #endif
   #endif
 
@@ -54,6 +116,9 @@ void main()
   #else
   v_UV = vec2(0.,0.);
   #endif
+  #ifdef HAS_UV_1
+  v_UV1 = a_UV1;
+  #endif
 
   #ifdef HAS_COLORS
   v_Color = a_Color;
@@ -61,7 +126,11 @@ void main()
   v_Color = vec4(1.0);
   #endif
 
-  gl_Position = u_MVPMatrix * a_Position; // needs w for proper perspective correction
+  #ifdef INSTANCED
+  gl_Position = u_ViewProjectionMatrix * pos;
+  #else
+  gl_Position = u_MVPMatrix * position; // needs w for proper perspective correction
+  #endif
 }
 
 
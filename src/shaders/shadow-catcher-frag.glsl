@@ -0,0 +1,43 @@
+#version 330 core
+in vec3 v_WorldPosition;
+
+uniform vec3 u_Center;
+uniform float u_Extent;
+uniform sampler2D u_ShadowMap;
+uniform mat4 u_LightSpaceMatrix; // world space -> light clip space
+uniform float u_ShadowBias;
+uniform float u_Opacity;
+
+out vec4 FragColor;
+
+// Fraction of the shadow map texels around the fragment that occlude it, averaged over 5x5 texels
+// for soft edges (see `shadowFactor` in pbr-frag.glsl)
+float occlusion()
+{
+    vec4 lightSpacePosition = u_LightSpaceMatrix * vec4(v_WorldPosition, 1.0);
+    vec3 coords = lightSpacePosition.xyz / lightSpacePosition.w * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 0.0;
+    }
+    vec2 texelSize = 1.0 / vec2(textureSize(u_ShadowMap, 0));
+    float occluded = 0.0;
+    for (int x = -2; x <= 2; ++x) {
+        for (int y = -2; y <= 2; ++y) {
+            float depth = texture(u_ShadowMap, coords.xy + vec2(x, y) * texelSize).r;
+            occluded += coords.z - u_ShadowBias > depth ? 1.0 : 0.0;
+        }
+    }
+    return occluded / 25.0;
+}
+
+void main()
+{
+    // only the shadow is visible, the plane itself is transparent
+    float alpha = occlusion() * u_Opacity;
+    float distance = length(v_WorldPosition.xz - u_Center.xz) / u_Extent;
+    alpha *= 1.0 - smoothstep(0.5, 1.0, distance);
+    if (alpha <= 0.0)
+        discard;
+
+    FragColor = vec4(0.0, 0.0, 0.0, alpha);
+}
@@ -0,0 +1,41 @@
+#version 330 core
+in vec3 v_Direction;
+
+uniform samplerCube u_EnvironmentSampler;
+
+// see pbr-frag.glsl
+uniform float u_Exposure;
+uniform int u_ToneMapping;
+
+out vec4 FragColor;
+
+vec3 toneMap(vec3 color)
+{
+    color *= u_Exposure;
+    if (u_ToneMapping == 1) {
+        return color / (color + vec3(1.0));
+    }
+    else if (u_ToneMapping == 2) {
+        const float a = 2.51;
+        const float b = 0.03;
+        const float c = 2.43;
+        const float d = 0.59;
+        const float e = 0.14;
+        return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+    }
+    return color;
+}
+
+vec3 linearToSrgb(vec3 color)
+{
+    if (u_ToneMapping == 3) {
+        return color;
+    }
+    return pow(color, vec3(1.0 / 2.2));
+}
+
+void main()
+{
+    vec3 color = textureLod(u_EnvironmentSampler, v_Direction, 0.0).rgb;
+    FragColor = vec4(linearToSrgb(toneMap(color)), 1.0);
+}
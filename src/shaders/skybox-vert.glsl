@@ -0,0 +1,13 @@
+#version 330 core
+layout (location = 0) in vec3 a_Position;
+
+uniform mat4 u_ViewProjection; // rotation only
+
+out vec3 v_Direction;
+
+void main()
+{
+    v_Direction = a_Position;
+    vec4 position = u_ViewProjection * vec4(a_Position, 1.0);
+    gl_Position = position.xyww; // at the far plane
+}
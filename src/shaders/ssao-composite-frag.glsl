@@ -0,0 +1,27 @@
+// Darkens the scene color by the (blurred) ambient occlusion and restores the scene depth
+#version 330 core
+in vec2 v_UV;
+
+out vec4 FragColor;
+
+uniform sampler2D u_ColorSampler;
+uniform sampler2D u_DepthSampler;
+uniform sampler2D u_OcclusionSampler;
+uniform float u_Intensity; // 0: no darkening, 1: full
+
+void main()
+{
+    // 4x4 box blur, matching the noise pattern of ssao-frag.glsl
+    vec2 texelSize = 1.0 / vec2(textureSize(u_OcclusionSampler, 0));
+    float occlusion = 0.0;
+    for (int x = -2; x < 2; ++x) {
+        for (int y = -2; y < 2; ++y) {
+            occlusion += texture(u_OcclusionSampler, v_UV + vec2(x, y) * texelSize).r;
+        }
+    }
+    occlusion /= 16.0;
+
+    vec4 color = texture(u_ColorSampler, v_UV);
+    FragColor = vec4(color.rgb * mix(1.0, occlusion, u_Intensity), color.a);
+    gl_FragDepth = texture(u_DepthSampler, v_UV).r;
+}
@@ -0,0 +1,60 @@
+// Screen space ambient occlusion from the depth buffer (normals are reconstructed from depth)
+// Based on https://learnopengl.com/Advanced-Lighting/SSAO
+#version 330 core
+in vec2 v_UV;
+
+out float FragColor; // 1: not occluded
+
+uniform sampler2D u_DepthSampler;
+uniform mat4 u_Projection;
+uniform mat4 u_InverseProjection;
+uniform float u_Radius; // view space
+
+const int SAMPLE_COUNT = 16;
+const float GOLDEN_ANGLE = 2.39996323;
+
+vec3 viewPosition(vec2 uv)
+{
+    float depth = texture(u_DepthSampler, uv).r;
+    vec4 position = u_InverseProjection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    return position.xyz / position.w;
+}
+
+void main()
+{
+    vec3 position = viewPosition(v_UV);
+    // before the background check, since derivatives need uniform control flow
+    vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+    if (texture(u_DepthSampler, v_UV).r >= 1.0) {
+        FragColor = 1.0;
+        return;
+    }
+
+    // rotate the sample kernel per pixel in a 4x4 pattern (removed by the blur in ssao-composite-frag.glsl)
+    ivec2 pixel = ivec2(gl_FragCoord.xy) % 4;
+    float angle = float(pixel.x * 4 + pixel.y) / 16.0 * 6.2831853;
+    vec3 randomVec = vec3(cos(angle), sin(angle), 0.0);
+    vec3 tangent = normalize(randomVec - normal * dot(randomVec, normal));
+    mat3 tbn = mat3(tangent, cross(normal, tangent), normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < SAMPLE_COUNT; ++i) {
+        // spiral in the hemisphere around the normal, denser close to the fragment
+        float t = (float(i) + 0.5) / float(SAMPLE_COUNT);
+        float cosTheta = sqrt(1.0 - t);
+        float sinTheta = sqrt(t);
+        float phi = float(i) * GOLDEN_ANGLE;
+        vec3 direction = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+        float scale = mix(0.1, 1.0, t * t);
+        vec3 samplePosition = position + tbn * direction * u_Radius * scale;
+
+        vec4 offset = u_Projection * vec4(samplePosition, 1.0);
+        vec2 sampleUV = offset.xy / offset.w * 0.5 + 0.5;
+        float sampleDepth = viewPosition(sampleUV).z;
+
+        // ignore occluders far in front of the fragment (e.g. at silhouettes)
+        float rangeCheck = smoothstep(0.0, 1.0, u_Radius / abs(position.z - sampleDepth));
+        occlusion += (sampleDepth >= samplePosition.z + 0.025 * u_Radius ? 1.0 : 0.0) * rangeCheck;
+    }
+    FragColor = 1.0 - occlusion / float(SAMPLE_COUNT);
+}
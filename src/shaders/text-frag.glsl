@@ -0,0 +1,14 @@
+#version 330 core
+in vec2 v_UV;
+
+uniform sampler2D u_FontSampler;
+uniform vec4 u_Color;
+
+out vec4 FragColor;
+
+void main()
+{
+    if (texture(u_FontSampler, v_UV).r < 0.5)
+        discard;
+    FragColor = u_Color;
+}
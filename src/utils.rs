@@ -1,11 +1,13 @@
 #![macro_use]
 
 use std::ffi::CStr;
-use std::mem;
+use std::fs::File;
+use std::io;
 use std::time::{Duration, Instant};
 
 use gl;
 use gl::types::GLubyte;
+use serde_json;
 
 pub fn elapsed(start_time: &Instant) -> String {
     let elapsed = start_time.elapsed();
@@ -37,6 +39,31 @@ pub fn print_elapsed(message: &str, start_time: &Instant) {
     info!("{:<25}{}", message, elapsed(start_time));
 }
 
+pub fn duration_ms(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + f64::from(duration.subsec_nanos()) / 1_000_000.0
+}
+
+/// Startup timings in milliseconds, saved with `--timings-json` (e.g. to track load time regressions)
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct StartupTimings {
+    pub download_ms: Option<f64>, // only for URLs
+    pub import_ms: f64,
+    pub scene_build_ms: f64,
+    pub first_frame_ms: Option<f64>, // drawing the first frame, including shader compilation
+}
+
+impl StartupTimings {
+    /// Writes the timings as JSON to `filename`, or to stdout for `-`
+    pub fn save_json(&self, filename: &str) -> io::Result<()> {
+        let result = if filename == "-" {
+            serde_json::to_writer_pretty(io::stdout(), self).map(|_| println!())
+        } else {
+            serde_json::to_writer_pretty(File::create(filename)?, self)
+        };
+        result.map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+}
+
 pub struct FrameTimer {
     message: String,
     averaging_window: usize,
@@ -47,10 +74,12 @@ pub struct FrameTimer {
 /// Timing helper that averages timings over `averaging_window`
 // frames and then prints avg/min/max
 impl FrameTimer {
+    /// A window of 0 is treated as 1 (i.e. every frame is printed)
     pub fn new(message: &str, averaging_window: usize) -> FrameTimer {
+        let averaging_window = averaging_window.max(1);
         FrameTimer {
             message: message.to_owned(),
-            averaging_window: averaging_window,
+            averaging_window,
             current_frame_start: Instant::now(),
             frame_times: Vec::with_capacity(averaging_window),
         }
@@ -67,18 +96,24 @@ impl FrameTimer {
         }
     }
 
+    /// Also usable for a partial window, e.g. when exiting. Does nothing without timings.
     pub fn print_and_reset(&mut self) {
-        {
-            let avg = self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32;
-            let min = self.frame_times.iter().min().unwrap();
-            let max = self.frame_times.iter().max().unwrap();
-            info!("{:<15}{} (min: {}, max: {})", self.message,
-                format_duration(avg), format_duration(*min), format_duration(*max));
+        if self.frame_times.is_empty() {
+            return
         }
+        info!("{:<15}{}", self.message, format_frame_stats(&self.frame_times));
         self.frame_times.clear();
     }
 }
 
+/// Average, min and max of `frame_times` (must not be empty)
+pub fn format_frame_stats(frame_times: &[Duration]) -> String {
+    let avg = frame_times.iter().sum::<Duration>() / frame_times.len() as u32;
+    let min = frame_times.iter().min().unwrap();
+    let max = frame_times.iter().max().unwrap();
+    format!("{} (min: {}, max: {})", format_duration(avg), format_duration(*min), format_duration(*max))
+}
+
 pub unsafe fn gl_check_error(file: &str, line: u32) -> u32 {
     let mut error_code = gl::GetError();
     while error_code != gl::NO_ERROR {
@@ -115,7 +150,7 @@ pub unsafe fn print_context_info() {
     debug!("Version      : {}", gl_string(gl::GetString(gl::VERSION)));
     debug!("GLSL         : {}", gl_string(gl::GetString(gl::SHADING_LANGUAGE_VERSION)));
 
-    let mut val = mem::uninitialized();
+    let mut val = 0;
     gl::GetIntegerv(gl::CONTEXT_PROFILE_MASK, &mut val);
     let val = val as gl::types::GLenum;
     let profile = if (val & gl::CONTEXT_COMPATIBILITY_PROFILE_BIT) != 0 {
@@ -128,7 +163,7 @@ pub unsafe fn print_context_info() {
     debug!("Profile      : {}", profile);
 
     let (debug, forward_compatible) = {
-        let mut val = mem::uninitialized();
+        let mut val = 0;
         gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut val);
         let val = val as gl::types::GLenum;
         ((val & gl::CONTEXT_FLAG_DEBUG_BIT) != 0,
@@ -136,12 +171,44 @@ pub unsafe fn print_context_info() {
     };
     debug!("Context Flags: Debug: {}, Forward Compatible: {}", debug, forward_compatible);
 
+    debug!("Extensions   : {}", extensions().join(", "))
+}
+
+pub unsafe fn extensions() -> Vec<String> {
     let mut num_extensions = 0;
     gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut num_extensions);
-    let extensions: Vec<_> = (0 .. num_extensions).map(|num| {
+    (0 .. num_extensions).map(|num| {
         gl_string(gl::GetStringi(gl::EXTENSIONS, num as gl::types::GLuint))
-    }).collect();
-    debug!("Extensions   : {}", extensions.join(", "))
+    }).collect()
+}
+
+/// Whether the loaded GL functions include instanced drawing (core since OpenGL 3.3,
+/// might be missing in the fallback headless context)
+pub fn instancing_supported() -> bool {
+    gl::VertexAttribDivisor::is_loaded() &&
+        gl::DrawElementsInstanced::is_loaded() &&
+        gl::DrawArraysInstanced::is_loaded()
+}
+
+/// Decodes the `%XX` escapes of a URI (e.g. `%20` for a space). Invalid escapes are kept as they are.
+pub fn percent_decode(uri: &str) -> String {
+    let hex_value = |c: &u8| (*c as char).to_digit(16).map(|digit| digit as u8);
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1).and_then(&hex_value), bytes.get(i + 2).and_then(&hex_value)) {
+            (b'%', Some(high), Some(low)) => {
+                decoded.push(high << 4 | low);
+                i += 3;
+            },
+            (byte, _, _) => {
+                decoded.push(byte);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
 pub unsafe fn gl_string(raw_string: *const GLubyte) -> String {
@@ -1,11 +1,16 @@
+use std::collections::HashMap;
+use std::error::Error;
 use std::f32::consts::PI;
-use std::fs::File;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
 use std::os::raw::c_void;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::time::Instant;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use cgmath::{ Point3 };
 use collision::Aabb;
 use gl;
 use glutin;
@@ -13,6 +18,8 @@ use glutin::{
     Api,
     MouseScrollDelta,
     MouseButton,
+    Touch,
+    TouchPhase,
     GlContext,
     GlRequest,
     GlProfile,
@@ -21,33 +28,131 @@ use glutin::{
 };
 use glutin::ElementState::*;
 
+use gltf;
 use gltf_importer;
 use gltf_importer::config::ValidationStrategy;
-use image::{DynamicImage, ImageFormat};
+use gif;
+use gif::SetParameter;
+use image::{self, imageops, ColorType, DynamicImage, GenericImage, ImageError, ImageFormat, RgbaImage};
+use image::jpeg::JPEGEncoder;
+use image::png::PNGEncoder;
+use num_traits::clamp;
+use serde_json;
 
 
-use controls::{OrbitControls, NavState};
+use controls::{CameraParams, CameraPose, OrbitControls, NavMode, NavState, SPEED};
 use controls::CameraMovement::*;
+use file_watch::FileWatch;
+#[cfg(feature = "gamepad")]
+use gamepad::Gamepads;
+use info::{animation_list, camera_list, scene_tree, ModelInfo};
 use framebuffer::Framebuffer;
+use hdr_image::HdrImage;
+use http_source::{self, HttpSource};
+use palette::{dominant_colors, save_palette};
 use render::*;
+use shader::{ShaderFlags, ToneMapping, DEBUG_CHANNELS};
 use render::math::*;
-use utils::{print_elapsed, FrameTimer, gl_check_error, print_context_info};
-
-// TODO!: complete and pass through draw calls? or get rid of multiple shaders?
-// How about state ordering anyway?
-// struct DrawState {
-//     current_shader: ShaderFlags,
-//     back_face_culling_enabled: bool
-// }
+use utils::{duration_ms, format_frame_stats, print_elapsed, FrameTimer, StartupTimings, gl_check_error,
+    instancing_supported, print_context_info};
 
 pub struct CameraOptions {
     pub index: i32,
+    /// Name of the camera node to use, takes precedence over `index`
+    pub name: Option<String>,
     pub position: Option<Vector3>,
     pub target: Option<Vector3>,
     pub fovy: f32,
+    pub orthographic: bool,
+    /// JSON file with a saved `CameraPose`
+    pub pose_file: Option<String>,
+
+    // mouse sensitivity multipliers
+    pub rotate_sensitivity: f32,
+    pub pan_sensitivity: f32,
+    pub zoom_sensitivity: f32,
+    pub invert_scroll: bool,
+    /// Scrolling zooms towards the point under the cursor instead of the target
+    pub zoom_to_cursor: bool,
+    /// Keyboard movement speed in units per second. Default: derived from the scene size.
+    pub move_speed: Option<f32>,
+    /// Limits for the distance between camera and orbit target. Default: derived from the scene size.
+    pub min_distance: Option<f32>,
+    pub max_distance: Option<f32>,
+    /// Duration of the eased transition when switching the camera at runtime (seconds, 0: instant)
+    pub transition: f32,
+    /// Spin the model around the target at this speed (degrees per second) while there's no input
+    /// for `idle_timeout` seconds, e.g. for unattended displays
+    pub auto_rotate: Option<f32>,
+    pub idle_timeout: f32,
+}
+
+pub struct RenderOptions {
+    /// RGBA clear color. Default: transparent for screenshots, dark blue otherwise.
+    pub background: Option<[f32; 4]>,
+    /// Vertical gradient (top and bottom RGB color) drawn instead of the background color.
+    /// Left out of screenshots with a transparent background.
+    pub background_gradient: Option<([f32; 3], [f32; 3])>,
+    /// Forces a transparent (true) or opaque (false) background, overriding the alpha of `background`.
+    /// Default: as described for `background`.
+    pub transparent: Option<bool>,
+    /// Direction towards the directional light (world space).
+    /// Default: from above and behind the initial camera position.
+    pub light_direction: Option<Vector3>,
+    /// Number of samples for multisample anti-aliasing (0: off)
+    pub msaa_samples: u16,
+    /// Synchronize buffer swaps with the display refresh (off: uncapped frame rate, e.g. for benchmarking)
+    pub vsync: bool,
+    /// Skip redundant program switches and material setup between draws (off: for comparison)
+    pub state_caching: bool,
+    /// Draw opaque primitives grouped by shader variant and material (off: in scene order, for comparison)
+    pub draw_sorting: bool,
+    /// Draw nodes sharing a mesh with instanced draw calls (if supported by the context).
+    /// Off by default until compared with the individual draws on more drivers.
+    pub instancing: bool,
+    /// Linear multiplier applied before tone mapping
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+    /// Equirectangular .hdr file for image based lighting and as background
+    pub environment: Option<String>,
+    /// Show a reference grid below the scene (also in screenshots with transparent background)
+    pub grid: bool,
+    /// Debug visualization, e.g. `ShaderFlags::DEBUG_NORMALS`
+    pub debug_flags: ShaderFlags,
+    /// Shadow map for the primary directional light
+    pub shadows: bool,
+    /// Depth bias of the shadow map lookup (against shadow acne)
+    pub shadow_bias: f32,
+    /// Transparent ground plane that only shows the received shadows (implies `shadows`)
+    pub shadow_catcher: bool,
+    /// Screen space ambient occlusion post-process
+    pub ssao: bool,
+    /// SSAO sampling radius in scene units. Default: derived from the scene size.
+    pub ssao_radius: Option<f32>,
+    /// SSAO darkening in [0, 1]
+    pub ssao_intensity: f32,
+    /// Outlines of silhouettes and creases (post-process), e.g. for technical illustrations
+    pub outline: bool,
+    /// Outline thickness in pixels
+    pub outline_thickness: f32,
+    pub outline_color: [f32; 4],
+    /// Flat color replacing the shading while outlines are shown
+    pub outline_fill: Option<[f32; 4]>,
+    /// Red/cyan anaglyph stereo
+    pub stereo: bool,
+    /// Depth offset of all materials, e.g. against z-fighting of decals
+    pub polygon_offset: Option<PolygonOffset>,
+    /// Depth offsets of individual materials (by name), overriding `polygon_offset`
+    pub material_polygon_offsets: HashMap<String, PolygonOffset>,
+    /// Blurred refraction for `KHR_materials_transmission` (otherwise simple alpha blending)
+    pub transmission: bool,
+    /// Number of frames the logged render timings are averaged over
+    pub timer_window: usize,
 }
 
 pub struct GltfViewer {
+    source: Option<String>, // None: loaded with `from_bytes` (nothing to reload or watch)
+
     width: u32,
     height: u32,
 
@@ -55,133 +160,497 @@ pub struct GltfViewer {
     first_mouse: bool,
     last_x: f32,
     last_y: f32,
+    headless: bool, // requested; might be rendering to an invisible window instead (see `new`)
     events_loop: Option<glutin::EventsLoop>,
     gl_window: Option<glutin::GlWindow>,
+    headless_context: Option<glutin::HeadlessContext>, // kept alive while rendering
+    framebuffer: Option<Framebuffer>, // headless context only
 
-    // TODO!: get rid of scene?
     root: Root,
-    scene: Scene,
+    scenes: Vec<Scene>,
+    scene_index: usize, // active scene
+
+    active_animation: Option<usize>, // index into `root.animations`
+    animation_time: f32, // seconds
+    animation_paused: bool,
+    morph_target: usize, // target whose weight is changed with J/K
+
+    wireframe: bool,
+    clear_color: [f32; 4],
+    background_gradient: Option<([f32; 3], [f32; 3])>, // top, bottom
+    gradient_background: Option<GradientBackground>, // created when first needed
+    light_direction: Vector3,
+    exposure: f32,
+    tone_mapping: ToneMapping,
+    move_speed: Option<f32>, // overrides the speed derived from the scene size
+    min_distance: Option<f32>, // override the limits derived from the scene size
+    max_distance: Option<f32>,
+    auto_clip_planes: bool, // near/far from the scene bounds (off for glTF cameras)
+    camera_index: Option<usize>, // active glTF camera (in `Root::camera_nodes`), None: free orbit camera
+    auto_rotate: Option<f32>, // radians per second
+    idle_timeout: Duration, // before auto rotation starts
+    last_input: Instant, // mouse, keyboard, touch or gamepad
+
+    file_watch: Option<FileWatch>, // for reloading on changes
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<Gamepads>, // only with --gamepad
+    touches: HashMap<u64, Vector2>, // last location of active touch points
+    clicks: ClickTracker,
+    pick_framebuffer: Option<Framebuffer>, // created on the first click
+    palette_output: Option<(String, usize)>, // JSON file name and number of colors (for screenshots)
+    tile_projection: Option<Matrix4>, // applied after the camera projection while rendering tiles
+    jpeg_quality: u8,
+    composite_background: Option<[f32; 3]>, // for formats without alpha, default: clear color
+    logo: Option<(RgbaImage, Corner, f32)>, // blended into screenshots with the given opacity
+    screenshot_grading: Option<ColorGrading>, // not applied to the live view
+
+    show_overlay: bool,
+    text_renderer: Option<TextRenderer>, // created when the overlay is first shown
+    fovy_changed: Option<Instant>, // the field of view is shown in the overlay for a while
+
+    bounds_mode: BoundsMode,
+    bounding_box_renderer: Option<BoundingBoxRenderer>, // created when first needed
+
+    show_grid: bool,
+    grid_in_screenshots: bool, // only when requested explicitly
+    grid: Option<Grid>, // created when the grid is first shown
+
+    shadows: bool,
+    shadow_bias: f32,
+    shadow_map: Option<ShadowMap>, // created when shadows are first enabled
+    show_shadow_catcher: bool,
+    shadow_catcher: Option<ShadowCatcher>, // created when first needed
+
+    ssao_enabled: bool,
+    ssao_radius: Option<f32>, // overrides the radius derived from the scene size
+    ssao_intensity: f32,
+    ssao: Option<Ssao>, // created when first needed
+
+    outline_enabled: bool,
+    outline_thickness: f32,
+    outline_color: [f32; 4],
+    outline_fill: Option<[f32; 4]>,
+    outline: Option<Outline>, // created when first needed
+
+    stereo: bool, // red/cyan anaglyph
+    eye_separation: f32, // derived from the scene size
+
+    material_polygon_offsets: HashMap<String, PolygonOffset>, // applied again when reloading
 
     delta_time: f64, // seconds
+    avg_frame_time: f64, // seconds, smoothed
     last_frame: Instant,
 
     render_timer: FrameTimer,
+    startup_timings: StartupTimings,
+    timings_output: Option<String>, // JSON file name (`-`: stdout)
+    max_frames: Option<u32>, // render loop exits after this many frames
+    deterministic: bool, // fixed time step, see `set_deterministic`
+}
+
+/// OpenGL versions to try (core profile), in order of preference. The shaders only need 3.3.
+const GL_VERSIONS: [(u8, u8); 3] = [(4, 5), (4, 1), (3, 3)];
+
+fn create_window(events_loop: &glutin::EventsLoop, width: u32, height: u32, visible: bool,
+        render_options: &RenderOptions) -> Result<glutin::GlWindow, ViewerError> {
+    let mut last_error = None;
+    for &(major, minor) in &GL_VERSIONS {
+        let window = glutin::WindowBuilder::new()
+                .with_title("gltf-viewer")
+                .with_dimensions(width, height)
+                .with_visibility(visible);
+
+        let mut context = glutin::ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGl, (major, minor)))
+            .with_gl_profile(GlProfile::Core)
+            .with_vsync(render_options.vsync);
+        if render_options.msaa_samples > 0 {
+            context = context.with_multisampling(render_options.msaa_samples);
+        }
+        match glutin::GlWindow::new(window, context, events_loop) {
+            Ok(gl_window) => {
+                info!("Created OpenGL {}.{} core context", major, minor);
+                return Ok(gl_window)
+            },
+            Err(err) => {
+                debug!("Failed to create OpenGL {}.{} core context: {}", major, minor, err);
+                last_error = Some(err);
+            }
+        }
+    }
+    Err(ViewerError::NoContext(last_error.unwrap()))
+}
+
+/// Fails if no 3.3+ core context can be created (the shaders need one), so that `new` falls back
+/// to an invisible window.
+fn create_headless_context(width: u32, height: u32) -> Result<glutin::HeadlessContext, ViewerError> {
+    let mut last_error = None;
+    for &(major, minor) in &GL_VERSIONS {
+        let context = glutin::HeadlessRendererBuilder::new(width, height)
+            .with_gl(GlRequest::Specific(Api::OpenGl, (major, minor)))
+            .with_gl_profile(GlProfile::Core)
+            .build();
+        match context {
+            Ok(context) => {
+                info!("Created headless OpenGL {}.{} core context", major, minor);
+                return Ok(context)
+            },
+            Err(err) => {
+                debug!("Failed to create headless OpenGL {}.{} core context: {}", major, minor, err);
+                last_error = Some(err);
+            }
+        }
+    }
+    Err(ViewerError::NoHeadlessContext(last_error.unwrap()))
+}
+
+fn create_current_headless_context(width: u32, height: u32) -> Result<glutin::HeadlessContext, ViewerError> {
+    let context = create_headless_context(width, height)?;
+    unsafe { context.make_current().map_err(ViewerError::MakeCurrent)? }
+    Ok(context)
+}
+
+/// For tests that need OpenGL: a current headless context with the function pointers loaded,
+/// or `None` if none can be created (e.g. on a machine without GPU driver or OSMesa)
+#[cfg(test)]
+pub fn test_gl_context() -> Option<glutin::HeadlessContext> {
+    match create_current_headless_context(64, 64) {
+        Ok(context) => {
+            gl::load_with(|symbol| context.get_proc_address(symbol) as *const _);
+            Some(context)
+        },
+        Err(err) => {
+            eprintln!("skipping OpenGL test: {}", err);
+            None
+        }
+    }
 }
 
 /// Note about `headless` and `visible`: True headless rendering doesn't work on
-/// all operating systems, but an invisible window usually works
+/// all operating systems, but an invisible window usually works. So if the headless
+/// context can't be created or activated, `new` falls back to an invisible window.
 impl GltfViewer {
-    pub fn new(source: &str, width: u32, height: u32, headless: bool, visible: bool, camera_options: CameraOptions) -> GltfViewer {
-        let gl_request = GlRequest::Specific(Api::OpenGl, (3, 3));
-        let gl_profile = GlProfile::Core;
-        let (events_loop, gl_window, width, height) =
-            if headless {
-                let headless_context = glutin::HeadlessRendererBuilder::new(width, height)
-                    // .with_gl(gl_request)
-                    // .with_gl_profile(gl_profile)
-                    .build()
-                    .unwrap();
-                unsafe { headless_context.make_current().unwrap() }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        source: &str,
+        width: u32, height: u32,
+        headless: bool, visible: bool,
+        camera_options: CameraOptions,
+        render_options: RenderOptions,
+        scene_index: usize,
+        animation_index: Option<usize>,
+    ) -> Result<GltfViewer, ViewerError> {
+        let headless_context = if headless {
+            match create_current_headless_context(width, height) {
+                Ok(context) => {
+                    info!("Rendering headless (offscreen framebuffer)");
+                    Some(context)
+                },
+                Err(err) => {
+                    warn!("{}", err);
+                    warn!("Falling back to rendering in an invisible window");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let (events_loop, gl_window, framebuffer, width, height) =
+            if let Some(ref headless_context) = headless_context {
                 gl::load_with(|symbol| headless_context.get_proc_address(symbol) as *const _);
-                let framebuffer = Framebuffer::new(width, height);
+                let framebuffer = Framebuffer::new(width, height, render_options.msaa_samples);
                 framebuffer.bind();
                 unsafe { gl::Viewport(0, 0, width as i32, height as i32); }
 
-                (None, None, width, height) // TODO: real height (retina?)
+                // no window -> no HiDPI scaling
+                (None, None, Some(framebuffer), width, height)
             }
             else {
                 // glutin: initialize and configure
                 let events_loop = glutin::EventsLoop::new();
 
-                // TODO?: hints for 4.1, core profile, forward compat
-                let window = glutin::WindowBuilder::new()
-                        .with_title("gltf-viewer")
-                        .with_dimensions(width, height)
-                        .with_visibility(visible);
-
-                let context = glutin::ContextBuilder::new()
-                    .with_gl(gl_request)
-                    .with_gl_profile(gl_profile)
-                    .with_vsync(true);
-                let gl_window = glutin::GlWindow::new(window, context, &events_loop).unwrap();
+                let gl_window = create_window(&events_loop, width, height, visible && !headless, &render_options)?;
 
                 // Real dimensions might be much higher on High-DPI displays
-                let (real_width, real_height) = gl_window.get_inner_size().unwrap();
+                let (real_width, real_height) = gl_window.get_inner_size().ok_or(ViewerError::WindowClosed)?;
+                debug!("Framebuffer size: {}x{} (HiDPI factor: {})", real_width, real_height, gl_window.hidpi_factor());
 
-                unsafe { gl_window.make_current().unwrap(); }
+                unsafe { gl_window.make_current().map_err(ViewerError::MakeCurrent)?; }
 
                 // gl: load all OpenGL function pointers
                 gl::load_with(|symbol| gl_window.get_proc_address(symbol) as *const _);
 
-                (Some(events_loop), Some(gl_window), real_width, real_height)
+                (Some(events_loop), Some(gl_window), None, real_width, real_height)
             };
 
+        Self::init(source, width, height, headless, visible,
+            events_loop, gl_window, headless_context, framebuffer,
+            camera_options, render_options, scene_index, animation_index)
+    }
+
+    /// Like `new`, but loads the model from the bytes of a binary glTF (e.g. read from stdin).
+    /// There's no path to resolve external files against, so only self-contained .glb files
+    /// (all buffers and images in the binary chunk or data URIs) are accepted.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_bytes(
+        data: &[u8],
+        width: u32, height: u32,
+        headless: bool, visible: bool,
+        camera_options: CameraOptions,
+        render_options: RenderOptions,
+        scene_index: usize,
+        animation_index: Option<usize>,
+    ) -> Result<GltfViewer, ViewerError> {
+        check_self_contained_glb(data)
+            .map_err(|reason| ViewerError::Load(LoadError::NotSelfContained(reason)))?;
+        // NOTE: gltf_importer can only import files
+        let path = save_temporary_glb(data).map_err(|err| ViewerError::Load(LoadError::TemporaryFile(err)))?;
+        let viewer = Self::new(path.to_str().expect("non-UTF8 temp path"), width, height, headless, visible,
+            camera_options, render_options, scene_index, animation_index);
+        if let Err(err) = fs::remove_file(&path) {
+            warn!("Failed to remove temporary file {}: {}", path.display(), err);
+        }
+        let mut viewer = viewer?;
+        viewer.source = None;
+        Ok(viewer)
+    }
+
+    /// Creates a viewer that renders with the OpenGL context that is current on the calling thread,
+    /// e.g. that of a host application (requires OpenGL 3.3 core). The GL functions are loaded with
+    /// `get_proc_address`. There's no window then: the host application forwards input (see
+    /// `controls`), calls `update` and `draw` for each frame (rendering into the bound framebuffer)
+    /// and `resize` when its viewport changes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_current_context<F>(
+        get_proc_address: F,
+        source: &str,
+        width: u32, height: u32,
+        camera_options: CameraOptions,
+        render_options: RenderOptions,
+        scene_index: usize,
+        animation_index: Option<usize>,
+    ) -> Result<GltfViewer, ViewerError>
+        where F: FnMut(&str) -> *const c_void
+    {
+        gl::load_with(get_proc_address);
+        unsafe { gl::Viewport(0, 0, width as i32, height as i32); }
+        Self::init(source, width, height, false, true, None, None, None, None,
+            camera_options, render_options, scene_index, animation_index)
+    }
+
+    /// Common part of `new` and `from_current_context` (the GL context is current and loaded)
+    #[allow(clippy::too_many_arguments)]
+    fn init(
+        source: &str,
+        width: u32, height: u32,
+        headless: bool, visible: bool,
+        events_loop: Option<glutin::EventsLoop>,
+        gl_window: Option<glutin::GlWindow>,
+        headless_context: Option<glutin::HeadlessContext>,
+        framebuffer: Option<Framebuffer>,
+        camera_options: CameraOptions,
+        render_options: RenderOptions,
+        scene_index: usize,
+        animation_index: Option<usize>,
+    ) -> Result<GltfViewer, ViewerError> {
         let mut orbit_controls = OrbitControls::new(
-            Point3::new(0.0, 0.0, 2.0), width as f32, height as f32
+            Point3::new(0.0, 0.0, 2.0), width as f32, height as f32,
+            camera_options.rotate_sensitivity,
+            camera_options.pan_sensitivity,
+            camera_options.zoom_sensitivity,
         );
+        orbit_controls.invert_scroll = camera_options.invert_scroll;
+        orbit_controls.zoom_to_cursor = camera_options.zoom_to_cursor;
+        orbit_controls.transition_duration = camera_options.transition;
         orbit_controls.camera = Camera::default();
         orbit_controls.camera.fovy = camera_options.fovy;
         orbit_controls.camera.update_aspect_ratio(width as f32 / height as f32); // updates projection matrix
 
+        let transparent = render_options.transparent.unwrap_or(
+            // transparent background for screenshots
+            headless || !visible);
+        let clear_color = match render_options.background {
+            Some(background) => match render_options.transparent {
+                Some(transparent) => [background[0], background[1], background[2], if transparent { 0.0 } else { 1.0 }],
+                None => background,
+            },
+            None if transparent => [0.0, 0.0, 0.0, 0.0],
+            None => [0.1, 0.2, 0.3, 1.0],
+        };
+
         let first_mouse = true;
         let last_x: f32 = width as f32 / 2.0;
         let last_y: f32 = height as f32 / 2.0;
 
         unsafe {
             print_context_info();
+            if gl_window.is_some() {
+                // NOTE: requested mode - glutin can't query the swap interval (drivers may override it)
+                debug!("VSync        : {}", if render_options.vsync { "on" } else { "off" });
+            }
 
             gl::ClearColor(0.0, 1.0, 0.0, 1.0); // green for debugging
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
-            if headless || !visible {
-                // transparent background for screenshots
-                gl::ClearColor(0.0, 0.0, 0.0, 0.0);
-            }
-            else {
-                gl::ClearColor(0.1, 0.2, 0.3, 1.0);
-            }
-
             gl::Enable(gl::DEPTH_TEST);
+            if render_options.msaa_samples > 0 {
+                gl::Enable(gl::MULTISAMPLE);
+            }
+        };
 
-            // TODO: keyboard switch?
-            // draw in wireframe
-            // gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+        let environment = match render_options.environment {
+            Some(ref path) => match Environment::from_hdr(Path::new(path)) {
+                Ok(environment) => Some(Rc::new(environment)),
+                Err(error) => return Err(ViewerError::Environment { path: path.clone(), error }),
+            },
+            None => None,
         };
 
-        let (root, scene) = Self::load(source);
+        let (mut root, scenes, startup_timings) = Self::load(source, environment, &render_options.material_polygon_offsets)
+            .map_err(ViewerError::Load)?;
+        if scene_index >= scenes.len() {
+            return Err(ViewerError::InvalidIndex { kind: "scene", index: scene_index, count: scenes.len() })
+        }
+        root.debug_flags = render_options.debug_flags;
+        root.transmission = render_options.transmission;
+        root.polygon_offset = render_options.polygon_offset;
+        root.state_caching = render_options.state_caching;
+        root.draw_sorting = render_options.draw_sorting;
+        root.instancing = render_options.instancing && instancing_supported();
+        if render_options.instancing && !root.instancing {
+            warn!("Instanced drawing not supported by the OpenGL context, drawing nodes individually");
+        }
         let mut viewer = GltfViewer {
+            source: Some(source.to_owned()),
+
             width,
             height,
 
             orbit_controls,
             first_mouse, last_x, last_y,
 
+            headless,
             events_loop,
             gl_window,
+            headless_context,
+            framebuffer,
 
             root,
-            scene,
+            scenes,
+            scene_index,
+
+            active_animation: None,
+            animation_time: 0.0,
+            animation_paused: false,
+            morph_target: 0,
+
+            wireframe: false,
+            clear_color,
+            background_gradient: render_options.background_gradient,
+            gradient_background: None,
+            light_direction: vec3(0.0, 0.5, 0.5),
+            exposure: render_options.exposure,
+            tone_mapping: render_options.tone_mapping,
+            move_speed: camera_options.move_speed,
+            min_distance: camera_options.min_distance,
+            max_distance: camera_options.max_distance,
+            auto_clip_planes: true,
+            camera_index: None,
+            auto_rotate: camera_options.auto_rotate.map(|speed| speed.to_radians()),
+            idle_timeout: Duration::from_millis((camera_options.idle_timeout * 1000.0) as u64),
+            last_input: Instant::now(),
+
+            file_watch: None,
+            #[cfg(feature = "gamepad")]
+            gamepads: None,
+            touches: HashMap::new(),
+            clicks: ClickTracker::new(),
+            pick_framebuffer: None,
+            palette_output: None,
+            tile_projection: None,
+            jpeg_quality: 90,
+            composite_background: None,
+            logo: None,
+            screenshot_grading: None,
+
+            show_overlay: false,
+            text_renderer: None,
+            fovy_changed: None,
+
+            bounds_mode: BoundsMode::Off,
+            bounding_box_renderer: None,
+
+            show_grid: render_options.grid,
+            grid_in_screenshots: render_options.grid,
+            grid: None,
+
+            shadows: render_options.shadows || render_options.shadow_catcher,
+            shadow_bias: render_options.shadow_bias,
+            shadow_map: None,
+            show_shadow_catcher: render_options.shadow_catcher,
+            shadow_catcher: None,
+
+            ssao_enabled: render_options.ssao,
+            ssao_radius: render_options.ssao_radius,
+            ssao_intensity: render_options.ssao_intensity,
+            ssao: None,
+
+            outline_enabled: render_options.outline,
+            outline_thickness: render_options.outline_thickness,
+            outline_color: render_options.outline_color,
+            outline_fill: render_options.outline_fill,
+            outline: None,
+
+            stereo: render_options.stereo,
+            eye_separation: 0.0,
+
+            material_polygon_offsets: render_options.material_polygon_offsets,
 
             delta_time: 0.0, // seconds
+            avg_frame_time: 0.0,
             last_frame: Instant::now(),
 
-            render_timer: FrameTimer::new("rendering", 300),
+            render_timer: FrameTimer::new("rendering", render_options.timer_window),
+            startup_timings,
+            timings_output: None,
+            max_frames: None,
+            deterministic: false,
         };
         unsafe { gl_check_error!(); };
 
-        if !viewer.root.camera_nodes.is_empty() && !camera_options.index == -1 {
-            if camera_options.index >= viewer.root.camera_nodes.len() as i32 {
-                error!("No camera with index {} found in glTF file (max: {})",
-                    camera_options.index, viewer.root.camera_nodes.len() - 1);
-                process::exit(2)
+        let camera_index = match camera_options.name {
+            Some(ref name) => match viewer.find_camera(name) {
+                Some(index) => index as i32,
+                None => return Err(ViewerError::CameraName {
+                    name: name.clone(),
+                    available: viewer.root.camera_nodes.iter()
+                        .map(|&node| viewer.root.nodes[node].name.clone().unwrap_or_else(|| "unnamed".to_owned()))
+                        .collect(),
+                }),
+            },
+            None => camera_options.index,
+        };
+
+        if let Some(ref pose_file) = camera_options.pose_file {
+            if let Err(error) = viewer.load_camera_pose(pose_file) {
+                return Err(ViewerError::CameraPose { path: pose_file.clone(), error })
+            }
+        } else if !viewer.root.camera_nodes.is_empty() && camera_index >= 0 {
+            if camera_index >= viewer.root.camera_nodes.len() as i32 {
+                return Err(ViewerError::InvalidIndex {
+                    kind: "camera", index: camera_index as usize, count: viewer.root.camera_nodes.len() })
             }
-            let cam_node = &viewer.root.get_camera_node(camera_options.index as usize);
+            let cam_node = &viewer.root.get_camera_node(camera_index as usize);
             viewer.orbit_controls.set_camera(
                 cam_node.camera.as_ref().unwrap(),
                 &cam_node.final_transform);
+            // keep the near/far planes of the glTF camera
+            viewer.auto_clip_planes = false;
+            viewer.camera_index = Some(camera_index as usize);
 
             if camera_options.position.is_some() || camera_options.target.is_some() {
-                warn!("Ignoring --cam-pos / --cam-target since --cam-index is given.")
+                warn!("Ignoring --cam-pos / --cam-target since a glTF camera is used.")
             }
         } else {
             viewer.set_camera_from_bounds();
@@ -192,209 +661,1822 @@ impl GltfViewer {
             if let Some(target) = camera_options.target {
                 viewer.orbit_controls.target = Point3::from_vec(target)
             }
+
+            if camera_options.orthographic {
+                let ymag = viewer.orbit_controls.matching_ortho_ymag();
+                viewer.orbit_controls.camera.set_orthographic(ymag);
+            }
+        }
+
+        viewer.light_direction = match render_options.light_direction {
+            Some(direction) => direction.normalize(),
+            None => {
+                let camera_direction = (viewer.orbit_controls.position - viewer.orbit_controls.target).normalize();
+                (camera_direction + vec3(0.0, 1.0, 0.0)).normalize()
+            }
+        };
+
+        // play the first animation by default
+        let num_animations = viewer.root.animations.len();
+        match animation_index {
+            Some(index) if index >= num_animations =>
+                return Err(ViewerError::InvalidIndex { kind: "animation", index, count: num_animations }),
+            Some(index) => viewer.set_animation(Some(index)),
+            None if num_animations > 0 => viewer.set_animation(Some(0)),
+            None => (),
         }
 
-        viewer
+        Ok(viewer)
     }
 
-    pub fn load(source: &str) -> (Root, Scene) {
+    /// Imports a glTF file (or downloads it first if `source` is a URL).
+    /// NOTE: Needs a current GL context, unless the import fails.
+    pub fn load(source: &str, environment: Option<Rc<Environment>>,
+            material_polygon_offsets: &HashMap<String, PolygonOffset>)
+            -> Result<(Root, Vec<Scene>, StartupTimings), LoadError> {
+        let mut timings = StartupTimings::default();
         let mut start_time = Instant::now();
-        // TODO!: http source
-        // let gltf =
-        if source.starts_with("http") {
-            panic!("not implemented: HTTP support temporarily removed.")
-            // let http_source = HttpSource::new(source);
-            // let import = gltf::Import::custom(http_source, Default::default());
-            // let gltf = import_gltf(import);
-            // println!(); // to end the "progress dots"
-            // gltf
-        }
-        //     else {
+        let downloaded_path;
+        let source = if source.starts_with("http") {
+            let download = HttpSource::new(source).and_then(|http_source| http_source.download());
+            match download {
+                Ok(path) => {
+                    print_elapsed("Downloaded glTF in ", &start_time);
+                    timings.download_ms = Some(duration_ms(start_time.elapsed()));
+                    start_time = Instant::now();
+                    downloaded_path = path;
+                    downloaded_path.to_str().expect("non-UTF8 download path")
+                },
+                Err(err) => return Err(LoadError::Download(err)),
+            }
+        } else {
+            source
+        };
+
+        // NOTE: .glb files are self-contained (JSON + binary chunk), so no external
+        // buffers need to be resolved relative to `source` for them.
+        let is_binary = is_binary_gltf(source);
         let config = gltf_importer::Config { validation_strategy: ValidationStrategy::Complete };
         let (gltf, buffers) = match gltf_importer::import_with_config(source, config) {
             Ok((gltf, buffers)) => (gltf, buffers),
-            Err(err) => {
-                error!("glTF import failed: {:?}", err);
-                if let gltf_importer::Error::Io(_) = err {
-                    error!("Hint: Are the .bin file(s) referenced by the .gltf file available?")
-                }
-                process::exit(1)
-            },
+            Err(err) => return Err(LoadError::Import { error: err, binary: is_binary }),
         };
 
-        print_elapsed("Imported glTF in ", &start_time);
+        if is_binary {
+            print_elapsed("Imported glTF (binary) in ", &start_time);
+        } else {
+            print_elapsed("Imported glTF in ", &start_time);
+        }
+        timings.import_ms = duration_ms(start_time.elapsed());
         start_time = Instant::now();
 
-        // load first scene
-        if gltf.scenes().len() > 1 {
-            warn!("Found more than 1 scene, can only load first at the moment.")
-        }
         let base_path = Path::new(source);
-        let mut root = Root::from_gltf(&gltf, &buffers, base_path);
-        let scene = Scene::from_gltf(&gltf.scenes().nth(0).unwrap(), &mut root);
-        print_elapsed(&format!("Loaded scene with {} nodes, {} meshes in ",
-                gltf.nodes().count(), root.meshes.len()), &start_time);
+        let mut root = Root::from_gltf(&gltf, &buffers, base_path, environment, material_polygon_offsets);
+        let mut scenes: Vec<_> = gltf.scenes()
+            .map(|g_scene| Scene::from_gltf(&g_scene, &mut root))
+            .collect();
+        if scenes.is_empty() {
+            warn!("Found no scenes in glTF file.");
+            scenes.push(Scene::default());
+        }
+        print_elapsed(&format!("Loaded {} scene(s) with {} nodes, {} meshes in ",
+                scenes.len(), gltf.nodes().count(), root.meshes.len()), &start_time);
+        timings.scene_build_ms = duration_ms(start_time.elapsed());
+
+        Ok((root, scenes, timings))
+    }
+
+    /// Replaces the current model with the one from `source`.
+    /// On failure, the current model is kept.
+    pub fn open(&mut self, source: &str) -> Result<(), LoadError> {
+        let (root, scenes, _) = Self::load(source, self.root.environment.clone(), &self.material_polygon_offsets)?;
+        self.source = Some(source.to_owned());
+        self.replace_model(root, scenes, 0, Some(0));
+        self.set_camera_from_bounds();
+        if self.file_watch.is_some() {
+            self.watch();
+        }
+        Ok(())
+    }
+
+    /// Reloads the current model from `source`, keeping the camera pose and - if they
+    /// still exist - the active scene and animation. On failure, the current model is kept.
+    pub fn reload(&mut self) -> Result<(), LoadError> {
+        let source = self.source.clone().ok_or(LoadError::NoSource)?;
+        let (root, scenes, _) = Self::load(&source, self.root.environment.clone(), &self.material_polygon_offsets)?;
+        let (scene_index, animation) = (self.scene_index, self.active_animation);
+        self.replace_model(root, scenes, scene_index, animation);
+        Ok(())
+    }
+
+    /// Falls back to the first scene / animation for indices that are out of range.
+    fn replace_model(&mut self, mut root: Root, scenes: Vec<Scene>, scene_index: usize, animation: Option<usize>) {
+        // NOTE: no need to reset the previous animation, its nodes are discarded
+        self.active_animation = None;
+        self.morph_target = 0;
+        root.debug_flags = self.root.debug_flags;
+        root.hide_vertex_colors = self.root.hide_vertex_colors;
+        root.disable_culling = self.root.disable_culling;
+        root.state_caching = self.root.state_caching;
+        root.draw_sorting = self.root.draw_sorting;
+        root.instancing = self.root.instancing;
+        root.transmission = self.root.transmission;
+        root.polygon_offset = self.root.polygon_offset;
+        self.root = root;
+        self.scenes = scenes;
+        self.scene_index = if scene_index < self.scenes.len() { scene_index } else { 0 };
+        self.scenes[self.scene_index].update_transforms(&mut self.root);
+        if let Some(ref mut grid) = self.grid {
+            grid.fit_to_bounds(&self.scenes[self.scene_index].bounds);
+        }
+        if let Some(ref mut shadow_catcher) = self.shadow_catcher {
+            shadow_catcher.fit_to_bounds(&self.scenes[self.scene_index].bounds);
+        }
+
+        let num_animations = self.root.animations.len();
+        match animation {
+            Some(index) if index < num_animations => self.set_animation(Some(index)),
+            Some(_) if num_animations > 0 => self.set_animation(Some(0)),
+            _ => (),
+        }
+    }
+
+    /// Saves the startup timings as JSON to `filename` (`-`: stdout) once the first frame is drawn
+    pub fn set_timings_output(&mut self, filename: &str) {
+        self.timings_output = Some(filename.to_owned());
+    }
+
+    /// Makes the render loop exit after `count` frames and print the frame time statistics (for benchmarking)
+    pub fn set_max_frames(&mut self, count: u32) {
+        self.max_frames = Some(count);
+    }
+
+    /// Makes the render loop reproducible (e.g. for visual regression tests, also with `set_max_frames`):
+    /// every frame advances by `FIXED_DELTA_TIME` instead of the measured frame time, animations start
+    /// paused (they only advance when stepped or resumed explicitly) and auto-rotation is disabled.
+    /// NOTE: Nothing else is random, e.g. SSAO uses a fixed sample pattern.
+    pub fn set_deterministic(&mut self) {
+        self.deterministic = true;
+        self.animation_paused = true;
+        if self.auto_rotate.take().is_some() {
+            warn!("Auto-rotation is disabled in deterministic mode");
+        }
+    }
+
+    /// Makes `screenshot` also save the `size` dominant colors of the frame to a JSON file
+    pub fn set_palette_output(&mut self, filename: &str, size: usize) {
+        self.palette_output = Some((filename.to_owned(), size));
+    }
+
+    /// Loads an image (e.g. a PNG with alpha) that is blended into a corner of screenshots,
+    /// with `opacity` in [0, 1].
+    pub fn set_logo(&mut self, filename: &str, corner: Corner, opacity: f32) -> Result<(), ImageError> {
+        let logo = image::open(filename)?.to_rgba();
+        self.logo = Some((logo, corner, opacity));
+        Ok(())
+    }
+
+    /// Adjusts the colors of screenshots after reading them back (the window isn't affected)
+    pub fn set_screenshot_grading(&mut self, grading: ColorGrading) {
+        self.screenshot_grading = if grading == ColorGrading::default() { None } else { Some(grading) };
+    }
+
+    /// Headless only: changes the size of the offscreen framebuffer, e.g. to take
+    /// screenshots in several sizes without reloading the model.
+    pub fn resize_framebuffer(&mut self, width: u32, height: u32) {
+        if self.framebuffer.is_none() {
+            warn!("resize_framebuffer is only supported with a headless context");
+            return
+        }
+        self.resize(width, height);
+    }
+
+    /// Changes the render size, e.g. when the viewport of a host application changes (see
+    /// `from_current_context`). NOTE: Windows are resized by the render loop.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if let Some(ref mut framebuffer) = self.framebuffer {
+            framebuffer.resize(width, height);
+            framebuffer.bind();
+        }
+        unsafe { gl::Viewport(0, 0, width as i32, height as i32); }
+        self.width = width;
+        self.height = height;
+        self.orbit_controls.screen_width = width as f32;
+        self.orbit_controls.screen_height = height as f32;
+        self.orbit_controls.camera.update_aspect_ratio(width as f32 / height as f32);
+    }
+
+    /// Size of the window's framebuffer in pixels (on HiDPI displays larger than the requested size)
+    pub fn framebuffer_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Prints a summary of the loaded model (see `ModelInfo`)
+    pub fn print_info(&self) {
+        ModelInfo::from_root(&self.root, &self.scenes).print();
+    }
+
+    /// Index (in `Root::camera_nodes`) of the first camera node named `name`
+    fn find_camera(&self, name: &str) -> Option<usize> {
+        self.root.camera_nodes.iter()
+            .position(|&node| self.root.nodes[node].name.as_ref().map_or(false, |node_name| node_name == name))
+    }
+
+    /// Prints the glTF cameras (see `camera_list`)
+    pub fn print_cameras(&self) {
+        print!("{}", camera_list(&self.root));
+    }
+
+    /// Prints the animations (see `animation_list`)
+    pub fn print_animations(&self) {
+        print!("{}", animation_list(&self.root));
+    }
+
+    /// Prints the node hierarchy of the active scene (see `scene_tree`)
+    pub fn print_tree(&self) {
+        print!("{}", scene_tree(&self.root, &self.scenes[self.scene_index], self.scene_index));
+    }
+
+    /// Saves the summary of the loaded model as JSON (see `ModelInfo`)
+    pub fn save_info_json(&self, filename: &str) {
+        match ModelInfo::from_root(&self.root, &self.scenes).save_json(filename) {
+            Ok(()) => info!("Saved model info to {}", filename),
+            Err(err) => error!("Failed to save model info: {}", err),
+        }
+    }
+
+    /// Enables navigation with gamepads, polled in the render loop.
+    #[cfg(feature = "gamepad")]
+    pub fn enable_gamepad(&mut self) {
+        match Gamepads::new() {
+            Ok(gamepads) => self.gamepads = Some(gamepads),
+            Err(err) => error!("Failed to initialize gamepad support: {}", err),
+        }
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn enable_gamepad(&mut self) {
+        error!("Gamepad support is not available (build with `--features gamepad`)");
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn poll_gamepads(&mut self) {
+        let gamepad_input = self.gamepads.as_mut().map(|gamepads| gamepads.poll());
+        if let Some(input) = gamepad_input {
+            if input.rotate != Vector2::zero() || input.pan != Vector2::zero() || input.zoom != 0.0 || input.recenter {
+                self.last_input = Instant::now();
+            }
+            self.orbit_controls.process_gamepad(input.rotate, input.pan, input.zoom, self.delta_time);
+            if input.recenter {
+                self.handle_action(Action::Recenter);
+            }
+        }
+    }
 
-        (root, scene)
+    /// Starts watching the source file for changes, which are then reloaded in the render loop.
+    pub fn watch(&mut self) {
+        self.file_watch = match self.source {
+            Some(ref source) if source.starts_with("http") => {
+                warn!("Watching is only supported for local files, not URLs");
+                None
+            },
+            Some(ref source) => Some(FileWatch::new(source.clone(), Duration::from_millis(300))),
+            None => {
+                warn!("Watching is only supported for local files, not models loaded from bytes");
+                None
+            },
+        };
     }
 
     /// determine "nice" camera perspective from bounding box. Inspired by donmccurdy/three-gltf-viewer
     fn set_camera_from_bounds(&mut self) {
-        let bounds = &self.scene.bounds;
+        let bounds = &self.scenes[self.scene_index].bounds;
         let size = (bounds.max - bounds.min).magnitude();
         let center = bounds.center();
 
-        let _max_distance = size * 10.0;
         // TODO: x,y addition optional, z optionally minus instead
         let cam_pos = Point3::new(
             center.x + size / 2.0,
             center.y + size / 5.0,
             center.z + size / 2.0,
         );
-        let _near = size / 100.0;
-        let _far = size * 100.0;
-
         self.orbit_controls.position = cam_pos;
         self.orbit_controls.target = center;
+        // cross the scene in about 4 seconds
+        self.orbit_controls.move_speed = match self.move_speed {
+            Some(speed) => speed,
+            None if size > 0.0 => size / 4.0,
+            None => SPEED,
+        };
+        // about 1/30 of the initial distance to the target
+        self.eye_separation = size / 40.0;
+        // close enough for details, but not so far that the scene is lost
+        let (min_distance, max_distance) = if size > 0.0 {
+            (size / 100.0, size * 10.0)
+        } else {
+            (0.0, ::std::f32::INFINITY)
+        };
+        self.orbit_controls.min_distance = self.min_distance.unwrap_or(min_distance);
+        self.orbit_controls.max_distance = self.max_distance.unwrap_or(max_distance).max(self.orbit_controls.min_distance);
 
-        // TODO!: set near, far, max_distance, obj_pos_modifier...
-    }
-
-    pub fn start_render_loop(&mut self) {
-        loop {
-            // per-frame time logic
-            // NOTE: Deliberately ignoring the seconds of `elapsed()`
-            self.delta_time = f64::from(self.last_frame.elapsed().subsec_nanos()) / 1_000_000_000.0;
-            self.last_frame = Instant::now();
+        if let Some(ref mut grid) = self.grid {
+            grid.fit_to_bounds(bounds);
+        }
+        if let Some(ref mut shadow_catcher) = self.shadow_catcher {
+            shadow_catcher.fit_to_bounds(bounds);
+        }
 
-            // events
-            let keep_running = process_events(
-                &mut self.events_loop.as_mut().unwrap(), self.gl_window.as_mut().unwrap(),
-                &mut self.orbit_controls,
-                &mut self.width, &mut self.height);
-            if !keep_running {
-                unsafe { gl_check_error!(); } // final error check so errors don't go unnoticed
-                break
-            }
+        if !self.orbit_controls.camera.is_perspective() {
+            let ymag = self.orbit_controls.matching_ortho_ymag();
+            self.orbit_controls.camera.set_orthographic(ymag);
+        }
 
-            self.orbit_controls.frame_update(self.delta_time); // keyboard navigation
+        self.auto_clip_planes = true;
+        self.camera_index = None;
+        self.update_clip_planes();
 
-            self.draw();
+        // TODO!: set obj_pos_modifier...
+    }
 
-            self.gl_window.as_ref().unwrap().swap_buffers().unwrap();
+    /// Switches to the glTF camera with the given index (in `Root::camera_nodes`) or - with `None` -
+    /// back to the free orbit camera framing the scene.
+    pub fn use_camera(&mut self, index: Option<usize>) {
+        let pose = self.orbit_controls.pose();
+        match index {
+            Some(index) => {
+                let cam_node = self.root.get_camera_node(index);
+                self.orbit_controls.set_camera(cam_node.camera.as_ref().unwrap(), &cam_node.final_transform);
+                self.auto_clip_planes = false;
+                self.camera_index = Some(index);
+            },
+            None => self.set_camera_from_bounds(),
         }
+        self.orbit_controls.start_transition(pose);
+        info!("Camera: {}", self.camera_description());
     }
 
-    // Returns whether to keep running
-    pub fn draw(&mut self) {
-        // render
-        unsafe {
-            self.render_timer.start();
-
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-
-            let cam_params = self.orbit_controls.camera_params();
-            self.scene.draw(&mut self.root, &cam_params);
+    /// Index and name of the active glTF camera (for the overlay)
+    fn camera_description(&self) -> String {
+        match self.camera_index {
+            Some(index) => format!("{} ({})", index,
+                self.root.get_camera_node(index).name.as_ref().map_or("unnamed", |name| name.as_str())),
+            None => "free orbit".to_owned(),
+        }
+    }
 
-            self.render_timer.end();
+    /// Adapts the near and far planes to the distance between camera and scene (see `clip_planes`),
+    /// so that small scenes aren't clipped and large ones don't lose depth precision.
+    /// Only substantial changes are applied (within the margins of `clip_planes`).
+    fn update_clip_planes(&mut self) {
+        if !self.auto_clip_planes {
+            return
+        }
+        let bounds = &self.scenes[self.scene_index].bounds;
+        let camera = &mut self.orbit_controls.camera;
+        if fit_clip_planes(camera, self.orbit_controls.position, bounds) {
+            debug!("Near/far planes: {} / {:?}", camera.znear, camera.zfar);
         }
     }
 
-    pub fn screenshot(&mut self, filename: &str, width: u32, height: u32) {
-        self.draw();
+    /// The camera navigation, e.g. for forwarding input of a host application
+    pub fn controls(&mut self) -> &mut OrbitControls {
+        &mut self.orbit_controls
+    }
 
-        let mut img = DynamicImage::new_rgba8(width, height);
-        unsafe {
-            let pixels = img.as_mut_rgba8().unwrap();
-            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
-            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA,
-                gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut c_void);
-            gl_check_error!();
+    /// Advances keyboard navigation and the active animation by `delta_time` seconds
+    /// (called by the render loop, or by a host application before `draw`).
+    pub fn update(&mut self, delta_time: f64) {
+        self.orbit_controls.frame_update(delta_time); // keyboard navigation
+        if self.active_animation.is_some() && !self.animation_paused {
+            self.update_animation(delta_time);
         }
+    }
 
-        let img = img.flipv();
+    /// Switches to the scene with the given index and re-frames the camera
+    pub fn set_scene(&mut self, index: usize) {
+        let pose = self.orbit_controls.pose();
+        self.scene_index = index;
+        self.scenes[index].update_transforms(&mut self.root);
+        self.set_camera_from_bounds();
+        self.orbit_controls.start_transition(pose);
+        info!("Showing scene {} ({})", index,
+            self.scenes[index].name.as_ref().map_or("unnamed", |name| name.as_str()));
+    }
 
-        let mut file = File::create(filename).unwrap();
-        if let Err(err) = img.save(&mut file, ImageFormat::PNG) {
-            error!("{}", err);
+    /// Switches to the animation with the given index (`None` stops playback).
+    /// Nodes animated by the previous animation are reset to their original transforms.
+    pub fn set_animation(&mut self, index: Option<usize>) {
+        if let Some(current) = self.active_animation {
+            self.root.animations[current].reset(&mut self.root.nodes);
         }
-        else {
-            println!("Saved {}x{} screenshot to {}", width, height, filename);
+        self.active_animation = index;
+        self.animation_time = 0.0;
+        if let Some(index) = index {
+            let animation = &self.root.animations[index];
+            info!("Playing animation {} ({}, {:.2} s)", index,
+                animation.name.as_ref().map_or("unnamed", |name| name.as_str()), animation.duration);
         }
+        self.update_animation(0.0);
     }
-    pub fn multiscreenshot(&mut self, filename: &str, width: u32, height: u32, count: u32) {
-        let min_angle : f32 = 0.0 ;
-        let max_angle : f32 =  2.0 * PI ;
-        let increment_angle : f32 = ((max_angle - min_angle)/(count as f32)) as f32;
-        for i in 1..(count+1) {
-            self.orbit_controls.rotate_object(increment_angle);
-            let dot = filename.rfind('.').unwrap_or_else(|| filename.len());
-            let mut actual_name = filename.to_string();
-            actual_name.insert_str(dot, &format!("_{}", i));
-            self.screenshot(&actual_name[..], width,height);
+
+    /// Advances the animation clock and updates the animated node transforms
+    fn update_animation(&mut self, delta_time: f64) {
+        if let Some(index) = self.active_animation {
+            let duration = self.root.animations[index].duration;
+            // wrap around (like `Animation::animate`), so the clock doesn't lose precision over time
+            self.animation_time = if duration > 0.0 {
+                (self.animation_time + delta_time as f32) % duration
+            } else {
+                0.0
+            };
+            self.root.animations[index].animate(&mut self.root.nodes, self.animation_time);
         }
+        self.scenes[self.scene_index].update_transforms(&mut self.root);
     }
-}
 
-#[allow(too_many_arguments)]
-fn process_events(
-    events_loop: &mut glutin::EventsLoop,
-    gl_window: &glutin::GlWindow,
-    mut orbit_controls: &mut OrbitControls,
-    width: &mut u32,
-    height: &mut u32) -> bool
-{
-    let mut keep_running = true;
-    #[allow(single_match)]
-    events_loop.poll_events(|event| {
-        match event {
-            glutin::Event::WindowEvent{ event, .. } => match event {
-                WindowEvent::Closed => keep_running = false,
-                WindowEvent::Resized(w, h) => {
-                    gl_window.resize(w, h);
-                    *width = w;
-                    *height = h;
-                    let w = w as f32;
-                    let h = h as f32;
-                    orbit_controls.camera.update_aspect_ratio(w / h);
-                    orbit_controls.screen_width = w;
-                    orbit_controls.screen_height = h;
+    /// File name for saving the camera pose: next to the model (or in the working directory for URLs
+    /// and models loaded from bytes)
+    fn camera_pose_path(&self) -> PathBuf {
+        let path = match self.source {
+            Some(ref source) if source.starts_with("http") =>
+                PathBuf::from(Path::new(source).file_name().unwrap_or_else(|| "model".as_ref())),
+            Some(ref source) => PathBuf::from(source),
+            None => PathBuf::from("model"),
+        };
+        path.with_extension("camera.json")
+    }
 
-                    trace!("Resized to {}x{}", w, h);
-                },
-                WindowEvent::DroppedFile(_path_buf) => (), // TODO: drag file in
-                WindowEvent::MouseInput { button, state: Pressed, ..} => {
-                    match button {
-                        MouseButton::Left => {
-                            orbit_controls.state = NavState::Rotating;
-                        },
-                        MouseButton::Right => {
-                            orbit_controls.state = NavState::Panning;
-                        },
-                        _ => ()
-                    }
-                },
-                WindowEvent::MouseInput { button, state: Released, ..} => {
-                    match (button, orbit_controls.state.clone()) {
-                        (MouseButton::Left, NavState::Rotating) | (MouseButton::Right, NavState::Panning) => {
-                            orbit_controls.state = NavState::None;
-                            orbit_controls.handle_mouse_up();
-                        },
+    pub fn save_camera_pose<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, &self.orbit_controls.pose())
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+
+    pub fn load_camera_pose<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let file = File::open(path)?;
+        let pose: CameraPose = serde_json::from_reader(file)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.orbit_controls.set_pose(&pose);
+        self.orbit_controls.camera.update_aspect_ratio(self.width as f32 / self.height as f32);
+        Ok(())
+    }
+
+    /// Runs until the window is closed, or for `max_frames` frames (headless: only the latter)
+    pub fn start_render_loop(&mut self) {
+        let windowless = self.gl_window.is_none();
+        if self.headless && self.max_frames.is_none() {
+            warn!("Nothing to render in headless mode (use --screenshot, --turntable or --max-frames)");
+            return
+        }
+        let mut frame_times = Vec::with_capacity(self.max_frames.unwrap_or(0) as usize);
+        loop {
+            let frame_start = Instant::now();
+            // per-frame time logic
+            // NOTE: Deliberately ignoring the seconds of `elapsed()`
+            let frame_time = f64::from(self.last_frame.elapsed().subsec_nanos()) / 1_000_000_000.0;
+            self.last_frame = Instant::now();
+            self.avg_frame_time = if self.avg_frame_time == 0.0 {
+                frame_time
+            } else {
+                0.95 * self.avg_frame_time + 0.05 * frame_time
+            };
+            self.delta_time = if self.deterministic { FIXED_DELTA_TIME } else { frame_time };
+
+            // events
+            let mut actions = vec![];
+            let has_selection = self.root.selected_node.is_some();
+            let keep_running = windowless || process_events(
+                self.events_loop.as_mut().unwrap(), self.gl_window.as_mut().unwrap(),
+                &mut self.orbit_controls,
+                &mut self.touches,
+                &mut self.clicks,
+                &mut self.last_input,
+                has_selection,
+                &mut self.width, &mut self.height,
+                &mut actions);
+            if !keep_running {
+                self.render_timer.print_and_reset(); // partial window
+                // don't leak wireframe mode into later screenshots
+                self.wireframe = false;
+                unsafe {
+                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+                    gl_check_error!(); // final error check so errors don't go unnoticed
+                }
+                break
+            }
+            for action in actions {
+                self.handle_action(action);
+            }
+
+            let source_changed = self.file_watch.as_mut().map_or(false, |watch| watch.poll());
+            if source_changed {
+                let source = self.source.clone().unwrap_or_default();
+                match self.reload() {
+                    Ok(()) => info!("Reloaded {}", source),
+                    Err(err) => error!("Failed to reload {}: {}", source, err),
+                }
+            }
+
+            let delta_time = self.delta_time;
+            self.update(delta_time);
+            #[cfg(feature = "gamepad")]
+            self.poll_gamepads();
+
+            if let Some(speed) = self.auto_rotate {
+                if self.last_input.elapsed() >= self.idle_timeout {
+                    self.orbit_controls.rotate_object(speed * self.delta_time as f32);
+                }
+            }
+
+            self.draw();
+
+            match self.gl_window {
+                Some(ref gl_window) => if let Err(err) = gl_window.swap_buffers() {
+                    error!("Failed to swap buffers: {}", err);
+                    break
+                },
+                // nothing waits for the frame otherwise
+                None => unsafe { gl::Finish() },
+            }
+
+            if let Some(max_frames) = self.max_frames {
+                frame_times.push(frame_start.elapsed());
+                if frame_times.len() >= max_frames as usize {
+                    self.render_timer.print_and_reset();
+                    info!("Rendered {} frames, frame time: {}", frame_times.len(), format_frame_stats(&frame_times));
+                    break
+                }
+            }
+        }
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::ToggleWireframe => {
+                self.wireframe = !self.wireframe;
+                info!("Wireframe mode: {}", if self.wireframe { "on" } else { "off" });
+            },
+            Action::RotateLight(theta, phi) => {
+                let mut spherical = Spherical::from_vec3(self.light_direction);
+                spherical.theta += theta;
+                spherical.phi = clamp(spherical.phi + phi, 0.01, PI - 0.01);
+                self.light_direction = spherical.to_vec3().normalize();
+                debug!("Light direction: {:?}", self.light_direction);
+            },
+            Action::ChangeExposure(stops) => {
+                self.exposure *= 2.0f32.powf(stops);
+                info!("Exposure: {:.3} ({:+.1} EV)", self.exposure, self.exposure.log2());
+            },
+            Action::ChangeFovy(delta) => {
+                self.orbit_controls.change_fovy(delta);
+                self.fovy_changed = Some(Instant::now());
+                info!("Field of view: {:.0}°{}", self.orbit_controls.camera.fovy,
+                    if self.orbit_controls.camera.is_perspective() { "" } else { " (for perspective projection)" });
+            },
+            Action::ToggleOrthographic => {
+                if self.orbit_controls.camera.is_perspective() {
+                    let ymag = self.orbit_controls.matching_ortho_ymag();
+                    self.orbit_controls.camera.set_orthographic(ymag);
+                    info!("Orthographic projection");
+                } else {
+                    self.orbit_controls.camera.set_perspective();
+                    info!("Perspective projection");
+                }
+            },
+            Action::SaveCameraPose => {
+                let path = self.camera_pose_path();
+                match self.save_camera_pose(&path) {
+                    Ok(()) => info!("Saved camera pose to {}", path.display()),
+                    Err(err) => error!("Failed to save camera pose to {}: {}", path.display(), err),
+                }
+            },
+            Action::LoadCameraPose => {
+                let path = self.camera_pose_path();
+                match self.load_camera_pose(&path) {
+                    Ok(()) => info!("Loaded camera pose from {}", path.display()),
+                    Err(err) => error!("Failed to load camera pose from {}: {}", path.display(), err),
+                }
+            },
+            Action::ToggleOverlay => self.show_overlay = !self.show_overlay,
+            Action::Screenshot => {
+                let filename = timestamped_filename("screenshot", "png", SystemTime::now());
+                let (width, height) = (self.width, self.height);
+                self.screenshot(&filename, width, height);
+            },
+            Action::ToggleGrid => self.show_grid = !self.show_grid,
+            Action::ToggleShadows => self.shadows = !self.shadows,
+            Action::ToggleOutline => {
+                self.outline_enabled = !self.outline_enabled;
+                info!("Outlines: {}", if self.outline_enabled { "on" } else { "off" });
+            },
+            Action::ToggleStereo => {
+                self.stereo = !self.stereo;
+                info!("Anaglyph stereo: {}", if self.stereo { "on" } else { "off" });
+            },
+            Action::CycleDebugChannel => {
+                let current = DEBUG_CHANNELS.iter().position(|&channel| self.root.debug_flags.contains(channel));
+                for &channel in &DEBUG_CHANNELS {
+                    self.root.debug_flags.remove(channel);
+                }
+                let next = match current {
+                    None => Some(0),
+                    Some(i) if i + 1 < DEBUG_CHANNELS.len() => Some(i + 1),
+                    Some(_) => None, // back to normal rendering
+                };
+                if let Some(i) = next {
+                    self.root.debug_flags.insert(DEBUG_CHANNELS[i]);
+                }
+                info!("Debug visualization: {:?}", self.root.debug_flags);
+            },
+            Action::ToggleVertexColors => {
+                self.root.hide_vertex_colors = !self.root.hide_vertex_colors;
+                info!("Vertex colors: {}", if self.root.hide_vertex_colors { "off" } else { "on" });
+            },
+            Action::ToggleBackfaceCulling => {
+                self.root.disable_culling = !self.root.disable_culling;
+                info!("Backface culling: {}", if self.root.disable_culling { "off (forced)" } else { "per material" });
+            },
+            Action::ToggleDebugFlag(flag) => {
+                self.root.debug_flags.toggle(flag);
+                info!("Debug visualization: {:?}", self.root.debug_flags);
+            },
+            Action::CycleBoundsMode => {
+                self.bounds_mode = match self.bounds_mode {
+                    BoundsMode::Off => BoundsMode::Scene,
+                    BoundsMode::Scene => BoundsMode::SceneAndMeshes,
+                    BoundsMode::SceneAndMeshes => BoundsMode::Off,
+                };
+                info!("Bounding boxes: {:?}", self.bounds_mode);
+            },
+            Action::ToggleNavMode => {
+                // position and view direction are kept, only the meaning of mouse dragging changes
+                self.orbit_controls.mode = match self.orbit_controls.mode {
+                    NavMode::Orbit => NavMode::Fly,
+                    NavMode::Fly => NavMode::Orbit,
+                };
+                info!("Navigation mode: {:?}", self.orbit_controls.mode);
+            },
+            Action::CycleCamera => {
+                // all glTF cameras, then the free orbit camera
+                let count = self.root.camera_nodes.len();
+                let next = match self.camera_index {
+                    None if count > 0 => Some(0),
+                    Some(index) if index + 1 < count => Some(index + 1),
+                    _ => None,
+                };
+                self.use_camera(next);
+                self.orbit_controls.handle_mouse_up();
+            },
+            Action::Recenter => {
+                let pose = self.orbit_controls.pose();
+                self.set_camera_from_bounds();
+                self.orbit_controls.start_transition(pose);
+                // a drag might be in progress -> restart it from the current cursor position
+                self.orbit_controls.handle_mouse_up();
+            },
+            Action::Open(path) => {
+                let source = path.to_string_lossy().into_owned();
+                match self.open(&source) {
+                    Ok(()) => info!("Loaded {}", source),
+                    Err(err) => error!("Failed to load {}: {}", source, err),
+                }
+            },
+            Action::NextScene => {
+                let index = (self.scene_index + 1) % self.scenes.len();
+                self.set_scene(index);
+            },
+            Action::PreviousScene => {
+                let index = (self.scene_index + self.scenes.len() - 1) % self.scenes.len();
+                self.set_scene(index);
+            },
+            Action::ToggleAnimationPause => {
+                self.animation_paused = !self.animation_paused;
+                info!("Animation {} at {:.3} s", if self.animation_paused { "paused" } else { "resumed" },
+                    self.animation_time);
+            },
+            Action::StepAnimation => {
+                // fixed delta, so stepping is deterministic (e.g. for screenshots)
+                self.animation_paused = true;
+                if self.active_animation.is_some() {
+                    self.update_animation(ANIMATION_STEP);
+                    info!("Animation time: {:.3} s", self.animation_time);
+                }
+            },
+            Action::NextMorphTarget => {
+                let num_targets = self.root.nodes.iter().map(|node| node.weights.len()).max().unwrap_or(0);
+                if num_targets == 0 {
+                    info!("No morph targets");
+                    return
+                }
+                self.morph_target = (self.morph_target + 1) % num_targets;
+                info!("Morph target: {}", self.morph_target);
+            },
+            Action::ChangeMorphWeight(delta) => {
+                // NOTE: overridden by an active animation with a weights channel
+                let target = self.morph_target;
+                for node in self.root.nodes.iter_mut().filter(|node| target < node.weights.len()) {
+                    node.weights[target] = (node.weights[target] + delta).max(0.0).min(1.0);
+                    info!("Morph target {} weight: {:.1} (node: {})", target, node.weights[target], node.index);
+                }
+            },
+            Action::SetPivot(x, y) => {
+                match self.pick_point(x, y) {
+                    Some(point) => {
+                        // NOTE: the camera turns towards the pivot, its position is kept
+                        self.orbit_controls.target = point;
+                        info!("Orbit pivot: ({:.3}, {:.3}, {:.3})", point.x, point.y, point.z);
+                    },
+                    None => info!("No pivot under the cursor"),
+                }
+            },
+            Action::Pick(x, y) => {
+                let picked = self.pick(x, y);
+                match picked {
+                    Some(index) => info!("Selected {}", describe_node(&self.root, index)),
+                    None => info!("Nothing picked"),
+                }
+                self.root.selected_node = picked;
+            },
+            Action::ClearSelection => {
+                self.root.selected_node = None;
+                info!("Selection cleared");
+            },
+        }
+    }
+
+    // Returns whether to keep running
+    pub fn draw(&mut self) {
+        let frame_start = Instant::now();
+        // render
+        unsafe {
+            self.render_timer.start();
+
+            self.update_clip_planes();
+
+            // KHR_lights_punctual or the default directional light
+            let mut lights = self.scenes[self.scene_index].lights(&self.root);
+            if lights.is_empty() {
+                lights.push(LightInstance::default_directional(self.light_direction));
+            }
+            self.root.set_lights(&lights);
+            let shadows = if self.shadows { self.render_shadow_map(&lights) } else { None };
+            self.root.set_shadows(shadows);
+
+            // NOTE: stereo is left out for orthographic projections (no depth perception) and the
+            // post-processes (SSAO/outlines of one eye don't fit the other)
+            let stereo = self.stereo && self.orbit_controls.camera.is_perspective();
+            // the outline pass composites the result of the SSAO pass (if both are enabled)
+            if self.outline_enabled && !stereo {
+                let (width, height) = (self.width, self.height);
+                self.outline.get_or_insert_with(|| Outline::new(width, height)).begin();
+            }
+            if self.ssao_enabled && !stereo {
+                let (width, height) = (self.width, self.height);
+                self.ssao.get_or_insert_with(|| Ssao::new(width, height)).begin();
+            }
+
+            let polygon_mode = if self.wireframe { gl::LINE } else { gl::FILL };
+            gl::PolygonMode(gl::FRONT_AND_BACK, polygon_mode);
+
+            let c = self.clear_color;
+            gl::ClearColor(c[0], c[1], c[2], c[3]);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            if let Some((top, bottom)) = self.background_gradient {
+                self.gradient_background.get_or_insert_with(GradientBackground::new)
+                    .draw(&top, &bottom, self.tile_projection.as_ref());
+                gl::PolygonMode(gl::FRONT_AND_BACK, polygon_mode);
+            }
+            self.root.set_tone_mapping(self.tone_mapping, self.exposure);
+
+            let cam_params = self.orbit_controls.camera_params();
+            let stats = if stereo {
+                // anaglyph: red for the left eye, cyan for the right one
+                let convergence = (self.orbit_controls.target - self.orbit_controls.position).magnitude();
+                let eyes = [(-0.5, [gl::TRUE, gl::FALSE, gl::FALSE]), (0.5, [gl::FALSE, gl::TRUE, gl::TRUE])];
+                let mut stats = DrawStats::default();
+                for &(side, mask) in &eyes {
+                    gl::ColorMask(mask[0], mask[1], mask[2], gl::TRUE);
+                    gl::Clear(gl::DEPTH_BUFFER_BIT);
+                    let (eye_params, eye_projection) = stereo_eye(&cam_params, side * self.eye_separation, convergence);
+                    stats = self.draw_view(eye_params, Some(eye_projection), false, false);
+                }
+                gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                stats
+            } else {
+                let (ssao, outline) = (self.ssao_enabled, self.outline_enabled);
+                self.draw_view(cam_params, None, ssao, outline)
+            };
+
+            self.render_timer.end();
+            if self.render_timer.frame_times.is_empty() {
+                // averaging window complete -> timings were just logged
+                debug!("{:<15}{}", "meshes", stats.meshes_text());
+            }
+
+            if self.show_overlay {
+                self.draw_overlay(&stats);
+            }
+
+            if self.startup_timings.first_frame_ms.is_none() {
+                gl::Finish(); // include the GPU work (and shader compilation) of the first frame
+                self.startup_timings.first_frame_ms = Some(duration_ms(frame_start.elapsed()));
+                print_elapsed("Drew first frame in ", &frame_start);
+                if let Some(ref filename) = self.timings_output {
+                    if let Err(err) = self.startup_timings.save_json(filename) {
+                        error!("Failed to save startup timings: {}", err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws the scene, skybox, grid and bounding boxes as seen from `cam_params`.
+    /// `eye_projection` is applied after the camera projection (for stereo, see `stereo_eye`),
+    /// like the projection of the current tile.
+    unsafe fn draw_view(&mut self, mut cam_params: CameraParams, eye_projection: Option<Matrix4>,
+            ssao: bool, outline: bool) -> DrawStats {
+        let post_projection = match (self.tile_projection, eye_projection) {
+            (Some(tile_projection), Some(eye_projection)) => Some(tile_projection * eye_projection),
+            (tile_projection, eye_projection) => tile_projection.or(eye_projection),
+        };
+        if let Some(post_projection) = post_projection {
+            cam_params.projection_matrix = post_projection * cam_params.projection_matrix;
+            cam_params.frustum = Frustum::from_matrix(&(cam_params.projection_matrix * cam_params.view_matrix));
+        }
+        let stats = self.scenes[self.scene_index].draw(&mut self.root, &cam_params);
+
+        if let Some(ref environment) = self.root.environment {
+            let camera = &self.orbit_controls.camera;
+            environment.draw_skybox(&cam_params.view_matrix, camera.fovy, camera.aspect_ratio(),
+                post_projection.as_ref(), self.tone_mapping, self.exposure);
+        }
+
+        if ssao {
+            let radius = match self.ssao_radius {
+                Some(radius) => radius,
+                None => {
+                    let bounds = &self.scenes[self.scene_index].bounds;
+                    ((bounds.max - bounds.min).magnitude() * 0.02).max(1e-4)
+                }
+            };
+            if let Some(ref ssao) = self.ssao {
+                ssao.apply(&cam_params.projection_matrix, radius, self.ssao_intensity);
+            }
+        }
+        if outline {
+            if let Some(ref outline) = self.outline {
+                outline.apply(self.outline_thickness, &self.outline_color, self.outline_fill.as_ref());
+            }
+        }
+
+        let view_projection = cam_params.projection_matrix * cam_params.view_matrix;
+        if self.show_shadow_catcher {
+            if let Some(shadows) = self.root.shadows {
+                self.draw_shadow_catcher(&view_projection, &shadows);
+            }
+        }
+        if self.show_grid {
+            self.draw_grid(&view_projection);
+        }
+        if self.bounds_mode != BoundsMode::Off {
+            let renderer = self.bounding_box_renderer.get_or_insert_with(BoundingBoxRenderer::new);
+            self.scenes[self.scene_index].draw_bounds(&self.root, renderer, &view_projection,
+                self.bounds_mode == BoundsMode::SceneAndMeshes);
+        }
+        stats
+    }
+
+    /// Renders the node indices color-coded into an offscreen framebuffer (see `pick_color`)
+    /// and returns the node at the given window position (origin at the top left), if any.
+    /// NOTE: Nodes hidden by the skybox or grid can be picked as well.
+    pub fn pick(&mut self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None
+        }
+        let mut pixel = [0u8; 4];
+        unsafe {
+            let previous_framebuffer = self.render_picking();
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(x as i32, (self.height - 1 - y) as i32, 1, 1, gl::RGBA,
+                gl::UNSIGNED_BYTE, pixel.as_mut_ptr() as *mut c_void);
+            gl_check_error!();
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer);
+        }
+        node_from_pick_color(pixel)
+    }
+
+    /// Returns the world space position of the surface at the given window position
+    /// (origin at the top left), read back from the depth of the picking pass (see `pick`).
+    /// `None` if there is only background.
+    pub fn pick_point(&mut self, x: u32, y: u32) -> Option<Point3> {
+        if x >= self.width || y >= self.height {
+            return None
+        }
+        let mut depth = 1.0f32;
+        unsafe {
+            let previous_framebuffer = self.render_picking();
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(x as i32, (self.height - 1 - y) as i32, 1, 1, gl::DEPTH_COMPONENT,
+                gl::FLOAT, &mut depth as *mut f32 as *mut c_void);
+            gl_check_error!();
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer);
+        }
+        if depth >= 1.0 {
+            return None
+        }
+        let cam_params = self.orbit_controls.camera_params();
+        let inverse_view_projection = match (cam_params.projection_matrix * cam_params.view_matrix).invert() {
+            Some(matrix) => matrix,
+            None => return None,
+        };
+        // center of the pixel
+        Some(unproject(x as f32 + 0.5, y as f32 + 0.5, depth, self.width, self.height, &inverse_view_projection))
+    }
+
+    /// Renders the node indices color-coded into the (bound) pick framebuffer.
+    /// Returns the previously bound framebuffer (e.g. of a host application), to rebind after reading.
+    unsafe fn render_picking(&mut self) -> u32 {
+        let mut previous_framebuffer = 0;
+        gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+        let size = (self.width, self.height);
+        if self.pick_framebuffer.as_ref().map_or(false, |framebuffer| framebuffer.size() != size) {
+            self.pick_framebuffer.take().unwrap().delete();
+        }
+        self.pick_framebuffer.get_or_insert_with(|| Framebuffer::new(size.0, size.1, 0)).bind();
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+        self.root.picking = true;
+        let cam_params = self.orbit_controls.camera_params();
+        self.scenes[self.scene_index].draw(&mut self.root, &cam_params);
+        self.root.picking = false;
+        previous_framebuffer as u32
+    }
+
+    /// Renders the shadow map for the first directional light (if any) and binds it.
+    unsafe fn render_shadow_map(&mut self, lights: &[LightInstance]) -> Option<ShadowSettings> {
+        let light_index = match lights.iter().position(|light| light.light_type == LightType::Directional) {
+            Some(index) => index,
+            None => return None,
+        };
+        let light_params = light_camera_params(&lights[light_index].direction,
+            &self.scenes[self.scene_index].bounds);
+        let shadow_map = self.shadow_map.get_or_insert_with(|| ShadowMap::new(SHADOW_MAP_SIZE));
+        shadow_map.render(&mut self.scenes[self.scene_index], &mut self.root, &light_params);
+        shadow_map.bind();
+        Some(ShadowSettings {
+            light_space_matrix: light_params.projection_matrix * light_params.view_matrix,
+            light_index,
+            bias: self.shadow_bias,
+        })
+    }
+
+    unsafe fn draw_shadow_catcher(&mut self, view_projection: &Matrix4, shadows: &ShadowSettings) {
+        if self.shadow_catcher.is_none() {
+            let mut shadow_catcher = ShadowCatcher::new();
+            shadow_catcher.fit_to_bounds(&self.scenes[self.scene_index].bounds);
+            self.shadow_catcher = Some(shadow_catcher);
+        }
+        self.shadow_catcher.as_ref().unwrap().draw(view_projection, shadows);
+    }
+
+    unsafe fn draw_grid(&mut self, view_projection: &Matrix4) {
+        if self.grid.is_none() {
+            let mut grid = Grid::new();
+            grid.fit_to_bounds(&self.scenes[self.scene_index].bounds);
+            self.grid = Some(grid);
+        }
+        self.grid.as_ref().unwrap().draw(view_projection);
+    }
+
+    unsafe fn draw_overlay(&mut self, stats: &DrawStats) {
+        let fps = if self.avg_frame_time > 0.0 { 1.0 / self.avg_frame_time } else { 0.0 };
+        let fovy = if self.fovy_changed.map_or(false, |changed| changed.elapsed() < Duration::from_secs(FOVY_OVERLAY_SECONDS)) {
+            format!("\nFOV: {:.0}", self.orbit_controls.camera.fovy)
+        } else {
+            String::new()
+        };
+        let text = format!("FPS: {:.1}\nFrame time: {:.2} ms\nTriangles: {}\nDraw calls: {}\nMeshes: {}\nCamera: {}{}",
+            fps, self.avg_frame_time * 1000.0, stats.triangles, stats.draw_calls, stats.meshes_text(),
+            self.camera_description(), fovy);
+        let text_renderer = self.text_renderer.get_or_insert_with(TextRenderer::new);
+        text_renderer.draw(&text, 10.0, 10.0, self.width, self.height);
+    }
+
+    /// Draws a frame and reads it back (flipped, so that the origin is at the top left).
+    /// The overlay is left out.
+    /// Renders a frame (without the overlay) and returns its pixels, with the origin at the top left.
+    /// Doesn't touch the file system, so it can be used e.g. for thumbnail generation.
+    /// Frames larger than the window/framebuffer are rendered in tiles (see `render_tiled`).
+    pub fn render_to_buffer(&mut self, width: u32, height: u32) -> RgbaImage {
+        if width > self.width || height > self.height {
+            return self.render_tiled(width, height)
+        }
+        let (show_overlay, show_grid, background_gradient) = (self.show_overlay, self.show_grid, self.background_gradient);
+        self.show_overlay = false;
+        if self.clear_color[3] < 1.0 && !self.grid_in_screenshots {
+            self.show_grid = false;
+        }
+        if self.clear_color[3] < 1.0 {
+            self.background_gradient = None;
+        }
+        self.draw();
+        self.show_overlay = show_overlay;
+        self.show_grid = show_grid;
+        self.background_gradient = background_gradient;
+
+        self.resolve_framebuffer();
+        let mut pixels = RgbaImage::new(width, height);
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA,
+                gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut c_void);
+            gl_check_error!();
+        }
+
+        imageops::flip_vertical(&pixels)
+    }
+
+    /// Renders a frame larger than the window/framebuffer in window-sized tiles, each with
+    /// a projection that enlarges the corresponding part of the view, and stitches them.
+    fn render_tiled(&mut self, width: u32, height: u32) -> RgbaImage {
+        let (tile_width, tile_height) = (self.width, self.height);
+        let columns = (width + tile_width - 1) / tile_width;
+        let rows = (height + tile_height - 1) / tile_height;
+        info!("Rendering {}x{} in {}x{} tiles of {}x{}", width, height, columns, rows, tile_width, tile_height);
+
+        let aspect_ratio = self.orbit_controls.camera.aspect_ratio();
+        self.orbit_controls.camera.update_aspect_ratio(width as f32 / height as f32);
+        let mut image = RgbaImage::new(width, height);
+        for row in 0..rows {
+            for column in 0..columns {
+                self.tile_projection = Some(tile_projection(width, height, tile_width, tile_height, column, row));
+                let mut tile = self.render_to_buffer(tile_width, tile_height);
+                // the last column/row may extend beyond the image
+                let (x, y) = (column * tile_width, row * tile_height);
+                let tile = imageops::crop(&mut tile, 0, 0, tile_width.min(width - x), tile_height.min(height - y))
+                    .to_image();
+                image.copy_from(&tile, x, y);
+            }
+        }
+        self.tile_projection = None;
+        self.orbit_controls.camera.update_aspect_ratio(aspect_ratio);
+        image
+    }
+
+    /// Headless + MSAA: makes the rendered frame available for `ReadPixels`
+    fn resolve_framebuffer(&self) {
+        if let Some(ref framebuffer) = self.framebuffer {
+            framebuffer.resolve();
+            unsafe { gl_check_error!(); }
+        }
+    }
+
+    /// Renders a frame with linear colors - exposure, but no tone mapping and sRGB encoding - into a
+    /// floating point framebuffer, so that values above 1 are kept (e.g. for compositing).
+    /// The overlay is left out.
+    pub fn render_linear(&mut self, width: u32, height: u32) -> HdrImage {
+        let framebuffer = Framebuffer::with_format(width, height, 0, gl::RGBA32F);
+        let mut pixels = vec![0.0f32; (width * height * 4) as usize];
+        unsafe {
+            let mut previous_framebuffer = 0;
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+            let mut viewport = [0; 4];
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            framebuffer.bind();
+            gl::Viewport(0, 0, width as i32, height as i32);
+            let aspect_ratio = self.orbit_controls.camera.aspect_ratio();
+            self.orbit_controls.camera.update_aspect_ratio(width as f32 / height as f32);
+
+            let (show_overlay, show_grid, tone_mapping) = (self.show_overlay, self.show_grid, self.tone_mapping);
+            let background_gradient = self.background_gradient;
+            self.show_overlay = false;
+            self.show_grid = self.grid_in_screenshots;
+            self.tone_mapping = ToneMapping::Linear;
+            if self.clear_color[3] < 1.0 {
+                self.background_gradient = None;
+            }
+            self.draw();
+            self.show_overlay = show_overlay;
+            self.show_grid = show_grid;
+            self.tone_mapping = tone_mapping;
+            self.background_gradient = background_gradient;
+
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA,
+                gl::FLOAT, pixels.as_mut_ptr() as *mut c_void);
+            gl_check_error!();
+
+            self.orbit_controls.camera.update_aspect_ratio(aspect_ratio);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as u32);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+        framebuffer.delete();
+
+        // origin at the top left
+        let row = (width * 4) as usize;
+        let pixels = pixels.chunks(row).rev().flat_map(|line| line.iter().cloned()).collect();
+        HdrImage { width, height, pixels }
+    }
+
+    /// Saves a linear HDR screenshot as OpenEXR (.exr) or Radiance (.hdr) image (see `render_linear`)
+    pub fn screenshot_hdr(&mut self, filename: &str, width: u32, height: u32) {
+        let image = self.render_linear(width, height);
+        match image.save(filename) {
+            Ok(()) => info!("Saved {}x{} HDR screenshot to {}", width, height, filename),
+            Err(err) => error!("Failed to save HDR screenshot to {}: {}", filename, err),
+        }
+    }
+
+    pub fn screenshot(&mut self, filename: &str, width: u32, height: u32) {
+        let lowercase_filename = filename.to_lowercase();
+        if lowercase_filename.ends_with(".exr") || lowercase_filename.ends_with(".hdr") {
+            return self.screenshot_hdr(filename, width, height)
+        }
+        let mut pixels = self.render_to_buffer(width, height);
+        if let Some(ref grading) = self.screenshot_grading {
+            grading.apply(&mut pixels);
+        }
+        if let Some((ref palette_filename, size)) = self.palette_output {
+            let colors = dominant_colors(&pixels, size);
+            match save_palette(palette_filename, &colors) {
+                Ok(()) => info!("Saved {} dominant colors to {}", colors.len(), palette_filename),
+                Err(err) => error!("Failed to save palette: {}", err),
+            }
+        }
+        // after the palette, which should only contain the model's colors
+        if let Some((ref logo, corner, opacity)) = self.logo {
+            blend_logo(&mut pixels, logo, corner, opacity);
+        }
+
+        let result = self.encode(pixels, ScreenshotFormat::from_filename(filename))
+            .and_then(|bytes| File::create(filename)
+                .and_then(|mut file| file.write_all(&bytes))
+                .map_err(ImageError::from));
+        if let Err(err) = result {
+            error!("{}", err);
+        }
+        else {
+            info!("Saved {}x{} screenshot to {}", width, height, filename);
+        }
+    }
+
+    fn encode(&self, pixels: RgbaImage, format: ScreenshotFormat) -> Result<Vec<u8>, ImageError> {
+        let (width, height) = pixels.dimensions();
+        let mut bytes = Vec::new();
+        match format {
+            ScreenshotFormat::Png => DynamicImage::ImageRgba8(pixels).save(&mut bytes, ImageFormat::PNG)?,
+            ScreenshotFormat::Jpeg => {
+                let background = match self.composite_background {
+                    Some(color) => [color[0], color[1], color[2], 1.0],
+                    None => self.clear_color,
+                };
+                let rgb = composite_over(&pixels, &background);
+                JPEGEncoder::new_with_quality(&mut bytes, self.jpeg_quality)
+                    .encode(&rgb, width, height, ColorType::RGB(8))?
+            },
+            ScreenshotFormat::Bmp => DynamicImage::ImageRgba8(pixels).save(&mut bytes, ImageFormat::BMP)?,
+        }
+        Ok(bytes)
+    }
+
+    /// Quality (1-100) of JPEG screenshots
+    pub fn set_jpeg_quality(&mut self, quality: u8) {
+        self.jpeg_quality = quality;
+    }
+
+    /// RGB color that transparent areas are blended over for formats without alpha (JPEG).
+    /// Default: the clear color (see `RenderOptions::background`).
+    pub fn set_composite_background(&mut self, color: [f32; 3]) {
+        self.composite_background = Some(color);
+    }
+
+    /// Saves the linearized depth as 16-bit grayscale PNG: black at the near plane, white at the far plane.
+    pub fn screenshot_depth(&mut self, filename: &str, width: u32, height: u32) {
+        let show_overlay = self.show_overlay;
+        self.show_overlay = false;
+        self.draw();
+        self.show_overlay = show_overlay;
+
+        self.resolve_framebuffer();
+        let mut depths = vec![0.0f32; (width * height) as usize];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::DEPTH_COMPONENT,
+                gl::FLOAT, depths.as_mut_ptr() as *mut c_void);
+            gl_check_error!();
+        }
+
+        let camera = &self.orbit_controls.camera;
+        let zfar = camera.zfar.unwrap_or(1000.0);
+        // flipped, so that the origin is at the top left; 16 bit PNG samples are big endian
+        let mut data = Vec::with_capacity(depths.len() * 2);
+        for row in depths.chunks(width as usize).rev() {
+            for &depth in row {
+                let value = linearize_depth(depth, camera.znear, zfar, camera.is_perspective());
+                let value = (value * 65535.0).round() as u16;
+                data.push((value >> 8) as u8);
+                data.push(value as u8);
+            }
+        }
+
+        let result = File::create(filename).and_then(|file|
+            PNGEncoder::new(file).encode(&data, width, height, ColorType::Gray(16)));
+        match result {
+            Ok(()) => info!("Saved {}x{} depth screenshot to {}", width, height, filename),
+            Err(err) => error!("Failed to save depth screenshot: {}", err),
+        }
+    }
+
+    pub fn multiscreenshot(&mut self, filename: &str, width: u32, height: u32, count: u32) {
+        let min_angle : f32 = 0.0 ;
+        let max_angle : f32 =  2.0 * PI ;
+        let increment_angle : f32 = ((max_angle - min_angle)/(count as f32)) as f32;
+        for i in 1..(count+1) {
+            self.orbit_controls.rotate_object(increment_angle);
+            let actual_name = numbered_filename(filename, i);
+            self.screenshot(&actual_name[..], width,height);
+        }
+    }
+
+    /// Like `multiscreenshot`, but combines the frames into an animated GIF (.gif)
+    /// or a contact sheet with a grid of frames (.png), depending on the file extension.
+    /// `count` must be at least 1, `fps` is clamped to 1-100 (GIF frame delays are multiples of 10 ms).
+    pub fn turntable(&mut self, filename: &str, width: u32, height: u32, count: u32, fps: u32) -> io::Result<()> {
+        let lowercase_filename = filename.to_lowercase();
+        if !lowercase_filename.ends_with(".gif") && !lowercase_filename.ends_with(".png") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "turntable filename must end with .gif or .png"))
+        }
+        if count == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "turntable needs at least 1 frame"))
+        }
+        let clamped_fps = clamp(fps, MIN_TURNTABLE_FPS, MAX_TURNTABLE_FPS);
+        if clamped_fps != fps {
+            warn!("Turntable frame rate {} is out of range, using {}", fps, clamped_fps);
+        }
+
+        let increment_angle = 2.0 * PI / count as f32;
+        let frames: Vec<RgbaImage> = (0..count)
+            .map(|_| {
+                self.orbit_controls.rotate_object(increment_angle);
+                self.render_to_buffer(width, height)
+            })
+            .collect();
+
+        if lowercase_filename.ends_with(".gif") {
+            save_gif(filename, frames, clamped_fps)?;
+        } else {
+            save_contact_sheet(filename, &frames)?;
+        }
+        info!("Saved {} frame turntable ({}x{}) to {}", count, width, height, filename);
+        Ok(())
+    }
+}
+
+pub const MIN_TURNTABLE_FPS: u32 = 1;
+pub const MAX_TURNTABLE_FPS: u32 = 100; // the GIF frame delay is in units of 10 ms
+
+fn no_frames_error() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "no frames")
+}
+
+/// Saves an endlessly looping GIF. Transparent pixels stay transparent.
+fn save_gif(filename: &str, frames: Vec<RgbaImage>, fps: u32) -> io::Result<()> {
+    let (width, height) = frames.first().ok_or_else(no_frames_error)?.dimensions();
+    if width > u32::from(u16::MAX) || height > u32::from(u16::MAX) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("GIF frames can't be larger than {0}x{0} pixels", u16::MAX)))
+    }
+    let file = File::create(filename)?;
+    let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[])?;
+    encoder.set(gif::Repeat::Infinite)?;
+    let delay = (100 / clamp(fps, MIN_TURNTABLE_FPS, MAX_TURNTABLE_FPS)) as u16; // in units of 10 ms
+    for frame in frames {
+        let mut pixels = frame.into_raw();
+        let mut gif_frame = gif::Frame::from_rgba(width as u16, height as u16, &mut pixels);
+        gif_frame.delay = delay;
+        // clear before drawing the next frame, otherwise the previous one shines through transparent areas
+        gif_frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&gif_frame)?;
+    }
+    Ok(())
+}
+
+/// Saves the frames as a (roughly square) grid, row by row
+fn save_contact_sheet(filename: &str, frames: &[RgbaImage]) -> io::Result<()> {
+    let (width, height) = frames.first().ok_or_else(no_frames_error)?.dimensions();
+    let columns = (frames.len() as f32).sqrt().ceil() as u32;
+    let rows = (frames.len() as u32 + columns - 1) / columns;
+    let mut sheet = RgbaImage::new(columns * width, rows * height);
+    for (i, frame) in frames.iter().enumerate() {
+        let i = i as u32;
+        sheet.copy_from(frame, (i % columns) * width, (i / columns) * height);
+    }
+    sheet.save(filename)
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Download(http_source::Error),
+    Import {
+        error: gltf_importer::Error,
+        binary: bool, // .glb
+    },
+    /// Bytes passed to `from_bytes` aren't a self-contained binary glTF
+    NotSelfContained(String),
+    TemporaryFile(io::Error),
+    /// `reload` of a model loaded with `from_bytes`
+    NoSource,
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Download(_) => "download failed",
+            LoadError::Import { .. } => "glTF import failed",
+            LoadError::NotSelfContained(_) => "not a self-contained binary glTF",
+            LoadError::TemporaryFile(_) => "failed to save temporary file",
+            LoadError::NoSource => "no source to reload",
+        }
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LoadError::Download(ref err) => Some(err),
+            LoadError::TemporaryFile(ref err) => Some(err),
+            LoadError::Import { .. } | LoadError::NotSelfContained(_) | LoadError::NoSource => None,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Download(ref err) => write!(f, "Download failed: {}", err),
+            LoadError::Import { ref error, binary } => {
+                write!(f, "glTF import failed: {:?}", error)?;
+                if let gltf_importer::Error::Io(_) = *error {
+                    if binary {
+                        write!(f, "\nHint: Does the .glb file exist and is it readable?")?;
+                    } else {
+                        write!(f, "\nHint: Are the .bin file(s) referenced by the .gltf file available?")?;
+                    }
+                }
+                Ok(())
+            }
+            LoadError::NotSelfContained(ref reason) =>
+                write!(f, "Only self-contained binary glTF (.glb) can be loaded from memory/stdin: {}", reason),
+            LoadError::TemporaryFile(ref err) => write!(f, "Failed to save temporary glTF file: {}", err),
+            LoadError::NoSource => write!(f, "Models loaded from memory/stdin can't be reloaded"),
+        }
+    }
+}
+
+/// Errors during viewer initialization (see `GltfViewer::new`)
+#[derive(Debug)]
+pub enum ViewerError {
+    NoContext(glutin::CreationError), // of the last attempted version
+    NoHeadlessContext(glutin::CreationError),
+    MakeCurrent(glutin::ContextError),
+    WindowClosed,
+    Environment { path: String, error: String },
+    Load(LoadError),
+    InvalidIndex { kind: &'static str, index: usize, count: usize }, // e.g. --scene out of range
+    CameraName { name: String, available: Vec<String> }, // --cam-name not found
+    CameraPose { path: String, error: io::Error },
+}
+
+impl ViewerError {
+    /// 1 for setup/loading failures, 2 for invalid arguments
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            ViewerError::InvalidIndex { .. } | ViewerError::CameraName { .. } | ViewerError::CameraPose { .. } => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl Error for ViewerError {
+    fn description(&self) -> &str {
+        match *self {
+            ViewerError::NoContext(_) => "no suitable OpenGL context",
+            ViewerError::NoHeadlessContext(_) => "headless rendering unsupported",
+            ViewerError::MakeCurrent(_) => "failed to activate OpenGL context",
+            ViewerError::WindowClosed => "window closed",
+            ViewerError::Environment { .. } => "failed to load environment map",
+            ViewerError::Load(_) => "failed to load glTF",
+            ViewerError::InvalidIndex { .. } => "invalid index",
+            ViewerError::CameraName { .. } => "camera not found",
+            ViewerError::CameraPose { .. } => "failed to load camera pose",
+        }
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ViewerError::NoContext(ref err) | ViewerError::NoHeadlessContext(ref err) => Some(err),
+            ViewerError::MakeCurrent(ref err) => Some(err),
+            ViewerError::Load(ref err) => Some(err),
+            ViewerError::CameraPose { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ViewerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ViewerError::NoContext(ref err) =>
+                write!(f, "Failed to create an OpenGL context (3.3 core or newer required): {}", err),
+            ViewerError::NoHeadlessContext(ref err) =>
+                write!(f, "Failed to create a headless OpenGL context: {}", err),
+            ViewerError::MakeCurrent(ref err) => write!(f, "Failed to activate the OpenGL context: {}", err),
+            ViewerError::WindowClosed => write!(f, "The window was closed during initialization"),
+            ViewerError::Environment { ref path, ref error } =>
+                write!(f, "Failed to load environment map {}: {}", path, error),
+            ViewerError::Load(ref err) => write!(f, "{}", err),
+            ViewerError::InvalidIndex { kind, index, count } =>
+                write!(f, "No {} with index {} found in glTF file (count: {})", kind, index, count),
+            ViewerError::CameraName { ref name, ref available } if available.is_empty() =>
+                write!(f, "No camera node named \"{}\" found, the glTF file has no cameras", name),
+            ViewerError::CameraName { ref name, ref available } =>
+                write!(f, "No camera node named \"{}\" found in glTF file (cameras: {})", name, available.join(", ")),
+            ViewerError::CameraPose { ref path, ref error } =>
+                write!(f, "Failed to load camera pose from {}: {}", path, error),
+        }
+    }
+}
+
+/// View of one eye for stereo rendering: the camera is moved by `offset` along its x axis
+/// (negative: left eye), and the returned projection (to apply after the camera's) shifts the view,
+/// so that both eyes converge at `convergence` distance (off-axis stereo, without toe-in).
+fn stereo_eye(cam_params: &CameraParams, offset: f32, convergence: f32) -> (CameraParams, Matrix4) {
+    let view_matrix = Matrix4::from_translation(vec3(-offset, 0.0, 0.0)) * cam_params.view_matrix;
+    // the camera's x axis in world space is the first row of the view rotation
+    let right = vec3(cam_params.view_matrix.x.x, cam_params.view_matrix.y.x, cam_params.view_matrix.z.x);
+    let shift = offset * cam_params.projection_matrix.x.x / convergence.max(1e-6);
+    let eye_projection = Matrix4::from_translation(vec3(shift, 0.0, 0.0));
+    let params = CameraParams {
+        position: cam_params.position + right * offset,
+        view_matrix,
+        projection_matrix: cam_params.projection_matrix,
+        frustum: Frustum::from_matrix(&(cam_params.projection_matrix * view_matrix)),
+    };
+    (params, eye_projection)
+}
+
+/// Factor by which the near/far planes may differ from `clip_planes` before they are updated
+const CLIP_PLANE_TOLERANCE: f32 = 1.5;
+
+/// Near and far plane for a camera at `distance` from the center of a scene with bounding box diagonal `size`.
+/// Keeps a margin of factor 2 to the bounding sphere, and limits the ratio of far to near
+/// when the camera is inside it.
+fn clip_planes(distance: f32, size: f32) -> (f32, f32) {
+    let radius = size / 2.0;
+    let znear = ((distance - radius) / 2.0).max(distance.min(size) / 100.0);
+    let zfar = (distance + radius) * 2.0;
+    (znear, zfar)
+}
+
+/// Sets the near and far planes of `camera` at `position` to `clip_planes` for `bounds`,
+/// unless they are within `CLIP_PLANE_TOLERANCE` already. Returns whether they changed.
+fn fit_clip_planes(camera: &mut Camera, position: Point3, bounds: &Aabb3) -> bool {
+    let size = (bounds.max - bounds.min).magnitude();
+    if size <= 0.0 {
+        return false
+    }
+    let distance = (position - bounds.center()).magnitude();
+    let (znear, zfar) = clip_planes(distance, size);
+    let changed = |old: f32, new: f32| (old / new).max(new / old) > CLIP_PLANE_TOLERANCE;
+    if !changed(camera.znear, znear) && !camera.zfar.map_or(true, |old| changed(old, zfar)) {
+        return false
+    }
+    camera.znear = znear;
+    camera.zfar = Some(zfar);
+    camera.update_projection_matrix();
+    true
+}
+
+/// Converts a window position (origin at the top left) and its depth buffer value to world space
+fn unproject(x: f32, y: f32, depth: f32, width: u32, height: u32, inverse_view_projection: &Matrix4) -> Point3 {
+    let ndc = vec4(2.0 * x / width as f32 - 1.0, 1.0 - 2.0 * y / height as f32, 2.0 * depth - 1.0, 1.0);
+    Point3::from_homogeneous(inverse_view_projection * ndc)
+}
+
+/// Converts a depth buffer value to the distance from the near plane, normalized
+/// to [0, 1] (`znear` -> 0, `zfar` -> 1).
+fn linearize_depth(depth: f32, znear: f32, zfar: f32, perspective: bool) -> f32 {
+    if !perspective {
+        return depth // already linear
+    }
+    let ndc = 2.0 * depth - 1.0;
+    let z = 2.0 * znear * zfar / (zfar + znear - ndc * (zfar - znear));
+    clamp((z - znear) / (zfar - znear), 0.0, 1.0)
+}
+
+/// Clip space transform that maps the tile in `column`/`row` (starting at the top left)
+/// of an image of `width` x `height` pixels to the whole viewport.
+fn tile_projection(width: u32, height: u32, tile_width: u32, tile_height: u32, column: u32, row: u32) -> Matrix4 {
+    let scale_x = width as f32 / tile_width as f32;
+    let scale_y = height as f32 / tile_height as f32;
+    // tile center in NDC (y pointing up)
+    let center_x = -1.0 + (2 * column + 1) as f32 * tile_width as f32 / width as f32;
+    let center_y = 1.0 - (2 * row + 1) as f32 * tile_height as f32 / height as f32;
+    Matrix4::from_translation(vec3(-scale_x * center_x, -scale_y * center_y, 0.0)) *
+        Matrix4::from_nonuniform_scale(scale_x, scale_y, 1.0)
+}
+
+/// Image format of screenshots, determined by the file extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScreenshotFormat {
+    Png,
+    Jpeg,
+    Bmp,
+}
+
+impl ScreenshotFormat {
+    /// Falls back to PNG for unknown extensions and formats the `image` crate can't encode (WebP, TGA)
+    fn from_filename(filename: &str) -> ScreenshotFormat {
+        let extension = Path::new(filename).extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        match extension.as_deref() {
+            Some("png") => ScreenshotFormat::Png,
+            Some("jpg") | Some("jpeg") => ScreenshotFormat::Jpeg,
+            Some("bmp") => ScreenshotFormat::Bmp,
+            other => {
+                warn!("Unsupported screenshot format {:?}, saving as PNG", other.unwrap_or(""));
+                ScreenshotFormat::Png
+            }
+        }
+    }
+}
+
+/// Corner of the screenshot for the logo (see `GltfViewer::set_logo`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    pub fn from_name(name: &str) -> Option<Corner> {
+        match name {
+            "top-left" => Some(Corner::TopLeft),
+            "top-right" => Some(Corner::TopRight),
+            "bottom-left" => Some(Corner::BottomLeft),
+            "bottom-right" => Some(Corner::BottomRight),
+            _ => None,
+        }
+    }
+}
+
+/// Color adjustments for screenshots (see `GltfViewer::set_screenshot_grading`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorGrading {
+    pub exposure: f32, // stops (EV), applied to linear colors
+    pub contrast: f32, // around middle gray, 1: unchanged
+    pub saturation: f32, // 0: grayscale, 1: unchanged
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        ColorGrading { exposure: 0.0, contrast: 1.0, saturation: 1.0 }
+    }
+}
+
+impl ColorGrading {
+    /// Adjusts the sRGB colors of `image` in place (alpha is kept)
+    pub fn apply(&self, image: &mut RgbaImage) {
+        let exposure = 2.0f32.powf(self.exposure);
+        for pixel in image.pixels_mut() {
+            // approximate sRGB -> linear
+            let mut rgb = [0.0f32; 3];
+            for (linear, &value) in rgb.iter_mut().zip(&pixel.data) {
+                *linear = (f32::from(value) / 255.0).powf(2.2) * exposure;
+            }
+            let luminance = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+            for (value, &channel) in pixel.data.iter_mut().zip(&rgb) {
+                let linear = (luminance + (channel - luminance) * self.saturation).max(0.0);
+                let graded = (linear.powf(1.0 / 2.2) - 0.5) * self.contrast + 0.5;
+                *value = (graded * 255.0).round().max(0.0).min(255.0) as u8;
+            }
+        }
+    }
+}
+
+/// Distance of the logo from the image border
+const LOGO_MARGIN: u32 = 10; // pixels
+
+/// Blends `logo` (straight alpha) over the corner of `image` (origin at the top left),
+/// clipping it if it doesn't fit.
+fn blend_logo(image: &mut RgbaImage, logo: &RgbaImage, corner: Corner, opacity: f32) {
+    let (width, height) = image.dimensions();
+    let (logo_width, logo_height) = logo.dimensions();
+    let left = match corner {
+        Corner::TopLeft | Corner::BottomLeft => LOGO_MARGIN as i64,
+        Corner::TopRight | Corner::BottomRight => i64::from(width) - i64::from(logo_width) - i64::from(LOGO_MARGIN),
+    };
+    let top = match corner {
+        Corner::TopLeft | Corner::TopRight => LOGO_MARGIN as i64,
+        Corner::BottomLeft | Corner::BottomRight => i64::from(height) - i64::from(logo_height) - i64::from(LOGO_MARGIN),
+    };
+    for (x, y, logo_pixel) in logo.enumerate_pixels() {
+        let (image_x, image_y) = (left + i64::from(x), top + i64::from(y));
+        if image_x < 0 || image_y < 0 || image_x >= i64::from(width) || image_y >= i64::from(height) {
+            continue
+        }
+        let pixel = image.get_pixel_mut(image_x as u32, image_y as u32);
+        let source_alpha = f32::from(logo_pixel.data[3]) / 255.0 * opacity;
+        let target_alpha = f32::from(pixel.data[3]) / 255.0;
+        let alpha = source_alpha + target_alpha * (1.0 - source_alpha);
+        if alpha <= 0.0 {
+            continue
+        }
+        for channel in 0..3 {
+            let value = (f32::from(logo_pixel.data[channel]) * source_alpha +
+                f32::from(pixel.data[channel]) * target_alpha * (1.0 - source_alpha)) / alpha;
+            pixel.data[channel] = value.round().max(0.0).min(255.0) as u8;
+        }
+        pixel.data[3] = (alpha * 255.0).round() as u8;
+    }
+}
+
+/// Drops the alpha channel by blending the pixels over `background` (for formats without alpha)
+fn composite_over(pixels: &RgbaImage, background: &[f32; 4]) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(pixels.len() / 4 * 3);
+    for pixel in pixels.pixels() {
+        let alpha = f32::from(pixel.data[3]) / 255.0;
+        for (&channel, &background) in pixel.data.iter().zip(background).take(3) {
+            let value = f32::from(channel) * alpha + background * 255.0 * (1.0 - alpha);
+            rgb.push(value.round().max(0.0).min(255.0) as u8);
+        }
+    }
+    rgb
+}
+
+/// Which bounding boxes to visualize
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BoundsMode {
+    Off,
+    Scene,
+    SceneAndMeshes,
+}
+
+const LIGHT_ROTATION_STEP: f32 = PI / 36.0; // 5 degrees
+const PINCH_ZOOM_SPEED: f32 = 0.05; // like scrolling by one line per 60 pixels
+const ANIMATION_STEP: f64 = 1.0 / 60.0; // seconds, when stepping a paused animation
+const FIXED_DELTA_TIME: f64 = 1.0 / 60.0; // seconds per frame in deterministic mode
+const FOVY_STEP: f32 = 5.0; // degrees
+const FOVY_OVERLAY_SECONDS: u64 = 2;
+
+/// Input events that need to be handled by the viewer itself
+/// (as opposed to those that only affect the `OrbitControls`)
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    ToggleWireframe,
+    RotateLight(f32, f32), // angles (radians) around the y-axis / towards the poles
+    ChangeExposure(f32), // in stops (EV)
+    ChangeFovy(f32), // degrees
+    ToggleOrthographic,
+    ToggleNavMode,
+    SaveCameraPose,
+    LoadCameraPose,
+    CycleCamera,
+    Recenter,
+    ToggleOverlay,
+    Screenshot,
+    ToggleGrid,
+    ToggleShadows,
+    ToggleOutline,
+    ToggleStereo,
+    ToggleDebugFlag(ShaderFlags),
+    CycleDebugChannel,
+    ToggleVertexColors,
+    ToggleBackfaceCulling,
+    CycleBoundsMode,
+    Open(PathBuf),
+    NextScene,
+    PreviousScene,
+    NextMorphTarget,
+    ChangeMorphWeight(f32),
+    ToggleAnimationPause,
+    StepAnimation,
+    Pick(u32, u32), // window position
+    SetPivot(u32, u32), // window position
+    ClearSelection,
+}
+
+/// Tells clicks from drags: a click is a left button press and release at (almost) the same position.
+/// A second click at the same position shortly after is a double click.
+struct ClickTracker {
+    cursor: Vector2,
+    pressed_at: Option<Vector2>,
+    last_click: Option<(Instant, Vector2)>,
+}
+
+impl ClickTracker {
+    fn new() -> ClickTracker {
+        ClickTracker { cursor: Vector2::new(0.0, 0.0), pressed_at: None, last_click: None }
+    }
+
+    /// Call on a click at the current cursor position, returns whether it completes a double click
+    fn is_double_click(&mut self) -> bool {
+        let cursor = self.cursor;
+        let double_click = self.last_click.map_or(false, |(time, position)|
+            time.elapsed() <= Duration::from_millis(DOUBLE_CLICK_TIME_MS) &&
+            (cursor - position).magnitude() <= CLICK_TOLERANCE);
+        self.last_click = if double_click { None } else { Some((Instant::now(), cursor)) };
+        double_click
+    }
+}
+
+const CLICK_TOLERANCE: f32 = 3.0; // pixels
+const DOUBLE_CLICK_TIME_MS: u64 = 400;
+
+#[allow(clippy::too_many_arguments)]
+fn process_events(
+    events_loop: &mut glutin::EventsLoop,
+    gl_window: &glutin::GlWindow,
+    orbit_controls: &mut OrbitControls,
+    touches: &mut HashMap<u64, Vector2>,
+    clicks: &mut ClickTracker,
+    last_input: &mut Instant,
+    has_selection: bool,
+    width: &mut u32,
+    height: &mut u32,
+    actions: &mut Vec<Action>) -> bool
+{
+    let mut keep_running = true;
+    #[allow(clippy::single_match)]
+    events_loop.poll_events(|event| {
+        if is_user_input(&event) {
+            *last_input = Instant::now();
+        }
+        match event {
+            glutin::Event::WindowEvent{ event, .. } => match event {
+                WindowEvent::Closed => keep_running = false,
+                WindowEvent::Resized(w, h) => {
+                    gl_window.resize(w, h);
+                    *width = w;
+                    *height = h;
+                    let w = w as f32;
+                    let h = h as f32;
+                    orbit_controls.camera.update_aspect_ratio(w / h);
+                    orbit_controls.screen_width = w;
+                    orbit_controls.screen_height = h;
+
+                    trace!("Resized to {}x{}", w, h);
+                },
+                WindowEvent::DroppedFile(path_buf) => actions.push(Action::Open(path_buf)),
+                WindowEvent::Touch(touch) => process_touch(touch, touches, orbit_controls),
+                WindowEvent::MouseInput { button, state: Pressed, ..} => {
+                    match button {
+                        MouseButton::Left => {
+                            orbit_controls.state = NavState::Rotating;
+                            clicks.pressed_at = Some(clicks.cursor);
+                        },
+                        MouseButton::Right | MouseButton::Middle => {
+                            orbit_controls.state = NavState::Panning;
+                        },
+                        _ => ()
+                    }
+                },
+                WindowEvent::MouseInput { button, state: Released, ..} => {
+                    if button == MouseButton::Left {
+                        if let Some(pressed_at) = clicks.pressed_at.take() {
+                            if (clicks.cursor - pressed_at).magnitude() <= CLICK_TOLERANCE {
+                                let (x, y) = (clicks.cursor.x as u32, clicks.cursor.y as u32);
+                                // the first click of a double click selects as well
+                                actions.push(if clicks.is_double_click() { Action::SetPivot(x, y) } else { Action::Pick(x, y) });
+                            }
+                        }
+                    }
+                    match (button, orbit_controls.state.clone()) {
+                        (MouseButton::Left, NavState::Rotating) |
+                        (MouseButton::Right, NavState::Panning) |
+                        (MouseButton::Middle, NavState::Panning) => {
+                            orbit_controls.state = NavState::None;
+                            orbit_controls.handle_mouse_up();
+                        },
                         _ => ()
                     }
                 }
                 WindowEvent::CursorMoved { position: (xpos, ypos), .. } => {
                     let (xpos, ypos) = (xpos as f32, ypos as f32);
+                    clicks.cursor = Vector2::new(xpos, ypos);
                     orbit_controls.handle_mouse_move(xpos, ypos);
                 },
                 WindowEvent::MouseWheel { delta: MouseScrollDelta::PixelDelta(_xoffset, yoffset), .. } => {
-                    orbit_controls.process_mouse_scroll(yoffset);
+                    orbit_controls.process_mouse_scroll(yoffset * orbit_controls.scroll_direction());
                 }
                 WindowEvent::MouseWheel { delta: MouseScrollDelta::LineDelta(_rows, lines), .. } => {
-                    orbit_controls.process_mouse_scroll(lines * 3.0);
+                    orbit_controls.process_mouse_scroll(lines * 3.0 * orbit_controls.scroll_direction());
                 }
                 WindowEvent::KeyboardInput { input, .. } => {
-                    keep_running = process_input(input, &mut orbit_controls);
+                    keep_running = process_input(input, orbit_controls, has_selection, actions);
                 }
                 _ => ()
             },
@@ -405,20 +2487,543 @@ fn process_events(
     keep_running
 }
 
-fn process_input(input: glutin::KeyboardInput, controls: &mut OrbitControls) -> bool {
+/// Node index and name, mesh and materials, e.g. for reporting the picked node
+fn describe_node(root: &Root, index: usize) -> String {
+    let unnamed = |name: &Option<String>| name.clone().unwrap_or_else(|| "unnamed".to_owned());
+    let node = &root.nodes[index];
+    let (mesh, materials) = match node.mesh {
+        Some(ref mesh) => {
+            let materials: Vec<String> = mesh.primitives.iter()
+                .map(|primitive| match primitive.material.index {
+                    Some(index) => format!("{} ({})", index, unnamed(&primitive.material.name)),
+                    None => "default".to_owned(),
+                })
+                .collect();
+            (format!("{} ({})", mesh.index, unnamed(&mesh.name)), materials.join(", "))
+        },
+        None => ("none".to_owned(), "none".to_owned()),
+    };
+    format!("node {} ({}), mesh: {}, materials: {}", index, unnamed(&node.name), mesh, materials)
+}
+
+/// Mouse, keyboard and touch events (they pause the auto rotation)
+fn is_user_input(event: &glutin::Event) -> bool {
+    match *event {
+        glutin::Event::WindowEvent { ref event, .. } => matches!(*event,
+            WindowEvent::MouseInput { .. } | WindowEvent::CursorMoved { .. } | WindowEvent::MouseWheel { .. } |
+            WindowEvent::KeyboardInput { .. } | WindowEvent::Touch(_)),
+        _ => false,
+    }
+}
+
+/// Single finger drag rotates, two finger drag pans and pinching zooms.
+/// `touches` contains the last location of each active touch point (by touch id).
+fn process_touch(touch: Touch, touches: &mut HashMap<u64, Vector2>, controls: &mut OrbitControls) {
+    let location = Vector2::new(touch.location.0 as f32, touch.location.1 as f32);
+    match touch.phase {
+        TouchPhase::Started => {
+            touches.insert(touch.id, location);
+        },
+        TouchPhase::Moved => {
+            let previous = match touches.get(&touch.id) {
+                Some(&previous) => previous,
+                None => return,
+            };
+            match touches.len() {
+                1 => controls.rotate_by(location - previous),
+                2 => {
+                    let other = touches.iter()
+                        .find(|&(&id, _)| id != touch.id)
+                        .map(|(_, &other)| other)
+                        .unwrap();
+                    // the center of both fingers moves by half the distance
+                    controls.pan_by((location - previous) / 2.0);
+                    let spread = (location - other).magnitude() - (previous - other).magnitude();
+                    controls.process_mouse_scroll(spread * PINCH_ZOOM_SPEED);
+                },
+                _ => (), // no gestures with more fingers
+            }
+            touches.insert(touch.id, location);
+        },
+        TouchPhase::Ended | TouchPhase::Cancelled => {
+            touches.remove(&touch.id);
+        },
+    }
+}
+
+fn process_input(input: glutin::KeyboardInput, controls: &mut OrbitControls, has_selection: bool,
+        actions: &mut Vec<Action>) -> bool {
     let pressed = match input.state {
         Pressed => true,
         Released => false
     };
+    let shift = input.modifiers.shift;
     if let Some(code) = input.virtual_keycode {
         match code {
+            // Escape clears the selection first, quits otherwise
+            VirtualKeyCode::Escape if pressed && has_selection => actions.push(Action::ClearSelection),
             VirtualKeyCode::Escape if pressed => return false,
+            // Shift + cursor keys: rotate light
+            VirtualKeyCode::Left if pressed && shift  => actions.push(Action::RotateLight(-LIGHT_ROTATION_STEP, 0.0)),
+            VirtualKeyCode::Right if pressed && shift => actions.push(Action::RotateLight(LIGHT_ROTATION_STEP, 0.0)),
+            VirtualKeyCode::Up if pressed && shift    => actions.push(Action::RotateLight(0.0, -LIGHT_ROTATION_STEP)),
+            VirtualKeyCode::Down if pressed && shift  => actions.push(Action::RotateLight(0.0, LIGHT_ROTATION_STEP)),
             VirtualKeyCode::W | VirtualKeyCode::Up    => controls.process_keyboard(FORWARD, pressed),
             VirtualKeyCode::S | VirtualKeyCode::Down  => controls.process_keyboard(BACKWARD, pressed),
             VirtualKeyCode::A | VirtualKeyCode::Left  => controls.process_keyboard(LEFT, pressed),
             VirtualKeyCode::D | VirtualKeyCode::Right => controls.process_keyboard(RIGHT, pressed),
+            VirtualKeyCode::LShift | VirtualKeyCode::RShift => controls.fast = pressed,
+            VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => controls.slow = pressed,
+            VirtualKeyCode::Tab if pressed => actions.push(Action::ToggleNavMode),
+            VirtualKeyCode::F if pressed => actions.push(Action::ToggleWireframe),
+            VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 if pressed => actions.push(Action::ToggleOrthographic),
+            VirtualKeyCode::P if pressed => actions.push(Action::SaveCameraPose),
+            VirtualKeyCode::R if pressed => actions.push(Action::Recenter),
+            VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 if pressed => actions.push(Action::CycleCamera),
+            VirtualKeyCode::F3 if pressed => actions.push(Action::ToggleOverlay),
+            VirtualKeyCode::F12 if pressed => actions.push(Action::Screenshot),
+            VirtualKeyCode::G if pressed => actions.push(Action::ToggleGrid),
+            VirtualKeyCode::L if pressed => actions.push(Action::ToggleShadows),
+            VirtualKeyCode::E if pressed => actions.push(Action::ToggleOutline),
+            VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 if pressed => actions.push(Action::ToggleStereo),
+            VirtualKeyCode::U if pressed => actions.push(Action::ToggleDebugFlag(ShaderFlags::CLAY)),
+            VirtualKeyCode::N if pressed => actions.push(Action::ToggleDebugFlag(ShaderFlags::DEBUG_NORMALS)),
+            VirtualKeyCode::C if pressed => actions.push(Action::CycleDebugChannel),
+            VirtualKeyCode::V if pressed => actions.push(Action::ToggleVertexColors),
+            VirtualKeyCode::X if pressed => actions.push(Action::ToggleBackfaceCulling),
+            VirtualKeyCode::B if pressed => actions.push(Action::CycleBoundsMode),
+            VirtualKeyCode::Equals | VirtualKeyCode::Add if pressed => actions.push(Action::ChangeExposure(0.5)),
+            VirtualKeyCode::Minus | VirtualKeyCode::Subtract if pressed => actions.push(Action::ChangeExposure(-0.5)),
+            VirtualKeyCode::Key9 if pressed => actions.push(Action::ChangeFovy(-FOVY_STEP)),
+            VirtualKeyCode::Key0 if pressed => actions.push(Action::ChangeFovy(FOVY_STEP)),
+            VirtualKeyCode::O if pressed => actions.push(Action::LoadCameraPose),
+            VirtualKeyCode::RBracket if pressed => actions.push(Action::NextScene),
+            VirtualKeyCode::LBracket if pressed => actions.push(Action::PreviousScene),
+            VirtualKeyCode::Space if pressed => actions.push(Action::ToggleAnimationPause),
+            VirtualKeyCode::Period if pressed => actions.push(Action::StepAnimation),
+            VirtualKeyCode::M if pressed => actions.push(Action::NextMorphTarget),
+            VirtualKeyCode::J if pressed => actions.push(Action::ChangeMorphWeight(-0.1)),
+            VirtualKeyCode::K if pressed => actions.push(Action::ChangeMorphWeight(0.1)),
             _ => ()
         }
     }
     true
 }
+
+/// Inserts `_<number>` before the extension of the file name (not the directory!),
+/// e.g. `./v1.2/shot.png` -> `./v1.2/shot_1.png`
+fn numbered_filename(filename: &str, number: u32) -> String {
+    let path = Path::new(filename);
+    let stem = path.file_stem().map_or("".into(), |stem| stem.to_string_lossy());
+    let mut name = format!("{}_{}", stem, number);
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// `<prefix>-YYYYMMDD-HHMMSS-mmm.<extension>` (UTC, with milliseconds so that quick repetitions
+/// don't overwrite each other)
+fn timestamped_filename(prefix: &str, extension: &str, time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_else(|_| Duration::from_secs(0));
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // civil date from days since 1970-01-01 (proleptic Gregorian calendar, eras of 400 years)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153; // month, starting in March
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{}-{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}.{}", prefix, year, month, day,
+        seconds_of_day / 3600, seconds_of_day / 60 % 60, seconds_of_day % 60,
+        since_epoch.subsec_millis(), extension)
+}
+
+/// Checks that `data` is a binary glTF that references no external files
+fn check_self_contained_glb(data: &[u8]) -> Result<(), String> {
+    if !data.starts_with(b"glTF") {
+        return Err("not a binary glTF file".to_owned())
+    }
+    let json = http_source::json_chunk(data).map_err(|err| err.to_string())?;
+    let json = serde_json::from_slice::<gltf::json::Root>(json).map_err(|err| format!("invalid JSON chunk: {}", err))?;
+    let buffer_uris = json.buffers.iter().filter_map(|buffer| buffer.uri.as_ref());
+    let image_uris = json.images.iter().filter_map(|image| image.uri.as_ref());
+    for uri in buffer_uris.chain(image_uris) {
+        if !uri.starts_with("data:") {
+            return Err(format!("references the external file {}", uri))
+        }
+    }
+    Ok(())
+}
+
+static TEMPORARY_GLB_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Saves `data` as .glb file in the temp dir (unique per process and call,
+/// so viewers can be created from bytes on several threads at once)
+fn save_temporary_glb(data: &[u8]) -> io::Result<PathBuf> {
+    let dir = ::std::env::temp_dir().join("gltf-viewer");
+    fs::create_dir_all(&dir)?;
+    loop {
+        let count = TEMPORARY_GLB_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("bytes-{}-{}.glb", process::id(), count));
+        // never overwrite a file, e.g. one left behind by an earlier process with the same ID
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(data)?;
+                return Ok(path)
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Whether `source` refers to a binary glTF (.glb) file
+fn is_binary_gltf(source: &str) -> bool {
+    source.to_lowercase().ends_with(".glb")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn detect_binary_gltf() {
+        assert!(is_binary_gltf("src/data/Box.glb"));
+        assert!(is_binary_gltf("BOX.GLB"));
+        assert!(!is_binary_gltf("src/data/Box.gltf"));
+    }
+
+    #[test]
+    fn numbered_filenames() {
+        assert_eq!(numbered_filename("shot.png", 1), "shot_1.png");
+        assert_eq!(numbered_filename("./v1.2/shot.png", 2), "./v1.2/shot_2.png");
+        assert_eq!(numbered_filename("../out/model", 3), "../out/model_3");
+    }
+
+    #[test]
+    fn turntable_without_frames() {
+        let err = save_gif("no-frames.gif", vec![], 10).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(save_contact_sheet("no-frames.png", &[]).is_err());
+        assert!(!Path::new("no-frames.gif").exists());
+    }
+
+    #[test]
+    fn timestamped_filenames() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_042);
+        assert_eq!(timestamped_filename("screenshot", "png", time), "screenshot-20231114-221320-042.png");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400); // 2000-02-29
+        assert_eq!(timestamped_filename("shot", "png", leap_day), "shot-20000229-000000-000.png");
+        assert_eq!(timestamped_filename("shot", "png", UNIX_EPOCH), "shot-19700101-000000-000.png");
+    }
+
+    #[test]
+    fn screenshot_format_from_extension() {
+        assert_eq!(ScreenshotFormat::from_filename("shot.png"), ScreenshotFormat::Png);
+        assert_eq!(ScreenshotFormat::from_filename("shot.JPG"), ScreenshotFormat::Jpeg);
+        assert_eq!(ScreenshotFormat::from_filename("shot.jpeg"), ScreenshotFormat::Jpeg);
+        assert_eq!(ScreenshotFormat::from_filename("shot.bmp"), ScreenshotFormat::Bmp);
+        assert_eq!(ScreenshotFormat::from_filename("shot.webp"), ScreenshotFormat::Png);
+        assert_eq!(ScreenshotFormat::from_filename("shot"), ScreenshotFormat::Png);
+    }
+
+    #[test]
+    fn composite_over_background() {
+        let pixels = RgbaImage::from_raw(2, 1, vec![255, 0, 0, 255, 255, 0, 0, 0]).unwrap();
+        assert_eq!(composite_over(&pixels, &[0.0, 0.0, 1.0, 0.0]), vec![255, 0, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn blend_logo_into_corner() {
+        let mut image = RgbaImage::from_raw(30, 20, vec![0; 30 * 20 * 4]).unwrap();
+        let logo = RgbaImage::from_raw(2, 1, vec![255, 0, 0, 255, 0, 255, 0, 255]).unwrap();
+        blend_logo(&mut image, &logo, Corner::BottomRight, 0.5);
+        // 2x1 logo at (18, 9) - transparent black below -> half transparent logo color
+        assert_eq!(image.get_pixel(18, 9).data, [255, 0, 0, 128]);
+        assert_eq!(image.get_pixel(19, 9).data, [0, 255, 0, 128]);
+        assert_eq!(image.get_pixel(17, 9).data, [0, 0, 0, 0]);
+
+        // opaque background, partly outside
+        let mut image = RgbaImage::from_raw(11, 11, vec![255; 11 * 11 * 4]).unwrap();
+        blend_logo(&mut image, &logo, Corner::TopLeft, 1.0);
+        assert_eq!(image.get_pixel(10, 10).data, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn unique_temporary_glbs() {
+        let paths: Vec<PathBuf> = (0..4)
+            .map(|i| ::std::thread::spawn(move || save_temporary_glb(&[i]).unwrap()))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect();
+        for (i, path) in paths.iter().enumerate() {
+            assert_eq!(fs::read(path).unwrap(), [i as u8]);
+            assert_eq!(paths.iter().filter(|other| *other == path).count(), 1);
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn reject_oversized_gif() {
+        let filename = ::std::env::temp_dir().join("gltf-viewer-oversized.gif");
+        let filename = filename.to_str().unwrap();
+        let frames = vec![RgbaImage::new(u32::from(u16::MAX) + 1, 1)];
+        let err = save_gif(filename, frames, 10).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!Path::new(filename).exists());
+    }
+
+    #[test]
+    fn grade_screenshot_colors() {
+        let original = RgbaImage::from_raw(2, 1, vec![200, 100, 20, 255, 0, 0, 0, 0]).unwrap();
+        let mut image = original.clone();
+        ColorGrading::default().apply(&mut image);
+        assert_eq!(&*image, &*original);
+
+        // +1 EV doubles the linear value (transparent black stays)
+        let mut image = original.clone();
+        ColorGrading { exposure: 1.0, ..ColorGrading::default() }.apply(&mut image);
+        assert_eq!(image.get_pixel(0, 0).data, [255, 137, 27, 255]);
+        assert_eq!(image.get_pixel(1, 0).data, [0, 0, 0, 0]);
+
+        let mut image = original.clone();
+        ColorGrading { saturation: 0.0, ..ColorGrading::default() }.apply(&mut image);
+        let gray = image.get_pixel(0, 0).data;
+        assert!(gray[0] == gray[1] && gray[1] == gray[2]);
+
+        let mut image = original;
+        ColorGrading { contrast: 1.5, ..ColorGrading::default() }.apply(&mut image);
+        assert_eq!(image.get_pixel(0, 0).data, [236, 86, 0, 255]);
+    }
+
+    #[test]
+    fn tile_projections() {
+        // pixel (in the full image, origin top left) -> clip space -> tile pixel
+        let (width, height, tile_width, tile_height) = (1000, 500, 300, 200);
+        for &(x, y) in &[(0.0, 0.0), (450.0, 250.0), (999.0, 499.0), (610.0, 30.0)] {
+            let ndc = vec4(2.0 * x / width as f32 - 1.0, 1.0 - 2.0 * y / height as f32, 0.5, 1.0);
+            let (column, row) = ((x / tile_width as f32) as u32, (y / tile_height as f32) as u32);
+            let tile = tile_projection(width, height, tile_width, tile_height, column, row) * ndc;
+            let tile_x = (tile.x + 1.0) / 2.0 * tile_width as f32;
+            let tile_y = (1.0 - tile.y) / 2.0 * tile_height as f32;
+            assert!((tile_x - (x - (column * tile_width) as f32)).abs() < 1e-3);
+            assert!((tile_y - (y - (row * tile_height) as f32)).abs() < 1e-3);
+            assert_eq!(tile.z, 0.5);
+        }
+    }
+
+    #[test]
+    fn clip_planes_enclose_scene() {
+        // tiny and huge scenes, camera inside the bounding sphere, framed (see `set_camera_from_bounds`) and far away
+        for &size in &[0.001, 1.0, 100_000.0] {
+            let radius = size / 2.0;
+            for &distance in &[size / 100.0, size * 0.73, size * 10.0] {
+                let (znear, zfar) = clip_planes(distance, size);
+                assert!(znear > 0.0 && znear < zfar);
+                // with the tolerance of `update_clip_planes`
+                if distance > radius {
+                    assert!(znear * CLIP_PLANE_TOLERANCE < distance - radius, "{} {}", size, distance);
+                }
+                assert!(zfar / CLIP_PLANE_TOLERANCE > distance + radius, "{} {}", size, distance);
+                // depth precision
+                assert!(zfar / znear <= 1e4 * 1.1, "{} {}", size, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn fit_clip_planes_to_tiny_and_huge_scenes() {
+        // the same camera, going from a tiny to a huge scene and back, framed like `set_camera_from_bounds`
+        let mut camera = Camera::default();
+        camera.update_projection_matrix();
+        for &size in &[0.001f32, 100_000.0, 1.0, 0.001] {
+            let half = size / 2.0 / 3f32.sqrt();
+            let bounds = Aabb3::new(Point3::new(-half, -half, -half), Point3::new(half, half, half));
+            let center = bounds.center();
+            let position = Point3::new(center.x + size / 2.0, center.y + size / 5.0, center.z + size / 2.0);
+            assert!(fit_clip_planes(&mut camera, position, &bounds), "{}", size);
+            // a small camera movement doesn't change them
+            assert!(!fit_clip_planes(&mut camera, position + vec3(0.0, 0.0, size / 100.0), &bounds));
+
+            // no corner of the bounding box is clipped
+            let view = Matrix4::look_at(position, center, vec3(0.0, 1.0, 0.0));
+            let view_projection = camera.projection_matrix * view;
+            for &x in &[bounds.min.x, bounds.max.x] {
+                for &y in &[bounds.min.y, bounds.max.y] {
+                    for &z in &[bounds.min.z, bounds.max.z] {
+                        let clip = view_projection * vec4(x, y, z, 1.0);
+                        assert!(clip.z > -clip.w && clip.z < clip.w, "{} {:?}", size, clip);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stereo_eyes_converge() {
+        use cgmath::{perspective, Deg};
+        let projection = perspective(Deg(45.0), 1.5, 0.1, 100.0);
+        let position = Point3::new(0.0, 1.0, 4.0);
+        let view_matrix = Matrix4::look_at(position, Point3::new(0.0, 1.0, 0.0), Vector3::unit_y());
+        let cam_params = CameraParams {
+            position: position.to_vec(),
+            view_matrix,
+            projection_matrix: projection,
+            frustum: Frustum::from_matrix(&(projection * view_matrix)),
+        };
+        let ndc_x = |(params, eye_projection): (CameraParams, Matrix4), point: Point3| {
+            let clip = eye_projection * params.projection_matrix * params.view_matrix * point.to_homogeneous();
+            clip.x / clip.w
+        };
+        let eyes = |point: Point3| (
+            ndc_x(stereo_eye(&cam_params, -0.05, 4.0), point),
+            ndc_x(stereo_eye(&cam_params, 0.05, 4.0), point));
+
+        // no parallax at the convergence distance
+        let (left, right) = eyes(Point3::new(0.5, 1.2, 0.0));
+        assert!((left - right).abs() < 1e-5);
+        // behind: positive parallax, in front: negative
+        let (left, right) = eyes(Point3::new(0.5, 1.2, -3.0));
+        assert!(right > left);
+        let (left, right) = eyes(Point3::new(0.5, 1.2, 2.0));
+        assert!(right < left);
+    }
+
+    #[test]
+    fn unproject_window_position() {
+        use cgmath::{perspective, Deg};
+        let projection = perspective(Deg(45.0), 2.0, 0.1, 100.0);
+        let view = Matrix4::look_at(Point3::new(1.0, 2.0, 5.0), Point3::new(0.0, 0.0, 0.0), Vector3::unit_y());
+        let view_projection = projection * view;
+        let (width, height) = (200, 100);
+        let point = Point3::new(0.3, -0.2, 0.5);
+        let clip = view_projection * point.to_homogeneous();
+        let ndc = clip.truncate() / clip.w;
+        let x = (ndc.x + 1.0) / 2.0 * width as f32;
+        let y = (1.0 - ndc.y) / 2.0 * height as f32;
+        let depth = (ndc.z + 1.0) / 2.0;
+        let unprojected = unproject(x, y, depth, width, height, &view_projection.invert().unwrap());
+        assert!((unprojected - point).magnitude() < 1e-3, "{:?}", unprojected);
+    }
+
+    #[test]
+    fn linearized_depth() {
+        let (znear, zfar) = (0.1, 100.0);
+        assert!(linearize_depth(0.0, znear, zfar, true).abs() < 1e-5);
+        assert!((linearize_depth(1.0, znear, zfar, true) - 1.0).abs() < 1e-5);
+        // perspective depth is non-linear: half of the depth range is very close to the camera
+        assert!(linearize_depth(0.5, znear, zfar, true) < 0.01);
+        assert_eq!(linearize_depth(0.5, znear, zfar, false), 0.5);
+    }
+
+    #[test]
+    fn load_glb() {
+        let _context = match test_gl_context() {
+            Some(context) => context,
+            None => return,
+        };
+        let (root, scenes, _) = GltfViewer::load("src/data/Box.glb", None, &HashMap::new())
+            .expect("failed to load Box.glb");
+        assert_eq!(scenes.len(), 1);
+        assert_eq!(root.meshes.len(), 1);
+        assert_eq!(root.meshes[0].primitives.len(), 1);
+        assert_eq!(root.meshes[0].primitives[0].num_triangles(), 12);
+    }
+
+    /// Headless viewer with the command line defaults, or `None` without OpenGL (see `test_gl_context`)
+    fn test_viewer(source: &str, width: u32, height: u32) -> Option<GltfViewer> {
+        test_gl_context()?;
+        let camera_options = CameraOptions {
+            index: 0, name: None, position: None, target: None, fovy: 75.0, orthographic: false, pose_file: None,
+            rotate_sensitivity: 1.0, pan_sensitivity: 1.0, zoom_sensitivity: 1.0,
+            invert_scroll: false, zoom_to_cursor: false,
+            move_speed: None, min_distance: None, max_distance: None,
+            transition: 0.0, auto_rotate: None, idle_timeout: 10.0,
+        };
+        let render_options = RenderOptions {
+            background: None, background_gradient: None, transparent: None, light_direction: None,
+            msaa_samples: 0, vsync: false, state_caching: true, draw_sorting: true, instancing: false,
+            exposure: 1.0, tone_mapping: ToneMapping::None, environment: None, grid: false,
+            debug_flags: ShaderFlags::empty(),
+            shadows: false, shadow_bias: 0.005, shadow_catcher: false,
+            ssao: false, ssao_radius: None, ssao_intensity: 1.0,
+            outline: false, outline_thickness: 1.5, outline_color: [0.0, 0.0, 0.0, 1.0], outline_fill: None,
+            stereo: false, polygon_offset: None, material_polygon_offsets: HashMap::new(),
+            transmission: true, timer_window: 300,
+        };
+        let viewer = GltfViewer::new(source, width, height, true, false, camera_options, render_options, 0, None);
+        Some(viewer.unwrap_or_else(|err| panic!("failed to create viewer for {}: {}", source, err)))
+    }
+
+    #[test]
+    fn instanced_draws_match_individual_draws() {
+        let mut viewer = match test_viewer("src/data/SharedMesh.gltf", 64, 64) {
+            Some(viewer) => viewer,
+            None => return,
+        };
+        assert!(viewer.scenes[0].shared_meshes.contains(&0));
+        if !instancing_supported() {
+            eprintln!("skipping instancing test: not supported by the OpenGL context");
+            return
+        }
+        let individual = viewer.render_to_buffer(64, 64);
+        viewer.root.instancing = true;
+        let instanced = viewer.render_to_buffer(64, 64);
+        // the nodes cover more than a few pixels
+        let background = *individual.get_pixel(0, 0);
+        assert!(individual.pixels().filter(|pixel| **pixel != background).count() > 64);
+        let max_difference = individual.iter().zip(instanced.iter())
+            .map(|(a, b)| (i16::from(*a) - i16::from(*b)).abs())
+            .max().unwrap();
+        assert!(max_difference <= 2, "instanced rendering differs by up to {}", max_difference);
+    }
+
+    #[test]
+    fn load_corrupt_gltf() {
+        match GltfViewer::load("src/data/Corrupt.gltf", None, &HashMap::new()) {
+            Err(LoadError::Import { binary, .. }) => assert!(!binary),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("loading a corrupt glTF succeeded"),
+        }
+        assert!(GltfViewer::load("src/data/does-not-exist.glb", None, &HashMap::new()).is_err());
+    }
+
+    /// Binary glTF with only a JSON chunk
+    fn glb(json: &str) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        let mut data = b"glTF".to_vec();
+        for &value in &[2, 20 + json.len() as u32, json.len() as u32] {
+            data.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+        }
+        data.extend_from_slice(b"JSON");
+        data.extend_from_slice(&json);
+        data
+    }
+
+    #[test]
+    fn only_self_contained_glb_from_bytes() {
+        let mut box_glb = Vec::new();
+        File::open("src/data/Box.glb").unwrap().read_to_end(&mut box_glb).unwrap();
+        assert_eq!(check_self_contained_glb(&box_glb), Ok(()));
+
+        let json = r#"{"asset":{"version":"2.0"},"buffers":[{"byteLength":4,"uri":"Box0.bin"}]}"#;
+        assert!(check_self_contained_glb(json.as_bytes()).is_err());
+        assert_eq!(check_self_contained_glb(&glb(json)), Err("references the external file Box0.bin".to_owned()));
+        let json = r#"{"asset":{"version":"2.0"},"buffers":[{"byteLength":4,"uri":"data:application/octet-stream;base64,AAAAAA=="}]}"#;
+        assert_eq!(check_self_contained_glb(&glb(json)), Ok(()));
+    }
+}